@@ -1,5 +1,57 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
 // generated by `sqlx migrate build-script`
 fn main() {
     // trigger recompilation when a new migration is added
     println!("cargo:rerun-if-changed=migrations");
-}
\ No newline at end of file
+
+    if env::var_os("CARGO_FEATURE_EMBED").is_some() {
+        embed_assets();
+    }
+}
+
+/// Generate the list of front end files included in the binary with the `embed` feature.
+fn embed_assets() {
+    let dist = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join("dist");
+    println!("cargo:rerun-if-changed={}", dist.display());
+
+    if !dist.join("index.html").exists() {
+        panic!(
+            "the embed feature requires the front end to be built to {}",
+            dist.display()
+        );
+    }
+
+    let mut files = vec![];
+    collect_files(&dist, &mut files);
+    files.sort();
+
+    let mut out = String::from("pub static ASSETS: &[(&str, &[u8])] = &[\n");
+    for file in files {
+        let name = file.strip_prefix(&dist).unwrap().to_str().unwrap();
+        out.push_str(&format!(
+            "    ({:?}, include_bytes!({:?})),\n",
+            name.replace('\\', "/"),
+            file.display().to_string()
+        ));
+    }
+    out.push_str("];\n");
+
+    let path = Path::new(&env::var("OUT_DIR").unwrap()).join("assets.rs");
+    fs::write(path, out).unwrap();
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        println!("cargo:rerun-if-changed={}", path.display());
+        if path.is_dir() {
+            collect_files(&path, files);
+        } else {
+            files.push(path);
+        }
+    }
+}
@@ -0,0 +1,5 @@
+CREATE TABLE document_aliases (
+    alias TEXT PRIMARY KEY NOT NULL,
+    document UUID NOT NULL REFERENCES documents(id) ON DELETE CASCADE ON UPDATE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1 @@
+ALTER TABLE documents ADD COLUMN draft BOOLEAN NOT NULL DEFAULT FALSE;
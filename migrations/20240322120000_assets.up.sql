@@ -0,0 +1,2 @@
+-- Canonicalised path of the binary asset described by a sidecar document
+ALTER TABLE documents ADD COLUMN asset TEXT;
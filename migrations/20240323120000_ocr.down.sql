@@ -0,0 +1,2 @@
+ALTER TABLE documents DROP COLUMN asset_hash;
+DROP TABLE ocr_cache;
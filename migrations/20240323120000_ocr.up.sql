@@ -0,0 +1,8 @@
+-- Text extracted from assets, keyed by the SHA-256 of the asset contents
+CREATE TABLE ocr_cache (
+    hash TEXT PRIMARY KEY NOT NULL,
+    content TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+ALTER TABLE documents ADD COLUMN asset_hash TEXT;
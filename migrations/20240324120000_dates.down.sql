@@ -0,0 +1,3 @@
+ALTER TABLE documents DROP COLUMN date_published;
+ALTER TABLE documents DROP COLUMN date_created;
+ALTER TABLE documents DROP COLUMN date_updated;
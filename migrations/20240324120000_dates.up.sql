@@ -0,0 +1,4 @@
+-- Dates obtained from the document frontmatter
+ALTER TABLE documents ADD COLUMN date_published TIMESTAMPTZ;
+ALTER TABLE documents ADD COLUMN date_created TIMESTAMPTZ;
+ALTER TABLE documents ADD COLUMN date_updated TIMESTAMPTZ;
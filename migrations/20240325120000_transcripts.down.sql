@@ -0,0 +1,5 @@
+DELETE FROM asset_text WHERE kind != 'ocr';
+ALTER TABLE asset_text DROP CONSTRAINT asset_text_pkey;
+ALTER TABLE asset_text DROP COLUMN kind;
+ALTER TABLE asset_text RENAME TO ocr_cache;
+ALTER TABLE ocr_cache ADD CONSTRAINT ocr_cache_pkey PRIMARY KEY (hash);
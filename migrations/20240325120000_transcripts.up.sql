@@ -0,0 +1,5 @@
+-- The cache now holds text extracted from assets by any means, e.g. OCR or transcription
+ALTER TABLE ocr_cache RENAME TO asset_text;
+ALTER TABLE asset_text ADD COLUMN kind TEXT NOT NULL DEFAULT 'ocr';
+ALTER TABLE asset_text DROP CONSTRAINT ocr_cache_pkey;
+ALTER TABLE asset_text ADD PRIMARY KEY (hash, kind);
@@ -0,0 +1,19 @@
+CREATE TABLE asset_text (
+    hash TEXT NOT NULL,
+    kind TEXT NOT NULL DEFAULT 'ocr',
+    content TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (hash, kind)
+);
+
+ALTER TABLE documents ADD COLUMN asset_hash TEXT;
+
+INSERT INTO asset_text(hash, kind, content)
+SELECT DISTINCT ON (source_hash, kind) source_hash, kind, content
+FROM derived_documents WHERE kind IN ('ocr', 'transcript');
+
+UPDATE documents doc SET asset_hash = der.source_hash
+FROM derived_documents der
+WHERE der.document = doc.id AND der.kind IN ('ocr', 'transcript');
+
+DROP TABLE derived_documents;
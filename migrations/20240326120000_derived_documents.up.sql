@@ -0,0 +1,22 @@
+-- Content generated from documents, e.g. OCR text, transcripts, summaries and translations
+CREATE TABLE derived_documents (
+    id UUID PRIMARY KEY NOT NULL DEFAULT uuid_generate_v4(),
+    document UUID NOT NULL REFERENCES documents(id) ON DELETE CASCADE ON UPDATE CASCADE,
+    kind TEXT NOT NULL,
+    content TEXT NOT NULL,
+    provenance TEXT NOT NULL, -- What generated the content, e.g. a command or service URL
+    source_hash TEXT NOT NULL, -- Hash of the content this was derived from
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (document, kind)
+);
+
+SELECT manage_updated_at('derived_documents');
+
+INSERT INTO derived_documents(document, kind, content, provenance, source_hash)
+SELECT doc.id, txt.kind, txt.content, 'unknown', txt.hash
+FROM asset_text txt
+INNER JOIN documents doc ON doc.asset_hash = txt.hash;
+
+ALTER TABLE documents DROP COLUMN asset_hash;
+DROP TABLE asset_text;
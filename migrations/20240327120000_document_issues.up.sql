@@ -0,0 +1,9 @@
+-- Problems found while processing documents, e.g. invalid frontmatter
+CREATE TABLE document_issues (
+    id UUID PRIMARY KEY NOT NULL DEFAULT uuid_generate_v4(),
+    document UUID NOT NULL REFERENCES documents(id) ON DELETE CASCADE ON UPDATE CASCADE,
+    message TEXT NOT NULL,
+    line INT,
+    col INT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
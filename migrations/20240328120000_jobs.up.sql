@@ -0,0 +1,13 @@
+-- Background work processed by the job worker
+CREATE TABLE jobs (
+    id UUID PRIMARY KEY NOT NULL DEFAULT uuid_generate_v4(),
+    kind TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending', -- pending, running, failed, done, cancelled
+    document UUID REFERENCES documents(id) ON DELETE CASCADE ON UPDATE CASCADE,
+    error TEXT,
+    attempts INT NOT NULL DEFAULT 0,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+SELECT manage_updated_at('jobs');
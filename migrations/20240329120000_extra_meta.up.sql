@@ -0,0 +1,2 @@
+-- Frontmatter fields not known to ledgeknaw
+ALTER TABLE documents ADD COLUMN extra JSONB NOT NULL DEFAULT '{}';
@@ -0,0 +1,11 @@
+-- Responses of admin requests sent with an Idempotency-Key header
+CREATE TABLE idempotency_keys (
+    key TEXT PRIMARY KEY NOT NULL,
+    method TEXT NOT NULL,
+    path TEXT NOT NULL,
+    -- NULL while the request is in progress
+    status SMALLINT,
+    content_type TEXT,
+    body BYTEA,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,3 @@
+-- Set from the root directory configuration on every sync
+ALTER TABLE directories ADD COLUMN private BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE documents ADD COLUMN private BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,2 @@
+-- Ordering of documents within their directory
+ALTER TABLE documents ADD COLUMN weight INT;
@@ -0,0 +1,4 @@
+ALTER TABLE documents DROP COLUMN git_created;
+ALTER TABLE documents DROP COLUMN git_created_by;
+ALTER TABLE documents DROP COLUMN git_updated;
+ALTER TABLE documents DROP COLUMN git_updated_by;
@@ -0,0 +1,5 @@
+-- First and last commits of documents in git repositories
+ALTER TABLE documents ADD COLUMN git_created TIMESTAMPTZ;
+ALTER TABLE documents ADD COLUMN git_created_by TEXT;
+ALTER TABLE documents ADD COLUMN git_updated TIMESTAMPTZ;
+ALTER TABLE documents ADD COLUMN git_updated_by TEXT;
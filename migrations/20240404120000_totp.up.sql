@@ -0,0 +1,16 @@
+-- Second factor for the admin login, there is only ever one row.
+-- The secret is encrypted with TOTP_KEY and prefixed with its nonce.
+CREATE TABLE totp (
+    id BOOLEAN PRIMARY KEY NOT NULL DEFAULT TRUE CHECK (id),
+    secret BYTEA NOT NULL,
+    enabled BOOLEAN NOT NULL DEFAULT FALSE,
+    -- The time step of the last accepted code, to prevent replays
+    last_step BIGINT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Single use codes for logging in without the authenticator
+CREATE TABLE totp_recovery_codes (
+    code_hash TEXT PRIMARY KEY NOT NULL,
+    used_at TIMESTAMPTZ
+);
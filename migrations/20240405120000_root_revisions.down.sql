@@ -0,0 +1 @@
+DROP TABLE root_revisions;
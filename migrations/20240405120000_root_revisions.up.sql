@@ -0,0 +1,7 @@
+-- Revisions of git-backed root directories served instead of their working copies
+CREATE TABLE root_revisions (
+    alias TEXT PRIMARY KEY NOT NULL,
+    pinned TEXT,
+    staged TEXT,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
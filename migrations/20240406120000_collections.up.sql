@@ -0,0 +1,20 @@
+-- Named queries listed in the sidebar
+CREATE TABLE collections (
+    id UUID PRIMARY KEY NOT NULL DEFAULT uuid_generate_v4(),
+    name TEXT NOT NULL UNIQUE,
+    query TEXT NOT NULL,
+    sort TEXT NOT NULL DEFAULT 'date_desc',
+    max_documents INT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+SELECT manage_updated_at('collections');
+
+-- The documents matching the collection queries, refreshed on every sync
+CREATE TABLE collection_documents (
+    collection UUID NOT NULL REFERENCES collections(id) ON DELETE CASCADE ON UPDATE CASCADE,
+    document UUID NOT NULL REFERENCES documents(id) ON DELETE CASCADE ON UPDATE CASCADE,
+    position INT NOT NULL,
+    PRIMARY KEY (collection, document)
+);
@@ -0,0 +1,15 @@
+DROP TRIGGER bump_parents ON directories;
+DROP TRIGGER bump_parents ON documents;
+DROP FUNCTION bump_directory_parents;
+DROP FUNCTION bump_document_parents;
+
+DROP TRIGGER set_version ON collections;
+DROP TRIGGER set_version ON directories;
+DROP TRIGGER set_version ON documents;
+DROP FUNCTION set_version;
+
+ALTER TABLE collections DROP COLUMN version;
+ALTER TABLE directories DROP COLUMN version;
+ALTER TABLE documents DROP COLUMN version;
+
+DROP SEQUENCE entry_version;
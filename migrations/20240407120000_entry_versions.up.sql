@@ -0,0 +1,68 @@
+-- Versions of sidebar entries, bumped whenever an entry changes so polling
+-- clients can fetch only the entries changed since the last version they saw
+CREATE SEQUENCE entry_version;
+
+ALTER TABLE documents ADD COLUMN version BIGINT NOT NULL DEFAULT nextval('entry_version');
+ALTER TABLE directories ADD COLUMN version BIGINT NOT NULL DEFAULT nextval('entry_version');
+ALTER TABLE collections ADD COLUMN version BIGINT NOT NULL DEFAULT nextval('entry_version');
+
+CREATE OR REPLACE FUNCTION set_version() RETURNS trigger AS $$
+BEGIN
+    IF (
+        NEW IS DISTINCT FROM OLD AND
+        NEW.version IS NOT DISTINCT FROM OLD.version
+    ) THEN
+        NEW.version := nextval('entry_version');
+    END IF;
+    RETURN NEW;
+END
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER set_version BEFORE UPDATE ON documents FOR EACH ROW EXECUTE PROCEDURE set_version();
+CREATE TRIGGER set_version BEFORE UPDATE ON directories FOR EACH ROW EXECUTE PROCEDURE set_version();
+CREATE TRIGGER set_version BEFORE UPDATE ON collections FOR EACH ROW EXECUTE PROCEDURE set_version();
+
+-- Directory and collection entries include the amount of their children,
+-- so their versions are bumped whenever a child is added, removed or hidden
+
+CREATE OR REPLACE FUNCTION bump_document_parents() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'UPDATE' AND (NEW.directory, NEW.draft, NEW.private) IS NOT DISTINCT FROM (OLD.directory, OLD.draft, OLD.private) THEN
+        RETURN NULL;
+    END IF;
+
+    IF TG_OP <> 'INSERT' THEN
+        UPDATE directories SET version = nextval('entry_version') WHERE id = OLD.directory;
+        UPDATE collections SET version = nextval('entry_version')
+        WHERE id IN (SELECT collection FROM collection_documents WHERE document = OLD.id);
+    END IF;
+
+    IF TG_OP <> 'DELETE' THEN
+        UPDATE directories SET version = nextval('entry_version') WHERE id = NEW.directory;
+    END IF;
+
+    RETURN NULL;
+END
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER bump_parents AFTER INSERT OR UPDATE OR DELETE ON documents FOR EACH ROW EXECUTE PROCEDURE bump_document_parents();
+
+CREATE OR REPLACE FUNCTION bump_directory_parents() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'UPDATE' AND (NEW.parent, NEW.private) IS NOT DISTINCT FROM (OLD.parent, OLD.private) THEN
+        RETURN NULL;
+    END IF;
+
+    IF TG_OP <> 'INSERT' THEN
+        UPDATE directories SET version = nextval('entry_version') WHERE id = OLD.parent;
+    END IF;
+
+    IF TG_OP <> 'DELETE' THEN
+        UPDATE directories SET version = nextval('entry_version') WHERE id = NEW.parent;
+    END IF;
+
+    RETURN NULL;
+END
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER bump_parents AFTER INSERT OR UPDATE OR DELETE ON directories FOR EACH ROW EXECUTE PROCEDURE bump_directory_parents();
@@ -0,0 +1,6 @@
+DROP TRIGGER invalidate_listing ON directories;
+DROP TRIGGER invalidate_listing ON documents;
+DROP FUNCTION invalidate_directory_listing;
+DROP FUNCTION invalidate_document_listing;
+
+DROP TABLE sidebar_cache;
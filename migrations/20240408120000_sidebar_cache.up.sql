@@ -0,0 +1,48 @@
+-- Serialized sidebar listings of directories, one per access level.
+-- Rows are removed whenever a listing changes and rebuilt after syncing.
+CREATE TABLE sidebar_cache (
+    directory UUID NOT NULL REFERENCES directories(id) ON DELETE CASCADE ON UPDATE CASCADE,
+    private BOOLEAN NOT NULL,
+    entries JSONB NOT NULL,
+    PRIMARY KEY (directory, private)
+);
+
+CREATE OR REPLACE FUNCTION invalidate_document_listing() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'UPDATE' AND NEW IS NOT DISTINCT FROM OLD THEN
+        RETURN NULL;
+    END IF;
+
+    IF TG_OP <> 'INSERT' THEN
+        DELETE FROM sidebar_cache WHERE directory = OLD.directory;
+    END IF;
+
+    IF TG_OP <> 'DELETE' THEN
+        DELETE FROM sidebar_cache WHERE directory = NEW.directory;
+    END IF;
+
+    RETURN NULL;
+END
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER invalidate_listing AFTER INSERT OR UPDATE OR DELETE ON documents FOR EACH ROW EXECUTE PROCEDURE invalidate_document_listing();
+
+CREATE OR REPLACE FUNCTION invalidate_directory_listing() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'UPDATE' AND NEW IS NOT DISTINCT FROM OLD THEN
+        RETURN NULL;
+    END IF;
+
+    IF TG_OP <> 'INSERT' THEN
+        DELETE FROM sidebar_cache WHERE directory = OLD.parent;
+    END IF;
+
+    IF TG_OP <> 'DELETE' THEN
+        DELETE FROM sidebar_cache WHERE directory = NEW.parent;
+    END IF;
+
+    RETURN NULL;
+END
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER invalidate_listing AFTER INSERT OR UPDATE OR DELETE ON directories FOR EACH ROW EXECUTE PROCEDURE invalidate_directory_listing();
@@ -0,0 +1,6 @@
+DROP INDEX documents_custom_id;
+DROP INDEX documents_updated_at;
+DROP INDEX directories_parent_name;
+DROP INDEX documents_directory_file_name;
+DROP INDEX directories_path;
+DROP INDEX documents_path;
@@ -0,0 +1,20 @@
+-- Lookups by path when syncing and watching
+CREATE INDEX documents_path ON documents(path);
+CREATE INDEX directories_path ON directories(path);
+
+-- Listings of directories
+CREATE INDEX documents_directory_file_name ON documents(directory, file_name);
+CREATE INDEX directories_parent_name ON directories(parent, name);
+
+CREATE INDEX documents_updated_at ON documents(updated_at);
+
+-- Custom IDs resolve to a single document. Of documents already sharing one,
+-- the oldest keeps it.
+UPDATE documents SET custom_id = NULL WHERE id IN (
+    SELECT id FROM (
+        SELECT id, ROW_NUMBER() OVER (PARTITION BY custom_id ORDER BY created_at, id) AS n
+        FROM documents WHERE custom_id IS NOT NULL
+    ) dup WHERE n > 1
+);
+
+CREATE UNIQUE INDEX documents_custom_id ON documents(custom_id);
@@ -0,0 +1,9 @@
+-- Feature flags switched on the admin API, overriding the config
+CREATE TABLE feature_flags (
+    name TEXT PRIMARY KEY NOT NULL,
+    enabled BOOLEAN NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+SELECT manage_updated_at('feature_flags');
@@ -0,0 +1 @@
+DROP TABLE telemetry_reports;
@@ -0,0 +1,10 @@
+-- Usage statistics sent to the configured endpoint, kept so operators can see what was shared
+CREATE TABLE telemetry_reports (
+    id UUID PRIMARY KEY NOT NULL DEFAULT uuid_generate_v4(),
+    url TEXT NOT NULL,
+    payload JSONB NOT NULL,
+    -- NULL if the request failed before a response was received
+    status SMALLINT,
+    error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1 @@
+ALTER TABLE directories DROP CONSTRAINT directories_root_alias;
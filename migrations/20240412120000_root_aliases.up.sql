@@ -0,0 +1,13 @@
+-- Root directories are identified by their alias. Of roots already sharing one,
+-- the oldest keeps it and the others get theirs on the next sync.
+UPDATE directories SET alias = NULL WHERE id IN (
+    SELECT id FROM (
+        SELECT id, ROW_NUMBER() OVER (PARTITION BY alias ORDER BY created_at, id) AS n
+        FROM directories WHERE parent IS NULL AND alias IS NOT NULL
+    ) dup WHERE n > 1
+);
+
+-- Deferred so aliases can be swapped between roots in a single statement
+ALTER TABLE directories ADD CONSTRAINT directories_root_alias
+    EXCLUDE USING btree (alias WITH =) WHERE (parent IS NULL)
+    DEFERRABLE INITIALLY DEFERRED;
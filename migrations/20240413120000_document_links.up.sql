@@ -0,0 +1,9 @@
+-- Links between documents, resolved from the markdown links in their content
+CREATE TABLE document_links (
+    document UUID NOT NULL REFERENCES documents(id) ON DELETE CASCADE,
+    target UUID NOT NULL REFERENCES documents(id) ON DELETE CASCADE,
+    PRIMARY KEY (document, target)
+);
+
+-- Backlinks
+CREATE INDEX document_links_target ON document_links(target);
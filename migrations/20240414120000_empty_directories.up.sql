@@ -0,0 +1,3 @@
+-- Directories without documents at any depth, e.g. folders of attachments.
+-- Updated after every sync.
+ALTER TABLE directories ADD COLUMN empty BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1 @@
+DROP TABLE unresolved_links;
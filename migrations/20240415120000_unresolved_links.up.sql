@@ -0,0 +1,6 @@
+-- Links to documents or files that do not exist, found along with the document links
+CREATE TABLE unresolved_links (
+    document UUID NOT NULL REFERENCES documents(id) ON DELETE CASCADE,
+    link TEXT NOT NULL,
+    PRIMARY KEY (document, link)
+);
@@ -0,0 +1,8 @@
+-- Broken links found by the last link check
+CREATE TABLE link_issues (
+    document UUID NOT NULL REFERENCES documents(id) ON DELETE CASCADE,
+    link TEXT NOT NULL,
+    message TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (document, link)
+);
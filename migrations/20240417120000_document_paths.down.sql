@@ -0,0 +1,3 @@
+DROP TABLE document_paths;
+DROP INDEX documents_file_id;
+ALTER TABLE documents DROP COLUMN dev, DROP COLUMN inode;
@@ -0,0 +1,9 @@
+-- Device and inode of the file, the same for all of its hard links
+ALTER TABLE documents ADD COLUMN dev BIGINT, ADD COLUMN inode BIGINT;
+CREATE INDEX documents_file_id ON documents(dev, inode);
+
+-- Other paths of documents, through hard links or bind mounts within the roots
+CREATE TABLE document_paths (
+    path TEXT PRIMARY KEY NOT NULL,
+    document UUID NOT NULL REFERENCES documents(id) ON DELETE CASCADE
+);
@@ -0,0 +1,9 @@
+-- Anonymous view counts of documents per day
+CREATE TABLE document_views (
+    document UUID NOT NULL REFERENCES documents(id) ON DELETE CASCADE,
+    day DATE NOT NULL,
+    views BIGINT NOT NULL,
+    PRIMARY KEY (document, day)
+);
+
+CREATE INDEX document_views_day ON document_views(day);
@@ -0,0 +1,9 @@
+-- Documents pinned by the admin or by anonymous clients identified by a cookie
+CREATE TABLE bookmarks (
+    owner UUID NOT NULL,
+    document UUID NOT NULL REFERENCES documents(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (owner, document)
+);
+
+CREATE INDEX bookmarks_document ON bookmarks(document);
@@ -0,0 +1,16 @@
+-- Comments on documents, optionally anchored to a range of their content
+CREATE TABLE annotations (
+    id UUID PRIMARY KEY NOT NULL DEFAULT uuid_generate_v4(),
+    document UUID NOT NULL REFERENCES documents(id) ON DELETE CASCADE,
+    start_offset INTEGER,
+    end_offset INTEGER,
+    quote TEXT,
+    body TEXT NOT NULL,
+    author TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK ((start_offset IS NULL) = (end_offset IS NULL)),
+    CHECK (start_offset >= 0 AND end_offset >= start_offset)
+);
+
+CREATE INDEX annotations_document ON annotations(document);
@@ -0,0 +1,35 @@
+CREATE OR REPLACE FUNCTION invalidate_document_listing() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'UPDATE' AND NEW IS NOT DISTINCT FROM OLD THEN
+        RETURN NULL;
+    END IF;
+
+    IF TG_OP <> 'INSERT' THEN
+        DELETE FROM sidebar_cache WHERE directory = OLD.directory;
+    END IF;
+
+    IF TG_OP <> 'DELETE' THEN
+        DELETE FROM sidebar_cache WHERE directory = NEW.directory;
+    END IF;
+
+    RETURN NULL;
+END
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION invalidate_directory_listing() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'UPDATE' AND NEW IS NOT DISTINCT FROM OLD THEN
+        RETURN NULL;
+    END IF;
+
+    IF TG_OP <> 'INSERT' THEN
+        DELETE FROM sidebar_cache WHERE directory = OLD.parent;
+    END IF;
+
+    IF TG_OP <> 'DELETE' THEN
+        DELETE FROM sidebar_cache WHERE directory = NEW.parent;
+    END IF;
+
+    RETURN NULL;
+END
+$$ LANGUAGE plpgsql;
@@ -0,0 +1,70 @@
+-- Listings hold the document and subdirectory counts of the directories in them, so
+-- changing the contents of a directory also invalidates the listing of its parent.
+CREATE OR REPLACE FUNCTION invalidate_document_listing() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'UPDATE' AND NEW IS NOT DISTINCT FROM OLD THEN
+        RETURN NULL;
+    END IF;
+
+    IF TG_OP <> 'INSERT' THEN
+        DELETE FROM sidebar_cache WHERE directory = OLD.directory;
+    END IF;
+
+    IF TG_OP <> 'DELETE' THEN
+        DELETE FROM sidebar_cache WHERE directory = NEW.directory;
+    END IF;
+
+    -- Only changes of what is counted affect the listing of the parent
+    IF TG_OP = 'UPDATE'
+        AND NEW.directory = OLD.directory
+        AND NEW.draft = OLD.draft
+        AND NEW.private = OLD.private THEN
+        RETURN NULL;
+    END IF;
+
+    IF TG_OP <> 'INSERT' THEN
+        DELETE FROM sidebar_cache WHERE directory = (SELECT parent FROM directories WHERE id = OLD.directory);
+    END IF;
+
+    IF TG_OP <> 'DELETE' THEN
+        DELETE FROM sidebar_cache WHERE directory = (SELECT parent FROM directories WHERE id = NEW.directory);
+    END IF;
+
+    RETURN NULL;
+END
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION invalidate_directory_listing() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'UPDATE' AND NEW IS NOT DISTINCT FROM OLD THEN
+        RETURN NULL;
+    END IF;
+
+    IF TG_OP <> 'INSERT' THEN
+        DELETE FROM sidebar_cache WHERE directory = OLD.parent;
+    END IF;
+
+    IF TG_OP <> 'DELETE' THEN
+        DELETE FROM sidebar_cache WHERE directory = NEW.parent;
+    END IF;
+
+    IF TG_OP = 'UPDATE'
+        AND NEW.parent IS NOT DISTINCT FROM OLD.parent
+        AND NEW.private = OLD.private THEN
+        RETURN NULL;
+    END IF;
+
+    IF TG_OP <> 'INSERT' THEN
+        DELETE FROM sidebar_cache WHERE directory = (SELECT parent FROM directories WHERE id = OLD.parent);
+    END IF;
+
+    IF TG_OP <> 'DELETE' THEN
+        DELETE FROM sidebar_cache WHERE directory = (SELECT parent FROM directories WHERE id = NEW.parent);
+    END IF;
+
+    RETURN NULL;
+END
+$$ LANGUAGE plpgsql;
+
+-- Listings cached before may hold stale counts
+DELETE FROM sidebar_cache;
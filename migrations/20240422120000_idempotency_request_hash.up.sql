@@ -0,0 +1,3 @@
+-- SHA-256 of the request body, so keys reused for another body are rejected.
+-- NULL for keys claimed before it was recorded.
+ALTER TABLE idempotency_keys ADD COLUMN request_hash TEXT;
@@ -0,0 +1,2 @@
+DROP INDEX bookmarks_owner_updated_at;
+ALTER TABLE bookmarks DROP COLUMN updated_at;
@@ -0,0 +1,6 @@
+-- Last time the owner bookmarked the document, as the cookie of anonymous owners
+-- is renewed then. Owners not seen for longer than the cookie lasts are removed.
+ALTER TABLE bookmarks ADD COLUMN updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW();
+UPDATE bookmarks SET updated_at = created_at;
+
+CREATE INDEX bookmarks_owner_updated_at ON bookmarks(owner, updated_at);
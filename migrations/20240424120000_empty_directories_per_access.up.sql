@@ -0,0 +1,4 @@
+-- Directories without documents at any depth visible to requests with a session or
+-- API key, which see private documents and drafts. `empty` only counts the published
+-- documents visible to anyone. Updated after every sync.
+ALTER TABLE directories ADD COLUMN empty_private BOOLEAN NOT NULL DEFAULT FALSE;
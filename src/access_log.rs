@@ -0,0 +1,68 @@
+//! A line per request on the `ledgeknaw::access_log` target, with the method, path, status,
+//! latency, size of the response body and whether the request was authenticated.
+
+use crate::{auth::Access, config::AccessLogConfig};
+use axum::{
+    body::HttpBody,
+    extract::{Request, State},
+    http::header,
+    middleware::Next,
+    response::Response,
+};
+use std::{sync::Arc, time::Instant};
+use tracing::info;
+
+/// Marks the responses with the static files of the front end.
+#[derive(Debug, Clone, Copy)]
+pub struct StaticAsset;
+
+/// Logs at INFO once the response is ready. The latency does not include the time
+/// spent streaming the body, and its size is only known when it is not streamed.
+pub async fn access_log(
+    config: State<Arc<AccessLogConfig>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let started = Instant::now();
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+
+    if config.exclude.iter().any(|prefix| path.starts_with(prefix)) {
+        return next.run(request).await;
+    }
+
+    let response = next.run(request).await;
+    if config.exclude_assets && response.extensions().get::<StaticAsset>().is_some() {
+        return response;
+    }
+
+    let latency = started.elapsed();
+    let bytes = response.body().size_hint().exact().or_else(|| {
+        response
+            .headers()
+            .get(header::CONTENT_LENGTH)?
+            .to_str()
+            .ok()?
+            .parse()
+            .ok()
+    });
+    let access = response
+        .extensions()
+        .get::<Access>()
+        .copied()
+        .unwrap_or_default();
+
+    info!(
+        method = %method,
+        path,
+        status = response.status().as_u16(),
+        latency_ms = latency.as_secs_f64() * 1000.0,
+        bytes,
+        authenticated = access.authenticated(),
+        session = access.session.map(tracing::field::display),
+        "{method} {path} {}",
+        response.status().as_u16()
+    );
+
+    response
+}
@@ -0,0 +1,131 @@
+//! Comments on documents for reviewing them. Annotations are anchored to a range of
+//! characters of the document content as served, or to the whole document. The quoted
+//! text is kept so clients can find the range again once the document changes.
+
+use crate::{auth::Access, error::LedgeknawError, state::DocumentService};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+pub mod db;
+
+/// Longest body and quote accepted, in characters
+const MAX_BODY: usize = 10_000;
+
+const MAX_AUTHOR: usize = 100;
+
+#[derive(Debug, Serialize)]
+pub struct Annotation {
+    pub id: uuid::Uuid,
+    pub document: uuid::Uuid,
+    /// Character offset of the start of the range, none for the whole document
+    #[serde(rename = "start")]
+    pub start_offset: Option<i32>,
+    /// Character offset after the end of the range
+    #[serde(rename = "end")]
+    pub end_offset: Option<i32>,
+    /// The text of the range when the annotation was made
+    pub quote: Option<String>,
+    pub body: String,
+    pub author: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AnnotationPayload {
+    pub start: Option<i32>,
+    pub end: Option<i32>,
+    pub quote: Option<String>,
+    pub body: String,
+    pub author: String,
+}
+
+impl AnnotationPayload {
+    fn validate(&self) -> Result<(), LedgeknawError> {
+        match (self.start, self.end) {
+            (None, None) => {}
+            (Some(start), Some(end)) if 0 <= start && start <= end => {}
+            (Some(_), Some(_)) => {
+                return Err(LedgeknawError::InvalidParameter(
+                    "the range must start at or before its end".to_string(),
+                ))
+            }
+            _ => {
+                return Err(LedgeknawError::InvalidParameter(
+                    "start and end must be given together".to_string(),
+                ))
+            }
+        }
+
+        if self.body.trim().is_empty() {
+            return Err(LedgeknawError::InvalidParameter(
+                "empty annotation".to_string(),
+            ));
+        }
+        if self.author.trim().is_empty() {
+            return Err(LedgeknawError::InvalidParameter(
+                "missing author".to_string(),
+            ));
+        }
+
+        let too_long = self.body.chars().count() > MAX_BODY
+            || self
+                .quote
+                .as_ref()
+                .is_some_and(|quote| quote.chars().count() > MAX_BODY)
+            || self.author.chars().count() > MAX_AUTHOR;
+        if too_long {
+            return Err(LedgeknawError::InvalidParameter(format!(
+                "bodies and quotes are limited to {MAX_BODY} characters, authors to {MAX_AUTHOR}"
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+impl DocumentService {
+    /// Annotations of the document with the ID or custom ID, if it is visible.
+    pub async fn list_annotations(
+        &self,
+        id: String,
+        access: Access,
+    ) -> Result<Vec<Annotation>, LedgeknawError> {
+        let Some(document) = self.resolve_document_id(&id, access).await? else {
+            return Err(LedgeknawError::NotFound(id));
+        };
+        self.annotations.list(document).await
+    }
+
+    pub async fn add_annotation(
+        &self,
+        id: String,
+        annotation: AnnotationPayload,
+        access: Access,
+    ) -> Result<Annotation, LedgeknawError> {
+        annotation.validate()?;
+        let Some(document) = self.resolve_document_id(&id, access).await? else {
+            return Err(LedgeknawError::NotFound(id));
+        };
+        self.annotations.insert(document, &annotation).await
+    }
+
+    pub async fn update_annotation(
+        &self,
+        id: uuid::Uuid,
+        annotation: AnnotationPayload,
+    ) -> Result<Annotation, LedgeknawError> {
+        annotation.validate()?;
+        self.annotations
+            .update(id, &annotation)
+            .await?
+            .ok_or_else(|| LedgeknawError::NotFound(id.to_string()))
+    }
+
+    pub async fn delete_annotation(&self, id: uuid::Uuid) -> Result<(), LedgeknawError> {
+        if !self.annotations.delete(id).await? {
+            return Err(LedgeknawError::NotFound(id.to_string()));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,91 @@
+use super::{Annotation, AnnotationPayload};
+use crate::{db::retry_read, error::LedgeknawError};
+use sqlx::PgPool;
+
+#[derive(Debug, Clone)]
+pub struct AnnotationDb {
+    pool: sqlx::PgPool,
+}
+
+impl AnnotationDb {
+    pub async fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Annotations of the document in the order they appear in it, followed by
+    /// those on the whole document, oldest first.
+    pub async fn list(&self, document: uuid::Uuid) -> Result<Vec<Annotation>, LedgeknawError> {
+        retry_read(|| {
+            sqlx::query_as!(
+                Annotation,
+                r#"
+                SELECT id, document, start_offset, end_offset, quote, body, author, created_at, updated_at
+                FROM annotations
+                WHERE document = $1
+                ORDER BY start_offset NULLS LAST, end_offset, created_at
+            "#,
+                document
+            )
+            .fetch_all(&self.pool)
+        })
+        .await
+        .map_err(LedgeknawError::from)
+    }
+
+    pub async fn insert(
+        &self,
+        document: uuid::Uuid,
+        annotation: &AnnotationPayload,
+    ) -> Result<Annotation, LedgeknawError> {
+        Ok(sqlx::query_as!(
+            Annotation,
+            r#"
+            INSERT INTO annotations(document, start_offset, end_offset, quote, body, author)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id, document, start_offset, end_offset, quote, body, author, created_at, updated_at
+        "#,
+            document,
+            annotation.start,
+            annotation.end,
+            annotation.quote,
+            annotation.body,
+            annotation.author
+        )
+        .fetch_one(&self.pool)
+        .await?)
+    }
+
+    /// Replace the anchor, body and author of the annotation, keeping its document.
+    pub async fn update(
+        &self,
+        id: uuid::Uuid,
+        annotation: &AnnotationPayload,
+    ) -> Result<Option<Annotation>, LedgeknawError> {
+        Ok(sqlx::query_as!(
+            Annotation,
+            r#"
+            UPDATE annotations
+            SET start_offset = $2, end_offset = $3, quote = $4, body = $5, author = $6, updated_at = NOW()
+            WHERE id = $1
+            RETURNING id, document, start_offset, end_offset, quote, body, author, created_at, updated_at
+        "#,
+            id,
+            annotation.start,
+            annotation.end,
+            annotation.quote,
+            annotation.body,
+            annotation.author
+        )
+        .fetch_optional(&self.pool)
+        .await?)
+    }
+
+    /// Returns whether the annotation existed.
+    pub async fn delete(&self, id: uuid::Uuid) -> Result<bool, LedgeknawError> {
+        Ok(sqlx::query!("DELETE FROM annotations WHERE id = $1", id)
+            .execute(&self.pool)
+            .await?
+            .rows_affected()
+            > 0)
+    }
+}
@@ -0,0 +1,160 @@
+//! Moving an instance between hosts with `migrate export` and `migrate import`.
+//!
+//! An archive is gzipped JSON containing the rows of the tables below keyed by column
+//! and, optionally, the documents and assets. It does not depend on the database,
+//! so it can be imported by any backend reading the same format.
+
+use crate::{error::LedgeknawError, update::VERSION};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{DateTime, Utc};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs::File, io::BufReader, path::Path};
+use tracing::{info, warn};
+
+use self::db::ArchiveDb;
+
+pub mod db;
+pub mod dump;
+
+/// Bumped on changes to the archive layout, not to the tables.
+const FORMAT: u32 = 1;
+
+/// Tables moved with the instance, in insertion order. Sessions, idempotency keys,
+/// jobs, telemetry and the sidebar cache are local to the host and left out.
+pub const TABLES: [&str; 12] = [
+    "directories",
+    "documents",
+    "document_aliases",
+    "document_issues",
+    "derived_documents",
+    "collections",
+    "collection_documents",
+    "root_revisions",
+    "feature_flags",
+    "totp",
+    "totp_recovery_codes",
+    "annotations",
+];
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Archive {
+    pub format: u32,
+    /// Version of Ledgeknaw the archive was exported with
+    pub version: String,
+    pub exported_at: DateTime<Utc>,
+    /// Rows of every table as objects keyed by column
+    pub tables: BTreeMap<String, Vec<serde_json::Value>>,
+    /// Empty unless exported with the content
+    pub files: Vec<ArchivedFile>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchivedFile {
+    /// Canonicalised path, as stored in the database
+    pub path: String,
+    /// Base64 encoded contents
+    pub content: String,
+}
+
+/// Write the archive of the instance to `path`, including the documents
+/// and assets if `content` is set.
+pub async fn export(db: &ArchiveDb, path: &str, content: bool) -> Result<(), LedgeknawError> {
+    let mut tables = BTreeMap::new();
+    for table in TABLES {
+        let serde_json::Value::Array(rows) = db.export_table(table).await? else {
+            return Err(LedgeknawError::Archive(format!(
+                "expected the rows of {table}"
+            )));
+        };
+        info!("Exporting {} rows of {table}", rows.len());
+        tables.insert(table.to_string(), rows);
+    }
+
+    let mut files = vec![];
+    if content {
+        for path in db.list_content_paths().await? {
+            match tokio::fs::read(&path).await {
+                Ok(content) => files.push(ArchivedFile {
+                    content: STANDARD.encode(content),
+                    path,
+                }),
+                Err(e) => warn!("Skipping {path}: {e}"),
+            }
+        }
+        info!("Exporting {} files", files.len());
+    }
+
+    let archive = Archive {
+        format: FORMAT,
+        version: VERSION.to_string(),
+        exported_at: Utc::now(),
+        tables,
+        files,
+    };
+
+    let file = File::create(path)?;
+    let mut encoder = GzEncoder::new(file, Compression::default());
+    serde_json::to_writer(&mut encoder, &archive)?;
+    encoder.finish()?;
+
+    info!("Exported the instance to {path}");
+    Ok(())
+}
+
+/// Import the archive at `path` into an empty database. Files in the archive are
+/// written to their original paths unless a file already exists there.
+pub async fn import(db: &ArchiveDb, path: &str) -> Result<(), LedgeknawError> {
+    let archive: Archive =
+        serde_json::from_reader(GzDecoder::new(BufReader::new(File::open(path)?)))?;
+
+    if archive.format != FORMAT {
+        return Err(LedgeknawError::Archive(format!(
+            "unsupported archive format {}, expected {FORMAT}",
+            archive.format
+        )));
+    }
+
+    if let Some(table) = archive
+        .tables
+        .keys()
+        .find(|table| !TABLES.contains(&table.as_str()))
+    {
+        return Err(LedgeknawError::Archive(format!("unknown table {table}")));
+    }
+
+    if !db.is_empty().await? {
+        return Err(LedgeknawError::Archive(
+            "the database already contains documents, import into an empty one".to_string(),
+        ));
+    }
+
+    info!(
+        "Importing the archive exported with version {} at {}",
+        archive.version, archive.exported_at
+    );
+
+    let tables = TABLES
+        .iter()
+        .filter_map(|table| Some((*table, archive.tables.get(*table)?.as_slice())))
+        .collect::<Vec<_>>();
+    db.import_tables(&tables, false).await?;
+
+    for file in archive.files {
+        let path = Path::new(&file.path);
+        if path.exists() {
+            warn!("Not overwriting existing file {}", file.path);
+            continue;
+        }
+        let content = STANDARD
+            .decode(&file.content)
+            .map_err(|e| LedgeknawError::Archive(format!("{}: {e}", file.path)))?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(path, content).await?;
+    }
+
+    info!("Imported the instance from {path}");
+    Ok(())
+}
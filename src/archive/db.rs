@@ -0,0 +1,102 @@
+use crate::error::LedgeknawError;
+use sqlx::PgPool;
+
+#[derive(Debug, Clone)]
+pub struct ArchiveDb {
+    pool: sqlx::PgPool,
+}
+
+impl ArchiveDb {
+    pub async fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// The rows of the table as a JSON array of objects keyed by column.
+    /// The table name must come from [super::TABLES].
+    pub async fn export_table(&self, table: &str) -> Result<serde_json::Value, LedgeknawError> {
+        let rows: serde_json::Value = sqlx::query_scalar(&format!(
+            "SELECT COALESCE(json_agg(t), '[]') FROM {table} t"
+        ))
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(rows)
+    }
+
+    /// Paths of the documents and the assets they describe.
+    pub async fn list_content_paths(&self) -> Result<Vec<String>, LedgeknawError> {
+        Ok(
+            sqlx::query!("SELECT path, asset FROM documents ORDER BY path")
+                .fetch_all(&self.pool)
+                .await?
+                .into_iter()
+                .flat_map(|el| std::iter::once(el.path).chain(el.asset))
+                .collect(),
+        )
+    }
+
+    pub async fn is_empty(&self) -> Result<bool, LedgeknawError> {
+        Ok(sqlx::query_scalar!(
+            r#"SELECT NOT EXISTS (SELECT 1 FROM directories) AND NOT EXISTS (SELECT 1 FROM documents) AS "empty!""#
+        )
+        .fetch_one(&self.pool)
+        .await?)
+    }
+
+    /// Insert the exported rows of all tables in a single transaction, keeping their IDs.
+    /// Only the columns present in the archive are inserted, so archives of older
+    /// schemas get the defaults of columns added since. With `replace` the rows already
+    /// in the tables are deleted first, in reverse order.
+    pub async fn import_tables(
+        &self,
+        tables: &[(&str, &[serde_json::Value])],
+        replace: bool,
+    ) -> Result<(), LedgeknawError> {
+        let mut tx = self.pool.begin().await?;
+
+        if replace {
+            for (table, _) in tables.iter().rev() {
+                sqlx::query(&format!("DELETE FROM {table}"))
+                    .execute(&mut *tx)
+                    .await?;
+            }
+        }
+
+        for (table, rows) in tables {
+            let Some(first) = rows.first().and_then(|row| row.as_object()) else {
+                continue;
+            };
+
+            if let Some(column) = first
+                .keys()
+                .find(|column| !column.chars().all(|c| c.is_ascii_lowercase() || c == '_'))
+            {
+                return Err(LedgeknawError::Archive(format!(
+                    "invalid column {column} in table {table}"
+                )));
+            }
+            let columns = first.keys().cloned().collect::<Vec<_>>().join(", ");
+
+            sqlx::query(&format!(
+                "INSERT INTO {table} ({columns}) SELECT {columns} FROM jsonb_populate_recordset(NULL::{table}, $1)"
+            ))
+            .bind(serde_json::Value::Array(rows.to_vec()))
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        // Versions are kept, so new ones have to start after the imported ones
+        sqlx::query!(
+            "SELECT setval('entry_version', GREATEST(
+                (SELECT MAX(version) FROM documents),
+                (SELECT MAX(version) FROM directories),
+                (SELECT MAX(version) FROM collections),
+                1
+             ))"
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+}
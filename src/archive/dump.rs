@@ -0,0 +1,272 @@
+//! Dumps of the directories, documents, their links and everything derived from them as
+//! a single JSON or ndjson file, for moving the rows between database backends. Unlike
+//! archives, dumps hold no files or TOTP secrets and can replace the rows of a database
+//! already in use.
+
+use super::db::ArchiveDb;
+use crate::{error::LedgeknawError, job::SYNC, state::DocumentService, update::VERSION};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+use tracing::info;
+
+/// Bumped on changes to the dump layout, not to the tables.
+const FORMAT: u32 = 1;
+
+/// Tables in a dump, in insertion order.
+pub const TABLES: [&str; 16] = [
+    "directories",
+    "documents",
+    "document_paths",
+    "document_aliases",
+    "document_issues",
+    "document_links",
+    "unresolved_links",
+    "link_issues",
+    "derived_documents",
+    "collections",
+    "collection_documents",
+    "root_revisions",
+    "feature_flags",
+    "document_views",
+    "bookmarks",
+    "annotations",
+];
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum DumpFormat {
+    /// A single object with the rows of every table
+    #[default]
+    Json,
+    /// The header on the first line, followed by a line per row
+    Ndjson,
+}
+
+impl DumpFormat {
+    /// Ndjson for `.ndjson` and `.jsonl` files, JSON otherwise.
+    pub fn from_path(path: &str) -> Self {
+        match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+            Some("ndjson" | "jsonl") => Self::Ndjson,
+            _ => Self::Json,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Ndjson => "ndjson",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::Ndjson => "application/x-ndjson",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DumpHeader {
+    pub format: u32,
+    /// Version of Ledgeknaw the dump was made with
+    pub version: String,
+    pub dumped_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Dump {
+    #[serde(flatten)]
+    pub header: DumpHeader,
+    /// Rows of every table as objects keyed by column
+    pub tables: BTreeMap<String, Vec<serde_json::Value>>,
+}
+
+/// A row on its own line of an ndjson dump.
+#[derive(Debug, Serialize, Deserialize)]
+struct DumpLine {
+    table: String,
+    row: serde_json::Value,
+}
+
+impl Dump {
+    pub fn write(&self, format: DumpFormat, mut writer: impl Write) -> Result<(), LedgeknawError> {
+        match format {
+            DumpFormat::Json => serde_json::to_writer(&mut writer, self)?,
+            DumpFormat::Ndjson => {
+                serde_json::to_writer(&mut writer, &self.header)?;
+                writeln!(writer)?;
+                // In insertion order, so the rows can be inserted as they are read
+                let tables = TABLES
+                    .iter()
+                    .filter_map(|table| Some((*table, self.tables.get(*table)?)));
+                for (table, rows) in tables {
+                    for row in rows {
+                        serde_json::to_writer(
+                            &mut writer,
+                            &DumpLine {
+                                table: table.to_string(),
+                                row: row.clone(),
+                            },
+                        )?;
+                        writeln!(writer)?;
+                    }
+                }
+            }
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn read(format: DumpFormat, reader: impl BufRead) -> Result<Self, LedgeknawError> {
+        if format == DumpFormat::Json {
+            return Ok(serde_json::from_reader(reader)?);
+        }
+
+        let mut lines = reader.lines();
+        let Some(header) = lines.next() else {
+            return Err(LedgeknawError::InvalidParameter("empty dump".to_string()));
+        };
+        let header = serde_json::from_str(&header?)?;
+
+        let mut tables = BTreeMap::<_, Vec<_>>::new();
+        for line in lines {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let DumpLine { table, row } = serde_json::from_str(&line)?;
+            tables.entry(table).or_default().push(row);
+        }
+
+        Ok(Self { header, tables })
+    }
+}
+
+impl DocumentService {
+    /// Restore the dump over the rows in the database and queue a sync,
+    /// which brings them up to date with the files.
+    pub async fn restore_dump(
+        &self,
+        dump: Dump,
+        replace: bool,
+    ) -> Result<BTreeMap<String, usize>, LedgeknawError> {
+        let rows = restore(&self.archive, dump, replace).await?;
+        self.enqueue_job(SYNC, None).await?;
+        Ok(rows)
+    }
+}
+
+/// The rows of all tables in [TABLES].
+pub async fn dump(db: &ArchiveDb) -> Result<Dump, LedgeknawError> {
+    let mut tables = BTreeMap::new();
+    for table in TABLES {
+        let serde_json::Value::Array(rows) = db.export_table(table).await? else {
+            return Err(LedgeknawError::Archive(format!(
+                "expected the rows of {table}"
+            )));
+        };
+        info!("Dumping {} rows of {table}", rows.len());
+        tables.insert(table.to_string(), rows);
+    }
+
+    Ok(Dump {
+        header: DumpHeader {
+            format: FORMAT,
+            version: VERSION.to_string(),
+            dumped_at: Utc::now(),
+        },
+        tables,
+    })
+}
+
+/// Insert the rows of the dump, keeping their IDs. Unless `replace` is set the database
+/// has to be empty, otherwise the rows of the tables are deleted first. Returns the number
+/// of rows restored per table.
+pub async fn restore(
+    db: &ArchiveDb,
+    dump: Dump,
+    replace: bool,
+) -> Result<BTreeMap<String, usize>, LedgeknawError> {
+    if dump.header.format != FORMAT {
+        return Err(LedgeknawError::InvalidParameter(format!(
+            "unsupported dump format {}, expected {FORMAT}",
+            dump.header.format
+        )));
+    }
+
+    if let Some(table) = dump
+        .tables
+        .keys()
+        .find(|table| !TABLES.contains(&table.as_str()))
+    {
+        return Err(LedgeknawError::InvalidParameter(format!(
+            "unknown table {table}"
+        )));
+    }
+
+    if !replace && !db.is_empty().await? {
+        return Err(LedgeknawError::InvalidParameter(
+            "the database already contains documents, restore into an empty one or replace them"
+                .to_string(),
+        ));
+    }
+
+    info!(
+        "Restoring the dump made with version {} at {}",
+        dump.header.version, dump.header.dumped_at
+    );
+
+    let tables = TABLES
+        .iter()
+        .map(|table| {
+            let rows = dump
+                .tables
+                .get(*table)
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            (*table, rows)
+        })
+        .collect::<Vec<_>>();
+    db.import_tables(&tables, replace).await?;
+
+    Ok(tables
+        .into_iter()
+        .map(|(table, rows)| (table.to_string(), rows.len()))
+        .collect())
+}
+
+/// Write the dump to `path`, as ndjson or JSON depending on its extension unless `format` is given.
+pub async fn dump_to_file(
+    db: &ArchiveDb,
+    path: &str,
+    format: Option<DumpFormat>,
+) -> Result<(), LedgeknawError> {
+    let format = format.unwrap_or_else(|| DumpFormat::from_path(path));
+    dump(db)
+        .await?
+        .write(format, BufWriter::new(File::create(path)?))?;
+    info!("Dumped the database to {path}");
+    Ok(())
+}
+
+/// Restore the dump at `path`, read as ndjson or JSON depending on its extension
+/// unless `format` is given.
+pub async fn restore_from_file(
+    db: &ArchiveDb,
+    path: &str,
+    format: Option<DumpFormat>,
+    replace: bool,
+) -> Result<(), LedgeknawError> {
+    let format = format.unwrap_or_else(|| DumpFormat::from_path(path));
+    let dump = Dump::read(format, BufReader::new(File::open(path)?))?;
+    let rows = restore(db, dump, replace).await?;
+    info!("Restored {} rows from {path}", rows.values().sum::<usize>());
+    Ok(())
+}
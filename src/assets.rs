@@ -0,0 +1,70 @@
+//! The front end, served from `dist` in the working directory or, when built with the
+//! `embed` feature, from the files included in the binary.
+
+use crate::access_log::StaticAsset;
+use axum::{response::Response, Router};
+
+#[cfg(feature = "embed")]
+mod embedded {
+    include!(concat!(env!("OUT_DIR"), "/assets.rs"));
+}
+
+/// Serves the files, falling back to `index.html` for unknown paths
+/// so the front end can handle its own routes.
+#[cfg(not(feature = "embed"))]
+pub fn router<S>() -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    use tower_http::services::{ServeDir, ServeFile};
+
+    Router::new()
+        .fallback_service(ServeDir::new("dist").fallback(ServeFile::new("dist/index.html")))
+        .layer(axum::middleware::map_response(mark_asset))
+}
+
+/// Serves the files, falling back to `index.html` for unknown paths
+/// so the front end can handle its own routes.
+#[cfg(feature = "embed")]
+pub fn router<S>() -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    Router::new()
+        .fallback(serve_embedded)
+        .layer(axum::middleware::map_response(mark_asset))
+}
+
+#[cfg(feature = "embed")]
+async fn serve_embedded(uri: axum::http::Uri) -> axum::response::Response {
+    use axum::{
+        http::{header, StatusCode},
+        response::IntoResponse,
+    };
+
+    let find = |path: &str| {
+        embedded::ASSETS
+            .iter()
+            .find(|(name, _)| *name == path)
+            .map(|(_, content)| *content)
+    };
+
+    let path = uri.path().trim_start_matches('/');
+    let (path, content) = match find(path) {
+        Some(content) => (path, content),
+        None => match find("index.html") {
+            Some(content) => ("index.html", content),
+            None => return StatusCode::NOT_FOUND.into_response(),
+        },
+    };
+
+    let mime = mime_guess::from_path(path).first_or_octet_stream();
+
+    ([(header::CONTENT_TYPE, mime.as_ref())], content).into_response()
+}
+
+/// So the access log can leave out the files.
+async fn mark_asset(mut response: Response) -> Response {
+    response.extensions_mut().insert(StaticAsset);
+    response
+}
@@ -0,0 +1,453 @@
+use crate::{error::LedgeknawError, state::DocumentService};
+use argon2::{Argon2, PasswordHash, PasswordVerifier};
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap},
+    middleware::Next,
+    response::Response,
+};
+use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite};
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use subtle::{Choice, ConstantTimeEq};
+use tracing::warn;
+
+pub mod db;
+pub mod totp;
+
+use db::AuthDb;
+
+pub const SESSION_COOKIE: &str = "SID";
+
+/// How many days sessions stay valid after login
+pub const SESSION_DAYS: i64 = 7;
+
+/// Failed logins allowed before a client gets locked out
+const FREE_LOGIN_ATTEMPTS: u32 = 3;
+
+/// Lockout after the first failure past the free attempts, doubled on every further failure
+const BASE_LOCKOUT: Duration = Duration::from_secs(2);
+
+const MAX_LOCKOUT: Duration = Duration::from_secs(60 * 60);
+
+/// What a request is allowed to see, resolved by [authenticate].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Access {
+    /// Whether documents in private directories are visible
+    pub private: bool,
+
+    /// The admin session of the request, if it has one
+    pub session: Option<uuid::Uuid>,
+}
+
+impl Access {
+    /// Whether the request carries a valid session or API key
+    pub fn authenticated(&self) -> bool {
+        self.private
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Session {
+    pub id: uuid::Uuid,
+    pub user_agent: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TotpStatus {
+    pub enabled: bool,
+    pub recovery_codes_left: i64,
+}
+
+/// Returned when starting 2FA enrollment, to be entered in an authenticator app.
+#[derive(Debug, Serialize)]
+pub struct TotpEnrollment {
+    /// Base32 encoded secret
+    pub secret: String,
+    pub uri: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Auth {
+    pub db: AuthDb,
+
+    /// Argon2 hash of the admin password, login is disabled if not present
+    admin_pw_hash: Option<Arc<String>>,
+
+    /// Keys granting access to private directories and admin routes
+    api_keys: Arc<Vec<String>>,
+
+    /// Failed logins by client address
+    attempts: Arc<Mutex<HashMap<IpAddr, LoginAttempts>>>,
+
+    /// Key for encrypting TOTP secrets, 2FA cannot be enrolled if not present
+    totp_key: Option<Arc<[u8; 32]>>,
+}
+
+#[derive(Debug)]
+struct LoginAttempts {
+    failures: u32,
+    last_failure: Instant,
+    locked_until: Option<Instant>,
+}
+
+impl Auth {
+    /// Panics if the admin password hash is not a valid PHC string
+    /// or the TOTP key is not 32 base64 encoded bytes.
+    pub fn new(
+        db: AuthDb,
+        admin_pw_hash: Option<String>,
+        api_keys: Vec<String>,
+        totp_key: Option<String>,
+    ) -> Self {
+        if let Some(hash) = &admin_pw_hash {
+            PasswordHash::new(hash).expect("invalid admin password hash");
+        }
+
+        let totp_key = totp_key.map(|key| {
+            let key = base64::engine::general_purpose::STANDARD
+                .decode(key.trim())
+                .expect("TOTP key is not valid base64");
+            let key: [u8; 32] = key.try_into().expect("TOTP key must be 32 bytes");
+            Arc::new(key)
+        });
+
+        Self {
+            db,
+            admin_pw_hash: admin_pw_hash.map(Arc::new),
+            api_keys: Arc::new(api_keys),
+            attempts: Arc::default(),
+            totp_key,
+        }
+    }
+
+    /// Verify the admin password and create a session. If 2FA is enabled, a TOTP
+    /// or recovery code is required as well.
+    /// Clients failing too many times are locked out for exponentially longer periods.
+    pub async fn login(
+        &self,
+        password: &str,
+        code: Option<&str>,
+        client: IpAddr,
+        user_agent: Option<&str>,
+    ) -> Result<Session, LedgeknawError> {
+        let Some(hash) = self.admin_pw_hash.as_deref() else {
+            return Err(LedgeknawError::NotFound("login is disabled".to_string()));
+        };
+
+        // Counted as failed until it succeeds, so concurrent attempts cannot all get
+        // past the lockout before any of them failed
+        self.begin_attempt(client)?;
+
+        let hash = PasswordHash::new(hash).map_err(LedgeknawError::Argon)?;
+
+        if Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_err()
+        {
+            return Err(LedgeknawError::Unauthorized("invalid password".to_string()));
+        }
+
+        if let Some(totp) = self.db.get_enabled_totp().await? {
+            let Some(code) = code else {
+                // The password was right, the client is asked for the code
+                self.refund_attempt(client);
+                return Err(LedgeknawError::Unauthorized("missing 2FA code".to_string()));
+            };
+
+            if !self.verify_second_factor(totp, code).await? {
+                return Err(LedgeknawError::Unauthorized("invalid 2FA code".to_string()));
+            }
+        }
+
+        self.attempts
+            .lock()
+            .expect("login attempts poisoned")
+            .remove(&client);
+
+        self.db
+            .insert_session(
+                user_agent,
+                Utc::now() + chrono::Duration::days(SESSION_DAYS),
+            )
+            .await
+    }
+
+    /// Accepts either a TOTP code or an unused recovery code, which gets used up.
+    async fn verify_second_factor(
+        &self,
+        (secret, last_step): (Vec<u8>, Option<i64>),
+        code: &str,
+    ) -> Result<bool, LedgeknawError> {
+        if code.trim().chars().all(|c| c.is_ascii_digit()) {
+            let secret = totp::decrypt(self.totp_key()?, &secret)?;
+            return match totp::verify(&secret, code, Utc::now().timestamp(), last_step) {
+                Some(step) => self.db.update_totp_step(step).await,
+                None => Ok(false),
+            };
+        }
+
+        self.db
+            .use_recovery_code(&totp::hash_recovery_code(code))
+            .await
+    }
+
+    fn totp_key(&self) -> Result<&[u8; 32], LedgeknawError> {
+        self.totp_key
+            .as_deref()
+            .ok_or_else(|| LedgeknawError::Totp("TOTP_KEY is not set".to_string()))
+    }
+
+    /// Generate a new secret. 2FA is enabled only once a code is confirmed with
+    /// [Auth::confirm_totp], so a failed enrollment cannot lock out the admin.
+    pub async fn enroll_totp(&self, issuer: &str) -> Result<TotpEnrollment, LedgeknawError> {
+        let secret = totp::generate_secret();
+        let encrypted = totp::encrypt(self.totp_key()?, &secret)?;
+
+        if !self.db.insert_pending_totp(&encrypted).await? {
+            return Err(LedgeknawError::Conflict(
+                "2FA is already enabled".to_string(),
+            ));
+        }
+
+        Ok(TotpEnrollment {
+            secret: totp::base32(&secret),
+            uri: totp::provisioning_uri(&secret, issuer),
+        })
+    }
+
+    /// Enable 2FA if the code matches the pending secret.
+    /// Returns the recovery codes, which are not retrievable afterwards.
+    pub async fn confirm_totp(&self, code: &str) -> Result<Vec<String>, LedgeknawError> {
+        let Some(encrypted) = self.db.get_pending_totp().await? else {
+            return Err(LedgeknawError::NotFound(
+                "pending 2FA enrollment".to_string(),
+            ));
+        };
+
+        let secret = totp::decrypt(self.totp_key()?, &encrypted)?;
+
+        let Some(step) = totp::verify(&secret, code, Utc::now().timestamp(), None) else {
+            return Err(LedgeknawError::Unauthorized("invalid 2FA code".to_string()));
+        };
+
+        let (codes, hashes) = totp::generate_recovery_codes();
+        self.db.enable_totp(step, &hashes).await?;
+
+        Ok(codes)
+    }
+
+    /// Disable 2FA, requires a TOTP or recovery code.
+    pub async fn disable_totp(&self, code: &str) -> Result<(), LedgeknawError> {
+        let Some(totp) = self.db.get_enabled_totp().await? else {
+            return Err(LedgeknawError::NotFound("2FA is not enabled".to_string()));
+        };
+
+        if !self.verify_second_factor(totp, code).await? {
+            return Err(LedgeknawError::Unauthorized("invalid 2FA code".to_string()));
+        }
+
+        self.db.delete_totp().await
+    }
+
+    /// Fail if the client is locked out, otherwise count the attempt as failed.
+    /// Clients are locked out once they failed more than [FREE_LOGIN_ATTEMPTS] times.
+    fn begin_attempt(&self, client: IpAddr) -> Result<(), LedgeknawError> {
+        let mut attempts = self.attempts.lock().expect("login attempts poisoned");
+        let now = Instant::now();
+
+        // Forget clients which have not failed in a while
+        attempts.retain(|_, attempt| now - attempt.last_failure < MAX_LOCKOUT);
+
+        let attempt = attempts.entry(client).or_insert(LoginAttempts {
+            failures: 0,
+            last_failure: now,
+            locked_until: None,
+        });
+
+        if let Some(locked_until) = attempt.locked_until.filter(|until| *until > now) {
+            let retry_after = (locked_until - now).as_secs() + 1;
+            return Err(LedgeknawError::TooManyRequests(retry_after));
+        }
+
+        attempt.failures += 1;
+        attempt.last_failure = now;
+
+        if attempt.failures > FREE_LOGIN_ATTEMPTS {
+            let exponent = (attempt.failures - FREE_LOGIN_ATTEMPTS - 1).min(16);
+            let lockout = (BASE_LOCKOUT * 2u32.pow(exponent)).min(MAX_LOCKOUT);
+            attempt.locked_until = Some(now + lockout);
+            warn!(
+                "Locking out {client} for {}s after {} failed logins",
+                lockout.as_secs(),
+                attempt.failures
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Uncount an attempt which did not fail, lifting the lockout it caused.
+    fn refund_attempt(&self, client: IpAddr) {
+        let mut attempts = self.attempts.lock().expect("login attempts poisoned");
+        if let Some(attempt) = attempts.get_mut(&client) {
+            attempt.failures = attempt.failures.saturating_sub(1);
+            if attempt.failures <= FREE_LOGIN_ATTEMPTS {
+                attempt.locked_until = None;
+            }
+        }
+    }
+
+    /// Compared in constant time with every key so neither a key nor which one
+    /// matched can be guessed byte by byte.
+    fn is_api_key(&self, token: &str) -> bool {
+        let matched = self.api_keys.iter().fold(Choice::from(0), |matched, key| {
+            matched | key.as_bytes().ct_eq(token.as_bytes())
+        });
+        matched.into()
+    }
+
+    /// Resolve the access of a request from its bearer token or session cookie.
+    /// Unknown tokens are rejected, unknown or expired sessions are ignored.
+    pub async fn resolve(&self, headers: &HeaderMap) -> Result<Access, LedgeknawError> {
+        let mut access = Access::default();
+
+        if let Some(value) = headers.get(header::AUTHORIZATION) {
+            let token = value
+                .to_str()
+                .ok()
+                .and_then(|value| value.strip_prefix("Bearer "))
+                .map(str::trim);
+
+            match token {
+                Some(token) if self.is_api_key(token) => {
+                    access.private = true;
+                }
+                _ => return Err(LedgeknawError::Unauthorized("invalid API key".to_string())),
+            }
+        }
+
+        let session = CookieJar::from_headers(headers)
+            .get(SESSION_COOKIE)
+            .and_then(|cookie| uuid::Uuid::from_str(cookie.value()).ok());
+
+        if let Some(session) = session {
+            if self.db.session_valid(session).await? {
+                access.private = true;
+                access.session = Some(session);
+            }
+        }
+
+        Ok(access)
+    }
+}
+
+/// Middleware resolving the [Access] of the request. Requests without
+/// credentials get public access.
+pub async fn authenticate(
+    state: State<DocumentService>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, LedgeknawError> {
+    let access = state.auth.resolve(request.headers()).await?;
+    request.extensions_mut().insert(access);
+    Ok(with_access(next.run(request).await, access))
+}
+
+/// Same as [authenticate], but rejects requests without a valid session or API key.
+pub async fn require_auth(
+    state: State<DocumentService>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, LedgeknawError> {
+    let access = state.auth.resolve(request.headers()).await?;
+
+    if !access.authenticated() {
+        return Err(LedgeknawError::Unauthorized(
+            "missing session or API key".to_string(),
+        ));
+    }
+
+    request.extensions_mut().insert(access);
+    Ok(with_access(next.run(request).await, access))
+}
+
+/// The access of the request is kept on the response for the access log.
+fn with_access(mut response: Response, access: Access) -> Response {
+    response.extensions_mut().insert(access);
+    response
+}
+
+/// The cookie is scoped to the base path, `/` when served from the root.
+pub fn session_cookie(session: &Session, base_path: &str) -> Cookie<'static> {
+    Cookie::build((SESSION_COOKIE, session.id.to_string()))
+        .path(cookie_path(base_path))
+        .http_only(true)
+        .same_site(SameSite::Strict)
+        .max_age(cookie::time::Duration::days(SESSION_DAYS))
+        .build()
+}
+
+pub fn removal_cookie(base_path: &str) -> Cookie<'static> {
+    Cookie::build((SESSION_COOKIE, ""))
+        .path(cookie_path(base_path))
+        .build()
+}
+
+pub(crate) fn cookie_path(base_path: &str) -> String {
+    if base_path.is_empty() {
+        "/".to_string()
+    } else {
+        base_path.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn auth() -> Auth {
+        let pool = sqlx::PgPool::connect_lazy("postgres://localhost/unused").unwrap();
+        Auth::new(AuthDb::new(pool).await, None, vec![], None)
+    }
+
+    #[tokio::test]
+    async fn locks_out_after_the_free_attempts() {
+        let auth = auth().await;
+        let client = IpAddr::from([203, 0, 113, 7]);
+
+        // Attempts in flight count as failed, so only one gets past the free ones
+        for _ in 0..=FREE_LOGIN_ATTEMPTS {
+            auth.begin_attempt(client).unwrap();
+        }
+        assert!(matches!(
+            auth.begin_attempt(client),
+            Err(LedgeknawError::TooManyRequests(_))
+        ));
+
+        // Other clients are not affected
+        auth.begin_attempt(IpAddr::from([203, 0, 113, 8])).unwrap();
+    }
+
+    #[tokio::test]
+    async fn refunded_attempts_lift_the_lockout() {
+        let auth = auth().await;
+        let client = IpAddr::from([203, 0, 113, 7]);
+
+        for _ in 0..=FREE_LOGIN_ATTEMPTS {
+            auth.begin_attempt(client).unwrap();
+        }
+        auth.refund_attempt(client);
+        auth.begin_attempt(client).unwrap();
+    }
+}
@@ -0,0 +1,180 @@
+use super::{Session, TotpStatus};
+use crate::error::LedgeknawError;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+#[derive(Debug, Clone)]
+pub struct AuthDb {
+    pool: sqlx::PgPool,
+}
+
+impl AuthDb {
+    pub async fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Create a session and remove any expired ones.
+    pub async fn insert_session(
+        &self,
+        user_agent: Option<&str>,
+        expires_at: DateTime<Utc>,
+    ) -> Result<Session, LedgeknawError> {
+        sqlx::query!("DELETE FROM sessions WHERE expires_at < NOW()")
+            .execute(&self.pool)
+            .await?;
+
+        sqlx::query_as!(
+            Session,
+            "INSERT INTO sessions(user_agent, expires_at) VALUES($1, $2) RETURNING *",
+            user_agent,
+            expires_at
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(LedgeknawError::from)
+    }
+
+    /// Whether the session exists and has not expired.
+    pub async fn session_valid(&self, id: uuid::Uuid) -> Result<bool, LedgeknawError> {
+        Ok(sqlx::query!(
+            "SELECT id FROM sessions WHERE id = $1 AND expires_at > NOW()",
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .is_some())
+    }
+
+    pub async fn list_sessions(&self) -> Result<Vec<Session>, LedgeknawError> {
+        sqlx::query_as!(
+            Session,
+            "SELECT * FROM sessions WHERE expires_at > NOW() ORDER BY created_at DESC"
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(LedgeknawError::from)
+    }
+
+    /// Returns whether the session existed.
+    pub async fn delete_session(&self, id: uuid::Uuid) -> Result<bool, LedgeknawError> {
+        let result = sqlx::query!("DELETE FROM sessions WHERE id = $1", id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn get_totp_status(&self) -> Result<TotpStatus, LedgeknawError> {
+        let enabled = sqlx::query!("SELECT enabled FROM totp")
+            .fetch_optional(&self.pool)
+            .await?
+            .is_some_and(|row| row.enabled);
+
+        let recovery_codes_left = sqlx::query!(
+            r#"SELECT COUNT(*) AS "count!" FROM totp_recovery_codes WHERE used_at IS NULL"#
+        )
+        .fetch_one(&self.pool)
+        .await?
+        .count;
+
+        Ok(TotpStatus {
+            enabled,
+            recovery_codes_left,
+        })
+    }
+
+    /// Returns the encrypted secret and the last accepted step if 2FA is enabled.
+    pub async fn get_enabled_totp(&self) -> Result<Option<(Vec<u8>, Option<i64>)>, LedgeknawError> {
+        Ok(
+            sqlx::query!("SELECT secret, last_step FROM totp WHERE enabled")
+                .fetch_optional(&self.pool)
+                .await?
+                .map(|row| (row.secret, row.last_step)),
+        )
+    }
+
+    /// Returns the encrypted secret of an enrollment which was not confirmed yet.
+    pub async fn get_pending_totp(&self) -> Result<Option<Vec<u8>>, LedgeknawError> {
+        Ok(sqlx::query!("SELECT secret FROM totp WHERE NOT enabled")
+            .fetch_optional(&self.pool)
+            .await?
+            .map(|row| row.secret))
+    }
+
+    /// Store a new secret pending confirmation, replacing any previous pending one.
+    /// Returns false if 2FA is already enabled.
+    pub async fn insert_pending_totp(&self, secret: &[u8]) -> Result<bool, LedgeknawError> {
+        let result = sqlx::query!(
+            "INSERT INTO totp(secret) VALUES($1)
+             ON CONFLICT(id) DO UPDATE SET secret = $1, created_at = NOW() WHERE NOT totp.enabled",
+            secret
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Enable the pending secret and replace the recovery codes.
+    pub async fn enable_totp(
+        &self,
+        step: i64,
+        recovery_code_hashes: &[String],
+    ) -> Result<(), LedgeknawError> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query!("UPDATE totp SET enabled = TRUE, last_step = $1", step)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query!("DELETE FROM totp_recovery_codes")
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query!(
+            "INSERT INTO totp_recovery_codes(code_hash) SELECT * FROM UNNEST($1::TEXT[])",
+            recovery_code_hashes
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Returns false if the step was already used, which happens when
+    /// the same code is submitted concurrently.
+    pub async fn update_totp_step(&self, step: i64) -> Result<bool, LedgeknawError> {
+        let result = sqlx::query!(
+            "UPDATE totp SET last_step = $1 WHERE last_step IS NULL OR last_step < $1",
+            step
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Mark the recovery code as used. Returns whether it was valid and unused.
+    pub async fn use_recovery_code(&self, code_hash: &str) -> Result<bool, LedgeknawError> {
+        let result = sqlx::query!(
+            "UPDATE totp_recovery_codes SET used_at = NOW() WHERE code_hash = $1 AND used_at IS NULL",
+            code_hash
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn delete_totp(&self) -> Result<(), LedgeknawError> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query!("DELETE FROM totp").execute(&mut *tx).await?;
+
+        sqlx::query!("DELETE FROM totp_recovery_codes")
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+}
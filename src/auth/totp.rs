@@ -0,0 +1,235 @@
+//! Time-based one-time passwords (RFC 6238) for the admin login.
+
+use crate::error::LedgeknawError;
+use aes_gcm::{
+    aead::{Aead, KeyInit},
+    Aes256Gcm, Nonce,
+};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+/// Seconds a code is valid for
+const STEP: i64 = 30;
+
+const DIGITS: u32 = 6;
+
+/// Steps before and after the current one whose codes are accepted,
+/// to allow for clock drift
+const DRIFT: i64 = 1;
+
+const SECRET_LEN: usize = 20;
+
+const NONCE_LEN: usize = 12;
+
+pub const RECOVERY_CODES: usize = 10;
+
+pub fn generate_secret() -> Vec<u8> {
+    let mut secret = vec![0; SECRET_LEN];
+    rand::thread_rng().fill_bytes(&mut secret);
+    secret
+}
+
+/// Returns the step the code is valid for, if any. Steps up to and including
+/// `last_step` are rejected so codes cannot be used twice.
+pub fn verify(secret: &[u8], code: &str, now: i64, last_step: Option<i64>) -> Option<i64> {
+    let code = code.trim().parse::<u32>().ok()?;
+    let current = now / STEP;
+
+    (current - DRIFT..=current + DRIFT)
+        .filter(|step| last_step.is_none_or(|last| *step > last))
+        .find(|step| code_at(secret, *step) == code)
+}
+
+fn code_at(secret: &[u8], step: i64) -> u32 {
+    let mut mac =
+        <Hmac<Sha1> as Mac>::new_from_slice(secret).expect("HMAC accepts keys of any size");
+    mac.update(&step.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+
+    let offset = (hash[hash.len() - 1] & 0xf) as usize;
+    let binary = u32::from_be_bytes([
+        hash[offset] & 0x7f,
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]);
+
+    binary % 10u32.pow(DIGITS)
+}
+
+/// The URI authenticator apps use to enroll the secret, usually shown as a QR code.
+pub fn provisioning_uri(secret: &[u8], issuer: &str) -> String {
+    let issuer = issuer.replace(|c: char| !c.is_ascii_alphanumeric(), "");
+    format!(
+        "otpauth://totp/{issuer}:admin?secret={}&issuer={issuer}&digits={DIGITS}&period={STEP}",
+        base32(secret)
+    )
+}
+
+/// Unpadded RFC 4648 base32, the encoding authenticator apps expect secrets in.
+pub fn base32(data: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+    let mut out = String::with_capacity(data.len().div_ceil(5) * 8);
+    let mut buffer = 0u16;
+    let mut bits = 0;
+
+    for byte in data {
+        buffer = (buffer << 8) | *byte as u16;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+
+    if bits > 0 {
+        out.push(ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+
+    out
+}
+
+/// Encrypt the secret for storage. The nonce is prepended to the ciphertext.
+pub fn encrypt(key: &[u8; 32], secret: &[u8]) -> Result<Vec<u8>, LedgeknawError> {
+    let mut nonce = [0; NONCE_LEN];
+    rand::thread_rng().fill_bytes(&mut nonce);
+
+    let ciphertext = Aes256Gcm::new(key.into())
+        .encrypt(Nonce::from_slice(&nonce), secret)
+        .map_err(|_| LedgeknawError::Totp("encryption failed".to_string()))?;
+
+    Ok([nonce.as_slice(), &ciphertext].concat())
+}
+
+pub fn decrypt(key: &[u8; 32], stored: &[u8]) -> Result<Vec<u8>, LedgeknawError> {
+    if stored.len() < NONCE_LEN {
+        return Err(LedgeknawError::Totp("invalid stored secret".to_string()));
+    }
+
+    let (nonce, ciphertext) = stored.split_at(NONCE_LEN);
+
+    Aes256Gcm::new(key.into())
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| LedgeknawError::Totp("decryption failed, was TOTP_KEY changed?".to_string()))
+}
+
+/// Generate single use recovery codes, returned along with the hashes to store.
+pub fn generate_recovery_codes() -> (Vec<String>, Vec<String>) {
+    let mut rng = rand::thread_rng();
+
+    (0..RECOVERY_CODES)
+        .map(|_| {
+            let mut bytes = [0; 10];
+            rng.fill_bytes(&mut bytes);
+            let code = base32(&bytes).to_lowercase();
+            let hash = hash_recovery_code(&code);
+            (code, hash)
+        })
+        .unzip()
+}
+
+/// Codes are random, so a plain hash suffices.
+pub fn hash_recovery_code(code: &str) -> String {
+    hex::encode(Sha256::digest(code.trim().to_lowercase().as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The SHA-1 secret of RFC 6238 Appendix B
+    const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn matches_rfc_6238_vectors() {
+        // The RFC lists 8 digit codes, of which 6 digit codes are the last digits
+        let vectors = [
+            (59, 94287082),
+            (1111111109, 7081804),
+            (1111111111, 14050471),
+            (1234567890, 89005924),
+            (2000000000, 69279037),
+            (20000000000, 65353130),
+        ];
+
+        for (time, code) in vectors {
+            assert_eq!(code_at(RFC_SECRET, time / STEP), code % 1_000_000, "{time}");
+        }
+    }
+
+    #[test]
+    fn verifies_codes_within_the_drift() {
+        let now = 1111111111;
+        let step = now / STEP;
+        let code = |step: i64| format!("{:06}", code_at(RFC_SECRET, step));
+
+        assert_eq!(verify(RFC_SECRET, &code(step), now, None), Some(step));
+        assert_eq!(
+            verify(RFC_SECRET, &code(step - 1), now, None),
+            Some(step - 1)
+        );
+        assert_eq!(
+            verify(RFC_SECRET, &code(step + 1), now, None),
+            Some(step + 1)
+        );
+        assert_eq!(verify(RFC_SECRET, &code(step - 2), now, None), None);
+        assert_eq!(verify(RFC_SECRET, "not a code", now, None), None);
+    }
+
+    #[test]
+    fn rejects_replayed_codes() {
+        let now = 1234567890;
+        let code = format!("{:06}", code_at(RFC_SECRET, now / STEP));
+
+        let step = verify(RFC_SECRET, &code, now, None).unwrap();
+        assert_eq!(verify(RFC_SECRET, &code, now, Some(step)), None);
+        assert_eq!(verify(RFC_SECRET, &code, now + STEP, Some(step)), None);
+
+        // Codes of earlier steps are rejected once a later one was used
+        let previous = format!("{:06}", code_at(RFC_SECRET, now / STEP - 1));
+        assert_eq!(verify(RFC_SECRET, &previous, now, Some(step)), None);
+    }
+
+    #[test]
+    fn encodes_rfc_4648_base32() {
+        let vectors = [
+            ("", ""),
+            ("f", "MY"),
+            ("fo", "MZXQ"),
+            ("foo", "MZXW6"),
+            ("foob", "MZXW6YQ"),
+            ("fooba", "MZXW6YTB"),
+            ("foobar", "MZXW6YTBOI"),
+        ];
+
+        for (data, encoded) in vectors {
+            assert_eq!(base32(data.as_bytes()), encoded);
+        }
+        assert_eq!(base32(RFC_SECRET), "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+    }
+
+    #[test]
+    fn decrypts_what_was_encrypted() {
+        let key = [7; 32];
+        let secret = generate_secret();
+
+        let stored = encrypt(&key, &secret).unwrap();
+        assert_eq!(decrypt(&key, &stored).unwrap(), secret);
+        assert!(decrypt(&[8; 32], &stored).is_err());
+        assert!(decrypt(&key, &stored[..NONCE_LEN - 1]).is_err());
+    }
+
+    #[test]
+    fn hashes_recovery_codes_ignoring_case_and_spaces() {
+        let (codes, hashes) = generate_recovery_codes();
+
+        assert_eq!(codes.len(), RECOVERY_CODES);
+        assert_eq!(
+            hash_recovery_code(&format!(" {} ", codes[0].to_uppercase())),
+            hashes[0]
+        );
+    }
+}
@@ -0,0 +1,120 @@
+//! Documents pinned to the sidebar. Requests with a session or API key share the
+//! bookmarks of the admin, anonymous clients get their own, tied to a cookie set
+//! when they bookmark their first document.
+
+use crate::{
+    auth::{self, Access},
+    error::LedgeknawError,
+    state::DocumentService,
+};
+use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::{net::IpAddr, str::FromStr};
+use tracing::debug;
+
+pub mod db;
+
+pub const BOOKMARK_COOKIE: &str = "BID";
+
+/// How many days the cookie of anonymous clients lasts after their last bookmark.
+/// Their bookmarks are removed once it expired.
+const COOKIE_DAYS: i32 = 365;
+
+/// Bookmarks an owner can have
+pub const MAX_BOOKMARKS: i64 = 500;
+
+/// Bookmarks of all anonymous clients together, so they cannot fill the table
+/// by starting over without the cookie
+pub const MAX_ANONYMOUS_BOOKMARKS: i64 = 100_000;
+
+/// Anonymous clients an address can start bookmarking as per hour
+pub const NEW_OWNERS_PER_HOUR: u32 = 10;
+
+/// Owner of the bookmarks of authenticated requests
+const ADMIN: uuid::Uuid = uuid::Uuid::nil();
+
+#[derive(Debug, Serialize)]
+pub struct Bookmark {
+    pub id: uuid::Uuid,
+    pub name: String,
+    pub title: Option<String>,
+    pub custom_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// The owner of the bookmarks of the request, if it has any.
+pub fn owner(access: Access, jar: &CookieJar) -> Option<uuid::Uuid> {
+    if access.authenticated() {
+        return Some(ADMIN);
+    }
+    jar.get(BOOKMARK_COOKIE)
+        .and_then(|cookie| uuid::Uuid::from_str(cookie.value()).ok())
+        .filter(|owner| *owner != ADMIN)
+}
+
+/// The cookie is scoped to the base path like the session cookie.
+pub fn bookmark_cookie(owner: uuid::Uuid, base_path: &str) -> Cookie<'static> {
+    Cookie::build((BOOKMARK_COOKIE, owner.to_string()))
+        .path(auth::cookie_path(base_path))
+        .http_only(true)
+        .same_site(SameSite::Strict)
+        .max_age(cookie::time::Duration::days(COOKIE_DAYS.into()))
+        .build()
+}
+
+impl DocumentService {
+    pub async fn list_bookmarks(
+        &self,
+        owner: Option<uuid::Uuid>,
+        access: Access,
+    ) -> Result<Vec<Bookmark>, LedgeknawError> {
+        match owner {
+            Some(owner) => self.bookmarks.list(owner, access).await,
+            None => Ok(vec![]),
+        }
+    }
+
+    /// Bookmark the document with the ID or custom ID, if it is visible to the request.
+    /// Anonymous clients without bookmarks yet get a new owner, which is returned.
+    pub async fn add_bookmark(
+        &self,
+        owner: Option<uuid::Uuid>,
+        id: String,
+        access: Access,
+        client: IpAddr,
+    ) -> Result<uuid::Uuid, LedgeknawError> {
+        let Some(document) = self.resolve_document_id(&id, access).await? else {
+            return Err(LedgeknawError::NotFound(id));
+        };
+
+        let owner = match owner {
+            Some(owner) => owner,
+            None => {
+                self.bookmark_limiter.check(client)?;
+                let pruned = self.bookmarks.prune(COOKIE_DAYS).await?;
+                if pruned > 0 {
+                    debug!("Removed {pruned} bookmarks of expired anonymous clients");
+                }
+                uuid::Uuid::new_v4()
+            }
+        };
+
+        if owner != ADMIN && self.bookmarks.count_anonymous().await? >= MAX_ANONYMOUS_BOOKMARKS {
+            return Err(LedgeknawError::InvalidParameter(
+                "no more documents can be bookmarked anonymously".to_string(),
+            ));
+        }
+
+        if !self
+            .bookmarks
+            .insert(owner, document, MAX_BOOKMARKS)
+            .await?
+        {
+            return Err(LedgeknawError::InvalidParameter(format!(
+                "at most {MAX_BOOKMARKS} documents can be bookmarked"
+            )));
+        }
+        Ok(owner)
+    }
+}
@@ -0,0 +1,136 @@
+use super::{Bookmark, ADMIN};
+use crate::{auth::Access, db::retry_read, error::LedgeknawError};
+use sqlx::PgPool;
+
+#[derive(Debug, Clone)]
+pub struct BookmarkDb {
+    pool: sqlx::PgPool,
+}
+
+impl BookmarkDb {
+    pub async fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// The bookmarked documents of the owner in the order they were bookmarked.
+    pub async fn list(
+        &self,
+        owner: uuid::Uuid,
+        access: Access,
+    ) -> Result<Vec<Bookmark>, LedgeknawError> {
+        retry_read(|| {
+            sqlx::query_as!(
+                Bookmark,
+                r#"
+                SELECT d.id, d.file_name AS name, d.title, d.custom_id, b.created_at
+                FROM bookmarks b
+                INNER JOIN documents d ON d.id = b.document
+                WHERE b.owner = $1
+                AND (NOT d.draft OR $3) AND (NOT d.private OR $2)
+                ORDER BY b.created_at, d.file_name
+            "#,
+                owner,
+                access.private,
+                access.authenticated()
+            )
+            .fetch_all(&self.pool)
+        })
+        .await
+        .map_err(LedgeknawError::from)
+    }
+
+    /// Bookmark the document unless the owner already has `max` bookmarks.
+    /// Returns whether the document is bookmarked afterwards.
+    pub async fn insert(
+        &self,
+        owner: uuid::Uuid,
+        document: uuid::Uuid,
+        max: i64,
+    ) -> Result<bool, LedgeknawError> {
+        let inserted = sqlx::query!(
+            r#"
+            INSERT INTO bookmarks(owner, document)
+            SELECT $1, $2
+            WHERE (SELECT COUNT(*) FROM bookmarks WHERE owner = $1) < $3
+            ON CONFLICT (owner, document) DO UPDATE SET updated_at = NOW()
+        "#,
+            owner,
+            document,
+            max
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected()
+            > 0;
+
+        if inserted {
+            return Ok(true);
+        }
+
+        Ok(sqlx::query!(
+            r#"SELECT EXISTS (SELECT 1 FROM bookmarks WHERE owner = $1 AND document = $2) AS "exists!""#,
+            owner,
+            document
+        )
+        .fetch_one(&self.pool)
+        .await?
+        .exists)
+    }
+
+    /// Number of bookmarks of anonymous owners.
+    pub async fn count_anonymous(&self) -> Result<i64, LedgeknawError> {
+        Ok(sqlx::query!(
+            r#"SELECT COUNT(*) AS "count!" FROM bookmarks WHERE owner <> $1"#,
+            ADMIN
+        )
+        .fetch_one(&self.pool)
+        .await?
+        .count)
+    }
+
+    /// Remove the bookmarks of anonymous owners who bookmarked nothing in the last
+    /// `days`, by when their cookie expired. Returns the number of bookmarks removed.
+    pub async fn prune(&self, days: i32) -> Result<u64, LedgeknawError> {
+        Ok(sqlx::query!(
+            r#"
+            DELETE FROM bookmarks
+            WHERE owner IN (
+                SELECT owner FROM bookmarks
+                WHERE owner <> $1
+                GROUP BY owner
+                HAVING MAX(updated_at) < NOW() - MAKE_INTERVAL(days => $2)
+            )
+        "#,
+            ADMIN,
+            days
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected())
+    }
+
+    /// Remove the bookmark of the document with the ID or custom ID.
+    pub async fn delete(&self, owner: uuid::Uuid, id: &str) -> Result<(), LedgeknawError> {
+        match uuid::Uuid::parse_str(id) {
+            Ok(document) => {
+                sqlx::query!(
+                    "DELETE FROM bookmarks WHERE owner = $1 AND document = $2",
+                    owner,
+                    document
+                )
+                .execute(&self.pool)
+                .await?;
+            }
+            Err(_) => {
+                sqlx::query!(
+                    "DELETE FROM bookmarks WHERE owner = $1 AND document IN (SELECT id FROM documents WHERE custom_id = $2)",
+                    owner,
+                    id
+                )
+                .execute(&self.pool)
+                .await?;
+            }
+        }
+        Ok(())
+    }
+}
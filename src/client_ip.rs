@@ -0,0 +1,126 @@
+//! The address of the client of a request, which rate limits and login lockouts are
+//! keyed on. Behind a reverse proxy every request comes from the proxy, so the address
+//! is taken from `X-Forwarded-For` when the request comes from a trusted proxy.
+
+use crate::state::DocumentService;
+use async_trait::async_trait;
+use axum::{
+    extract::{ConnectInfo, FromRequestParts},
+    http::{request::Parts, HeaderMap},
+};
+use std::net::{IpAddr, SocketAddr};
+
+const FORWARDED_FOR: &str = "x-forwarded-for";
+
+/// Extracts the address of the client, see [client_ip].
+#[derive(Debug, Clone, Copy)]
+pub struct ClientIp(pub IpAddr);
+
+#[async_trait]
+impl FromRequestParts<DocumentService> for ClientIp {
+    type Rejection = <ConnectInfo<SocketAddr> as FromRequestParts<DocumentService>>::Rejection;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &DocumentService,
+    ) -> Result<Self, Self::Rejection> {
+        let peer = ConnectInfo::<SocketAddr>::from_request_parts(parts, state).await?;
+        Ok(Self(client_ip(
+            peer.ip(),
+            &parts.headers,
+            &state.trusted_proxies,
+        )))
+    }
+}
+
+/// The address of the peer, unless it is a trusted proxy. Then it is the last address in
+/// `X-Forwarded-For` not of a trusted proxy, as those before it can be set by the client.
+/// Falls back to the first address if all of them are trusted, and to the peer if the
+/// header is missing or invalid.
+pub fn client_ip(peer: IpAddr, headers: &HeaderMap, trusted: &[IpAddr]) -> IpAddr {
+    if !trusted.contains(&peer) {
+        return peer;
+    }
+
+    let forwarded = headers
+        .get_all(FORWARDED_FOR)
+        .iter()
+        .map(|value| value.to_str().ok())
+        .collect::<Option<Vec<_>>>()
+        .and_then(|values| {
+            values
+                .iter()
+                .flat_map(|value| value.split(','))
+                .map(|addr| addr.trim().parse::<IpAddr>().ok())
+                .collect::<Option<Vec<_>>>()
+        })
+        .unwrap_or_default();
+
+    forwarded
+        .iter()
+        .rev()
+        .find(|addr| !trusted.contains(addr))
+        .or(forwarded.first())
+        .copied()
+        .unwrap_or(peer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    const PROXY: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(10, 0, 0, 1));
+
+    fn ip(addr: &str) -> IpAddr {
+        addr.parse().unwrap()
+    }
+
+    fn forwarded(values: &[&str]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for value in values {
+            headers.append(FORWARDED_FOR, HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn ignores_the_header_of_untrusted_peers() {
+        let headers = forwarded(&["203.0.113.7"]);
+        assert_eq!(
+            client_ip(ip("198.51.100.1"), &headers, &[]),
+            ip("198.51.100.1")
+        );
+        assert_eq!(
+            client_ip(ip("198.51.100.1"), &headers, &[PROXY]),
+            ip("198.51.100.1")
+        );
+    }
+
+    #[test]
+    fn takes_the_last_untrusted_address() {
+        let trusted = [PROXY, ip("10.0.0.2")];
+
+        // The first address is whatever the client sent
+        let headers = forwarded(&["1.2.3.4, 203.0.113.7, 10.0.0.2"]);
+        assert_eq!(client_ip(PROXY, &headers, &trusted), ip("203.0.113.7"));
+
+        let headers = forwarded(&["1.2.3.4", "203.0.113.7"]);
+        assert_eq!(client_ip(PROXY, &headers, &trusted), ip("203.0.113.7"));
+
+        let headers = forwarded(&["2001:db8::1"]);
+        assert_eq!(client_ip(PROXY, &headers, &trusted), ip("2001:db8::1"));
+    }
+
+    #[test]
+    fn falls_back_for_trusted_or_invalid_addresses() {
+        let trusted = [PROXY, ip("10.0.0.2")];
+
+        let headers = forwarded(&["10.0.0.2"]);
+        assert_eq!(client_ip(PROXY, &headers, &trusted), ip("10.0.0.2"));
+
+        for headers in [HeaderMap::new(), forwarded(&["203.0.113.7, unknown"])] {
+            assert_eq!(client_ip(PROXY, &headers, &trusted), PROXY);
+        }
+    }
+}
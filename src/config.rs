@@ -1,7 +1,16 @@
+use crate::archive::dump::DumpFormat;
+use crate::document::query::Order;
 use crate::error::LedgeknawError;
+use crate::idempotency::IDEMPOTENCY_KEY;
 use clap::Parser;
 use serde::Deserialize;
-use std::{collections::HashMap, fs, path::Path};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+    net::IpAddr,
+    path::Path,
+};
+use unicode_segmentation::UnicodeSegmentation;
 
 #[derive(Debug, Clone, Parser)]
 pub struct StartArgs {
@@ -16,6 +25,108 @@ pub struct StartArgs {
 
     #[arg(short, long, default_value = "INFO")]
     pub log_level: tracing::Level,
+
+    /// Re-read the metadata of all documents already in the database
+    #[arg(long)]
+    pub reindex: bool,
+
+    /// Serve the configured directories from the fs without a database.
+    /// Private directories are skipped.
+    #[arg(long)]
+    pub no_db: bool,
+
+    /// Path prefix all routes are served under, overrides `base_path` from the config
+    #[arg(long)]
+    pub base_path: Option<String>,
+
+    /// PEM file with the certificate chain to serve HTTPS with,
+    /// overrides `tls` from the config
+    #[arg(long, requires = "tls_key")]
+    pub tls_cert: Option<String>,
+
+    /// PEM file with the private key of the certificate
+    #[arg(long, requires = "tls_cert")]
+    pub tls_key: Option<String>,
+
+    /// Run under the Service Control Manager from this working directory,
+    /// passed by services installed with `service install` on Windows
+    #[arg(long, hide = true)]
+    pub windows_service: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Clone, clap::Subcommand)]
+pub enum Command {
+    /// Serve a single directory from the fs without a database.
+    /// The config file is optional in this mode.
+    Serve { path: String },
+
+    /// Same as serve, but also pushes reload events to the front end
+    /// whenever a file changes.
+    Preview {
+        #[arg(default_value = ".")]
+        path: String,
+    },
+
+    /// Move the instance to another host through a single archive file.
+    Migrate {
+        #[command(subcommand)]
+        action: MigrateCommand,
+    },
+
+    /// Write the directories, documents, links and everything derived from them
+    /// to a JSON or ndjson file, e.g. to move them to another database backend.
+    Dump {
+        path: String,
+        /// Defaults to ndjson for `.ndjson` and `.jsonl` files and JSON otherwise
+        #[arg(long, value_enum)]
+        format: Option<DumpFormat>,
+    },
+
+    /// Read a dump into an empty database, keeping all IDs.
+    Restore {
+        path: String,
+        /// Defaults to ndjson for `.ndjson` and `.jsonl` files and JSON otherwise
+        #[arg(long, value_enum)]
+        format: Option<DumpFormat>,
+        /// Delete the rows already in the database instead of requiring an empty one
+        #[arg(long)]
+        replace: bool,
+    },
+
+    /// Run as a background service on Windows or macOS.
+    /// Use a systemd unit on Linux instead.
+    Service {
+        #[command(subcommand)]
+        action: ServiceCommand,
+    },
+}
+
+#[derive(Debug, Clone, clap::Subcommand)]
+pub enum ServiceCommand {
+    /// Register a service running with the options given before `service`
+    /// and the current working directory, started on boot on Windows
+    /// and on login on macOS.
+    Install,
+
+    /// Stop and remove the service.
+    Uninstall,
+}
+
+#[derive(Debug, Clone, clap::Subcommand)]
+pub enum MigrateCommand {
+    /// Write the database rows and optionally the documents to an archive.
+    Export {
+        path: String,
+        /// Include the documents and assets
+        #[arg(long)]
+        content: bool,
+    },
+
+    /// Read an archive into an empty database, keeping all IDs.
+    Import { path: String },
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -23,14 +134,846 @@ pub struct Config {
     /// The document title for the front end
     pub title: Option<String>,
 
+    /// Path prefix all routes are served under, e.g. `/notes` when a reverse
+    /// proxy forwards `https://example.com/notes/`. Served from the root if not present.
+    pub base_path: Option<String>,
+
+    /// Addresses of the reverse proxies in front of the instance. Requests from them are
+    /// attributed to the client in their `X-Forwarded-For` header for rate limits and
+    /// login lockouts, instead of to the proxy. No header is trusted if empty.
+    #[serde(default)]
+    pub trusted_proxies: Vec<IpAddr>,
+
+    /// Public URL of the instance including the base path, e.g. `https://example.com/notes`.
+    /// Used for the canonical links of pages, which are relative if not present.
+    pub public_url: Option<String>,
+
+    /// Serve HTTPS instead of HTTP. Disabled if not present.
+    pub tls: Option<TlsConfig>,
+
     /// The list of directories to initially include for the public page.
     /// Maps names to directory paths.
-    pub directories: HashMap<String, String>,
+    pub directories: HashMap<String, RootConfig>,
+
+    /// Keys granting access to private directories when sent as bearer tokens
+    #[serde(default)]
+    pub api_keys: Vec<String>,
+
+    /// Content normalization applied when reading documents
+    #[serde(default)]
+    pub normalize: Normalization,
+
+    /// Reading time calculation
+    #[serde(default)]
+    pub reading_time: ReadingTime,
+
+    /// Support for conventions of other static site generators
+    #[serde(default)]
+    pub compat: Compat,
+
+    /// Directories nested deeper than this below their root are skipped when syncing
+    #[serde(default = "Config::default_max_depth")]
+    pub max_depth: usize,
+
+    /// Text extraction for image and scanned document assets.
+    /// Disabled if not present.
+    pub ocr: Option<OcrConfig>,
+
+    /// Transcription of audio and video assets.
+    /// Disabled if not present.
+    pub transcriber: Option<TranscriberConfig>,
+
+    /// Machine translation of documents.
+    /// Disabled if not present.
+    pub translator: Option<TranslatorConfig>,
+
+    /// Commit dates and authors of documents in git repositories,
+    /// used when the frontmatter omits the dates. Disabled if not present.
+    pub git: Option<GitConfig>,
+
+    /// Syncing when pushes land in the git repositories of the root directories,
+    /// announced by GitHub or GitLab webhooks on `/hooks/git`. Disabled if not present.
+    pub hooks: Option<HookConfig>,
+
+    /// Checking external links in the link check job started on `/admin/links/check`.
+    /// Only links to documents and files are checked if not present.
+    pub link_check: Option<LinkCheckConfig>,
+
+    /// Checks for new releases, logged when found and shown on `/admin/status`.
+    /// Disabled if not present.
+    pub updates: Option<UpdateConfig>,
+
+    /// Endpoints notified of the documents created, updated or deleted by syncing
+    /// or reindexing. Nothing is sent if empty.
+    #[serde(default)]
+    pub webhooks: Vec<WebhookConfig>,
+
+    /// Generate `/robots.txt`, disallowing the documents of roots with `crawl` disabled.
+    /// Not served if not present.
+    pub robots: Option<RobotsConfig>,
+
+    /// Read the sidebar, the index and the most recent documents after every sync,
+    /// so the first visitors are not slowed down by cold caches. Shown on `/admin/status`.
+    #[serde(default)]
+    pub warmup: bool,
+
+    /// Count the views of documents on `/document/:id` and `/pages/:id` per day,
+    /// listed on `/popular`. Not counted if not present.
+    pub views: Option<ViewsConfig>,
+
+    /// Anonymous usage statistics, reporting the version, the rough number of documents
+    /// and the enabled subsystems. Nothing is sent if not present.
+    pub telemetry: Option<TelemetryConfig>,
+
+    /// Connection pool settings
+    #[serde(default)]
+    pub database: DatabaseConfig,
+
+    /// Log every request at INFO on the `ledgeknaw::access_log` target.
+    /// Disabled if not present.
+    pub access_log: Option<AccessLogConfig>,
+
+    /// Format, destination and levels of the logs.
+    /// Text on the console at `--log-level` by default.
+    #[serde(default)]
+    pub log: LogConfig,
+
+    /// Sizes of request bodies
+    #[serde(default)]
+    pub limits: LimitsConfig,
+
+    /// Feature flags by name. Flags set on `/admin/flags` take precedence.
+    #[serde(default)]
+    pub flags: BTreeMap<String, bool>,
+
+    /// Cross-origin requests, by default allowed from any origin without credentials
+    #[serde(default)]
+    pub cors: CorsConfig,
+
+    /// Collections listed in the sidebar next to the root directories.
+    /// Created or updated on startup, more can be added on `/admin/collections`.
+    #[serde(default)]
+    pub collections: Vec<CollectionConfig>,
 }
 
+/// See [Config::max_depth].
+pub const DEFAULT_MAX_DEPTH: usize = 64;
+
 impl Config {
+    fn default_max_depth() -> usize {
+        DEFAULT_MAX_DEPTH
+    }
+
+    /// Read the config, replacing `${NAME}` in any string with the secret `NAME`.
+    /// See [secret] for where secrets are read from.
     pub fn read(path: impl AsRef<Path>) -> Result<Self, LedgeknawError> {
         let config = fs::read_to_string(path)?;
-        Ok(serde_json::from_str(&config)?)
+        check_duplicate_roots(&config)?;
+        let mut config = serde_json::from_str(&config)?;
+        interpolate(&mut config)?;
+        let config: Self = serde_json::from_value(config)?;
+        check_roots(&config.directories)?;
+        Ok(config)
+    }
+}
+
+/// A [serde_json::Value] keeps only the last of duplicate keys,
+/// so duplicate root aliases are looked for in the raw config.
+fn check_duplicate_roots(config: &str) -> Result<(), LedgeknawError> {
+    #[derive(Deserialize)]
+    struct Roots {
+        #[serde(default, rename = "directories", deserialize_with = "unique_keys")]
+        _directories: (),
+    }
+
+    serde_json::from_str::<Roots>(config)?;
+    Ok(())
+}
+
+fn unique_keys<'de, D>(deserializer: D) -> Result<(), D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    struct Keys;
+
+    impl<'de> serde::de::Visitor<'de> for Keys {
+        type Value = ();
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str("a map of root directories")
+        }
+
+        fn visit_map<A>(self, mut map: A) -> Result<(), A::Error>
+        where
+            A: serde::de::MapAccess<'de>,
+        {
+            let mut aliases = HashSet::new();
+            while let Some(alias) = map.next_key::<String>()? {
+                map.next_value::<serde::de::IgnoredAny>()?;
+                if !aliases.insert(alias.clone()) {
+                    return Err(serde::de::Error::custom(format!(
+                        "the directory {alias} is configured more than once"
+                    )));
+                }
+            }
+            Ok(())
+        }
+    }
+
+    deserializer.deserialize_map(Keys)
+}
+
+/// Roots nested in each other would be synced twice, once on their own and once
+/// as a subdirectory of the outer root, so each directory must belong to a single root.
+/// Roots that do not exist are left to fail when they are read.
+pub fn check_roots(roots: &HashMap<String, RootConfig>) -> Result<(), LedgeknawError> {
+    let mut paths = roots
+        .iter()
+        .filter_map(|(name, root)| Some((name, Path::new(root.path()).canonicalize().ok()?)))
+        .collect::<Vec<_>>();
+    paths.sort();
+
+    if let Some(alias) = roots.keys().find(|alias| alias.ends_with(STAGED_SUFFIX)) {
+        return Err(LedgeknawError::InvalidParameter(format!(
+            "the directory {alias} would conflict with the staged revision of {}",
+            alias.trim_end_matches(STAGED_SUFFIX)
+        )));
+    }
+
+    for (i, (name, path)) in paths.iter().enumerate() {
+        for (other_name, other_path) in &paths[i + 1..] {
+            if path.starts_with(other_path) || other_path.starts_with(path) {
+                return Err(LedgeknawError::InvalidParameter(format!(
+                    "the directories {name} ({}) and {other_name} ({}) overlap, remove one or move it out of the other",
+                    path.display(),
+                    other_path.display()
+                )));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Read the secret from the environment variable `name` or, if it is not set,
+/// from the file `<name>_FILE` points to, as provided by container secret mounts.
+pub fn secret(name: &str) -> Result<Option<String>, LedgeknawError> {
+    if let Ok(value) = std::env::var(name) {
+        return Ok(Some(value));
+    }
+
+    let Ok(path) = std::env::var(format!("{name}_FILE")) else {
+        return Ok(None);
+    };
+
+    match fs::read_to_string(&path) {
+        Ok(value) => Ok(Some(value.trim_end().to_string())),
+        Err(e) => Err(LedgeknawError::InvalidParameter(format!(
+            "{name}_FILE: {path}: {e}"
+        ))),
+    }
+}
+
+fn interpolate(value: &mut serde_json::Value) -> Result<(), LedgeknawError> {
+    match value {
+        serde_json::Value::String(s) => *s = interpolate_str(s)?,
+        serde_json::Value::Array(values) => values.iter_mut().try_for_each(interpolate)?,
+        serde_json::Value::Object(values) => values.values_mut().try_for_each(interpolate)?,
+        _ => {}
+    }
+    Ok(())
+}
+
+fn interpolate_str(mut s: &str) -> Result<String, LedgeknawError> {
+    let mut out = String::with_capacity(s.len());
+
+    while let Some(start) = s.find("${") {
+        let Some(len) = s[start..].find('}') else {
+            break;
+        };
+
+        let name = &s[start + 2..start + len];
+        let Some(value) = secret(name)? else {
+            return Err(LedgeknawError::InvalidParameter(format!(
+                "{name} is referenced in the config but not set"
+            )));
+        };
+
+        out.push_str(&s[..start]);
+        out.push_str(&value);
+        s = &s[start + len + 1..];
+    }
+
+    out.push_str(s);
+    Ok(out)
+}
+
+/// PEM encoded certificate chain and private key for serving HTTPS
+#[derive(Debug, Clone, Deserialize)]
+pub struct TlsConfig {
+    pub cert: String,
+    pub key: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct DatabaseConfig {
+    /// Used if neither `DATABASE_URL` nor `DATABASE_URL_FILE` is set
+    pub url: Option<String>,
+
+    pub max_connections: u32,
+
+    /// Connections kept open even when idle
+    pub min_connections: u32,
+
+    /// Seconds to wait for a free connection before a request fails
+    pub acquire_timeout_secs: u64,
+
+    /// Seconds after which the database aborts a statement. No limit if not present.
+    pub statement_timeout_secs: Option<u64>,
+
+    /// Seconds to keep retrying to connect on startup, with exponential backoff
+    /// between the attempts. `0` gives up after the first attempt.
+    pub connect_retry_secs: u64,
+
+    /// Queries taking at least this many milliseconds are logged.
+    /// The slow query log is disabled if not present.
+    pub slow_query_ms: Option<u64>,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            url: None,
+            max_connections: 10,
+            min_connections: 0,
+            acquire_timeout_secs: 30,
+            statement_timeout_secs: None,
+            connect_retry_secs: 60,
+            slow_query_ms: Some(500),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct LogConfig {
+    #[serde(default)]
+    pub format: LogFormat,
+
+    /// Levels per module in the syntax of `RUST_LOG`, e.g. `info,sqlx=warn,ledgeknaw::job=debug`.
+    /// Takes precedence over `--log-level`.
+    pub filter: Option<String>,
+
+    /// Log to a file instead of the console
+    pub file: Option<LogFileConfig>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct AccessLogConfig {
+    /// Leave out the static files of the front end
+    pub exclude_assets: bool,
+
+    /// Leave out requests for paths starting with any of these, e.g. `/health`.
+    /// Paths include the base path.
+    pub exclude: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    /// A JSON object per line, with the fields of the event and its spans
+    Json,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LogFileConfig {
+    /// The log file. When rotated, the period each file covers is appended
+    /// to the path, e.g. `ledgeknaw.log.2024-04-18`.
+    pub path: String,
+
+    #[serde(default)]
+    pub rotation: LogRotation,
+
+    /// Files kept, including the current one, the oldest are deleted. All are kept if not present.
+    pub max_files: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogRotation {
+    Hourly,
+    #[default]
+    Daily,
+    Never,
+}
+
+/// Bodies over the limits are rejected with 413 before being read into memory.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LimitsConfig {
+    /// Largest request body in bytes accepted by any route,
+    /// including JSON and multipart bodies
+    pub body_bytes: usize,
+
+    /// Largest request body in bytes accepted when logging in
+    pub login_body_bytes: usize,
+
+    /// Largest archive or dump in bytes accepted by `/admin/import` and `/admin/restore`,
+    /// instead of `body_bytes`
+    pub import_body_bytes: usize,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            body_bytes: 1024 * 1024,
+            login_body_bytes: 4 * 1024,
+            import_body_bytes: 64 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CorsConfig {
+    /// Origins such as `https://notes.example.com` allowed to make requests. `*` allows any.
+    pub origins: Vec<String>,
+
+    pub methods: Vec<String>,
+
+    /// Request headers the front end may send
+    pub headers: Vec<String>,
+
+    /// Whether cookies are sent along, needed for sessions when the front end is served
+    /// from another origin. Requires the origins to be listed explicitly.
+    pub credentials: bool,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            origins: vec!["*".to_string()],
+            methods: ["GET", "POST", "PUT", "DELETE"].map(String::from).to_vec(),
+            headers: ["authorization", "if-none-match", IDEMPOTENCY_KEY]
+                .map(String::from)
+                .to_vec(),
+            credentials: false,
+        }
+    }
+}
+
+/// Normalize the base path to either an empty string for the root or
+/// a path with a leading and without a trailing slash.
+pub fn normalize_base_path(path: Option<&str>) -> String {
+    match path.unwrap_or_default().trim_matches('/') {
+        "" => String::new(),
+        path => format!("/{path}"),
+    }
+}
+
+/// A named query whose matching documents are listed like a directory.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CollectionConfig {
+    pub name: String,
+
+    /// See [crate::document::query] for the syntax
+    pub query: String,
+
+    #[serde(default)]
+    pub sort: Order,
+
+    /// Limit of documents listed, all matching documents if not set
+    pub max_documents: Option<i32>,
+}
+
+/// A root directory, given either as its path or with its options.
+/// Appended to the alias of a root to serve its staged revision under.
+pub const STAGED_SUFFIX: &str = " (staged)";
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum RootConfig {
+    Path(String),
+    Options {
+        path: String,
+        /// Private directories and their documents are only
+        /// visible to authenticated requests
+        #[serde(default)]
+        private: bool,
+        /// Allow crawlers in `/robots.txt`
+        #[serde(default = "RootConfig::default_crawl")]
+        crawl: bool,
+        /// Bucket the files are stored in, mirrored to `path` before syncing
+        s3: Option<S3Config>,
+    },
+}
+
+impl RootConfig {
+    pub fn path(&self) -> &str {
+        match self {
+            Self::Path(path) | Self::Options { path, .. } => path,
+        }
+    }
+
+    pub fn private(&self) -> bool {
+        match self {
+            Self::Path(_) => false,
+            Self::Options { private, .. } => *private,
+        }
+    }
+
+    pub fn crawl(&self) -> bool {
+        match self {
+            Self::Path(_) => true,
+            Self::Options { crawl, .. } => *crawl,
+        }
+    }
+
+    pub fn s3(&self) -> Option<&S3Config> {
+        match self {
+            Self::Path(_) => None,
+            Self::Options { s3, .. } => s3.as_ref(),
+        }
+    }
+
+    fn default_crawl() -> bool {
+        true
+    }
+}
+
+/// A bucket on S3 or a compatible object storage holding the files of a root directory.
+#[derive(Debug, Clone, Deserialize)]
+pub struct S3Config {
+    /// e.g. `https://s3.eu-central-1.amazonaws.com` or `http://localhost:9000`
+    pub endpoint: String,
+
+    pub bucket: String,
+
+    #[serde(default = "S3Config::default_region")]
+    pub region: String,
+
+    /// Only the objects below the prefix are served, e.g. `notes/`
+    #[serde(default)]
+    pub prefix: String,
+
+    /// Address the bucket in the path instead of the host name,
+    /// as required by most self-hosted object storages
+    #[serde(default)]
+    pub path_style: bool,
+
+    /// Read from `AWS_ACCESS_KEY_ID` if not set
+    pub access_key_id: Option<String>,
+
+    /// Read from `AWS_SECRET_ACCESS_KEY` if not set
+    pub secret_access_key: Option<String>,
+}
+
+impl S3Config {
+    fn default_region() -> String {
+        "us-east-1".to_string()
+    }
+}
+
+/// Conventions of other tools understood when reading documents,
+/// so existing repositories can be served without editing them.
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct Compat {
+    /// Map Hugo's `publishDate` and `lastmod` frontmatter to the publish
+    /// and updated dates
+    pub hugo: bool,
+
+    /// Take titles and ordering from mdBook's `SUMMARY.md`, found either
+    /// in the root directory or its `src` directory
+    pub mdbook: bool,
+}
+
+/// Controls how document contents are canonicalised when read from the fs,
+/// so files edited on different platforms are processed the same.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct Normalization {
+    /// Convert CRLF and lone CR line endings to LF
+    pub line_endings: bool,
+
+    /// Strip the UTF-8 byte order mark from the start of the file
+    pub bom: bool,
+
+    /// Strip trailing whitespace from every line
+    pub trailing_whitespace: bool,
+}
+
+impl Default for Normalization {
+    fn default() -> Self {
+        Self {
+            line_endings: true,
+            bom: true,
+            trailing_whitespace: false,
+        }
+    }
+}
+
+impl Normalization {
+    pub fn apply(&self, mut content: String) -> String {
+        if self.bom {
+            if let Some(stripped) = content.strip_prefix('\u{feff}') {
+                content = stripped.to_string();
+            }
+        }
+
+        if self.line_endings && content.contains('\r') {
+            content = content.replace("\r\n", "\n").replace('\r', "\n");
+        }
+
+        if self.trailing_whitespace {
+            let trailing_newline = content.ends_with('\n');
+            content = content
+                .lines()
+                .map(str::trim_end)
+                .collect::<Vec<_>>()
+                .join("\n");
+            if trailing_newline {
+                content.push('\n');
+            }
+        }
+
+        content
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct ReadingTime {
+    /// Reading speed for prose
+    pub words_per_minute: u32,
+
+    /// Reading speed for fenced code blocks, usually much slower than prose
+    pub code_words_per_minute: u32,
+}
+
+impl Default for ReadingTime {
+    fn default() -> Self {
+        Self {
+            words_per_minute: 200,
+            code_words_per_minute: 80,
+        }
+    }
+}
+
+impl ReadingTime {
+    /// Calculate the reading time of the content in minutes, rounded up.
+    pub fn calculate(&self, content: &str) -> i32 {
+        let mut prose = 0;
+        let mut code = 0;
+        let mut in_code = false;
+
+        for line in content.lines() {
+            if line.trim_start().starts_with("```") {
+                in_code = !in_code;
+                continue;
+            }
+
+            if in_code {
+                code += line.split_whitespace().count();
+            } else {
+                prose += line.unicode_words().count();
+            }
+        }
+
+        let minutes = prose as f64 / self.words_per_minute.max(1) as f64
+            + code as f64 / self.code_words_per_minute.max(1) as f64;
+
+        minutes.ceil() as i32
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct OcrConfig {
+    /// The program and its arguments used to extract text from an asset.
+    /// `{path}` is replaced with the asset path and the text is read from stdout.
+    pub command: Vec<String>,
+
+    /// Extensions of the assets to extract text from
+    pub extensions: Vec<String>,
+}
+
+impl Default for OcrConfig {
+    fn default() -> Self {
+        Self {
+            command: vec!["tesseract".into(), "{path}".into(), "stdout".into()],
+            extensions: ["png", "jpg", "jpeg", "tif", "tiff", "bmp", "webp"]
+                .map(String::from)
+                .to_vec(),
+        }
+    }
+}
+
+/// Compatible with whisper.cpp's server and OpenAI style transcription APIs.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TranscriberConfig {
+    /// The endpoint the assets are posted to as multipart forms
+    pub url: String,
+
+    /// The form field containing the asset
+    #[serde(default = "TranscriberConfig::default_field")]
+    pub field: String,
+
+    /// Additional form fields sent with every asset.
+    /// The response is expected to contain only the transcript text.
+    #[serde(default = "TranscriberConfig::default_params")]
+    pub params: HashMap<String, String>,
+
+    /// Extensions of the assets to transcribe
+    #[serde(default = "TranscriberConfig::default_extensions")]
+    pub extensions: Vec<String>,
+}
+
+impl TranscriberConfig {
+    fn default_field() -> String {
+        "file".to_string()
+    }
+
+    fn default_params() -> HashMap<String, String> {
+        HashMap::from([("response_format".to_string(), "text".to_string())])
+    }
+
+    fn default_extensions() -> Vec<String> {
+        [
+            "mp3", "wav", "m4a", "ogg", "flac", "mp4", "webm", "mkv", "mov",
+        ]
+        .map(String::from)
+        .to_vec()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct UpdateConfig {
+    /// The GitHub repository releases are published in, as `owner/name`
+    pub repository: String,
+
+    /// Hours between checks
+    #[serde(default = "UpdateConfig::default_interval_hours")]
+    pub interval_hours: u64,
+}
+
+impl UpdateConfig {
+    fn default_interval_hours() -> u64 {
+        24
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ViewsConfig {
+    /// Seconds between writes of the views counted in the meantime
+    #[serde(default = "ViewsConfig::default_flush_secs")]
+    pub flush_secs: u64,
+}
+
+impl ViewsConfig {
+    fn default_flush_secs() -> u64 {
+        60
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TelemetryConfig {
+    /// The endpoint reports are posted to as JSON
+    pub url: String,
+
+    /// Hours between reports
+    #[serde(default = "TelemetryConfig::default_interval_hours")]
+    pub interval_hours: u64,
+}
+
+impl TelemetryConfig {
+    fn default_interval_hours() -> u64 {
+        24
+    }
+}
+
+/// Compatible with LibreTranslate style translation APIs.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TranslatorConfig {
+    /// The endpoint documents are posted to for translation
+    pub url: String,
+
+    /// Sent as `api_key` with every request if present
+    pub api_key: Option<String>,
+}
+
+/// Enrichment of documents in git repositories with their commit history
+/// and serving pinned revisions of them.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct GitConfig {
+    /// The git executable
+    pub program: String,
+
+    /// Where pinned and staged revisions of root directories are checked out
+    pub worktrees: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct HookConfig {
+    /// The secret of the webhook, which GitHub signs the payloads with
+    /// and GitLab sends as is
+    pub secret: String,
+
+    /// Aliases of the root directories whose repositories are pulled before syncing.
+    /// Nothing is pulled if empty, e.g. if the repositories are pulled by another process.
+    #[serde(default)]
+    pub pull: Vec<String>,
+}
+
+/// An endpoint document events are posted to.
+#[derive(Debug, Clone, Deserialize)]
+pub struct WebhookConfig {
+    /// Events are posted to it as JSON, one per request
+    pub url: String,
+
+    /// Payloads are signed with it in `X-Ledgeknaw-Signature-256` if present
+    pub secret: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct RobotsConfig {
+    /// Disallow crawling anything
+    pub disallow_all: bool,
+
+    /// URLs of sitemaps, listed as `Sitemap` lines
+    pub sitemaps: Vec<String>,
+}
+
+/// Requests made for external links, one at a time.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LinkCheckConfig {
+    /// Milliseconds between requests
+    pub interval_ms: u64,
+
+    /// Seconds until a link without a response counts as broken
+    pub timeout_secs: u64,
+}
+
+impl Default for LinkCheckConfig {
+    fn default() -> Self {
+        Self {
+            interval_ms: 1000,
+            timeout_secs: 10,
+        }
+    }
+}
+
+impl Default for GitConfig {
+    fn default() -> Self {
+        Self {
+            program: "git".to_string(),
+            worktrees: "worktrees".to_string(),
+        }
     }
 }
@@ -0,0 +1,104 @@
+//! Where the contents of documents and assets are read from. Files are read from the fs,
+//! unless their root directory is stored elsewhere, such as in an S3 bucket. Metadata is
+//! always kept in the database.
+
+use crate::{config::RootConfig, error::LedgeknawError};
+use async_trait::async_trait;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+pub mod s3;
+
+#[async_trait]
+pub trait ContentStore: Send + Sync {
+    /// Bring the local files of the root directory up to date, before it is synced.
+    async fn pull(&self) -> Result<(), LedgeknawError>;
+
+    /// Read the file at `path`, as stored in the database.
+    async fn read(&self, path: &Path) -> Result<Vec<u8>, LedgeknawError>;
+}
+
+/// Files kept on the fs only.
+pub struct LocalStore;
+
+#[async_trait]
+impl ContentStore for LocalStore {
+    async fn pull(&self) -> Result<(), LedgeknawError> {
+        Ok(())
+    }
+
+    async fn read(&self, path: &Path) -> Result<Vec<u8>, LedgeknawError> {
+        Ok(tokio::fs::read(path).await?)
+    }
+}
+
+/// The stores of the root directories, chosen by the paths read.
+#[derive(Clone, Default)]
+pub struct ContentStores {
+    /// Stores of the roots not kept on the fs only, keyed by their canonical paths
+    roots: Arc<Vec<(PathBuf, Arc<dyn ContentStore>)>>,
+}
+
+impl std::fmt::Debug for ContentStores {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.roots.iter().map(|(path, _)| path))
+            .finish()
+    }
+}
+
+impl ContentStores {
+    /// Fails if the store of any root cannot be set up, e.g. due to missing credentials.
+    pub fn new(roots: &HashMap<String, RootConfig>) -> Result<Self, LedgeknawError> {
+        let mut stores: Vec<(PathBuf, Arc<dyn ContentStore>)> = vec![];
+
+        for root in roots.values() {
+            let Some(config) = root.s3() else {
+                continue;
+            };
+
+            // The local copy may not have been pulled yet
+            std::fs::create_dir_all(root.path())?;
+            let path = Path::new(root.path()).canonicalize()?;
+
+            let store = s3::S3Store::new(config.clone(), path.clone())?;
+            stores.push((path, Arc::new(store)));
+        }
+
+        Ok(Self {
+            roots: Arc::new(stores),
+        })
+    }
+
+    fn store(&self, path: &Path) -> &dyn ContentStore {
+        self.roots
+            .iter()
+            .find(|(root, _)| path.starts_with(root))
+            .map_or(&LocalStore, |(_, store)| store.as_ref())
+    }
+
+    /// Whether the path is in the local copy of a root not kept on the fs only. Files
+    /// written there are replaced or deleted on the next pull, so they are not.
+    pub fn is_mirrored(&self, path: &Path) -> bool {
+        self.roots.iter().any(|(root, _)| path.starts_with(root))
+    }
+
+    /// Pull the files of all roots not kept on the fs only.
+    pub async fn pull(&self) -> Result<(), LedgeknawError> {
+        for (_, store) in self.roots.iter() {
+            store.pull().await?;
+        }
+        Ok(())
+    }
+
+    pub async fn read(&self, path: impl AsRef<Path>) -> Result<Vec<u8>, LedgeknawError> {
+        self.store(path.as_ref()).read(path.as_ref()).await
+    }
+
+    pub async fn read_to_string(&self, path: impl AsRef<Path>) -> Result<String, LedgeknawError> {
+        Ok(String::from_utf8(self.read(path).await?)?)
+    }
+}
@@ -1,9 +1,88 @@
-use sqlx::PgPool;
+use crate::{config::DatabaseConfig, error::LedgeknawError};
+use sqlx::{
+    postgres::{PgConnectOptions, PgPoolOptions},
+    Connection, PgConnection, PgPool,
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Write,
+    future::Future,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tracing::warn;
 
-pub(super) async fn create_pool(url: &str) -> PgPool {
-    sqlx::postgres::PgPool::connect(url)
-        .await
-        .expect("error while connecting to db")
+/// Delay before the first retry, doubled after every failed attempt
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+
+const MAX_BACKOFF: Duration = Duration::from_secs(10);
+
+/// Attempts of read queries failing with transient errors
+const READ_ATTEMPTS: u32 = 3;
+
+const READ_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Durations kept per query for calculating percentiles
+const QUERY_SAMPLES: usize = 1024;
+
+const QUANTILES: [f64; 3] = [0.5, 0.9, 0.99];
+
+pub(super) async fn create_pool(
+    url: &str,
+    config: &DatabaseConfig,
+) -> Result<PgPool, LedgeknawError> {
+    if config.max_connections == 0 || config.min_connections > config.max_connections {
+        return Err(LedgeknawError::InvalidParameter(format!(
+            "min_connections ({}) must not exceed max_connections ({}), which must be positive",
+            config.min_connections, config.max_connections
+        )));
+    }
+
+    let mut options = PgConnectOptions::from_str(url)?;
+
+    if let Some(timeout) = config.statement_timeout_secs {
+        options = options.options([("statement_timeout", format!("{}s", timeout))]);
+    }
+
+    wait_for_database(&options, Duration::from_secs(config.connect_retry_secs)).await?;
+
+    Ok(PgPoolOptions::new()
+        .max_connections(config.max_connections)
+        .min_connections(config.min_connections)
+        .acquire_timeout(Duration::from_secs(config.acquire_timeout_secs))
+        .connect_with(options)
+        .await?)
+}
+
+/// Try connecting until the database accepts connections or `retry` passes,
+/// backing off exponentially between the attempts.
+async fn wait_for_database(
+    options: &PgConnectOptions,
+    retry: Duration,
+) -> Result<(), LedgeknawError> {
+    let deadline = Instant::now() + retry;
+    let mut backoff = INITIAL_BACKOFF;
+    let mut attempt = 1;
+
+    loop {
+        let e = match PgConnection::connect_with(options).await {
+            Ok(connection) => return Ok(connection.close().await?),
+            Err(e) => e,
+        };
+
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(e.into());
+        }
+
+        let delay = backoff.min(remaining);
+        warn!("Could not connect to the database (attempt {attempt}): {e}, retrying in {delay:?}");
+        tokio::time::sleep(delay).await;
+
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+        attempt += 1;
+    }
 }
 
 pub(super) async fn migrate(pool: &PgPool) {
@@ -12,3 +91,160 @@ pub(super) async fn migrate(pool: &PgPool) {
         .await
         .expect("error in migrations")
 }
+
+/// Run the query again if it fails with a transient error, e.g. when connections
+/// are reset during a database failover. Only for queries that are safe to repeat.
+pub(crate) async fn retry_read<T, F, Fut>(mut query: F) -> Result<T, sqlx::Error>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, sqlx::Error>>,
+{
+    let mut backoff = READ_BACKOFF;
+    let mut attempt = 1;
+
+    loop {
+        match query().await {
+            Err(e) if attempt < READ_ATTEMPTS && is_transient(&e) => {
+                warn!("Transient database error (attempt {attempt}), retrying in {backoff:?}: {e}");
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Whether the query may succeed when run again.
+pub(crate) fn is_transient(error: &sqlx::Error) -> bool {
+    match error {
+        sqlx::Error::Io(_) | sqlx::Error::PoolTimedOut => true,
+        sqlx::Error::Database(e) => e.code().is_some_and(|code| {
+            // Connection exceptions
+            code.starts_with("08")
+                // Serialization failure and deadlocks
+                || code == "40001"
+                || code == "40P01"
+                // Shutdowns and the server not accepting connections yet
+                || code == "57P01"
+                || code == "57P02"
+                || code == "57P03"
+        }),
+        _ => false,
+    }
+}
+
+/// Durations of database calls by name, with calls slower than the threshold
+/// logged. Only the name is logged, never the parameters of the query.
+#[derive(Debug, Clone)]
+pub struct QueryMetrics {
+    slow: Option<Duration>,
+    queries: Arc<Mutex<HashMap<&'static str, QueryStats>>>,
+}
+
+#[derive(Debug, Default)]
+struct QueryStats {
+    count: u64,
+    total: Duration,
+    recent: VecDeque<Duration>,
+}
+
+impl QueryMetrics {
+    /// `slow` of `None` disables the slow query log.
+    pub fn new(slow: Option<Duration>) -> Self {
+        Self {
+            slow,
+            queries: Arc::default(),
+        }
+    }
+
+    /// Start timing the query, which is recorded once the guard is dropped.
+    pub fn time(&self, query: &'static str) -> QueryTimer {
+        QueryTimer {
+            metrics: self.clone(),
+            query,
+            start: Instant::now(),
+        }
+    }
+
+    fn record(&self, query: &'static str, elapsed: Duration) {
+        if self.slow.is_some_and(|slow| elapsed >= slow) {
+            warn!("Slow query {query} took {elapsed:?}");
+        }
+
+        let mut queries = self.queries.lock().unwrap();
+        let stats = queries.entry(query).or_default();
+        stats.count += 1;
+        stats.total += elapsed;
+        if stats.recent.len() == QUERY_SAMPLES {
+            stats.recent.pop_front();
+        }
+        stats.recent.push_back(elapsed);
+    }
+
+    /// The number of queries run and the time they took in total.
+    pub fn totals(&self) -> (u64, Duration) {
+        let queries = self.queries.lock().unwrap();
+        queries
+            .values()
+            .fold((0, Duration::ZERO), |(count, total), stats| {
+                (count + stats.count, total + stats.total)
+            })
+    }
+
+    /// The metrics in the Prometheus text format. Quantiles are calculated
+    /// over the most recent calls, counts and sums over all of them.
+    pub fn render(&self) -> String {
+        let queries = self.queries.lock().unwrap();
+        let mut names = queries.keys().copied().collect::<Vec<_>>();
+        names.sort_unstable();
+
+        let mut out = String::from(
+            "# HELP ledgeknaw_query_duration_seconds Duration of database queries\n\
+             # TYPE ledgeknaw_query_duration_seconds summary\n",
+        );
+
+        for name in names {
+            let stats = &queries[name];
+            let mut recent = stats.recent.iter().copied().collect::<Vec<_>>();
+            recent.sort_unstable();
+
+            for quantile in QUANTILES {
+                let index = ((recent.len() as f64 * quantile).ceil() as usize).saturating_sub(1);
+                let value = recent.get(index).copied().unwrap_or_default();
+                let _ = writeln!(
+                    out,
+                    "ledgeknaw_query_duration_seconds{{query=\"{name}\",quantile=\"{quantile}\"}} {}",
+                    value.as_secs_f64()
+                );
+            }
+
+            let _ = writeln!(
+                out,
+                "ledgeknaw_query_duration_seconds_sum{{query=\"{name}\"}} {}",
+                stats.total.as_secs_f64()
+            );
+            let _ = writeln!(
+                out,
+                "ledgeknaw_query_duration_seconds_count{{query=\"{name}\"}} {}",
+                stats.count
+            );
+        }
+
+        out
+    }
+}
+
+/// Records the time since it was created when dropped, so every return
+/// path of the timed method is covered.
+pub struct QueryTimer {
+    metrics: QueryMetrics,
+    query: &'static str,
+    start: Instant,
+}
+
+impl Drop for QueryTimer {
+    fn drop(&mut self) {
+        self.metrics.record(self.query, self.start.elapsed());
+    }
+}
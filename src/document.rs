@@ -1,20 +1,33 @@
 use self::db::DocumentDb;
-use self::models::Document;
+use self::models::{DepthReport, Document};
+use crate::annotation::Annotation;
+use crate::config::{Compat, Normalization, ReadingTime, DEFAULT_MAX_DEPTH};
 use crate::error::LedgeknawError;
-use crate::{FILES_PER_THREAD, MAX_THREADS};
-use async_recursion::async_recursion;
-use chrono::{DateTime, Utc};
+use crate::MAX_CONCURRENT_READS;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::ffi::OsStr;
 use std::fs::{self, DirEntry};
 use std::path::PathBuf;
-use std::thread::ScopedJoinHandle;
+use std::sync::Arc;
 use std::time::Instant;
 use std::{fmt::Debug, path::Path};
-use tracing::{debug, error, info};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tracing::{debug, error, info, warn};
 
 pub mod db;
+pub mod git;
+pub mod links;
 pub mod models;
+pub mod ocr;
+pub mod query;
+pub mod summary;
+pub mod toml;
+pub mod transcribe;
+pub mod translate;
 
 /// Document read from the fs with its metadata.
 #[derive(Debug, Default, Serialize)]
@@ -25,21 +38,82 @@ pub struct DocumentData {
     pub content: String,
     /// Metadata
     pub meta: DocumentMeta,
+    /// Only filled in on `/document/:id`
+    pub annotations: Vec<Annotation>,
 }
 
 impl DocumentData {
-    pub fn read_from_disk(id: uuid::Uuid, path: impl AsRef<Path>) -> Result<Self, LedgeknawError> {
+    pub fn read_from_disk(
+        id: uuid::Uuid,
+        path: impl AsRef<Path>,
+        options: ReadOptions,
+    ) -> Result<Self, LedgeknawError> {
         debug!("Reading {}", path.as_ref().display());
+        let content = fs::read_to_string(path.as_ref())?;
+        Ok(Self::from_content(id, path, content, options))
+    }
 
+    /// Used when the content was read elsewhere, the path is only used for reporting.
+    pub fn from_content(
+        id: uuid::Uuid,
+        path: impl AsRef<Path>,
+        content: String,
+        options: ReadOptions,
+    ) -> Self {
         let mut data = Self {
             id,
             ..Default::default()
         };
-        let content = fs::read_to_string(path)?;
-        let (meta, content) = DocumentMeta::from_str(&content)?;
+        let content = options.normalize.apply(content);
+        let (mut meta, content, issue) = DocumentMeta::from_str_lenient(&content);
+        if let Some(issue) = issue {
+            warn!("{}: {issue}", path.as_ref().display());
+        }
+        meta.reading_time = Some(options.reading_time.calculate(content));
+        if options.compat.hugo {
+            meta.apply_hugo();
+        }
         data.content = content.to_string();
         data.meta = meta;
-        Ok(data)
+        data
+    }
+}
+
+/// Document content machine translated to another language.
+#[derive(Debug, Serialize)]
+pub struct TranslatedDocument {
+    /// Database ID of the source document
+    pub id: uuid::Uuid,
+    /// Target language code
+    pub lang: String,
+    /// Translated markdown content
+    pub content: String,
+    /// Metadata of the source document
+    pub meta: DocumentMeta,
+    /// Always true, so clients can label the content accordingly
+    pub machine_translated: bool,
+    /// The provider that translated the content
+    pub provenance: String,
+}
+
+/// Settings used when reading documents from the fs.
+#[derive(Debug, Clone, Copy)]
+pub struct ReadOptions {
+    pub normalize: Normalization,
+    pub reading_time: ReadingTime,
+    pub compat: Compat,
+    /// Directories nested deeper below their root are skipped
+    pub max_depth: usize,
+}
+
+impl Default for ReadOptions {
+    fn default() -> Self {
+        Self {
+            normalize: Normalization::default(),
+            reading_time: ReadingTime::default(),
+            compat: Compat::default(),
+            max_depth: DEFAULT_MAX_DEPTH,
+        }
     }
 }
 
@@ -50,8 +124,117 @@ pub struct DocumentMeta {
     #[serde(alias = "id")]
     pub custom_id: Option<String>,
     pub title: Option<String>,
+    /// Shown in link previews of `/pages` instead of the first paragraph
+    pub description: Option<String>,
     pub reading_time: Option<i32>,
+    #[serde(default, deserialize_with = "deserialize_tags")]
     pub tags: Option<Vec<String>>,
+    /// Previous identifiers of the document. Requests using them
+    /// are redirected to the current identifier.
+    pub aliases: Option<Vec<String>>,
+    /// Drafts are excluded from all public routes.
+    #[serde(default)]
+    pub draft: bool,
+    /// Publish date
+    #[serde(default, deserialize_with = "deserialize_date")]
+    pub date: Option<DateTime<Utc>>,
+    #[serde(default, deserialize_with = "deserialize_date")]
+    pub created: Option<DateTime<Utc>>,
+    #[serde(default, deserialize_with = "deserialize_date")]
+    pub updated: Option<DateTime<Utc>>,
+    /// Position among the other entries of the directory, lower first
+    pub weight: Option<i32>,
+    /// How long clients and proxies may cache the document
+    pub cache: Option<CachePolicy>,
+    /// Asks search engines not to index the document
+    #[serde(default)]
+    pub noindex: bool,
+    /// Any other frontmatter fields, kept as is.
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_yaml::Value>,
+}
+
+/// Cacheability of a document, sent as its `Cache-Control` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CachePolicy {
+    /// Never cached
+    None,
+    /// Cached for 5 minutes
+    Short,
+    /// Cached for a day
+    Long,
+}
+
+impl CachePolicy {
+    /// Seconds the document may be cached for.
+    pub fn max_age(&self) -> Option<u32> {
+        match self {
+            Self::None => None,
+            Self::Short => Some(300),
+            Self::Long => Some(86400),
+        }
+    }
+}
+
+/// Accepts lists of tags and Jekyll style strings of tags separated by spaces.
+fn deserialize_tags<'de, D>(deserializer: D) -> Result<Option<Vec<String>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Tags {
+        List(Vec<String>),
+        Words(String),
+    }
+
+    Ok(match Option::<Tags>::deserialize(deserializer)? {
+        Some(Tags::List(tags)) => Some(tags),
+        Some(Tags::Words(tags)) => Some(tags.split_whitespace().map(String::from).collect()),
+        None => None,
+    })
+}
+
+/// Accepts RFC 3339 timestamps, `YYYY-MM-DD HH:MM:SS` datetimes with an optional
+/// Jekyll style `+HHMM` offset, `YYYY-MM-DDTHH:MM:SS` datetimes and plain
+/// `YYYY-MM-DD` dates. Naive values are interpreted as UTC.
+fn deserialize_date<'de, D>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let Some(date) = Option::<String>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+
+    match parse_date(&date) {
+        Some(date) => Ok(Some(date)),
+        None => Err(serde::de::Error::custom(format!("invalid date: {date}"))),
+    }
+}
+
+fn parse_date(date: &str) -> Option<DateTime<Utc>> {
+    let date = date.trim();
+
+    if let Ok(date) = DateTime::parse_from_rfc3339(date) {
+        return Some(date.with_timezone(&Utc));
+    }
+
+    if let Ok(date) = DateTime::parse_from_str(date, "%Y-%m-%d %H:%M:%S %z") {
+        return Some(date.with_timezone(&Utc));
+    }
+
+    for format in ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S"] {
+        if let Ok(date) = NaiveDateTime::parse_from_str(date, format) {
+            return Some(date.and_utc());
+        }
+    }
+
+    if let Ok(date) = NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+        return Some(date.and_time(NaiveTime::MIN).and_utc());
+    }
+
+    None
 }
 
 impl DocumentMeta {
@@ -64,45 +247,179 @@ impl DocumentMeta {
             .to_string()
     }
 
-    pub fn read_from_file(path: impl AsRef<Path>) -> Result<Self, LedgeknawError> {
+    /// Invalid frontmatter does not fail the read, the issue is returned alongside the
+    /// metadata obtainable without it instead.
+    pub fn read_from_file(
+        path: impl AsRef<Path>,
+        options: ReadOptions,
+    ) -> Result<(Self, Option<FrontmatterIssue>), LedgeknawError> {
         debug!("Reading {}", path.as_ref().display());
-        let content = fs::read_to_string(path)?;
-        Ok(Self::from_str(&content)?.0)
+        Ok(Self::from_content(fs::read_to_string(path)?, options))
+    }
+
+    /// Same as [DocumentMeta::read_from_file], for content read elsewhere.
+    pub fn from_content(content: String, options: ReadOptions) -> (Self, Option<FrontmatterIssue>) {
+        let content = options.normalize.apply(content);
+        let (mut meta, content, issue) = Self::from_str_lenient(&content);
+        meta.reading_time = Some(options.reading_time.calculate(content));
+        if options.compat.hugo {
+            meta.apply_hugo();
+        }
+        (meta, issue)
+    }
+
+    /// Take the string value of `key` out of `extra`. Values of other types are left in place.
+    fn take_extra_str(&mut self, key: &str) -> Option<String> {
+        match self.extra.remove(key) {
+            Some(serde_yaml::Value::String(value)) => Some(value),
+            Some(value) => {
+                self.extra.insert(key.to_string(), value);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Populate the fields from the keys common to Hugo and Jekyll frontmatter, unless
+    /// already set, so their repositories can be served as they are. The keys are taken
+    /// out of `extra`.
+    fn apply_static_site(&mut self) {
+        if let Some(slug) = self.take_extra_str("slug") {
+            self.custom_id.get_or_insert(slug);
+        }
+
+        if let Some(summary) = self.take_extra_str("summary") {
+            self.description.get_or_insert(summary);
+        }
+
+        // Jekyll's counterpart of drafts
+        if let Some(serde_yaml::Value::Bool(published)) = self.extra.get("published") {
+            self.draft |= !published;
+            self.extra.remove("published");
+        }
+
+        let categories = match self.extra.remove("categories") {
+            Some(serde_yaml::Value::Sequence(categories)) => categories
+                .into_iter()
+                .filter_map(|category| match category {
+                    serde_yaml::Value::String(category) => Some(category),
+                    _ => None,
+                })
+                .collect(),
+            Some(serde_yaml::Value::String(categories)) => {
+                categories.split_whitespace().map(String::from).collect()
+            }
+            Some(value) => {
+                self.extra.insert("categories".to_string(), value);
+                vec![]
+            }
+            None => vec![],
+        };
+
+        if !categories.is_empty() {
+            let tags = self.tags.get_or_insert_with(Vec::new);
+            for category in categories {
+                if !tags.contains(&category) {
+                    tags.push(category);
+                }
+            }
+        }
+    }
+
+    /// Populate the fields from their Hugo counterparts, unless already set.
+    /// The Hugo fields are taken out of `extra`.
+    pub fn apply_hugo(&mut self) {
+        let publish_date = self
+            .take_extra_str("publishDate")
+            .and_then(|date| parse_date(&date));
+        let last_modified = self
+            .take_extra_str("lastmod")
+            .and_then(|date| parse_date(&date));
+
+        if self.date.is_none() {
+            self.date = publish_date;
+        }
+
+        if self.updated.is_none() {
+            self.updated = last_modified;
+        }
+    }
+
+    /// Same as [DocumentMeta::from_str], but falls back to the metadata obtainable
+    /// without the frontmatter if it is invalid and returns the issue instead.
+    pub fn from_str_lenient(content: &str) -> (Self, &str, Option<FrontmatterIssue>) {
+        match Self::from_str(content) {
+            Ok((meta, content)) => (meta, content, None),
+            Err(e) => {
+                let content = Self::strip_frontmatter(content);
+                let meta = Self {
+                    title: Self::find_title_from_h1(content),
+                    ..Default::default()
+                };
+                (meta, content, Some(FrontmatterIssue::from_error(&e)))
+            }
+        }
+    }
+
+    /// The fence of the frontmatter the content starts with, `---` for YAML or `+++` for TOML.
+    fn frontmatter_fence(content: &str) -> Option<&'static str> {
+        ["---", "+++"]
+            .into_iter()
+            .find(|fence| content.starts_with(fence))
+    }
+
+    fn strip_frontmatter(content: &str) -> &str {
+        let Some(fence) = Self::frontmatter_fence(content) else {
+            return content;
+        };
+
+        if content.len() < 4 {
+            return content;
+        }
+
+        match content[3..].find(fence) {
+            Some(end_i) => content.get(end_i + 6..).unwrap_or_default(),
+            None => &content[3..],
+        }
     }
 
     /// Used when we already read the file from the fs.
     /// Returns the read meta and the remainder of the content.
+    /// The reading time is left to the caller.
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(content: &str) -> Result<(Self, &str), LedgeknawError> {
         let mut data = Self {
             title: Self::find_title_from_h1(content),
             ..Default::default()
         };
 
-        if !content.starts_with("---") {
+        let Some(fence) = Self::frontmatter_fence(content) else {
             return Ok((data, content));
-        }
+        };
 
         if content.len() < 4 {
             return Ok((data, content));
         }
 
-        let Some(end_i) = &content[3..].find("---") else {
+        let Some(end_i) = &content[3..].find(fence) else {
             return Ok((data, &content[3..]));
         };
 
-        // Offset to account for the skipped ---
+        // Offset to account for the skipped fence
         let meta_str = &content[3..*end_i + 2];
 
         if meta_str.is_empty() {
             return Ok((data, &content[end_i + 6..]));
         }
 
-        data = serde_yaml::from_str(meta_str)?;
+        data = match fence {
+            "+++" => serde_yaml::from_value(toml::parse(meta_str)?)?,
+            _ => serde_yaml::from_str(meta_str)?,
+        };
+        data.apply_static_site();
 
         let content = &content[end_i + 6..];
 
-        data.reading_time = Some(Self::calculate_reading_time(content));
-
         if data.title.is_none() {
             data.title = Self::find_title_from_h1(content);
         }
@@ -122,10 +439,46 @@ impl DocumentMeta {
 
         None
     }
+}
+
+/// Problem found while parsing the frontmatter of a document.
+#[derive(Debug, Clone)]
+pub struct FrontmatterIssue {
+    pub message: String,
+    /// Line in the file, starting from 1
+    pub line: Option<i32>,
+    pub column: Option<i32>,
+}
 
-    fn calculate_reading_time(content: &str) -> i32 {
-        let words = content.split(' ').collect::<Vec<_>>().len();
-        ((words / 200) as f32 * 0.60) as i32
+impl FrontmatterIssue {
+    fn from_error(error: &LedgeknawError) -> Self {
+        match error {
+            LedgeknawError::SerdeYaml(e) => {
+                // The YAML starts right after the opening ---, so its lines match the file's
+                let location = e.location();
+                Self {
+                    message: e.to_string(),
+                    line: location.as_ref().map(|l| l.line() as i32),
+                    column: location.as_ref().map(|l| l.column() as i32),
+                }
+            }
+            LedgeknawError::Toml(e) => Self {
+                message: e.message.clone(),
+                line: Some(e.line as i32),
+                column: None,
+            },
+            e => Self {
+                message: e.to_string(),
+                line: None,
+                column: None,
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for FrontmatterIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid frontmatter: {}", self.message)
     }
 }
 
@@ -141,80 +494,113 @@ pub struct Directory {
     /// Present only in nested directories
     pub parent: Option<uuid::Uuid>,
 
+    /// Inherited from the root directory configuration
+    pub private: bool,
+
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+
+    /// See [models::DirectoryEntry::version]
+    pub version: i64,
+
+    /// Whether there are no published public documents in the directory at any depth
+    pub empty: bool,
+
+    /// Whether there are no documents in the directory at any depth, including
+    /// private ones and drafts
+    pub empty_private: bool,
 }
 
-#[async_recursion]
-pub async fn process_directory(
+/// Store the directories and documents below the root directory. The tree is walked
+/// breadth first with a queue, so deep trees cannot overflow the stack. Directories
+/// deeper than [ReadOptions::max_depth] or failing to be read are skipped and reported.
+pub async fn process_root_directory(
     db: &DocumentDb,
-    path: impl AsRef<Path> + 'async_recursion + Send,
-    parent_id: uuid::Uuid,
-) -> Result<(), LedgeknawError> {
+    path: impl AsRef<Path>,
+    alias: &str,
+    options: ReadOptions,
+) -> Result<DepthReport, LedgeknawError> {
     let full_path = path.as_ref().canonicalize()?.display().to_string();
     debug!("Loading {full_path}");
 
-    // Normalize dir name
     let dir_name = get_valid_name(path.as_ref())?;
 
-    // Attempt to find existing parent
-    let parent = db.get_dir_by_name_and_parent(dir_name, parent_id).await?;
-    let directory = match parent {
+    let root = db.get_root_dir_by_path(&full_path).await?;
+    let directory = match root {
         Some(dir) => dir,
-        None => db.insert_dir(&full_path, dir_name, parent_id).await?,
+        None => db.insert_root_dir(&full_path, dir_name, alias).await?,
     };
 
-    // Scan contents, call this fn again on directories
     let entries = fs::read_dir(&path)?
         .filter_map(Result::ok)
         .collect::<Vec<_>>();
 
-    for entry in entries.iter() {
-        if entry.path().is_dir() {
-            process_directory(db, entry.path(), directory.id).await?;
+    let mut report = DepthReport::default();
+    let mut queue = VecDeque::from([(entries, directory, 0)]);
+
+    while let Some((entries, directory, depth)) = queue.pop_front() {
+        report.count(depth, &entries);
+
+        for entry in entries.iter() {
+            let path = entry.path();
+            if !path.is_dir() {
+                continue;
+            }
+
+            if depth + 1 > options.max_depth {
+                warn!(
+                    "Skipping {}, deeper than {} directories",
+                    path.display(),
+                    options.max_depth
+                );
+                report.skipped.push(path.display().to_string());
+                continue;
+            }
+
+            match read_directory(db, &path, directory.id).await {
+                Ok((child, entries)) => queue.push_back((entries, child, depth + 1)),
+                Err(e) => {
+                    warn!("Skipping {}: {e}", path.display());
+                    report.skipped.push(path.display().to_string());
+                }
+            }
         }
-    }
 
-    read_and_store_directory_files(db, &entries, &directory).await?;
+        read_and_store_directory_files(db, &entries, &directory, options).await?;
+    }
 
-    Ok(())
+    Ok(report)
 }
 
-pub async fn process_root_directory(
+/// Find or store the directory and list its entries.
+async fn read_directory(
     db: &DocumentDb,
-    path: impl AsRef<Path>,
-    alias: &str,
-) -> Result<(), LedgeknawError> {
-    let entries = fs::read_dir(&path)?
+    path: &Path,
+    parent_id: uuid::Uuid,
+) -> Result<(Directory, Vec<DirEntry>), LedgeknawError> {
+    let full_path = path.canonicalize()?.display().to_string();
+    debug!("Loading {full_path}");
+
+    let entries = fs::read_dir(path)?
         .filter_map(Result::ok)
         .collect::<Vec<_>>();
 
-    let full_path = path.as_ref().canonicalize()?.display().to_string();
-    debug!("Loading {full_path}");
-
-    let dir_name = get_valid_name(path.as_ref())?;
+    // Normalize dir name
+    let dir_name = get_valid_name(path)?;
 
-    let root = db.get_root_dir_by_name(dir_name).await?;
-    let directory = match root {
+    let directory = match db.get_dir_by_name_and_parent(dir_name, parent_id).await? {
         Some(dir) => dir,
-        None => db.insert_root_dir(&full_path, dir_name, alias).await?,
+        None => db.insert_dir(&full_path, dir_name, parent_id).await?,
     };
 
-    for entry in entries.iter() {
-        if entry.path().is_dir() {
-            process_directory(db, entry.path(), directory.id).await?;
-        }
-    }
-
-    read_and_store_directory_files(db, &entries, &directory).await?;
-
-    Ok(())
+    Ok((directory, entries))
 }
 
 async fn read_and_store_directory_files(
     db: &DocumentDb,
     entries: &[DirEntry],
     directory_entry: &Directory,
+    options: ReadOptions,
 ) -> Result<(), LedgeknawError> {
     // Collect md files
     let mut md_files = vec![];
@@ -257,7 +643,7 @@ async fn read_and_store_directory_files(
 
     for item in existing {
         let idx = md_files.iter().position(|el| {
-            let Some(file_name) = el.iter().last() else {
+            let Some(file_name) = el.iter().next_back() else {
                 return false;
             };
 
@@ -277,126 +663,230 @@ async fn read_and_store_directory_files(
         }
     }
 
-    let files_processed = process_files(directory_entry.id, md_files)?;
+    // Hard links to stored documents are stored as other paths of them instead,
+    // as are hard links to a file already read from this directory
+    let file_ids = md_files.iter().map(file_id).collect::<Vec<_>>();
+    let stored = db
+        .list_docs_by_file_ids(&file_ids.iter().flatten().copied().collect::<Vec<_>>())
+        .await?
+        .into_iter()
+        .map(|(file_id, id, _)| (file_id, id))
+        .collect::<HashMap<_, _>>();
+
+    let mut to_read = vec![];
+    let mut read_ids = HashSet::new();
+    let mut links = vec![];
+    let mut deferred = vec![];
+
+    for (path, file_id) in md_files.into_iter().zip(file_ids) {
+        match file_id {
+            Some(file_id) => match stored.get(&file_id) {
+                Some(id) => links.push((canonical(&path)?, *id)),
+                None if read_ids.insert(file_id) => to_read.push(path),
+                None => deferred.push((canonical(&path)?, file_id)),
+            },
+            None => to_read.push(path),
+        }
+    }
+
+    let mut tasks = read_files(directory_entry.id, to_read, options);
+    let mut files_processed = 0;
+
+    // Store the files as they are read
+    while let Some(result) = tasks.join_next().await {
+        let processed = result
+            .map_err(|e| LedgeknawError::IO(std::io::Error::other(e)))
+            .and_then(|processed| processed);
+
+        let (file, mut meta, mut issue) = match processed {
+            Ok(processed) => processed,
+            Err(e) => {
+                error!("Error occurred while processing file: {e:?}");
+                continue;
+            }
+        };
+
+        release_taken_custom_id(db, &file.path, &mut meta, &mut issue).await?;
+
+        let id = db
+            .insert_doc(&file, &meta, file_id(Path::new(&file.path)))
+            .await?;
+
+        if let Some(issue) = &issue {
+            warn!("{}: {issue}", file.path);
+        }
+
+        if let Some(id) = id {
+            db.set_issue(id, issue.as_ref()).await?;
+        }
+
+        files_processed += 1;
+    }
+
+    if !deferred.is_empty() {
+        let (paths, file_ids): (Vec<_>, Vec<_>) = deferred.into_iter().unzip();
+        let stored = db
+            .list_docs_by_file_ids(&file_ids)
+            .await?
+            .into_iter()
+            .map(|(file_id, id, _)| (file_id, id))
+            .collect::<HashMap<_, _>>();
+        for (path, file_id) in paths.into_iter().zip(file_ids) {
+            if let Some(id) = stored.get(&file_id) {
+                links.push((path, *id));
+            }
+        }
+    }
 
-    for (file, meta) in files_processed.iter() {
-        db.insert_doc(file, meta).await?;
+    if !links.is_empty() {
+        debug!("Hard links to documents: {links:?}");
+        db.insert_document_paths(&links).await?;
     }
 
     info!(
-        "{} - Existing files: {amt_files_existing} Processed files: {}",
+        "{} - Existing files: {amt_files_existing} Processed files: {files_processed} Hard links: {}",
         directory_entry.name,
-        files_processed.len()
+        links.len()
     );
 
     Ok(())
 }
 
-fn process_files(
-    directory: uuid::Uuid,
-    file_paths: Vec<PathBuf>,
-) -> Result<Vec<(Document, DocumentMeta)>, LedgeknawError> {
-    let files_total = file_paths.len();
-    let mut files_remaining = files_total;
+type ProcessedFile = (Document, DocumentMeta, Option<FrontmatterIssue>);
+
+/// Custom IDs are unique, so a document reusing one already taken is stored
+/// without it and the conflict is reported as its issue, unless it has another.
+pub async fn release_taken_custom_id(
+    db: &DocumentDb,
+    path: &str,
+    meta: &mut DocumentMeta,
+    issue: &mut Option<FrontmatterIssue>,
+) -> Result<(), LedgeknawError> {
+    let Some(custom_id) = &meta.custom_id else {
+        return Ok(());
+    };
+
+    if !db.custom_id_taken(custom_id, path).await? {
+        return Ok(());
+    }
 
-    let mut files = vec![];
+    let message = format!("the ID {custom_id} is already used by another document");
+    meta.custom_id = None;
+    issue.get_or_insert(FrontmatterIssue {
+        message,
+        line: None,
+        column: None,
+    });
+
+    Ok(())
+}
 
-    while files_remaining > 0 {
-        let mut batches: Vec<&[PathBuf]> = vec![&[]; *MAX_THREADS];
+/// Read the files on the blocking pool, at most [MAX_CONCURRENT_READS] at a time.
+fn read_files(
+    directory: uuid::Uuid,
+    file_paths: Vec<PathBuf>,
+    options: ReadOptions,
+) -> JoinSet<Result<ProcessedFile, LedgeknawError>> {
+    let permits = Arc::new(Semaphore::new(*MAX_CONCURRENT_READS));
+    let mut tasks = JoinSet::new();
+
+    for file_path in file_paths {
+        let permits = permits.clone();
+        tasks.spawn(async move {
+            let _permit = permits
+                .acquire_owned()
+                .await
+                .expect("semaphore is never closed");
+            tokio::task::spawn_blocking(move || read_file(directory, &file_path, options))
+                .await
+                .map_err(|e| LedgeknawError::IO(std::io::Error::other(e)))?
+        });
+    }
 
-        for (i, batch) in batches.iter_mut().enumerate() {
-            let start = i * FILES_PER_THREAD;
+    tasks
+}
 
-            let mut end = (i + 1) * FILES_PER_THREAD;
+fn read_file(
+    directory: uuid::Uuid,
+    file_path: &Path,
+    options: ReadOptions,
+) -> Result<ProcessedFile, LedgeknawError> {
+    let start = Instant::now();
+    let full_path = file_path.canonicalize()?;
+    let document = Document::new(
+        directory,
+        DocumentMeta::name_from_fs(&full_path),
+        full_path.display().to_string(),
+        find_sidecar_asset(file_path)?,
+    );
+    let (document_meta, issue) = DocumentMeta::read_from_file(file_path, options)?;
+    debug!(
+        "Read {} in {}ms",
+        document.path,
+        start.elapsed().as_nanos() as f32 * 0.000001
+    );
+    Ok((document, document_meta, issue))
+}
 
-            if end > files_total {
-                end = files_total;
+/// Hash the contents of a file, used as the key of the asset text cache.
+pub fn hash_content(content: &[u8]) -> String {
+    hex::encode(Sha256::digest(content))
+}
 
-                *batch = &file_paths[start..end];
+/// The last modification time of the file, if the platform reports it.
+pub fn modified_time(path: impl AsRef<Path>) -> Option<DateTime<Utc>> {
+    fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+        .map(DateTime::from)
+}
 
-                files_remaining -= end - start;
+/// Device and inode of a file, shared by all of its hard links.
+pub type FileId = (i64, i64);
+
+/// The device and inode of the file, if it exists and the platform has them.
+pub fn file_id(path: impl AsRef<Path>) -> Option<FileId> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        let metadata = fs::metadata(path).ok()?;
+        // Stored as signed integers, only ever compared for equality
+        Some((metadata.dev() as i64, metadata.ino() as i64))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        None
+    }
+}
 
-                break;
-            }
+fn canonical(path: &Path) -> Result<String, LedgeknawError> {
+    Ok(path.canonicalize()?.display().to_string())
+}
 
-            *batch = &file_paths[start..end];
+/// Sidecar files are markdown files named after a neighboring non-markdown
+/// file, e.g. `foo.pdf.md` describes `foo.pdf`. Returns the canonicalised
+/// path of the described asset if `path` is a sidecar.
+pub(crate) fn find_sidecar_asset(path: &Path) -> Result<Option<String>, LedgeknawError> {
+    let Some(asset_name) = path.file_stem() else {
+        return Ok(None);
+    };
 
-            files_remaining -= FILES_PER_THREAD;
-        }
+    let asset = path.with_file_name(asset_name);
 
-        type TaskWithStart<'a> = (
-            ScopedJoinHandle<'a, Result<Vec<(Document, DocumentMeta)>, LedgeknawError>>,
-            Instant,
-        );
-
-        batches.retain(|batch| !batch.is_empty());
-
-        if batches.len() > 1 {
-            debug!("Processing multiple ({}) batches", batches.len());
-            std::thread::scope(|scope| {
-                let mut tasks: Vec<TaskWithStart> = Vec::with_capacity(*MAX_THREADS);
-
-                for batch in batches {
-                    if batch.is_empty() {
-                        continue;
-                    }
-
-                    let task = scope.spawn(move || {
-                        let mut files = vec![];
-                        for file_path in batch {
-                            let file_name = DocumentMeta::name_from_fs(file_path.canonicalize()?);
-                            let document = Document::new(
-                                directory,
-                                file_name,
-                                file_path.display().to_string(),
-                            );
-                            let document_meta = DocumentMeta::read_from_file(file_path)?;
-                            files.push((document, document_meta));
-                        }
-                        Ok(files)
-                    });
-
-                    debug!("Spawned thread {:?}", task.thread().id());
-
-                    tasks.push((task, Instant::now()));
-                }
+    match asset.extension().and_then(OsStr::to_str) {
+        None | Some("md") => return Ok(None),
+        Some(_) => {}
+    }
 
-                for (task, start) in tasks {
-                    let id = task.thread().id();
-                    let result = task.join();
-                    match result {
-                        Ok(Ok(processed)) => {
-                            files.extend(processed);
-                            debug!(
-                                "Thread {:?} finished in {}ms",
-                                id,
-                                Instant::now().duration_since(start).as_nanos() as f32 * 0.001
-                            );
-                        }
-                        Ok(Err(e)) => error!("Error occurred while processing files: {e:?}"),
-                        Err(e) => error!("Error occurred while processing files: {e:?}"),
-                    }
-                }
-            });
-        } else {
-            debug!("Processing single batch");
-            for file_path in batches[0] {
-                let file_name = DocumentMeta::name_from_fs(file_path.canonicalize()?);
-                let document = Document::new(
-                    directory,
-                    file_name,
-                    file_path.canonicalize()?.display().to_string(),
-                );
-                let document_meta = DocumentMeta::read_from_file(file_path)?;
-                files.push((document, document_meta));
-            }
-        }
+    if !asset.is_file() {
+        return Ok(None);
     }
 
-    Ok(files)
+    Ok(Some(asset.canonicalize()?.display().to_string()))
 }
 
-fn get_valid_name(path: &Path) -> Result<&str, LedgeknawError> {
+pub(crate) fn get_valid_name(path: &Path) -> Result<&str, LedgeknawError> {
     let dir_name = path
         .file_name()
         .ok_or(LedgeknawError::InvalidDirectory(format!(
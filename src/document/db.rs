@@ -1,20 +1,46 @@
-use super::{models::Document, Directory, DocumentMeta};
-use crate::{document::models::DirectoryEntry, error::LedgeknawError};
+use super::{
+    git::FileHistory,
+    models::Document,
+    query::{Expr, Order},
+    Directory, DocumentMeta, FileId, FrontmatterIssue,
+};
+use crate::{
+    auth::Access,
+    db::{retry_read, QueryMetrics},
+    document::models::{
+        Author, Collection, DatedEntry, DerivedDocument, DirectoryEntry, DirectoryState,
+        DocumentIssue, DocumentState, ExportedDocument, GraphDocument, LinkReport, RootRevision,
+        TableScans,
+    },
+    error::LedgeknawError,
+};
+use chrono::{DateTime, Utc};
 use sqlx::PgPool;
+use std::collections::HashMap;
 use tracing::debug;
 
 #[derive(Debug, Clone)]
 pub struct DocumentDb {
     pool: sqlx::PgPool,
+    pub metrics: QueryMetrics,
 }
 
 impl DocumentDb {
-    pub async fn new(pool: PgPool) -> Self {
-        Self { pool }
+    pub async fn new(pool: PgPool, metrics: QueryMetrics) -> Self {
+        Self { pool, metrics }
+    }
+
+    pub async fn ping(&self) -> Result<(), LedgeknawError> {
+        let _timer = self.metrics.time("ping");
+        sqlx::query!("SELECT 1 AS one")
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(())
     }
 
     /// Retrieve all paths from the documents table
     pub async fn get_all_file_paths(&self) -> Result<Vec<String>, LedgeknawError> {
+        let _timer = self.metrics.time("get_all_file_paths");
         Ok(
             sqlx::query!("SELECT path FROM documents UNION SELECT path FROM directories",)
                 .fetch_all(&self.pool)
@@ -25,6 +51,58 @@ impl DocumentDb {
         )
     }
 
+    pub async fn count_documents(&self) -> Result<i64, LedgeknawError> {
+        let _timer = self.metrics.time("count_documents");
+        Ok(retry_read(|| {
+            sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM documents"#).fetch_one(&self.pool)
+        })
+        .await?
+        .count)
+    }
+
+    /// Retrieve the paths of all documents
+    pub async fn list_document_paths(&self) -> Result<Vec<String>, LedgeknawError> {
+        let _timer = self.metrics.time("list_document_paths");
+        Ok(sqlx::query!("SELECT path FROM documents")
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter()
+            .map(|el| el.path)
+            .collect())
+    }
+
+    /// The paths, titles and visibility of all documents, with digests of their metadata.
+    pub async fn list_document_states(&self) -> Result<Vec<DocumentState>, LedgeknawError> {
+        let _timer = self.metrics.time("list_document_states");
+        Ok(retry_read(|| {
+            sqlx::query_as!(
+                DocumentState,
+                r#"
+                SELECT id, path, title, private, md5(ROW(
+                    custom_id, title, reading_time, tags, draft, date_published,
+                    date_created, date_updated, weight, extra
+                )::TEXT) AS "digest!"
+                FROM documents
+                "#
+            )
+            .fetch_all(&self.pool)
+        })
+        .await?)
+    }
+
+    /// The versions of all directories.
+    pub async fn list_directory_states(&self) -> Result<Vec<DirectoryState>, LedgeknawError> {
+        let _timer = self.metrics.time("list_directory_states");
+        Ok(retry_read(|| {
+            sqlx::query_as!(
+                DirectoryState,
+                "SELECT id, private, version FROM directories"
+            )
+            .fetch_all(&self.pool)
+        })
+        .await?)
+    }
+
     /// Insert a child directory entry to the DB
     pub async fn insert_dir(
         &self,
@@ -32,6 +110,7 @@ impl DocumentDb {
         name: &str,
         parent: uuid::Uuid,
     ) -> Result<Directory, LedgeknawError> {
+        let _timer = self.metrics.time("insert_dir");
         sqlx::query_as!(
             Directory,
             "INSERT INTO directories(path, name, parent) VALUES($1, $2, $3) RETURNING *",
@@ -51,6 +130,7 @@ impl DocumentDb {
         name: &str,
         alias: &str,
     ) -> Result<Directory, LedgeknawError> {
+        let _timer = self.metrics.time("insert_root_dir");
         sqlx::query_as!(
             Directory,
             "INSERT INTO directories(path, name, alias) VALUES($1, $2, $3) RETURNING *",
@@ -63,32 +143,163 @@ impl DocumentDb {
         .map_err(LedgeknawError::from)
     }
 
+    /// Returns the ID of the inserted document, if it was inserted.
     pub async fn insert_doc(
         &self,
         document: &Document,
         meta: &DocumentMeta,
-    ) -> Result<(), LedgeknawError> {
+        file_id: Option<FileId>,
+    ) -> Result<Option<uuid::Uuid>, LedgeknawError> {
+        let _timer = self.metrics.time("insert_doc");
+        let (dev, inode) = file_id.unzip();
         let Document {
             file_name,
             directory,
             path,
+            asset,
         } = document;
 
         let DocumentMeta {
             custom_id,
             title,
+            reading_time,
             tags,
-            ..
+            aliases,
+            draft,
+            date,
+            created,
+            updated,
+            weight,
+            // Only read when serving
+            description: _,
+            cache: _,
+            noindex: _,
+            extra,
         } = meta;
 
-        sqlx::query!(
-            "INSERT INTO documents(file_name, directory, path, custom_id, title, tags) VALUES($1, $2, $3, $4, $5, $6) ON CONFLICT DO NOTHING",
+        let extra = serde_json::to_value(extra)?;
+
+        let id = sqlx::query!(
+            "INSERT INTO documents(file_name, directory, path, custom_id, title, tags, draft, asset, date_published, date_created, date_updated, reading_time, weight, extra, dev, inode) VALUES($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16) ON CONFLICT DO NOTHING RETURNING id",
             file_name,
             directory,
             path,
             custom_id.as_ref(),
             title.as_ref(),
-            tags.as_ref().map(|el|el.join(","))
+            tags.as_ref().map(|el|el.join(",")),
+            draft,
+            asset.as_ref(),
+            date.as_ref(),
+            created.as_ref(),
+            updated.as_ref(),
+            reading_time.as_ref(),
+            weight.as_ref(),
+            extra,
+            dev,
+            inode
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let Some(doc) = id else {
+            return Ok(None);
+        };
+
+        if let Some(aliases) = aliases {
+            self.insert_aliases(doc.id, aliases).await?;
+        }
+
+        Ok(Some(doc.id))
+    }
+
+    /// Replace the issue recorded for the document. Clears it if `issue` is `None`.
+    pub async fn set_issue(
+        &self,
+        document: uuid::Uuid,
+        issue: Option<&FrontmatterIssue>,
+    ) -> Result<(), LedgeknawError> {
+        let _timer = self.metrics.time("set_issue");
+        sqlx::query!("DELETE FROM document_issues WHERE document = $1", document)
+            .execute(&self.pool)
+            .await?;
+
+        let Some(FrontmatterIssue {
+            message,
+            line,
+            column,
+        }) = issue
+        else {
+            return Ok(());
+        };
+
+        sqlx::query!(
+            "INSERT INTO document_issues(document, message, line, col) VALUES($1, $2, $3, $4)",
+            document,
+            message,
+            line.as_ref(),
+            column.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map(|_| ())
+        .map_err(LedgeknawError::from)
+    }
+
+    /// Tables ordered by the rows read in sequential scans.
+    pub async fn list_table_scans(&self) -> Result<Vec<TableScans>, LedgeknawError> {
+        let _timer = self.metrics.time("list_table_scans");
+        Ok(retry_read(|| {
+            sqlx::query_as!(
+                TableScans,
+                r#"
+                SELECT relname::TEXT AS "table!",
+                n_live_tup AS "live_rows!",
+                seq_scan AS "seq_scans!",
+                seq_tup_read AS "seq_rows_read!",
+                COALESCE(idx_scan, 0) AS "index_scans!"
+                FROM pg_stat_user_tables
+                ORDER BY seq_tup_read DESC, relname
+            "#
+            )
+            .fetch_all(&self.pool)
+        })
+        .await?)
+    }
+
+    pub async fn list_issues(&self) -> Result<Vec<DocumentIssue>, LedgeknawError> {
+        let _timer = self.metrics.time("list_issues");
+        retry_read(|| {
+            sqlx::query_as!(
+                DocumentIssue,
+                r#"
+                SELECT doc.id AS "document!", doc.file_name AS "file_name!", doc.title, iss.message AS "message!", iss.line, iss.col AS column, iss.created_at AS "created_at!"
+                FROM document_issues iss
+                INNER JOIN documents doc ON doc.id = iss.document
+                UNION ALL
+                SELECT doc.id, doc.file_name, doc.title, 'Broken link ' || li.link || ': ' || li.message, NULL, NULL, li.created_at
+                FROM link_issues li
+                INNER JOIN documents doc ON doc.id = li.document
+                ORDER BY 7 DESC
+                "#
+            )
+            .fetch_all(&self.pool)
+        })
+        .await
+        .map_err(LedgeknawError::from)
+    }
+
+    /// Insert the aliases for the given document. Aliases already taken by
+    /// another document are ignored.
+    pub async fn insert_aliases(
+        &self,
+        document: uuid::Uuid,
+        aliases: &[String],
+    ) -> Result<(), LedgeknawError> {
+        let _timer = self.metrics.time("insert_aliases");
+        sqlx::query!(
+            "INSERT INTO document_aliases(document, alias) SELECT $1, UNNEST($2::TEXT[]) ON CONFLICT DO NOTHING",
+            document,
+            aliases
         )
         .execute(&self.pool)
         .await
@@ -96,36 +307,308 @@ impl DocumentDb {
         .map_err(LedgeknawError::from)
     }
 
-    pub async fn get_index_id_path(&self) -> Result<Option<(uuid::Uuid, String)>, LedgeknawError> {
+    /// Find the document an alias points to.
+    /// Returns the document ID and its custom ID, if any.
+    pub async fn get_doc_by_alias(
+        &self,
+        alias: &str,
+        access: Access,
+    ) -> Result<Option<(uuid::Uuid, Option<String>)>, LedgeknawError> {
+        let _timer = self.metrics.time("get_doc_by_alias");
+        Ok(retry_read(|| {
+            sqlx::query!(
+                "SELECT doc.id, doc.custom_id FROM documents doc
+             INNER JOIN document_aliases a ON a.document = doc.id
+             WHERE a.alias = $1 AND (NOT doc.draft OR $3) AND (NOT doc.private OR $2)",
+                alias,
+                access.private,
+                access.authenticated()
+            )
+            .fetch_optional(&self.pool)
+        })
+        .await?
+        .map(|el| (el.id, el.custom_id)))
+    }
+
+    pub async fn get_index_id_path(
+        &self,
+        access: Access,
+    ) -> Result<Option<(uuid::Uuid, String)>, LedgeknawError> {
+        let _timer = self.metrics.time("get_index_id_path");
+        Ok(retry_read(|| {
+            sqlx::query!(
+                "SELECT id, path FROM documents WHERE file_name = 'index.md' AND (NOT draft OR $2) AND (NOT private OR $1) LIMIT 1",
+                access.private,
+                access.authenticated()
+            )
+            .fetch_optional(&self.pool)
+        })
+        .await?
+        .map(|el| (el.id, el.path)))
+    }
+
+    pub async fn get_doc_path(
+        &self,
+        id: uuid::Uuid,
+        access: Access,
+    ) -> Result<Option<String>, LedgeknawError> {
+        let _timer = self.metrics.time("get_doc_path");
+        Ok(retry_read(|| {
+            sqlx::query!(
+                "SELECT path FROM documents WHERE id = $1 AND (NOT draft OR $3) AND (NOT private OR $2)",
+                id,
+                access.private,
+                access.authenticated()
+            )
+            .fetch_optional(&self.pool)
+        })
+        .await?
+        .map(|el| el.path))
+    }
+
+    /// Get the paths, IDs and custom IDs of the documents at any of the paths.
+    pub async fn list_doc_ids_by_paths(
+        &self,
+        paths: &[String],
+        access: Access,
+    ) -> Result<Vec<(String, uuid::Uuid, Option<String>)>, LedgeknawError> {
+        let _timer = self.metrics.time("list_doc_ids_by_paths");
+        Ok(retry_read(|| {
+            sqlx::query!(
+                "SELECT path, id, custom_id FROM documents WHERE path = ANY($1) AND (NOT draft OR $3) AND (NOT private OR $2)",
+                paths,
+                access.private,
+                access.authenticated()
+            )
+            .fetch_all(&self.pool)
+        })
+        .await?
+        .into_iter()
+        .map(|el| (el.path, el.id, el.custom_id))
+        .collect())
+    }
+
+    /// Get the path of the document along with when it was indexed and last updated.
+    pub async fn get_doc_timestamps(
+        &self,
+        id: uuid::Uuid,
+        access: Access,
+    ) -> Result<Option<(String, DateTime<Utc>, DateTime<Utc>)>, LedgeknawError> {
+        let _timer = self.metrics.time("get_doc_timestamps");
+        Ok(retry_read(|| {
+            sqlx::query!(
+                "SELECT path, created_at, updated_at FROM documents WHERE id = $1 AND (NOT draft OR $3) AND (NOT private OR $2)",
+                id,
+                access.private,
+                access.authenticated()
+            )
+            .fetch_optional(&self.pool)
+        })
+        .await?
+        .map(|el| (el.path, el.created_at, el.updated_at)))
+    }
+
+    /// Get the path of the asset described by the sidecar document with the given ID.
+    pub async fn get_asset_path(
+        &self,
+        id: uuid::Uuid,
+        access: Access,
+    ) -> Result<Option<String>, LedgeknawError> {
+        let _timer = self.metrics.time("get_asset_path");
+        Ok(retry_read(|| {
+            sqlx::query!(
+                "SELECT asset FROM documents WHERE id = $1 AND (NOT draft OR $3) AND (NOT private OR $2)",
+                id,
+                access.private,
+                access.authenticated()
+            )
+            .fetch_optional(&self.pool)
+        })
+        .await?
+        .and_then(|el| el.asset))
+    }
+
+    /// Get the asset path of the sidecar document, including drafts.
+    pub async fn get_document_asset(
+        &self,
+        id: uuid::Uuid,
+    ) -> Result<Option<String>, LedgeknawError> {
+        let _timer = self.metrics.time("get_document_asset");
+        Ok(retry_read(|| {
+            sqlx::query!("SELECT asset FROM documents WHERE id = $1", id).fetch_optional(&self.pool)
+        })
+        .await?
+        .and_then(|el| el.asset))
+    }
+
+    /// List the IDs and asset paths of all sidecar documents.
+    pub async fn list_assets(&self) -> Result<Vec<(uuid::Uuid, String)>, LedgeknawError> {
+        let _timer = self.metrics.time("list_assets");
         Ok(
-            sqlx::query!("SELECT id, path FROM documents WHERE file_name = 'index.md' LIMIT 1")
-                .fetch_optional(&self.pool)
+            sqlx::query!("SELECT id, asset FROM documents WHERE asset IS NOT NULL")
+                .fetch_all(&self.pool)
                 .await?
-                .map(|el| (el.id, el.path)),
+                .into_iter()
+                .filter_map(|el| Some((el.id, el.asset?)))
+                .collect(),
+        )
+    }
+
+    /// Find the content of any document of the given kind derived from content with the given hash.
+    /// Used as a cache so identical sources are never processed twice.
+    pub async fn find_derived_content(
+        &self,
+        kind: &str,
+        source_hash: &str,
+    ) -> Result<Option<String>, LedgeknawError> {
+        let _timer = self.metrics.time("find_derived_content");
+        Ok(sqlx::query!(
+            "SELECT content FROM derived_documents WHERE kind = $1 AND source_hash = $2 LIMIT 1",
+            kind,
+            source_hash
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .map(|el| el.content))
+    }
+
+    /// Whether the document has a derived document of the given kind generated from the current source.
+    pub async fn derived_up_to_date(
+        &self,
+        document: uuid::Uuid,
+        kind: &str,
+        source_hash: &str,
+    ) -> Result<bool, LedgeknawError> {
+        let _timer = self.metrics.time("derived_up_to_date");
+        Ok(sqlx::query!(
+            "SELECT id FROM derived_documents WHERE document = $1 AND kind = $2 AND source_hash = $3",
+            document,
+            kind,
+            source_hash
         )
+        .fetch_optional(&self.pool)
+        .await?
+        .is_some())
     }
 
-    pub async fn get_doc_path(&self, id: uuid::Uuid) -> Result<Option<String>, LedgeknawError> {
-        Ok(sqlx::query!("SELECT path FROM documents WHERE id = $1", id)
+    pub async fn upsert_derived(
+        &self,
+        document: uuid::Uuid,
+        kind: &str,
+        content: &str,
+        provenance: &str,
+        source_hash: &str,
+    ) -> Result<(), LedgeknawError> {
+        let _timer = self.metrics.time("upsert_derived");
+        sqlx::query!(
+            r#"
+            INSERT INTO derived_documents(document, kind, content, provenance, source_hash)
+            VALUES($1, $2, $3, $4, $5)
+            ON CONFLICT(document, kind) DO UPDATE SET
+            content = $3,
+            provenance = $4,
+            source_hash = $5
+        "#,
+            document,
+            kind,
+            content,
+            provenance,
+            source_hash
+        )
+        .execute(&self.pool)
+        .await
+        .map(|_| ())
+        .map_err(LedgeknawError::from)
+    }
+
+    pub async fn get_derived(
+        &self,
+        document: uuid::Uuid,
+        kind: &str,
+        access: Access,
+    ) -> Result<Option<DerivedDocument>, LedgeknawError> {
+        let _timer = self.metrics.time("get_derived");
+        retry_read(|| {
+            sqlx::query_as!(
+                DerivedDocument,
+                "SELECT der.id, der.document, der.kind, der.content, der.provenance, der.created_at, der.updated_at
+                 FROM derived_documents der
+                 INNER JOIN documents doc ON doc.id = der.document
+                 WHERE der.document = $1 AND der.kind = $2 AND (NOT doc.draft OR $4) AND (NOT doc.private OR $3)",
+                document,
+                kind,
+                access.private,
+                access.authenticated()
+            )
             .fetch_optional(&self.pool)
-            .await?
-            .map(|el| el.path))
+        })
+        .await
+        .map_err(LedgeknawError::from)
+    }
+
+    /// List the kinds of documents derived from the given document.
+    pub async fn list_derived_kinds(
+        &self,
+        document: uuid::Uuid,
+        access: Access,
+    ) -> Result<Vec<String>, LedgeknawError> {
+        let _timer = self.metrics.time("list_derived_kinds");
+        Ok(retry_read(|| {
+            sqlx::query!(
+                "SELECT der.kind FROM derived_documents der
+             INNER JOIN documents doc ON doc.id = der.document
+             WHERE der.document = $1 AND (NOT doc.draft OR $3) AND (NOT doc.private OR $2)
+             ORDER BY der.kind",
+                document,
+                access.private,
+                access.authenticated()
+            )
+            .fetch_all(&self.pool)
+        })
+        .await?
+        .into_iter()
+        .map(|el| el.kind)
+        .collect())
     }
 
     pub async fn get_doc_id_path_by_custom_id(
         &self,
         custom_id: &str,
+        access: Access,
     ) -> Result<Option<(uuid::Uuid, String)>, LedgeknawError> {
+        let _timer = self.metrics.time("get_doc_id_path_by_custom_id");
+        Ok(retry_read(|| {
+            sqlx::query!(
+                "SELECT id, path FROM documents WHERE custom_id = $1 AND (NOT draft OR $3) AND (NOT private OR $2)",
+                custom_id,
+                access.private,
+                access.authenticated()
+            )
+            .fetch_optional(&self.pool)
+        })
+        .await?
+        .map(|el| (el.id, el.path)))
+    }
+
+    /// Whether a document other than the one at `path` has the custom ID.
+    pub async fn custom_id_taken(
+        &self,
+        custom_id: &str,
+        path: &str,
+    ) -> Result<bool, LedgeknawError> {
+        let _timer = self.metrics.time("custom_id_taken");
         Ok(sqlx::query!(
-            "SELECT id, path FROM documents WHERE custom_id = $1",
-            custom_id
+            r#"SELECT EXISTS(SELECT 1 FROM documents WHERE custom_id = $1 AND path != $2) AS "taken!""#,
+            custom_id,
+            path
         )
-        .fetch_optional(&self.pool)
+        .fetch_one(&self.pool)
         .await?
-        .map(|el| (el.id, el.path)))
+        .taken)
     }
 
     pub async fn list_root_paths(&self) -> Result<Vec<String>, LedgeknawError> {
+        let _timer = self.metrics.time("list_root_paths");
         Ok(
             sqlx::query!("SELECT path FROM directories WHERE parent IS NULL",)
                 .fetch_all(&self.pool)
@@ -137,6 +620,7 @@ impl DocumentDb {
     }
 
     pub async fn get_dir_by_path(&self, path: &str) -> Result<Option<Directory>, LedgeknawError> {
+        let _timer = self.metrics.time("get_dir_by_path");
         sqlx::query_as!(Directory, "SELECT * FROM directories WHERE path = $1", path)
             .fetch_optional(&self.pool)
             .await
@@ -144,6 +628,7 @@ impl DocumentDb {
     }
 
     pub async fn get_root_by_path(&self, path: &str) -> Result<Option<Directory>, LedgeknawError> {
+        let _timer = self.metrics.time("get_root_by_path");
         sqlx::query_as!(
             Directory,
             "SELECT * FROM directories WHERE path = $1 AND parent IS NULL",
@@ -159,9 +644,10 @@ impl DocumentDb {
         directory: uuid::Uuid,
         file_names: &[String],
     ) -> Result<Vec<Document>, LedgeknawError> {
+        let _timer = self.metrics.time("list_document_in_dir");
         sqlx::query_as!(
             Document,
-            "SELECT file_name, directory, path 
+            "SELECT file_name, directory, path, asset
              FROM documents WHERE file_name = ANY($1) AND directory = $2",
             file_names,
             directory
@@ -171,15 +657,46 @@ impl DocumentDb {
         .map_err(LedgeknawError::from)
     }
 
-    pub async fn list_roots(&self) -> Result<Vec<DirectoryEntry>, LedgeknawError> {
-        sqlx::query_as_unchecked!(
-            DirectoryEntry,
-            r#"
-                SELECT id, parent, name, 'd' AS type, alias AS title, NULL AS custom_id
-                FROM directories WHERE parent IS NULL
-        "#
-        )
-        .fetch_all(&self.pool)
+    pub async fn list_roots(&self, access: Access) -> Result<Vec<DirectoryEntry>, LedgeknawError> {
+        let _timer = self.metrics.time("list_roots");
+        retry_read(|| {
+            sqlx::query_as_unchecked!(
+                DirectoryEntry,
+                r#"
+                    SELECT id, parent, name, 'd' AS type, alias AS title, NULL AS custom_id, FALSE AS asset,
+                    (SELECT COUNT(*) FROM documents doc WHERE doc.directory = dir.id AND (NOT doc.draft OR $2) AND (NOT doc.private OR $1)) AS document_count,
+                    (SELECT COUNT(*) FROM directories sub WHERE sub.parent = dir.id AND (NOT sub.private OR $1)) AS subdirectory_count,
+                    created_at, updated_at, version
+                    FROM directories dir WHERE parent IS NULL AND (NOT private OR $1)
+            "#,
+                access.private,
+                access.authenticated()
+            )
+            .fetch_all(&self.pool)
+        })
+        .await
+        .map_err(LedgeknawError::from)
+    }
+
+    /// The display name of a visible directory or a collection.
+    pub async fn get_entry_name(
+        &self,
+        id: uuid::Uuid,
+        access: Access,
+    ) -> Result<Option<String>, LedgeknawError> {
+        let _timer = self.metrics.time("get_entry_name");
+        retry_read(|| {
+            sqlx::query_scalar!(
+                r#"
+                    SELECT COALESCE(alias, name) AS "name!" FROM directories WHERE id = $1 AND (NOT private OR $2)
+                    UNION ALL
+                    SELECT name AS "name!" FROM collections WHERE id = $1
+            "#,
+                id,
+                access.private
+            )
+            .fetch_optional(&self.pool)
+        })
         .await
         .map_err(LedgeknawError::from)
     }
@@ -187,21 +704,256 @@ impl DocumentDb {
     pub async fn list_entries(
         &self,
         id: uuid::Uuid,
+        access: Access,
     ) -> Result<Vec<DirectoryEntry>, LedgeknawError> {
-        sqlx::query_as_unchecked!(
-            DirectoryEntry,
+        let _timer = self.metrics.time("list_entries");
+        retry_read(|| {
+            sqlx::query_as_unchecked!(
+                DirectoryEntry,
+                r#"
+                    SELECT id, parent, name, type, title, custom_id, asset, document_count, subdirectory_count, created_at, updated_at, version FROM (
+                        SELECT doc.id, dir.id AS parent, doc.file_name AS name, 'f' AS type, doc.title, doc.custom_id, doc.asset IS NOT NULL AS asset, doc.weight,
+                        NULL::BIGINT AS document_count, NULL::BIGINT AS subdirectory_count, doc.created_at, doc.updated_at, doc.version
+                        FROM documents doc
+                        INNER JOIN directories dir
+                        ON doc.directory = dir.id AND dir.id = $1
+                        WHERE (NOT doc.draft OR $3) AND (NOT doc.private OR $2)
+                        UNION
+                        SELECT id, parent, name, 'd' AS type, alias AS title, NULL AS custom_id, FALSE AS asset, NULL AS weight,
+                        (SELECT COUNT(*) FROM documents doc WHERE doc.directory = dir.id AND (NOT doc.draft OR $3) AND (NOT doc.private OR $2)) AS document_count,
+                        (SELECT COUNT(*) FROM directories sub WHERE sub.parent = dir.id AND (NOT sub.private OR $2)) AS subdirectory_count,
+                        created_at, updated_at, version
+                        FROM directories dir WHERE parent = $1 AND (NOT private OR $2)
+                    ) entries
+                    ORDER BY weight NULLS LAST, name
+            "#,
+                id,
+                access.private,
+                access.authenticated()
+            )
+            .fetch_all(&self.pool)
+        })
+        .await
+        .map_err(LedgeknawError::from)
+    }
+
+    /// Get the serialized entries of the directory from the sidebar cache.
+    pub async fn get_cached_entries(
+        &self,
+        directory: uuid::Uuid,
+        access: Access,
+    ) -> Result<Option<serde_json::Value>, LedgeknawError> {
+        let _timer = self.metrics.time("get_cached_entries");
+        Ok(retry_read(|| {
+            sqlx::query!(
+                "SELECT entries FROM sidebar_cache WHERE directory = $1 AND private = $2",
+                directory,
+                access.private
+            )
+            .fetch_optional(&self.pool)
+        })
+        .await?
+        .map(|el| el.entries))
+    }
+
+    /// List the directories and access levels missing from the sidebar cache.
+    pub async fn list_uncached_listings(&self) -> Result<Vec<(uuid::Uuid, bool)>, LedgeknawError> {
+        let _timer = self.metrics.time("list_uncached_listings");
+        Ok(sqlx::query!(
             r#"
-                SELECT doc.id, dir.id AS parent, doc.file_name AS name, 'f' AS type, doc.title, doc.custom_id
-                FROM documents doc
-                INNER JOIN directories dir
-                ON doc.directory = dir.id AND dir.id = $1
-                UNION
-                SELECT id, parent, name, 'd' AS type, alias AS title, NULL AS custom_id
-                FROM directories WHERE parent = $1
-        "#,
-            id
+                SELECT dir.id, access.private AS "private!"
+                FROM directories dir
+                CROSS JOIN (VALUES (FALSE), (TRUE)) AS access(private)
+                WHERE NOT EXISTS (
+                    SELECT 1 FROM sidebar_cache c WHERE c.directory = dir.id AND c.private = access.private
+                )
+            "#
         )
         .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|el| (el.id, el.private))
+        .collect())
+    }
+
+    pub async fn cache_entries(
+        &self,
+        directory: uuid::Uuid,
+        private: bool,
+        entries: &serde_json::Value,
+    ) -> Result<(), LedgeknawError> {
+        let _timer = self.metrics.time("cache_entries");
+        sqlx::query!(
+            "INSERT INTO sidebar_cache(directory, private, entries) VALUES($1, $2, $3)
+             ON CONFLICT(directory, private) DO UPDATE SET entries = $3",
+            directory,
+            private,
+            entries
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// List the directories and documents of every root down to `depth` levels,
+    /// the roots being the first level. Entries are ordered as in [Self::list_entries].
+    pub async fn list_tree(
+        &self,
+        depth: Option<i32>,
+        access: Access,
+    ) -> Result<Vec<DirectoryEntry>, LedgeknawError> {
+        let _timer = self.metrics.time("list_tree");
+        retry_read(|| {
+            sqlx::query_as_unchecked!(
+                DirectoryEntry,
+                r#"
+                    WITH RECURSIVE tree AS (
+                        SELECT id, parent, name, alias, created_at, updated_at, version, 1 AS depth
+                        FROM directories WHERE parent IS NULL AND (NOT private OR $2)
+                        UNION ALL
+                        SELECT dir.id, dir.parent, dir.name, dir.alias, dir.created_at, dir.updated_at, dir.version, tree.depth + 1
+                        FROM directories dir
+                        INNER JOIN tree ON dir.parent = tree.id
+                        WHERE (NOT dir.private OR $2) AND ($1::INT IS NULL OR tree.depth < $1)
+                    )
+                    SELECT id, parent, name, type, title, custom_id, asset, document_count, subdirectory_count, created_at, updated_at, version FROM (
+                        SELECT doc.id, doc.directory AS parent, doc.file_name AS name, 'f' AS type, doc.title, doc.custom_id, doc.asset IS NOT NULL AS asset, doc.weight,
+                        NULL::BIGINT AS document_count, NULL::BIGINT AS subdirectory_count, doc.created_at, doc.updated_at, doc.version
+                        FROM documents doc
+                        INNER JOIN tree ON doc.directory = tree.id
+                        WHERE (NOT doc.draft OR $3) AND (NOT doc.private OR $2) AND ($1::INT IS NULL OR tree.depth < $1)
+                        UNION ALL
+                        SELECT id, parent, name, 'd' AS type, alias AS title, NULL AS custom_id, FALSE AS asset, NULL AS weight,
+                        (SELECT COUNT(*) FROM documents doc WHERE doc.directory = tree.id AND (NOT doc.draft OR $3) AND (NOT doc.private OR $2)) AS document_count,
+                        (SELECT COUNT(*) FROM directories sub WHERE sub.parent = tree.id AND (NOT sub.private OR $2)) AS subdirectory_count,
+                        created_at, updated_at, version
+                        FROM tree
+                    ) entries
+                    ORDER BY weight NULLS LAST, name
+            "#,
+                depth,
+                access.private,
+                access.authenticated()
+            )
+            .fetch_all(&self.pool)
+        })
+        .await
+        .map_err(LedgeknawError::from)
+    }
+
+    /// List the metadata of all visible documents.
+    pub async fn list_exported_documents(
+        &self,
+        access: Access,
+    ) -> Result<Vec<ExportedDocument>, LedgeknawError> {
+        let _timer = self.metrics.time("list_exported_documents");
+        retry_read(|| {
+            sqlx::query_as!(
+                ExportedDocument,
+                r#"
+                SELECT id, STRING_TO_ARRAY(tags, ',') AS tags, reading_time,
+                date_published AS published,
+                COALESCE(date_created, git_created) AS created,
+                COALESCE(date_updated, git_updated) AS updated,
+                weight, extra
+                FROM documents
+                WHERE (NOT draft OR $2) AND (NOT private OR $1)
+                ORDER BY path
+        "#,
+                access.private,
+                access.authenticated()
+            )
+            .fetch_all(&self.pool)
+        })
+        .await
+        .map_err(LedgeknawError::from)
+    }
+
+    /// List the documents matching the query.
+    /// If `directory` is given, only documents in its subtree are matched.
+    pub async fn query_documents(
+        &self,
+        expr: &Expr,
+        directory: Option<uuid::Uuid>,
+        order: Order,
+        limit: i64,
+        access: Access,
+    ) -> Result<Vec<DirectoryEntry>, LedgeknawError> {
+        let _timer = self.metrics.time("query_documents");
+        let mut query = sqlx::QueryBuilder::new("");
+
+        if let Some(directory) = directory {
+            query
+                .push(
+                    r#"
+                WITH RECURSIVE subtree AS (
+                    SELECT id FROM directories WHERE id = "#,
+                )
+                .push_bind(directory)
+                .push(
+                    r#"
+                    UNION ALL
+                    SELECT dir.id FROM directories dir INNER JOIN subtree ON dir.parent = subtree.id
+                )"#,
+                );
+        }
+
+        query.push(
+            r#"
+                SELECT doc.id, doc.directory AS parent, doc.file_name AS name, 'f' AS type, doc.title, doc.custom_id, doc.asset IS NOT NULL AS asset,
+                NULL::BIGINT AS document_count, NULL::BIGINT AS subdirectory_count, doc.created_at, doc.updated_at, doc.version
+                FROM documents doc
+                WHERE (NOT doc.draft OR "#,
+        );
+
+        query
+            .push_bind(access.authenticated())
+            .push(") AND (NOT doc.private OR ")
+            .push_bind(access.private)
+            .push(") AND ");
+
+        if directory.is_some() {
+            query.push("doc.directory IN (SELECT id FROM subtree) AND ");
+        }
+
+        expr.push_sql(&mut query);
+        order.push_sql(&mut query);
+        query.push(" LIMIT ").push_bind(limit);
+
+        query
+            .build_query_as::<DirectoryEntry>()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(LedgeknawError::from)
+    }
+
+    /// List the documents with frontmatter or git dates, most recent first.
+    /// Documents are sorted by their updated, publish or created date,
+    /// whichever is present first. Frontmatter dates take precedence.
+    pub async fn list_recent(
+        &self,
+        limit: i64,
+        access: Access,
+    ) -> Result<Vec<DatedEntry>, LedgeknawError> {
+        let _timer = self.metrics.time("list_recent");
+        retry_read(|| {
+            sqlx::query_as!(
+                DatedEntry,
+                r#"
+                    SELECT id, file_name AS name, title, custom_id,
+                    COALESCE(date_updated, git_updated, date_published, date_created, git_created) AS "date!"
+                    FROM documents
+                    WHERE COALESCE(date_updated, git_updated, date_published, date_created, git_created) IS NOT NULL
+                    AND (NOT draft OR $3) AND (NOT private OR $2)
+                    ORDER BY 5 DESC
+                    LIMIT $1
+            "#,
+                limit,
+                access.private,
+                access.authenticated()
+            )
+            .fetch_all(&self.pool)
+        })
         .await
         .map_err(LedgeknawError::from)
     }
@@ -211,6 +963,7 @@ impl DocumentDb {
         name: &str,
         id: uuid::Uuid,
     ) -> Result<Option<Directory>, LedgeknawError> {
+        let _timer = self.metrics.time("get_dir_by_name_and_parent");
         sqlx::query_as!(
             Directory,
             "SELECT * FROM directories WHERE name=$1 AND parent=$2",
@@ -222,52 +975,362 @@ impl DocumentDb {
         .map_err(LedgeknawError::from)
     }
 
-    pub async fn get_root_dir_by_name(
+    /// Roots are looked up by their canonicalised path, as roots in
+    /// different places may have directories of the same name.
+    pub async fn get_root_dir_by_path(
         &self,
-        name: &str,
+        path: &str,
     ) -> Result<Option<Directory>, LedgeknawError> {
+        let _timer = self.metrics.time("get_root_dir_by_path");
         sqlx::query_as!(
             Directory,
-            "SELECT * FROM directories WHERE name=$1 AND parent IS NULL",
-            name
+            "SELECT * FROM directories WHERE path = $1 AND parent IS NULL",
+            path
         )
         .fetch_optional(&self.pool)
         .await
         .map_err(LedgeknawError::from)
     }
 
+    /// List the public directories and published documents below the roots with the
+    /// aliases, as their IDs, custom IDs and whether they are documents.
+    pub async fn list_root_contents(
+        &self,
+        aliases: &[String],
+    ) -> Result<Vec<(uuid::Uuid, Option<String>, bool)>, LedgeknawError> {
+        let _timer = self.metrics.time("list_root_contents");
+        Ok(retry_read(|| {
+            sqlx::query!(
+                r#"
+                WITH RECURSIVE dirs AS (
+                    SELECT id FROM directories
+                    WHERE parent IS NULL AND alias = ANY($1) AND NOT private
+                    UNION ALL
+                    SELECT dir.id FROM directories dir
+                    INNER JOIN dirs ON dir.parent = dirs.id
+                    WHERE NOT dir.private
+                )
+                SELECT id AS "id!", NULL::TEXT AS custom_id, FALSE AS "document!" FROM dirs
+                UNION ALL
+                SELECT doc.id, doc.custom_id, TRUE FROM documents doc
+                WHERE doc.directory IN (SELECT id FROM dirs) AND NOT doc.draft AND NOT doc.private
+                "#,
+                aliases
+            )
+            .fetch_all(&self.pool)
+        })
+        .await?
+        .into_iter()
+        .map(|el| (el.id, el.custom_id, el.document))
+        .collect())
+    }
+
+    /// Mark the directories without published documents at any depth as empty.
+    /// Only changed rows are updated, so listings stay cached otherwise.
+    pub async fn refresh_empty_directories(&self) -> Result<(), LedgeknawError> {
+        let _timer = self.metrics.time("refresh_empty_directories");
+        let count = sqlx::query!(
+            "
+            UPDATE directories dir SET empty = state.empty, empty_private = state.empty_private
+            FROM (
+                SELECT dir.id,
+                NOT EXISTS (
+                    SELECT 1 FROM documents doc
+                    WHERE NOT doc.draft AND NOT doc.private AND STARTS_WITH(doc.path, dir.path || '/')
+                ) AS empty,
+                NOT EXISTS (
+                    SELECT 1 FROM documents doc WHERE STARTS_WITH(doc.path, dir.path || '/')
+                ) AS empty_private
+                FROM directories dir
+            ) state
+            WHERE state.id = dir.id
+            AND (dir.empty, dir.empty_private) IS DISTINCT FROM (state.empty, state.empty_private)"
+        )
+        .execute(&self.pool)
+        .await?;
+        debug!("Updated {} empty directories", count.rows_affected());
+        Ok(())
+    }
+
+    /// The directories among `ids` without documents at any depth visible with the access.
+    pub async fn list_empty_directories(
+        &self,
+        ids: &[uuid::Uuid],
+        access: Access,
+    ) -> Result<Vec<uuid::Uuid>, LedgeknawError> {
+        let _timer = self.metrics.time("list_empty_directories");
+        retry_read(|| {
+            sqlx::query_scalar!(
+                "SELECT id FROM directories WHERE id = ANY($1) AND (CASE WHEN $2 THEN empty_private ELSE empty END)",
+                ids,
+                access.authenticated()
+            )
+            .fetch_all(&self.pool)
+        })
+        .await
+        .map_err(LedgeknawError::from)
+    }
+
+    /// IDs, custom IDs and paths of all documents, for resolving links.
+    pub async fn list_link_targets(
+        &self,
+    ) -> Result<Vec<(uuid::Uuid, Option<String>, String)>, LedgeknawError> {
+        let _timer = self.metrics.time("list_link_targets");
+        Ok(sqlx::query!("SELECT id, custom_id, path FROM documents")
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter()
+            .map(|el| (el.id, el.custom_id, el.path))
+            .collect())
+    }
+
+    /// Replace all links between documents and all unresolved links.
+    pub async fn set_links(
+        &self,
+        links: &[(uuid::Uuid, uuid::Uuid)],
+        unresolved: &[(uuid::Uuid, String)],
+    ) -> Result<(), LedgeknawError> {
+        let _timer = self.metrics.time("set_links");
+        let (documents, targets): (Vec<_>, Vec<_>) = links.iter().copied().unzip();
+        let (unresolved_documents, unresolved_links): (Vec<_>, Vec<_>) =
+            unresolved.iter().cloned().unzip();
+
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query!("DELETE FROM document_links")
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query!(
+            "INSERT INTO document_links(document, target)
+             SELECT * FROM UNNEST($1::UUID[], $2::UUID[])
+             ON CONFLICT DO NOTHING",
+            &documents,
+            &targets
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!("DELETE FROM unresolved_links")
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query!(
+            "INSERT INTO unresolved_links(document, link)
+             SELECT * FROM UNNEST($1::UUID[], $2::TEXT[])
+             ON CONFLICT DO NOTHING",
+            &unresolved_documents,
+            &unresolved_links
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Replace the broken links found by the last link check.
+    pub async fn set_link_issues(
+        &self,
+        issues: &[(uuid::Uuid, String, String)],
+    ) -> Result<(), LedgeknawError> {
+        let _timer = self.metrics.time("set_link_issues");
+        let mut documents = Vec::with_capacity(issues.len());
+        let mut links = Vec::with_capacity(issues.len());
+        let mut messages = Vec::with_capacity(issues.len());
+        for (document, link, message) in issues {
+            documents.push(*document);
+            links.push(link.clone());
+            messages.push(message.clone());
+        }
+
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query!("DELETE FROM link_issues")
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query!(
+            "INSERT INTO link_issues(document, link, message)
+             SELECT * FROM UNNEST($1::UUID[], $2::TEXT[], $3::TEXT[])
+             ON CONFLICT DO NOTHING",
+            &documents,
+            &links,
+            &messages
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Documents without tags that no other document links to.
+    pub async fn list_orphans(&self) -> Result<Vec<LinkReport>, LedgeknawError> {
+        let _timer = self.metrics.time("list_orphans");
+        retry_read(|| {
+            sqlx::query_as_unchecked!(
+                LinkReport,
+                r#"
+                    SELECT doc.id, doc.file_name, doc.title, doc.custom_id, doc.path, NULL::TEXT[] AS links
+                    FROM documents doc
+                    WHERE (doc.tags IS NULL OR doc.tags = '')
+                    AND NOT EXISTS (SELECT 1 FROM document_links l WHERE l.target = doc.id)
+                    ORDER BY doc.path
+            "#
+            )
+            .fetch_all(&self.pool)
+        })
+        .await
+        .map_err(LedgeknawError::from)
+    }
+
+    /// Documents with links to documents or files that do not exist, along with the links.
+    pub async fn list_dead_ends(&self) -> Result<Vec<LinkReport>, LedgeknawError> {
+        let _timer = self.metrics.time("list_dead_ends");
+        retry_read(|| {
+            sqlx::query_as_unchecked!(
+                LinkReport,
+                r#"
+                    SELECT doc.id, doc.file_name, doc.title, doc.custom_id, doc.path,
+                    ARRAY_AGG(l.link ORDER BY l.link) AS links
+                    FROM documents doc
+                    INNER JOIN unresolved_links l ON l.document = doc.id
+                    GROUP BY doc.id
+                    ORDER BY doc.path
+            "#
+            )
+            .fetch_all(&self.pool)
+        })
+        .await
+        .map_err(LedgeknawError::from)
+    }
+
+    /// The visible documents, or only those below the directory or in the collection.
+    pub async fn list_graph_documents(
+        &self,
+        scope: Option<uuid::Uuid>,
+        access: Access,
+    ) -> Result<Vec<GraphDocument>, LedgeknawError> {
+        let _timer = self.metrics.time("list_graph_documents");
+        retry_read(|| {
+            sqlx::query_as_unchecked!(
+                GraphDocument,
+                r#"
+                    SELECT doc.id, doc.file_name, doc.title, doc.custom_id, doc.directory,
+                    STRING_TO_ARRAY(doc.tags, ',') AS tags
+                    FROM documents doc
+                    WHERE (NOT doc.draft OR $3) AND (NOT doc.private OR $2) AND (
+                        $1::UUID IS NULL
+                        OR STARTS_WITH(doc.path, (SELECT path FROM directories WHERE id = $1 AND (NOT private OR $2)) || '/')
+                        OR doc.id IN (SELECT document FROM collection_documents WHERE collection = $1)
+                    )
+                    ORDER BY doc.path
+            "#,
+                scope,
+                access.private,
+                access.authenticated()
+            )
+            .fetch_all(&self.pool)
+        })
+        .await
+        .map_err(LedgeknawError::from)
+    }
+
+    /// Links between the given documents.
+    pub async fn list_links_between(
+        &self,
+        documents: &[uuid::Uuid],
+    ) -> Result<Vec<(uuid::Uuid, uuid::Uuid)>, LedgeknawError> {
+        let _timer = self.metrics.time("list_links_between");
+        Ok(retry_read(|| {
+            sqlx::query!(
+                "SELECT document, target FROM document_links
+                 WHERE document = ANY($1) AND target = ANY($1)",
+                documents
+            )
+            .fetch_all(&self.pool)
+        })
+        .await?
+        .into_iter()
+        .map(|el| (el.document, el.target))
+        .collect())
+    }
+
+    /// Returns the ID of the updated document, if it exists.
     pub async fn update_doc_by_path(
         &self,
         path: &str,
         meta: &DocumentMeta,
-    ) -> Result<(), LedgeknawError> {
+    ) -> Result<Option<uuid::Uuid>, LedgeknawError> {
+        let _timer = self.metrics.time("update_doc_by_path");
         let DocumentMeta {
             custom_id,
             title,
             reading_time,
             tags,
+            aliases,
+            draft,
+            date,
+            created,
+            updated,
+            weight,
+            // Only read when serving
+            description: _,
+            cache: _,
+            noindex: _,
+            extra,
         } = meta;
-        sqlx::query!(
+
+        let extra = serde_json::to_value(extra)?;
+        let doc = sqlx::query!(
             r#"
             UPDATE documents SET 
             custom_id = $1,
             title = $2,
             reading_time = $3,
-            tags = $4
-            WHERE path = $5 
+            tags = $4,
+            draft = $5,
+            date_published = $6,
+            date_created = $7,
+            date_updated = $8,
+            weight = $9,
+            extra = $10
+            WHERE path = $11 
+            RETURNING id
         "#,
             custom_id.as_ref(),
             title.as_ref(),
             reading_time.as_ref(),
             tags.as_ref().map(|t| t.join(",")),
+            draft,
+            date.as_ref(),
+            created.as_ref(),
+            updated.as_ref(),
+            weight.as_ref(),
+            extra,
             path
         )
-        .execute(&self.pool)
+        .fetch_optional(&self.pool)
         .await?;
-        Ok(())
+
+        let Some(doc) = doc else {
+            return Ok(None);
+        };
+
+        sqlx::query!("DELETE FROM document_aliases WHERE document = $1", doc.id)
+            .execute(&self.pool)
+            .await?;
+
+        if let Some(aliases) = aliases {
+            self.insert_aliases(doc.id, aliases).await?;
+        }
+
+        Ok(Some(doc.id))
     }
 
     pub async fn remove_dir(&self, path: &str) -> Result<(), LedgeknawError> {
+        let _timer = self.metrics.time("remove_dir");
         sqlx::query_as!(Directory, "DELETE FROM directories WHERE path = $1", path)
             .fetch_optional(&self.pool)
             .await
@@ -276,6 +1339,7 @@ impl DocumentDb {
     }
 
     pub async fn remove_file_by_path(&self, path: &str) -> Result<(), LedgeknawError> {
+        let _timer = self.metrics.time("remove_file_by_path");
         sqlx::query!("DELETE FROM documents WHERE path = $1", path)
             .execute(&self.pool)
             .await?;
@@ -286,8 +1350,281 @@ impl DocumentDb {
         Ok(())
     }
 
+    /// Update the devices and inodes of the documents at the paths.
+    pub async fn set_file_ids(
+        &self,
+        paths: &[String],
+        file_ids: &[FileId],
+    ) -> Result<(), LedgeknawError> {
+        let _timer = self.metrics.time("set_file_ids");
+        let (devs, inodes): (Vec<_>, Vec<_>) = file_ids.iter().copied().unzip();
+        sqlx::query!(
+            r#"
+            UPDATE documents doc SET dev = f.dev, inode = f.inode
+            FROM UNNEST($1::TEXT[], $2::BIGINT[], $3::BIGINT[]) AS f(path, dev, inode)
+            WHERE doc.path = f.path
+            AND (doc.dev IS DISTINCT FROM f.dev OR doc.inode IS DISTINCT FROM f.inode)
+            "#,
+            paths,
+            &devs,
+            &inodes
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Find the documents stored for any of the files, as their devices, inodes,
+    /// IDs and paths.
+    pub async fn list_docs_by_file_ids(
+        &self,
+        file_ids: &[FileId],
+    ) -> Result<Vec<(FileId, uuid::Uuid, String)>, LedgeknawError> {
+        let _timer = self.metrics.time("list_docs_by_file_ids");
+        let (devs, inodes): (Vec<_>, Vec<_>) = file_ids.iter().copied().unzip();
+        Ok(sqlx::query!(
+            r#"
+            SELECT doc.dev AS "dev!", doc.inode AS "inode!", doc.id, doc.path
+            FROM documents doc
+            INNER JOIN UNNEST($1::BIGINT[], $2::BIGINT[]) AS f(dev, inode)
+            ON doc.dev = f.dev AND doc.inode = f.inode
+            "#,
+            &devs,
+            &inodes
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|el| ((el.dev, el.inode), el.id, el.path))
+        .collect())
+    }
+
+    /// Store the paths as other paths of the documents.
+    pub async fn insert_document_paths(
+        &self,
+        paths: &[(String, uuid::Uuid)],
+    ) -> Result<(), LedgeknawError> {
+        let _timer = self.metrics.time("insert_document_paths");
+        let (paths, documents): (Vec<_>, Vec<_>) = paths.iter().cloned().unzip();
+        sqlx::query!(
+            "INSERT INTO document_paths(path, document)
+             SELECT * FROM UNNEST($1::TEXT[], $2::UUID[])
+             ON CONFLICT (path) DO UPDATE SET document = EXCLUDED.document",
+            &paths,
+            &documents
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// List the other paths of the documents along with the devices and inodes
+    /// of the documents.
+    pub async fn list_hard_links(&self) -> Result<Vec<(String, Option<FileId>)>, LedgeknawError> {
+        let _timer = self.metrics.time("list_hard_links");
+        Ok(sqlx::query!(
+            "SELECT dp.path, doc.dev, doc.inode FROM document_paths dp
+             INNER JOIN documents doc ON doc.id = dp.document"
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|el| (el.path, el.dev.zip(el.inode)))
+        .collect())
+    }
+
+    pub async fn remove_document_path(&self, path: &str) -> Result<(), LedgeknawError> {
+        let _timer = self.metrics.time("remove_document_path");
+        sqlx::query!("DELETE FROM document_paths WHERE path = $1", path)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Store the commit history of the documents, keyed by their paths.
+    pub async fn set_git_history(
+        &self,
+        history: &HashMap<String, FileHistory>,
+    ) -> Result<(), LedgeknawError> {
+        let _timer = self.metrics.time("set_git_history");
+        let mut paths = Vec::with_capacity(history.len());
+        let mut created = Vec::with_capacity(history.len());
+        let mut created_by = Vec::with_capacity(history.len());
+        let mut updated = Vec::with_capacity(history.len());
+        let mut updated_by = Vec::with_capacity(history.len());
+
+        for (path, file) in history {
+            paths.push(path.clone());
+            created.push(file.created);
+            created_by.push(file.created_by.clone());
+            updated.push(file.updated);
+            updated_by.push(file.updated_by.clone());
+        }
+
+        sqlx::query!(
+            r#"
+            UPDATE documents doc SET
+            git_created = h.created,
+            git_created_by = h.created_by,
+            git_updated = h.updated,
+            git_updated_by = h.updated_by
+            FROM UNNEST($1::TEXT[], $2::TIMESTAMPTZ[], $3::TEXT[], $4::TIMESTAMPTZ[], $5::TEXT[])
+            AS h(path, created, created_by, updated, updated_by)
+            WHERE doc.path = h.path
+        "#,
+            &paths,
+            &created,
+            &created_by,
+            &updated,
+            &updated_by
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Get the dates of the first and last commits of the document,
+    /// along with the author of the last one.
+    pub async fn get_git_history(
+        &self,
+        id: uuid::Uuid,
+    ) -> Result<(Option<DateTime<Utc>>, Option<DateTime<Utc>>, Option<String>), LedgeknawError>
+    {
+        let _timer = self.metrics.time("get_git_history");
+        Ok(retry_read(|| {
+            sqlx::query!(
+                "SELECT git_created, git_updated, git_updated_by FROM documents WHERE id = $1",
+                id
+            )
+            .fetch_optional(&self.pool)
+        })
+        .await?
+        .map(|el| (el.git_created, el.git_updated, el.git_updated_by))
+        .unwrap_or_default())
+    }
+
+    pub async fn list_authors(&self, access: Access) -> Result<Vec<Author>, LedgeknawError> {
+        let _timer = self.metrics.time("list_authors");
+        retry_read(|| {
+            sqlx::query_as!(
+                Author,
+                r#"
+                SELECT name AS "name!", COUNT(DISTINCT id) AS "documents!"
+                FROM (
+                    SELECT id, git_created_by AS name FROM documents
+                    WHERE (NOT draft OR $2) AND (NOT private OR $1)
+                    UNION
+                    SELECT id, git_updated_by AS name FROM documents
+                    WHERE (NOT draft OR $2) AND (NOT private OR $1)
+                ) authors
+                WHERE name IS NOT NULL
+                GROUP BY name
+                ORDER BY 2 DESC, 1
+        "#,
+                access.private,
+                access.authenticated()
+            )
+            .fetch_all(&self.pool)
+        })
+        .await
+        .map_err(LedgeknawError::from)
+    }
+
+    /// List the documents the author created or last updated, most recently updated first.
+    pub async fn list_by_author(
+        &self,
+        author: &str,
+        access: Access,
+    ) -> Result<Vec<DatedEntry>, LedgeknawError> {
+        let _timer = self.metrics.time("list_by_author");
+        retry_read(|| {
+            sqlx::query_as!(
+                DatedEntry,
+                r#"
+                SELECT id, file_name AS name, title, custom_id, git_updated AS "date!"
+                FROM documents
+                WHERE (git_created_by = $1 OR git_updated_by = $1)
+                AND git_updated IS NOT NULL
+                AND (NOT draft OR $3) AND (NOT private OR $2)
+                ORDER BY git_updated DESC
+        "#,
+                author,
+                access.private,
+                access.authenticated()
+            )
+            .fetch_all(&self.pool)
+        })
+        .await
+        .map_err(LedgeknawError::from)
+    }
+
+    /// Set the title and weight of the document with the given path.
+    pub async fn set_title_and_weight(
+        &self,
+        path: &str,
+        title: &str,
+        weight: i32,
+    ) -> Result<(), LedgeknawError> {
+        let _timer = self.metrics.time("set_title_and_weight");
+        sqlx::query!(
+            "UPDATE documents SET title = $1, weight = $2 WHERE path = $3",
+            title,
+            weight,
+            path
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Update the name a root directory is served under, which changes
+    /// when a revision of it is promoted.
+    /// Set the aliases of the existing roots under `paths` in a single statement,
+    /// so aliases may move between roots without violating their uniqueness.
+    pub async fn set_root_aliases(
+        &self,
+        paths: &[String],
+        aliases: &[String],
+    ) -> Result<(), LedgeknawError> {
+        let _timer = self.metrics.time("set_root_aliases");
+        sqlx::query!(
+            "UPDATE directories dir SET alias = root.alias
+             FROM UNNEST($1::TEXT[], $2::TEXT[]) AS root(path, alias)
+             WHERE dir.path = root.path AND dir.parent IS NULL",
+            paths,
+            aliases
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Mark the root directory under `path` and everything in it as private or public.
+    pub async fn set_root_private(&self, path: &str, private: bool) -> Result<(), LedgeknawError> {
+        let _timer = self.metrics.time("set_root_private");
+        sqlx::query!(
+            "UPDATE directories SET private = $2 WHERE path = $1 OR STARTS_WITH(path, $1 || '/')",
+            path,
+            private
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query!(
+            "UPDATE documents SET private = $2 WHERE STARTS_WITH(path, $1 || '/')",
+            path,
+            private
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
     /// Delete any root directories from the DB not in `paths`.
     pub async fn trim_roots(&self, paths: &[String]) -> Result<(), LedgeknawError> {
+        let _timer = self.metrics.time("trim_roots");
         // https://github.com/launchbadge/sqlx/blob/main/FAQ.md#how-can-i-do-a-select--where-foo-in--query
         let count = sqlx::query!(
             "
@@ -300,4 +1637,223 @@ impl DocumentDb {
         debug!("Trimmed {} directories", count.rows_affected());
         Ok(())
     }
+
+    pub async fn list_root_revisions(&self) -> Result<Vec<RootRevision>, LedgeknawError> {
+        let _timer = self.metrics.time("list_root_revisions");
+        sqlx::query_as!(RootRevision, "SELECT * FROM root_revisions ORDER BY alias")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(LedgeknawError::from)
+    }
+
+    pub async fn get_root_revision(
+        &self,
+        alias: &str,
+    ) -> Result<Option<RootRevision>, LedgeknawError> {
+        let _timer = self.metrics.time("get_root_revision");
+        sqlx::query_as!(
+            RootRevision,
+            "SELECT * FROM root_revisions WHERE alias = $1",
+            alias
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(LedgeknawError::from)
+    }
+
+    pub async fn set_root_revision(
+        &self,
+        alias: &str,
+        pinned: Option<&str>,
+        staged: Option<&str>,
+    ) -> Result<(), LedgeknawError> {
+        let _timer = self.metrics.time("set_root_revision");
+        sqlx::query!(
+            "INSERT INTO root_revisions(alias, pinned, staged) VALUES($1, $2, $3)
+             ON CONFLICT(alias) DO UPDATE SET pinned = $2, staged = $3, updated_at = NOW()",
+            alias,
+            pinned,
+            staged
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn list_collections(&self) -> Result<Vec<Collection>, LedgeknawError> {
+        let _timer = self.metrics.time("list_collections");
+        retry_read(|| {
+            sqlx::query_as!(Collection, "SELECT * FROM collections ORDER BY name")
+                .fetch_all(&self.pool)
+        })
+        .await
+        .map_err(LedgeknawError::from)
+    }
+
+    pub async fn get_collection(&self, name: &str) -> Result<Option<Collection>, LedgeknawError> {
+        let _timer = self.metrics.time("get_collection");
+        retry_read(|| {
+            sqlx::query_as!(
+                Collection,
+                "SELECT * FROM collections WHERE name = $1",
+                name
+            )
+            .fetch_optional(&self.pool)
+        })
+        .await
+        .map_err(LedgeknawError::from)
+    }
+
+    pub async fn upsert_collection(
+        &self,
+        name: &str,
+        query: &str,
+        sort: Order,
+        max_documents: Option<i32>,
+    ) -> Result<Collection, LedgeknawError> {
+        let _timer = self.metrics.time("upsert_collection");
+        sqlx::query_as!(
+            Collection,
+            "INSERT INTO collections(name, query, sort, max_documents) VALUES($1, $2, $3, $4)
+             ON CONFLICT(name) DO UPDATE SET query = $2, sort = $3, max_documents = $4
+             RETURNING *",
+            name,
+            query,
+            sort.as_str(),
+            max_documents
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(LedgeknawError::from)
+    }
+
+    /// Returns whether the collection existed.
+    pub async fn delete_collection(&self, name: &str) -> Result<bool, LedgeknawError> {
+        let _timer = self.metrics.time("delete_collection");
+        let result = sqlx::query!("DELETE FROM collections WHERE name = $1", name)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Replace the documents of the collection, keeping their order.
+    pub async fn set_collection_documents(
+        &self,
+        collection: uuid::Uuid,
+        documents: &[uuid::Uuid],
+    ) -> Result<(), LedgeknawError> {
+        let _timer = self.metrics.time("set_collection_documents");
+        let mut tx = self.pool.begin().await?;
+
+        let current = sqlx::query!(
+            "SELECT document FROM collection_documents WHERE collection = $1 ORDER BY position",
+            collection
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+
+        // Keep the collection version when nothing changed
+        if current
+            .iter()
+            .map(|el| el.document)
+            .eq(documents.iter().copied())
+        {
+            return Ok(());
+        }
+
+        sqlx::query!(
+            "DELETE FROM collection_documents WHERE collection = $1",
+            collection
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "INSERT INTO collection_documents(collection, document, position)
+             SELECT $1, document, position FROM UNNEST($2::UUID[]) WITH ORDINALITY AS docs(document, position)",
+            collection,
+            documents
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "UPDATE collections SET version = nextval('entry_version') WHERE id = $1",
+            collection
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// List the collections as sidebar entries.
+    pub async fn list_collection_entries(
+        &self,
+        access: Access,
+    ) -> Result<Vec<DirectoryEntry>, LedgeknawError> {
+        let _timer = self.metrics.time("list_collection_entries");
+        retry_read(|| {
+            sqlx::query_as_unchecked!(
+                DirectoryEntry,
+                r#"
+                    SELECT col.id, NULL AS parent, col.name, 'c' AS type, col.name AS title, NULL AS custom_id, FALSE AS asset,
+                    (SELECT COUNT(*) FROM collection_documents cd INNER JOIN documents doc ON cd.document = doc.id
+                     WHERE cd.collection = col.id AND (NOT doc.draft OR $2) AND (NOT doc.private OR $1)) AS document_count,
+                    0::BIGINT AS subdirectory_count,
+                    col.created_at, col.updated_at, col.version
+                    FROM collections col
+                    ORDER BY col.name
+            "#,
+                access.private,
+                access.authenticated()
+            )
+            .fetch_all(&self.pool)
+        })
+        .await
+        .map_err(LedgeknawError::from)
+    }
+
+    /// List the documents of the collection in their order.
+    /// Returns `None` if the collection does not exist.
+    pub async fn list_collection_documents(
+        &self,
+        collection: uuid::Uuid,
+        access: Access,
+    ) -> Result<Option<Vec<DirectoryEntry>>, LedgeknawError> {
+        let _timer = self.metrics.time("list_collection_documents");
+        let exists = retry_read(|| {
+            sqlx::query!("SELECT id FROM collections WHERE id = $1", collection)
+                .fetch_optional(&self.pool)
+        })
+        .await?
+        .is_some();
+
+        if !exists {
+            return Ok(None);
+        }
+
+        retry_read(|| {
+            sqlx::query_as_unchecked!(
+                DirectoryEntry,
+                r#"
+                    SELECT doc.id, cd.collection AS parent, doc.file_name AS name, 'f' AS type, doc.title, doc.custom_id, doc.asset IS NOT NULL AS asset,
+                    NULL::BIGINT AS document_count, NULL::BIGINT AS subdirectory_count, doc.created_at, doc.updated_at, doc.version
+                    FROM collection_documents cd
+                    INNER JOIN documents doc ON cd.document = doc.id
+                    WHERE cd.collection = $1 AND (NOT doc.draft OR $3) AND (NOT doc.private OR $2)
+                    ORDER BY cd.position
+            "#,
+                collection,
+                access.private,
+                access.authenticated()
+            )
+            .fetch_all(&self.pool)
+        })
+        .await
+        .map(Some)
+        .map_err(LedgeknawError::from)
+    }
 }
@@ -0,0 +1,307 @@
+use crate::{config::GitConfig, error::LedgeknawError};
+use chrono::{DateTime, Utc};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+use tracing::{debug, warn};
+
+/// First and last commit of a file.
+#[derive(Debug)]
+pub struct FileHistory {
+    pub created: DateTime<Utc>,
+    pub created_by: String,
+    pub updated: DateTime<Utc>,
+    pub updated_by: String,
+}
+
+/// Read the commit history of the markdown files in `root`, keyed by their absolute paths.
+/// Returns an empty map if `root` is not in a git repository.
+pub fn read_history(
+    config: &GitConfig,
+    root: impl AsRef<Path>,
+) -> Result<HashMap<String, FileHistory>, LedgeknawError> {
+    let root = root.as_ref();
+
+    let Some(toplevel) = git(config, root, &["rev-parse", "--show-toplevel"]).ok() else {
+        debug!("{} is not in a git repository", root.display());
+        return Ok(HashMap::new());
+    };
+
+    let toplevel = Path::new(toplevel.trim()).canonicalize()?;
+
+    // Commits are listed newest first, each starting with a record separator
+    // followed by the date and author, then the names of the changed files
+    let log = git(
+        config,
+        root,
+        &[
+            "-c",
+            "core.quotePath=false",
+            "log",
+            "--format=%x1e%aI%x1f%an",
+            "--name-only",
+            "--no-renames",
+            "--",
+            ".",
+        ],
+    )?;
+
+    let mut history: HashMap<String, FileHistory> = HashMap::new();
+
+    for commit in log.split('\x1e').skip(1) {
+        let mut lines = commit.lines();
+
+        let Some((date, author)) = lines.next().and_then(|line| line.split_once('\x1f')) else {
+            continue;
+        };
+
+        let Ok(date) = DateTime::parse_from_rfc3339(date) else {
+            continue;
+        };
+        let date = date.with_timezone(&Utc);
+
+        for file in lines.filter(|line| line.ends_with(".md")) {
+            let Some(path) = toplevel.join(file).to_str().map(String::from) else {
+                continue;
+            };
+
+            history
+                .entry(path)
+                .and_modify(|entry| {
+                    entry.created = date;
+                    entry.created_by = author.to_string();
+                })
+                .or_insert_with(|| FileHistory {
+                    created: date,
+                    created_by: author.to_string(),
+                    updated: date,
+                    updated_by: author.to_string(),
+                });
+        }
+    }
+
+    Ok(history)
+}
+
+/// Resolve a tag, branch or commit of the repository containing `root` to its commit hash.
+pub fn resolve_commit(
+    config: &GitConfig,
+    root: impl AsRef<Path>,
+    revision: &str,
+) -> Result<String, LedgeknawError> {
+    if revision.is_empty() || revision.starts_with('-') {
+        return Err(LedgeknawError::InvalidParameter(format!(
+            "invalid revision '{revision}'"
+        )));
+    }
+
+    let commit = git(
+        config,
+        root.as_ref(),
+        &["rev-parse", "--verify", &format!("{revision}^{{commit}}")],
+    )
+    .map_err(|_| LedgeknawError::NotFound(format!("revision '{revision}'")))?;
+
+    Ok(commit.trim().to_string())
+}
+
+/// Check out the commit of the repository containing `root` into a work tree in `dir`,
+/// unless it is already checked out. Returns the path of `root` within the work tree.
+pub fn checkout(
+    config: &GitConfig,
+    root: impl AsRef<Path>,
+    dir: impl AsRef<Path>,
+    commit: &str,
+) -> Result<PathBuf, LedgeknawError> {
+    let root = root.as_ref().canonicalize()?;
+
+    let toplevel = git(config, &root, &["rev-parse", "--show-toplevel"])?;
+    let toplevel = Path::new(toplevel.trim()).canonicalize()?;
+
+    fs::create_dir_all(dir.as_ref())?;
+    let worktree = dir.as_ref().canonicalize()?.join(commit);
+
+    if !worktree.exists() {
+        let Some(path) = worktree.to_str() else {
+            return Err(LedgeknawError::Git(format!(
+                "invalid work tree path {}",
+                worktree.display()
+            )));
+        };
+        git(
+            config,
+            &root,
+            &["worktree", "add", "--detach", path, commit],
+        )?;
+        debug!("Checked out {commit} to {path}");
+    }
+
+    let relative = root.strip_prefix(&toplevel).unwrap_or(Path::new(""));
+
+    Ok(worktree.join(relative))
+}
+
+/// Remove the work trees in `dir` not checked out at any of the `keep` commits.
+pub fn remove_checkouts(
+    config: &GitConfig,
+    root: impl AsRef<Path>,
+    dir: impl AsRef<Path>,
+    keep: &[&str],
+) -> Result<(), LedgeknawError> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Ok(());
+    };
+
+    for entry in entries.filter_map(Result::ok) {
+        let name = entry.file_name();
+        if keep.iter().any(|commit| name.as_os_str() == *commit) {
+            continue;
+        }
+
+        let path = entry.path();
+        let Some(path) = path.to_str() else {
+            continue;
+        };
+
+        match git(
+            config,
+            root.as_ref(),
+            &["worktree", "remove", "--force", path],
+        ) {
+            Ok(_) => debug!("Removed work tree {path}"),
+            Err(e) => warn!("Error while removing work tree {path}: {e}"),
+        }
+    }
+
+    Ok(())
+}
+
+/// List the files under `path` restoring the commit would change, as pairs of
+/// their absolute paths and git status letters, e.g. `D` if the file would be deleted.
+pub fn restore_changes(
+    config: &GitConfig,
+    path: impl AsRef<Path>,
+    commit: &str,
+) -> Result<Vec<(String, char)>, LedgeknawError> {
+    let (dir, pathspec) = split_pathspec(path.as_ref())?;
+
+    let toplevel = git(config, &dir, &["rev-parse", "--show-toplevel"])?;
+    let toplevel = Path::new(toplevel.trim()).canonicalize()?;
+
+    let changes = git(
+        config,
+        &dir,
+        &[
+            "-c",
+            "core.quotePath=false",
+            "diff",
+            "--name-status",
+            "--no-renames",
+            "-R",
+            commit,
+            "--",
+            &pathspec,
+        ],
+    )?;
+
+    Ok(changes
+        .lines()
+        .filter_map(|line| {
+            let (status, file) = line.split_once('\t')?;
+            let path = toplevel.join(file).to_str()?.to_string();
+            Some((path, status.chars().next()?))
+        })
+        .collect())
+}
+
+/// The patch restoring the commit would apply to the files under `path`.
+pub fn restore_diff(
+    config: &GitConfig,
+    path: impl AsRef<Path>,
+    commit: &str,
+) -> Result<String, LedgeknawError> {
+    let (dir, pathspec) = split_pathspec(path.as_ref())?;
+    git(
+        config,
+        &dir,
+        &["diff", "--no-renames", "-R", commit, "--", &pathspec],
+    )
+}
+
+/// Restore the files under `path` in the working copy to their state at the commit.
+/// Files which did not exist at the commit are deleted, the index is left untouched.
+pub fn restore(
+    config: &GitConfig,
+    path: impl AsRef<Path>,
+    commit: &str,
+) -> Result<(), LedgeknawError> {
+    let (dir, pathspec) = split_pathspec(path.as_ref())?;
+    git(
+        config,
+        &dir,
+        &[
+            "restore",
+            &format!("--source={commit}"),
+            "--worktree",
+            "--",
+            &pathspec,
+        ],
+    )?;
+    Ok(())
+}
+
+/// The top level directory of the repository containing `root`.
+pub fn toplevel(config: &GitConfig, root: impl AsRef<Path>) -> Result<String, LedgeknawError> {
+    Ok(
+        git(config, root.as_ref(), &["rev-parse", "--show-toplevel"])?
+            .trim()
+            .to_string(),
+    )
+}
+
+/// Fast forward the branch checked out in the repository containing `root`
+/// to its upstream branch. Returns the output of git.
+pub fn pull(config: &GitConfig, root: impl AsRef<Path>) -> Result<String, LedgeknawError> {
+    git(config, root.as_ref(), &["pull", "--ff-only"])
+}
+
+/// Git has to run in an existing directory, so files are passed
+/// as pathspecs relative to their parent.
+fn split_pathspec(path: &Path) -> Result<(PathBuf, String), LedgeknawError> {
+    if path.is_dir() {
+        return Ok((path.to_path_buf(), ".".to_string()));
+    }
+
+    match (
+        path.parent(),
+        path.file_name().and_then(|name| name.to_str()),
+    ) {
+        (Some(parent), Some(name)) => Ok((parent.to_path_buf(), format!(":(literal){name}"))),
+        _ => Err(LedgeknawError::InvalidParameter(format!(
+            "invalid path {}",
+            path.display()
+        ))),
+    }
+}
+
+fn git(config: &GitConfig, dir: &Path, args: &[&str]) -> Result<String, LedgeknawError> {
+    let output = Command::new(&config.program)
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()?;
+
+    if !output.status.success() {
+        return Err(LedgeknawError::Git(format!(
+            "{} exited with {}: {}",
+            config.program,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(String::from_utf8(output.stdout)?)
+}
@@ -0,0 +1,188 @@
+//! Links between documents, found in the markdown links of their content.
+
+use std::{
+    ops::Range,
+    path::{Component, Path, PathBuf},
+};
+
+/// What a link points to, if it may be a document.
+#[derive(Debug, PartialEq)]
+pub enum LinkTarget {
+    /// A file relative to the linking document, lexically normalised
+    Path(PathBuf),
+    /// A document ID or custom ID from a link to a route, e.g. `/document/:id`
+    Id(String),
+}
+
+/// The destinations of the inline and reference links outside of code.
+pub fn extract(content: &str) -> Vec<String> {
+    destinations(content)
+        .into_iter()
+        .map(|range| content[range].to_string())
+        .collect()
+}
+
+/// Replace the destinations of the links with the result of `rewrite`,
+/// leaving those it returns `None` for as they are.
+pub fn rewrite(content: &str, mut rewrite: impl FnMut(&str) -> Option<String>) -> String {
+    let mut rewritten = content.to_string();
+
+    // Replace from the back so the ranges stay valid
+    for range in destinations(content).into_iter().rev() {
+        if let Some(link) = rewrite(&content[range.clone()]) {
+            rewritten.replace_range(range, &link);
+        }
+    }
+
+    rewritten
+}
+
+/// The byte ranges of the link destinations in the content, without angle brackets.
+fn destinations(content: &str) -> Vec<Range<usize>> {
+    let mut ranges = vec![];
+    let mut in_code = false;
+    let mut offset = 0;
+
+    for line in content.split_inclusive('\n') {
+        let line_start = offset;
+        offset += line.len();
+
+        let line = line.trim_end_matches(['\n', '\r']);
+        let trimmed = line.trim_start();
+
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            continue;
+        }
+
+        // Reference definitions, `[label]: destination`
+        if let Some(definition) = trimmed.strip_prefix('[') {
+            if let Some(end) = definition.find("]:") {
+                let start = line_start + (line.len() - trimmed.len()) + 1 + end + 2;
+                if let Some(range) = first_word(content, start..line_start + line.len()) {
+                    ranges.push(unwrap_angle(content, range));
+                }
+                continue;
+            }
+        }
+
+        let mut pos = 0;
+        while let Some(found) = line[pos..].find("](") {
+            let start = pos + found + 2;
+
+            // Skip the brackets of code spans, e.g. `a[i](x)`
+            if line[..start].matches('`').count() % 2 == 1 {
+                pos = start;
+                continue;
+            }
+
+            let Some(end) = line[start..].find(')') else {
+                break;
+            };
+            if let Some(range) = first_word(content, line_start + start..line_start + start + end) {
+                ranges.push(unwrap_angle(content, range));
+            }
+            pos = start + end + 1;
+        }
+    }
+
+    ranges
+}
+
+/// The range of the first whitespace separated word in the range of the content.
+fn first_word(content: &str, range: Range<usize>) -> Option<Range<usize>> {
+    let text = &content[range.clone()];
+    let word = text.split_whitespace().next()?;
+    let start = range.start + (text.len() - text.trim_start().len());
+    Some(start..start + word.len())
+}
+
+fn unwrap_angle(content: &str, range: Range<usize>) -> Range<usize> {
+    let destination = &content[range.clone()];
+    if range.len() >= 2 && destination.starts_with('<') && destination.ends_with('>') {
+        range.start + 1..range.end - 1
+    } else {
+        range
+    }
+}
+
+/// Resolve the link in the document at `source`. Links with a scheme, links to
+/// other routes and links within the document are not documents.
+pub fn resolve(source: &Path, link: &str, base_path: &str) -> Option<LinkTarget> {
+    let link = link.split(['#', '?']).next().unwrap_or_default();
+
+    if link.is_empty() || link.starts_with("//") {
+        return None;
+    }
+
+    if link
+        .split_once(':')
+        .is_some_and(|(scheme, _)| !scheme.contains('/'))
+    {
+        return None;
+    }
+
+    if let Some(absolute) = link.strip_prefix('/') {
+        let route = match base_path.strip_prefix('/') {
+            Some(base) if !base.is_empty() => absolute.strip_prefix(base)?.strip_prefix('/')?,
+            _ => absolute,
+        };
+        let id = route
+            .strip_prefix("document/")
+            .or_else(|| route.strip_prefix("pages/"))
+            .unwrap_or(route)
+            .trim_end_matches('/');
+        if id.is_empty() || id.contains('/') {
+            return None;
+        }
+        return Some(LinkTarget::Id(decode(id)));
+    }
+
+    let mut path = source.parent()?.to_path_buf();
+    for component in Path::new(&decode(link)).components() {
+        match component {
+            Component::ParentDir => {
+                path.pop();
+            }
+            Component::Normal(part) => path.push(part),
+            _ => {}
+        }
+    }
+    Some(LinkTarget::Path(path))
+}
+
+/// Whether the link is to a web page, checked over HTTP instead of resolved.
+pub fn is_external(link: &str) -> bool {
+    link.split_once("://").is_some_and(|(scheme, _)| {
+        scheme.eq_ignore_ascii_case("http") || scheme.eq_ignore_ascii_case("https")
+    })
+}
+
+/// Decode percent encoded bytes, e.g. `%20` in file names with spaces.
+pub(crate) fn decode(link: &str) -> String {
+    let bytes = link.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let hex = (bytes[i] == b'%')
+            .then(|| link.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match hex {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
@@ -1,4 +1,7 @@
-use serde::Serialize;
+use super::DocumentMeta;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 
 /// Database model
 #[derive(Debug, Default)]
@@ -9,14 +12,17 @@ pub struct Document {
     pub directory: uuid::Uuid,
     /// Canonicalised path
     pub path: String,
+    /// Canonicalised path of the asset described by this file, present only in sidecar files
+    pub asset: Option<String>,
 }
 
 impl Document {
-    pub fn new(directory: uuid::Uuid, name: String, path: String) -> Self {
+    pub fn new(directory: uuid::Uuid, name: String, path: String, asset: Option<String>) -> Self {
         Self {
             file_name: name,
             directory,
             path,
+            asset,
         }
     }
 }
@@ -24,7 +30,7 @@ impl Document {
 /// Used for querying both files and directories.
 /// The type is either 'f' or 'd'.
 /// Only directories have the parent field.
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
 pub struct DirectoryEntry {
     pub id: uuid::Uuid,
     pub name: String,
@@ -34,4 +40,375 @@ pub struct DirectoryEntry {
     // Files only
     pub title: Option<String>,
     pub custom_id: Option<String>,
+    /// Whether the file is a sidecar describing an asset, served on `/asset/:id`
+    pub asset: bool,
+
+    // Directories only
+    /// Amount of visible documents directly in the directory
+    pub document_count: Option<i64>,
+    /// Amount of visible directories directly in the directory
+    pub subdirectory_count: Option<i64>,
+
+    /// When the entry was first indexed. Not present when served without a database.
+    pub created_at: Option<DateTime<Utc>>,
+    /// When the indexed metadata of the entry last changed
+    pub updated_at: Option<DateTime<Utc>>,
+    /// Increases whenever the entry changes, see [SidebarDelta]
+    pub version: Option<i64>,
+}
+
+/// The entries of a sidebar listing changed since a version the client has.
+#[derive(Debug, Serialize)]
+pub struct SidebarDelta {
+    /// The highest version in the listing, sent as `since` on the next request
+    pub version: Option<i64>,
+    /// Entries added or changed since the requested version
+    pub entries: Vec<DirectoryEntry>,
+    /// IDs of all entries in the listing in order. Entries
+    /// missing from it were removed or are no longer visible.
+    pub order: Vec<uuid::Uuid>,
+}
+
+/// Everything the front end needs on its first load.
+#[derive(Debug, Serialize)]
+pub struct Bootstrap {
+    pub title: Option<String>,
+    /// Prefix of all routes, empty when served from the root
+    pub base_path: String,
+    /// The sidebar roots, same as `/side`
+    pub roots: Vec<DirectoryEntry>,
+    pub features: Features,
+    pub auth: AuthState,
+    /// All feature flags by name, including ones only the front end knows about
+    pub flags: BTreeMap<String, bool>,
+}
+
+/// Optional functionality enabled on the server.
+#[derive(Debug, Default, Serialize)]
+pub struct Features {
+    /// Documents can be queried on `/query`
+    pub query: bool,
+    /// Documents can be translated on `/document/:id/translate`
+    pub translation: bool,
+    /// Text is extracted from image and scanned document assets
+    pub ocr: bool,
+    /// Audio and video assets are transcribed
+    pub transcription: bool,
+    /// Dates and authors are read from git
+    pub git_history: bool,
+    /// Admin routes are available, false without a database
+    pub admin: bool,
+}
+
+/// What the request the bootstrap was served for has access to.
+#[derive(Debug, Default, Serialize)]
+pub struct AuthState {
+    /// Whether the request carries a valid session or API key
+    pub authenticated: bool,
+    /// Whether private directories are visible
+    pub private: bool,
+}
+
+/// The frontmatter of a document along with when it was indexed and modified.
+#[derive(Debug, Serialize)]
+pub struct DocumentInfo {
+    #[serde(flatten)]
+    pub meta: DocumentMeta,
+    /// When the document was first indexed. Not present when served without a database.
+    pub created_at: Option<DateTime<Utc>>,
+    /// When the indexed metadata of the document last changed
+    pub updated_at: Option<DateTime<Utc>>,
+    /// Last modification time of the file
+    pub modified: Option<DateTime<Utc>>,
+    /// Author of the last commit changing the document, if git history is enabled
+    pub updated_by: Option<String>,
+}
+
+/// A directory entry along with everything below it.
+#[derive(Debug, Serialize)]
+pub struct TreeEntry {
+    #[serde(flatten)]
+    pub entry: DirectoryEntry,
+    /// Empty for files and directories past the depth limit
+    pub children: Vec<TreeEntry>,
+}
+
+impl TreeEntry {
+    /// Nest the entries under their parents, keeping their order.
+    pub fn build(entries: Vec<DirectoryEntry>) -> Vec<Self> {
+        let mut by_parent: HashMap<Option<uuid::Uuid>, Vec<DirectoryEntry>> = HashMap::new();
+
+        for entry in entries {
+            by_parent.entry(entry.parent).or_default().push(entry);
+        }
+
+        Self::children(None, &mut by_parent)
+    }
+
+    fn children(
+        parent: Option<uuid::Uuid>,
+        by_parent: &mut HashMap<Option<uuid::Uuid>, Vec<DirectoryEntry>>,
+    ) -> Vec<Self> {
+        by_parent
+            .remove(&parent)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|entry| {
+                let children = match entry.r#type.as_str() {
+                    "d" => Self::children(Some(entry.id), by_parent),
+                    _ => vec![],
+                };
+                Self { entry, children }
+            })
+            .collect()
+    }
+}
+
+/// The public directories and documents with their metadata, for static mirrors and crawlers.
+#[derive(Debug, Serialize)]
+pub struct TreeExport {
+    pub tree: Vec<TreeEntry>,
+    /// Metadata of the documents in the tree, by ID. Ordered so the
+    /// output, and with it the ETag, only changes with the documents.
+    pub documents: BTreeMap<uuid::Uuid, ExportedDocument>,
+}
+
+/// Document metadata without the content. Dates fall back to the git history.
+#[derive(Debug, Serialize)]
+pub struct ExportedDocument {
+    pub id: uuid::Uuid,
+    pub tags: Option<Vec<String>>,
+    pub reading_time: Option<i32>,
+    pub published: Option<DateTime<Utc>>,
+    pub created: Option<DateTime<Utc>>,
+    pub updated: Option<DateTime<Utc>>,
+    pub weight: Option<i32>,
+    /// Frontmatter fields not known to ledgeknaw
+    pub extra: serde_json::Value,
+}
+
+/// Used for listing documents by their frontmatter dates.
+#[derive(Debug, Serialize)]
+pub struct DatedEntry {
+    pub id: uuid::Uuid,
+    pub name: String,
+    pub title: Option<String>,
+    pub custom_id: Option<String>,
+    /// The most relevant of the updated, publish and created dates
+    pub date: DateTime<Utc>,
+}
+
+/// An author of documents in git repositories.
+#[derive(Debug, Serialize)]
+pub struct Author {
+    pub name: String,
+    /// Amount of documents the author created or last updated
+    pub documents: i64,
+}
+
+/// The revisions a git-backed root directory is served at.
+#[derive(Debug, Serialize)]
+pub struct RootRevision {
+    pub alias: String,
+    /// Commit served instead of the working copy
+    pub pinned: Option<String>,
+    /// Commit served privately for preview until promoted
+    pub staged: Option<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A named query whose matching documents are listed in the sidebar.
+#[derive(Debug, Serialize)]
+pub struct Collection {
+    pub id: uuid::Uuid,
+    pub name: String,
+    pub query: String,
+    /// See [crate::document::query::Order]
+    pub sort: String,
+    /// Limit of documents listed, all matching documents if not set
+    pub max_documents: Option<i32>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// See [DirectoryEntry::version]
+    pub version: i64,
+}
+
+/// The outcome of restoring documents from a git revision.
+#[derive(Debug, Serialize)]
+pub struct RollbackReport {
+    /// The commit the documents were restored from
+    pub revision: String,
+    pub dry_run: bool,
+    pub files: Vec<RolledBackFile>,
+    /// Patch of the changes, present only on dry runs
+    pub diff: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RolledBackFile {
+    pub path: String,
+    /// One of `added`, `modified` or `deleted`
+    pub status: &'static str,
+}
+
+/// Content generated from a document, e.g. OCR text or a transcript of its asset.
+#[derive(Debug, Serialize)]
+pub struct DerivedDocument {
+    pub id: uuid::Uuid,
+    /// ID of the source document
+    pub document: uuid::Uuid,
+    pub kind: String,
+    pub content: String,
+    /// What generated the content
+    pub provenance: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// The kinds of derived documents generated by ledgeknaw.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DerivedKind {
+    Ocr,
+    Transcript,
+}
+
+impl DerivedKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Ocr => "ocr",
+            Self::Transcript => "transcript",
+        }
+    }
+}
+
+/// Scan counters of a table since the statistics were last reset. Tables with
+/// many rows read by sequential scans are queried on columns without an index.
+#[derive(Debug, Serialize)]
+pub struct TableScans {
+    pub table: String,
+    pub live_rows: i64,
+    pub seq_scans: i64,
+    pub seq_rows_read: i64,
+    pub index_scans: i64,
+}
+
+/// Problem recorded while processing a document.
+#[derive(Debug, Serialize)]
+pub struct DocumentIssue {
+    pub document: uuid::Uuid,
+    pub file_name: String,
+    pub title: Option<String>,
+    pub message: String,
+    pub line: Option<i32>,
+    pub column: Option<i32>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Documents and what connects them, for force directed graph views.
+#[derive(Debug, Serialize)]
+pub struct Graph {
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GraphNode {
+    /// The document UUID, or `tag:<name>` for tags
+    pub id: String,
+    /// `document` or `tag`
+    pub kind: &'static str,
+    /// Title or file name of documents, name of tags
+    pub label: String,
+
+    // Documents only
+    pub custom_id: Option<String>,
+    pub directory: Option<uuid::Uuid>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GraphEdge {
+    pub source: String,
+    pub target: String,
+    /// `link` from a document to a document it links to,
+    /// `tag` from a document to one of its tags
+    pub kind: &'static str,
+}
+
+/// A document as a node of the [Graph].
+#[derive(Debug)]
+pub struct GraphDocument {
+    pub id: uuid::Uuid,
+    pub file_name: String,
+    pub title: Option<String>,
+    pub custom_id: Option<String>,
+    pub directory: uuid::Uuid,
+    pub tags: Option<Vec<String>>,
+}
+
+/// A document listed on `/admin/orphans` or `/admin/deadends`.
+#[derive(Debug, Serialize)]
+pub struct LinkReport {
+    pub id: uuid::Uuid,
+    pub file_name: String,
+    pub title: Option<String>,
+    pub custom_id: Option<String>,
+    pub path: String,
+    /// The links not resolving to anything, absent for orphans
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub links: Option<Vec<String>>,
+}
+
+/// The directories and documents found at each depth below a root directory
+/// in the last sync, with the root at depth 0.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DepthReport {
+    pub directories: Vec<usize>,
+    pub documents: Vec<usize>,
+    /// Directories deeper than the maximum depth or failing to be read
+    pub skipped: Vec<String>,
+}
+
+impl DepthReport {
+    /// Count the directory at the depth along with its documents.
+    pub fn count(&mut self, depth: usize, entries: &[std::fs::DirEntry]) {
+        if self.directories.len() <= depth {
+            self.directories.resize(depth + 1, 0);
+            self.documents.resize(depth + 1, 0);
+        }
+
+        self.directories[depth] += 1;
+        self.documents[depth] += entries
+            .iter()
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "md"))
+            .count();
+    }
+}
+
+/// The stored state of a document, compared before and after
+/// syncing to find the documents changed by it.
+#[derive(Debug, Clone)]
+pub struct DocumentState {
+    pub id: uuid::Uuid,
+    pub path: String,
+    pub title: Option<String>,
+    pub private: bool,
+    /// Hash of the stored metadata
+    pub digest: String,
+}
+
+/// The stored state of a directory, see [DocumentState].
+#[derive(Debug, Clone)]
+pub struct DirectoryState {
+    pub id: uuid::Uuid,
+    pub private: bool,
+    /// Bumped whenever the directory or its entries change
+    pub version: i64,
+}
+
+/// The last sync, shown on `/admin/status`.
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncReport {
+    pub finished_at: DateTime<Utc>,
+    /// By root directory alias
+    pub roots: BTreeMap<String, DepthReport>,
 }
@@ -0,0 +1,39 @@
+use crate::{config::OcrConfig, error::LedgeknawError};
+use std::{path::Path, process::Command};
+use tracing::debug;
+
+/// Whether the asset has one of the configured extensions.
+pub fn is_supported(config: &OcrConfig, path: impl AsRef<Path>) -> bool {
+    let Some(ext) = path.as_ref().extension().and_then(|ext| ext.to_str()) else {
+        return false;
+    };
+
+    config
+        .extensions
+        .iter()
+        .any(|supported| supported.eq_ignore_ascii_case(ext))
+}
+
+/// Run the configured OCR command on the asset and return its output.
+pub fn extract_text(config: &OcrConfig, path: impl AsRef<Path>) -> Result<String, LedgeknawError> {
+    let Some((program, args)) = config.command.split_first() else {
+        return Err(LedgeknawError::Ocr("empty OCR command".to_string()));
+    };
+
+    let path = path.as_ref().display().to_string();
+    debug!("Extracting text from {path}");
+
+    let output = Command::new(program)
+        .args(args.iter().map(|arg| arg.replace("{path}", &path)))
+        .output()?;
+
+    if !output.status.success() {
+        return Err(LedgeknawError::Ocr(format!(
+            "{program} exited with {} on {path}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(String::from_utf8(output.stdout)?)
+}
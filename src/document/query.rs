@@ -0,0 +1,653 @@
+//! A small query language for filtering documents, e.g.
+//! `tag:rust AND root:blog AND updated:>2024-01-01 AND "tokio"`.
+//!
+//! Terms are either `field:value` filters or free text matched against the
+//! title, file name and tags. Terms can be combined with `AND`, `OR`, `NOT`
+//! and parentheses, adjacent terms are implicitly joined with `AND`.
+//!
+//! Queries can also be embedded in documents as `ledge-query` code blocks,
+//! which are replaced with the matching documents when the document is read.
+
+use super::models::DirectoryEntry;
+use crate::error::LedgeknawError;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use sqlx::{Postgres, QueryBuilder};
+use std::{ops::Range, str::FromStr};
+
+/// Longer queries are rejected to bound the parser's recursion
+const MAX_QUERY_LEN: usize = 1024;
+
+/// The info string of code blocks containing queries
+const QUERY_BLOCK: &str = "ledge-query";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Term(Term),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Term {
+    /// Free text in the title, file name or tags
+    Text(String),
+    Tag(String),
+    /// Alias of the root directory
+    Root(String),
+    Title(String),
+    /// Author of the first or last commit
+    Author(String),
+    Date(DateField, Comparison, NaiveDate),
+    ReadingTime(Comparison, i32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DateField {
+    Created,
+    Updated,
+    Published,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Comparison {
+    Eq,
+    Lt,
+    Lte,
+    Gt,
+    Gte,
+}
+
+impl Comparison {
+    /// Split the comparison prefix off the value, e.g. `>2024-01-01`.
+    fn split(value: &str) -> (Self, &str) {
+        for (prefix, cmp) in [
+            (">=", Self::Gte),
+            ("<=", Self::Lte),
+            (">", Self::Gt),
+            ("<", Self::Lt),
+            ("=", Self::Eq),
+        ] {
+            if let Some(value) = value.strip_prefix(prefix) {
+                return (cmp, value);
+            }
+        }
+        (Self::Eq, value)
+    }
+
+    fn as_sql(&self) -> &'static str {
+        match self {
+            Self::Eq => " = ",
+            Self::Lt => " < ",
+            Self::Lte => " <= ",
+            Self::Gt => " > ",
+            Self::Gte => " >= ",
+        }
+    }
+}
+
+/// How matching documents are sorted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Order {
+    /// Most recently updated first
+    #[default]
+    DateDesc,
+    DateAsc,
+    Title,
+}
+
+impl Order {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::DateDesc => "date_desc",
+            Self::DateAsc => "date_asc",
+            Self::Title => "title",
+        }
+    }
+
+    /// Append the `ORDER BY` clause to a query selecting from `documents doc`.
+    pub fn push_sql(&self, query: &mut QueryBuilder<'_, Postgres>) {
+        const DATE: &str = "COALESCE(doc.date_updated, doc.git_updated, doc.date_published, doc.date_created, doc.git_created)";
+        query.push(match self {
+            Self::DateDesc => format!(" ORDER BY {DATE} DESC NULLS LAST, doc.title"),
+            Self::DateAsc => format!(" ORDER BY {DATE} ASC NULLS LAST, doc.title"),
+            Self::Title => " ORDER BY COALESCE(doc.title, doc.file_name)".to_string(),
+        });
+    }
+}
+
+impl FromStr for Order {
+    type Err = LedgeknawError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "date_desc" => Ok(Self::DateDesc),
+            "date_asc" => Ok(Self::DateAsc),
+            "title" => Ok(Self::Title),
+            _ => Err(invalid(format!("unknown sort '{s}'"))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    And,
+    Or,
+    Not,
+    Word(String),
+    /// Quoted text, never treated as a keyword or filter
+    Phrase(String),
+}
+
+pub fn parse(input: &str) -> Result<Expr, LedgeknawError> {
+    if input.len() > MAX_QUERY_LEN {
+        return Err(invalid(format!(
+            "query longer than {MAX_QUERY_LEN} characters"
+        )));
+    }
+
+    let tokens = tokenize(input)?;
+    let mut parser = Parser { tokens, pos: 0 };
+
+    if parser.tokens.is_empty() {
+        return Err(invalid("empty query"));
+    }
+
+    let expr = parser.or()?;
+
+    match parser.next() {
+        None => Ok(expr),
+        Some(token) => Err(invalid(format!("unexpected {token:?}"))),
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, LedgeknawError> {
+    let mut tokens = vec![];
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '(' => tokens.push(Token::LParen),
+            ')' => tokens.push(Token::RParen),
+            '"' => tokens.push(Token::Phrase(read_quoted(&mut chars)?)),
+            c => {
+                let mut word = String::from(c);
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace() && !"()".contains(*c)) {
+                    // Quoted filter values, e.g. title:"foo bar"
+                    if c == '"' {
+                        word.push_str(&read_quoted(&mut chars)?);
+                    } else {
+                        word.push(c);
+                    }
+                }
+                tokens.push(match word.as_str() {
+                    "AND" => Token::And,
+                    "OR" => Token::Or,
+                    "NOT" => Token::Not,
+                    _ => Token::Word(word),
+                });
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn read_quoted(chars: &mut impl Iterator<Item = char>) -> Result<String, LedgeknawError> {
+    let mut text = String::new();
+    for c in chars.by_ref() {
+        if c == '"' {
+            return Ok(text);
+        }
+        text.push(c);
+    }
+    Err(invalid("unterminated quote"))
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn or(&mut self) -> Result<Expr, LedgeknawError> {
+        let mut expr = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, LedgeknawError> {
+        let mut expr = self.unary()?;
+        loop {
+            match self.peek() {
+                Some(Token::And) => {
+                    self.next();
+                }
+                Some(Token::Or | Token::RParen) | None => return Ok(expr),
+                // Adjacent terms
+                Some(_) => {}
+            }
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, LedgeknawError> {
+        match self.next() {
+            Some(Token::Not) => Ok(Expr::Not(Box::new(self.unary()?))),
+            Some(Token::LParen) => {
+                let expr = self.or()?;
+                match self.next() {
+                    Some(Token::RParen) => Ok(expr),
+                    _ => Err(invalid("missing closing parenthesis")),
+                }
+            }
+            Some(Token::Phrase(text)) => Ok(Expr::Term(Term::Text(text))),
+            Some(Token::Word(word)) => Ok(Expr::Term(parse_term(&word)?)),
+            Some(token) => Err(invalid(format!("unexpected {token:?}"))),
+            None => Err(invalid("unexpected end of query")),
+        }
+    }
+}
+
+fn parse_term(word: &str) -> Result<Term, LedgeknawError> {
+    let Some((field, value)) = word.split_once(':') else {
+        return Ok(Term::Text(word.to_string()));
+    };
+
+    if value.is_empty() {
+        return Err(invalid(format!("missing value for {field}")));
+    }
+
+    let date = |field| {
+        let (cmp, value) = Comparison::split(value);
+        NaiveDate::parse_from_str(value, "%Y-%m-%d")
+            .map(|date| Term::Date(field, cmp, date))
+            .map_err(|_| invalid(format!("invalid date '{value}', expected YYYY-MM-DD")))
+    };
+
+    match field.to_lowercase().as_str() {
+        "tag" => Ok(Term::Tag(value.to_string())),
+        "root" => Ok(Term::Root(value.to_string())),
+        "title" => Ok(Term::Title(value.to_string())),
+        "author" => Ok(Term::Author(value.to_string())),
+        "created" => date(DateField::Created),
+        "updated" => date(DateField::Updated),
+        "published" => date(DateField::Published),
+        "reading_time" => {
+            let (cmp, value) = Comparison::split(value);
+            value
+                .parse()
+                .map(|minutes| Term::ReadingTime(cmp, minutes))
+                .map_err(|_| invalid(format!("invalid reading time '{value}'")))
+        }
+        // Not a filter, e.g. a URL
+        _ => Ok(Term::Text(word.to_string())),
+    }
+}
+
+fn invalid(message: impl Into<String>) -> LedgeknawError {
+    LedgeknawError::InvalidParameter(message.into())
+}
+
+impl Expr {
+    /// Append the condition to a query selecting from `documents doc`.
+    pub fn push_sql(&self, query: &mut QueryBuilder<'_, Postgres>) {
+        match self {
+            Self::And(left, right) | Self::Or(left, right) => {
+                query.push("(");
+                left.push_sql(query);
+                query.push(if matches!(self, Self::And(..)) {
+                    " AND "
+                } else {
+                    " OR "
+                });
+                right.push_sql(query);
+                query.push(")");
+            }
+            Self::Not(expr) => {
+                query.push("NOT COALESCE(");
+                expr.push_sql(query);
+                query.push(", FALSE)");
+            }
+            Self::Term(term) => term.push_sql(query),
+        }
+    }
+}
+
+impl Term {
+    fn push_sql(&self, query: &mut QueryBuilder<'_, Postgres>) {
+        match self {
+            Self::Text(text) => {
+                query
+                    .push("(STRPOS(LOWER(COALESCE(doc.title, '') || ' ' || doc.file_name || ' ' || COALESCE(doc.tags, '')), LOWER(")
+                    .push_bind(text.clone())
+                    .push(")) > 0)");
+            }
+            Self::Tag(tag) => {
+                query
+                    .push("(LOWER(")
+                    .push_bind(tag.clone())
+                    .push(") = ANY(STRING_TO_ARRAY(LOWER(COALESCE(doc.tags, '')), ',')))");
+            }
+            Self::Root(alias) => {
+                query
+                    .push("EXISTS (SELECT 1 FROM directories root WHERE root.parent IS NULL AND LOWER(root.alias) = LOWER(")
+                    .push_bind(alias.clone())
+                    .push(") AND STARTS_WITH(doc.path, root.path || '/'))");
+            }
+            Self::Title(title) => {
+                query
+                    .push("(STRPOS(LOWER(COALESCE(doc.title, '')), LOWER(")
+                    .push_bind(title.clone())
+                    .push(")) > 0)");
+            }
+            Self::Author(author) => {
+                query
+                    .push("(LOWER(")
+                    .push_bind(author.clone())
+                    .push(") IN (LOWER(doc.git_created_by), LOWER(doc.git_updated_by)))");
+            }
+            Self::Date(field, cmp, date) => {
+                let column = match field {
+                    DateField::Created => "COALESCE(doc.date_created, doc.git_created)",
+                    DateField::Updated => "COALESCE(doc.date_updated, doc.git_updated)",
+                    DateField::Published => "doc.date_published",
+                };
+                query
+                    .push(format!("({column})::DATE"))
+                    .push(cmp.as_sql())
+                    .push_bind(*date);
+            }
+            Self::ReadingTime(cmp, minutes) => {
+                query
+                    .push("doc.reading_time")
+                    .push(cmp.as_sql())
+                    .push_bind(*minutes);
+            }
+        }
+    }
+}
+
+/// A query embedded in a document.
+#[derive(Debug)]
+pub struct QueryBlock {
+    /// Byte range of the whole code block, fences included
+    pub range: Range<usize>,
+    pub query: String,
+    /// Render the results as a table instead of a list, set by
+    /// opening the block with ```` ```ledge-query table ````
+    pub table: bool,
+}
+
+/// Find the `ledge-query` code blocks in the markdown content.
+/// Unterminated blocks are ignored.
+pub fn find_blocks(content: &str) -> Vec<QueryBlock> {
+    let mut blocks = vec![];
+    let mut open: Option<(usize, bool, String)> = None;
+    let mut offset = 0;
+
+    for line in content.split_inclusive('\n') {
+        let start = offset;
+        offset += line.len();
+        let trimmed = line.trim();
+
+        match open.as_mut() {
+            None => {
+                let Some(info) = trimmed.strip_prefix("```") else {
+                    continue;
+                };
+                let mut words = info.split_whitespace();
+                if words.next() == Some(QUERY_BLOCK) {
+                    open = Some((start, words.any(|word| word == "table"), String::new()));
+                }
+            }
+            Some((_, _, query)) if trimmed != "```" => {
+                query.push_str(line);
+            }
+            Some(_) => {
+                if let Some((start, table, query)) = open.take() {
+                    blocks.push(QueryBlock {
+                        range: start..offset,
+                        query: query.trim().to_string(),
+                        table,
+                    });
+                }
+            }
+        }
+    }
+
+    blocks
+}
+
+/// Render the documents as markdown linking to them.
+/// Links are prefixed with the base path the application is served under.
+pub fn render(entries: &[DirectoryEntry], table: bool, base_path: &str) -> String {
+    if entries.is_empty() {
+        return "*No matching documents*\n".to_string();
+    }
+
+    let mut out = String::new();
+
+    if table {
+        out.push_str("| Title | File |\n| --- | --- |\n");
+    }
+
+    for entry in entries {
+        let title = entry.title.as_deref().unwrap_or(&entry.name);
+        let link = format!(
+            "[{}]({base_path}/{})",
+            title.replace('[', "\\[").replace(']', "\\]"),
+            entry
+                .custom_id
+                .clone()
+                .unwrap_or_else(|| entry.id.to_string())
+        );
+
+        if table {
+            out.push_str(&format!(
+                "| {} | {} |\n",
+                link.replace('|', "\\|"),
+                entry.name.replace('|', "\\|")
+            ));
+        } else {
+            out.push_str(&format!("- {link}\n"));
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(text: &str) -> Expr {
+        Expr::Term(Term::Text(text.to_string()))
+    }
+
+    fn tag(tag: &str) -> Expr {
+        Expr::Term(Term::Tag(tag.to_string()))
+    }
+
+    fn and(left: Expr, right: Expr) -> Expr {
+        Expr::And(Box::new(left), Box::new(right))
+    }
+
+    fn or(left: Expr, right: Expr) -> Expr {
+        Expr::Or(Box::new(left), Box::new(right))
+    }
+
+    fn not(expr: Expr) -> Expr {
+        Expr::Not(Box::new(expr))
+    }
+
+    fn rejects(input: &str) -> String {
+        match parse(input) {
+            Err(LedgeknawError::InvalidParameter(message)) => message,
+            result => panic!("{input}: expected an invalid query, got {result:?}"),
+        }
+    }
+
+    #[test]
+    fn binds_not_over_and_over_or() {
+        assert_eq!(
+            parse("a OR b AND NOT c").unwrap(),
+            or(text("a"), and(text("b"), not(text("c"))))
+        );
+        assert_eq!(
+            parse("a AND b OR c").unwrap(),
+            or(and(text("a"), text("b")), text("c"))
+        );
+        assert_eq!(parse("NOT a OR b").unwrap(), or(not(text("a")), text("b")));
+    }
+
+    #[test]
+    fn groups_left_to_right() {
+        assert_eq!(
+            parse("a OR b OR c").unwrap(),
+            or(or(text("a"), text("b")), text("c"))
+        );
+        assert_eq!(
+            parse("a b c").unwrap(),
+            and(and(text("a"), text("b")), text("c"))
+        );
+    }
+
+    #[test]
+    fn joins_adjacent_terms_with_and() {
+        assert_eq!(parse("a b").unwrap(), parse("a AND b").unwrap());
+        assert_eq!(
+            parse("tag:rust (a OR b)").unwrap(),
+            and(tag("rust"), or(text("a"), text("b")))
+        );
+        assert_eq!(
+            parse("a OR b c").unwrap(),
+            or(text("a"), and(text("b"), text("c")))
+        );
+    }
+
+    #[test]
+    fn overrides_precedence_with_parentheses() {
+        assert_eq!(
+            parse("(a OR b) AND c").unwrap(),
+            and(or(text("a"), text("b")), text("c"))
+        );
+        assert_eq!(
+            parse("NOT (a OR (b))").unwrap(),
+            not(or(text("a"), text("b")))
+        );
+    }
+
+    #[test]
+    fn quotes_phrases_and_filter_values() {
+        assert_eq!(parse("\"tokio AND OR\"").unwrap(), text("tokio AND OR"));
+        assert_eq!(parse("\"tag:rust\"").unwrap(), text("tag:rust"));
+        assert_eq!(
+            parse("title:\"foo (bar)\" \"\"").unwrap(),
+            and(Expr::Term(Term::Title("foo (bar)".to_string())), text(""))
+        );
+        // Keywords are case sensitive
+        assert_eq!(
+            parse("a and b").unwrap(),
+            and(and(text("a"), text("and")), text("b"))
+        );
+    }
+
+    #[test]
+    fn parses_filters() {
+        assert_eq!(
+            parse("updated:>=2024-01-31").unwrap(),
+            Expr::Term(Term::Date(
+                DateField::Updated,
+                Comparison::Gte,
+                NaiveDate::from_ymd_opt(2024, 1, 31).unwrap()
+            ))
+        );
+        assert_eq!(
+            parse("reading_time:<5").unwrap(),
+            Expr::Term(Term::ReadingTime(Comparison::Lt, 5))
+        );
+        assert_eq!(
+            parse("ROOT:blog").unwrap(),
+            Expr::Term(Term::Root("blog".to_string()))
+        );
+        assert_eq!(
+            parse("https://example.com").unwrap(),
+            text("https://example.com")
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_queries() {
+        for input in [
+            "",
+            "   ",
+            "a AND",
+            "OR a",
+            "NOT",
+            "(a",
+            "a)",
+            "()",
+            "a AND AND b",
+            "\"unterminated",
+            "title:\"unterminated",
+            "tag:",
+            "created:2024-13-01",
+            "updated:>yesterday",
+            "reading_time:long",
+        ] {
+            rejects(input);
+        }
+    }
+
+    #[test]
+    fn limits_the_query_length() {
+        let longest = "a ".repeat(MAX_QUERY_LEN / 2);
+        assert!(parse(&longest).is_ok());
+
+        let message = rejects(&format!("{longest}b"));
+        assert!(message.contains(&MAX_QUERY_LEN.to_string()), "{message}");
+    }
+
+    #[test]
+    fn parses_the_deepest_nesting_within_the_limit() {
+        let depth = (MAX_QUERY_LEN - 1) / 2;
+        let nested = format!("{}a{}", "(".repeat(depth), ")".repeat(depth));
+        assert_eq!(parse(&nested).unwrap(), text("a"));
+
+        let negated = format!("{}a", "NOT ".repeat((MAX_QUERY_LEN - 1) / 4));
+        assert!(parse(&negated).is_ok());
+    }
+
+    #[test]
+    fn finds_query_blocks() {
+        let content = "# Notes\n```ledge-query table\ntag:rust\n```\n```rust\nfn main() {}\n```\n```ledge-query\nunterminated\n";
+        let blocks = find_blocks(content);
+
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].query, "tag:rust");
+        assert!(blocks[0].table);
+        assert_eq!(
+            &content[blocks[0].range.clone()],
+            "```ledge-query table\ntag:rust\n```\n"
+        );
+    }
+}
@@ -0,0 +1,75 @@
+//! Parsing of mdBook's `SUMMARY.md`.
+
+use crate::error::LedgeknawError;
+use std::{fs, path::Path};
+
+/// A chapter listed in the summary.
+#[derive(Debug)]
+pub struct SummaryEntry {
+    /// Canonicalised path of the chapter document
+    pub path: String,
+    pub title: String,
+    /// Position of the chapter in the summary
+    pub weight: i32,
+}
+
+/// Read the summary of the book in `root`, if it has one. Chapters whose
+/// files do not exist, including draft chapters without a path, are skipped.
+pub fn read_summary(root: &Path) -> Result<Vec<SummaryEntry>, LedgeknawError> {
+    let Some(summary) = [root.join("SUMMARY.md"), root.join("src").join("SUMMARY.md")]
+        .into_iter()
+        .find(|path| path.is_file())
+    else {
+        return Ok(vec![]);
+    };
+
+    let content = fs::read_to_string(&summary)?;
+    let base = summary.parent().unwrap_or(root);
+
+    let mut entries = vec![];
+
+    for line in content.lines() {
+        let Some((title, link)) = parse_link(line) else {
+            continue;
+        };
+
+        // Anchors are irrelevant for the chapter itself
+        let link = link.split('#').next().unwrap_or_default();
+
+        let Ok(path) = base.join(link).canonicalize() else {
+            continue;
+        };
+
+        let Some(path) = path.to_str() else {
+            continue;
+        };
+
+        entries.push(SummaryEntry {
+            path: path.to_string(),
+            title: title.to_string(),
+            weight: entries.len() as i32,
+        });
+    }
+
+    Ok(entries)
+}
+
+/// Parse a `[title](link)` chapter line, optionally prefixed with a list marker.
+fn parse_link(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim_start();
+    let line = line
+        .strip_prefix("- ")
+        .or_else(|| line.strip_prefix("* "))
+        .unwrap_or(line)
+        .trim_start();
+
+    let rest = line.strip_prefix('[')?;
+    let (title, rest) = rest.split_once("](")?;
+    let (link, _) = rest.split_once(')')?;
+
+    if link.is_empty() {
+        return None;
+    }
+
+    Some((title.trim(), link.trim()))
+}
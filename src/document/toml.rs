@@ -0,0 +1,157 @@
+//! TOML found in `+++` fenced frontmatter, as written by Hugo. Values are converted to
+//! YAML, so the frontmatter is deserialized the same regardless of its format. Dates and
+//! times are kept as strings.
+
+use serde_yaml::{Mapping, Value};
+
+/// TOML that could not be parsed, with the line it was found on.
+#[derive(Debug, thiserror::Error)]
+#[error("{message} at line {line}")]
+pub struct TomlError {
+    pub message: String,
+    /// Line in the frontmatter, starting from 1
+    pub line: usize,
+}
+
+pub fn parse(toml: &str) -> Result<Value, TomlError> {
+    let table = toml.parse::<::toml::Table>().map_err(|e| TomlError {
+        message: e.message().to_string(),
+        line: e
+            .span()
+            .map(|span| toml[..span.start].matches('\n').count() + 1)
+            .unwrap_or(1),
+    })?;
+    Ok(table_to_yaml(table))
+}
+
+fn table_to_yaml(table: ::toml::Table) -> Value {
+    Value::Mapping(
+        table
+            .into_iter()
+            .map(|(key, value)| (Value::String(key), to_yaml(value)))
+            .collect::<Mapping>(),
+    )
+}
+
+fn to_yaml(value: ::toml::Value) -> Value {
+    match value {
+        ::toml::Value::String(s) => Value::String(s),
+        ::toml::Value::Integer(i) => Value::Number(i.into()),
+        ::toml::Value::Float(f) => Value::Number(f.into()),
+        ::toml::Value::Boolean(b) => Value::Bool(b),
+        ::toml::Value::Datetime(datetime) => Value::String(datetime.to_string()),
+        ::toml::Value::Array(array) => Value::Sequence(array.into_iter().map(to_yaml).collect()),
+        ::toml::Value::Table(table) => table_to_yaml(table),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn yaml(src: &str) -> Value {
+        serde_yaml::from_str(src).unwrap()
+    }
+
+    #[test]
+    fn parses_strings_with_escapes() {
+        let value = parse(
+            r#"
+title = "A \"quoted\" title\twith a tab \u00e9"
+literal = 'C:\path\no\escapes'
+multiline = """
+first
+second"""
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            value["title"],
+            Value::from("A \"quoted\" title\twith a tab é")
+        );
+        assert_eq!(value["literal"], Value::from(r"C:\path\no\escapes"));
+        assert_eq!(value["multiline"], Value::from("first\nsecond"));
+    }
+
+    #[test]
+    fn parses_arrays_and_tables() {
+        let value = parse(
+            r#"
+tags = ["rust", "web"]
+weights = [1, 2.5]
+draft = false
+
+[params]
+author = { name = "Ana", links = ["a", "b"] }
+
+[[related]]
+title = "One"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            value,
+            yaml(
+                r#"
+tags: [rust, web]
+weights: [1, 2.5]
+draft: false
+params:
+  author:
+    name: Ana
+    links: [a, b]
+related:
+  - title: One
+"#
+            )
+        );
+    }
+
+    #[test]
+    fn keeps_dates_as_strings() {
+        let value = parse(
+            r#"
+date = 2024-01-02T10:30:00+01:00
+published = 2024-01-02
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(value["date"], Value::from("2024-01-02T10:30:00+01:00"));
+        assert_eq!(value["published"], Value::from("2024-01-02"));
+    }
+
+    #[test]
+    fn reports_the_line_of_malformed_input() {
+        let error = parse("title = \"ok\"\ntags = [\"unterminated\"\n").unwrap_err();
+        assert!(error.line >= 2, "{error}");
+
+        let error = parse("title = \"ok\"\ntitle = \"again\"\n").unwrap_err();
+        assert_eq!(error.line, 2, "{error}");
+
+        assert!(parse("= \"no key\"").is_err());
+        assert!(parse("key = 'unterminated").is_err());
+    }
+
+    #[test]
+    fn deserializes_as_frontmatter() {
+        let meta: crate::document::DocumentMeta = serde_yaml::from_value(
+            parse(
+                r#"
+title = "Hugo post"
+tags = ["a", "b"]
+draft = true
+date = 2024-01-02T10:30:00Z
+"#,
+            )
+            .unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(meta.title.as_deref(), Some("Hugo post"));
+        assert!(meta.draft);
+        assert!(meta.date.is_some());
+    }
+}
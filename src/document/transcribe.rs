@@ -0,0 +1,72 @@
+use crate::{config::TranscriberConfig, error::LedgeknawError};
+use reqwest::multipart::{Form, Part};
+use std::path::Path;
+use tracing::debug;
+
+/// Whether the asset has one of the configured extensions.
+pub fn is_supported(config: &TranscriberConfig, path: impl AsRef<Path>) -> bool {
+    let Some(ext) = path.as_ref().extension().and_then(|ext| ext.to_str()) else {
+        return false;
+    };
+
+    config
+        .extensions
+        .iter()
+        .any(|supported| supported.eq_ignore_ascii_case(ext))
+}
+
+/// Post the asset to the configured transcriber and return the transcript.
+pub async fn transcribe(
+    client: &reqwest::Client,
+    config: &TranscriberConfig,
+    path: impl AsRef<Path>,
+    content: Vec<u8>,
+) -> Result<String, LedgeknawError> {
+    let path = path.as_ref();
+    debug!("Transcribing {}", path.display());
+
+    let file_name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("__unknown")
+        .to_string();
+
+    let mime = mime_guess::from_path(path).first_or_octet_stream();
+
+    let part = Part::bytes(content)
+        .file_name(file_name)
+        .mime_str(mime.as_ref())
+        .map_err(|e| LedgeknawError::Transcriber(e.to_string()))?;
+
+    let form = config
+        .params
+        .iter()
+        .fold(Form::new(), |form, (key, value)| {
+            form.text(key.clone(), value.clone())
+        })
+        .part(config.field.clone(), part);
+
+    let response = client
+        .post(&config.url)
+        .multipart(form)
+        .send()
+        .await
+        .map_err(|e| LedgeknawError::Transcriber(e.to_string()))?;
+
+    let status = response.status();
+    let body = response
+        .text()
+        .await
+        .map_err(|e| LedgeknawError::Transcriber(e.to_string()))?;
+
+    if !status.is_success() {
+        return Err(LedgeknawError::Transcriber(format!(
+            "{} responded with {status} for {}: {}",
+            config.url,
+            path.display(),
+            body.trim()
+        )));
+    }
+
+    Ok(body.trim().to_string())
+}
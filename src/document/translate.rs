@@ -0,0 +1,83 @@
+use crate::{config::TranslatorConfig, error::LedgeknawError};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+#[derive(Debug, Serialize)]
+struct TranslateRequest<'a> {
+    q: &'a str,
+    source: &'a str,
+    target: &'a str,
+    format: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    api_key: Option<&'a str>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TranslateResponse {
+    translated_text: String,
+}
+
+/// Translations are stored as derived documents per language, e.g. `translation:de`.
+pub fn derived_kind(lang: &str) -> String {
+    format!("translation:{lang}")
+}
+
+/// Language codes are passed to the provider and used in derived document kinds,
+/// so only short alphanumeric codes like `de` or `pt-BR` are accepted.
+pub fn validate_lang(lang: &str) -> Result<(), LedgeknawError> {
+    let valid = !lang.is_empty()
+        && lang.len() <= 16
+        && lang.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+
+    if !valid {
+        return Err(LedgeknawError::InvalidParameter(format!(
+            "{lang}: invalid language code"
+        )));
+    }
+
+    Ok(())
+}
+
+/// Translate the markdown content to the target language with the configured provider.
+pub async fn translate(
+    client: &reqwest::Client,
+    config: &TranslatorConfig,
+    content: &str,
+    lang: &str,
+) -> Result<String, LedgeknawError> {
+    debug!("Translating {} bytes to {lang}", content.len());
+
+    let request = TranslateRequest {
+        q: content,
+        source: "auto",
+        target: lang,
+        format: "text",
+        api_key: config.api_key.as_deref(),
+    };
+
+    let response = client
+        .post(&config.url)
+        .json(&request)
+        .send()
+        .await
+        .map_err(|e| LedgeknawError::Translator(e.to_string()))?;
+
+    let status = response.status();
+
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(LedgeknawError::Translator(format!(
+            "{} responded with {status}: {}",
+            config.url,
+            body.trim()
+        )));
+    }
+
+    let response: TranslateResponse = response
+        .json()
+        .await
+        .map_err(|e| LedgeknawError::Translator(e.to_string()))?;
+
+    Ok(response.translated_text)
+}
@@ -36,8 +36,62 @@ pub enum LedgeknawError {
     #[error("YAML error: {0}")]
     SerdeYaml(#[from] serde_yaml::Error),
 
+    #[error("TOML error: {0}")]
+    Toml(#[from] crate::document::toml::TomlError),
+
     #[error("Http: {0}")]
     Http(#[from] axum::http::Error),
+
+    #[error("OCR: {0}")]
+    Ocr(String),
+
+    #[error("Transcriber: {0}")]
+    Transcriber(String),
+
+    #[error("Translator: {0}")]
+    Translator(String),
+
+    #[error("Git: {0}")]
+    Git(String),
+
+    #[error("Update check: {0}")]
+    Update(String),
+
+    #[error("TLS: {0}")]
+    Tls(String),
+
+    #[error("Archive: {0}")]
+    Archive(String),
+
+    #[error("TOTP: {0}")]
+    Totp(String),
+
+    #[error("Service: {0}")]
+    Service(String),
+
+    #[error("S3: {0}")]
+    S3(String),
+
+    #[error("Invalid parameter: {0}")]
+    InvalidParameter(String),
+
+    #[error("Argon: {0}")]
+    Argon(argon2::password_hash::Error),
+
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+
+    #[error("Too many requests, retry after {0}s")]
+    TooManyRequests(u64),
+
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
+    #[error("Unprocessable: {0}")]
+    Unprocessable(String),
+
+    #[error("Body: {0}")]
+    Body(#[from] axum::Error),
 }
 
 impl IntoResponse for LedgeknawError {
@@ -53,15 +107,25 @@ impl IntoResponse for LedgeknawError {
             | KE::Utf8(_)
             | KE::Watcher(_)
             // This one can only occur on startup if an invalid hash is given
+            | KE::Argon(_)
             | KE::Sqlx(_)
-            | KE::SerdeYaml(_) | KE::Http(_)=> {
+            | KE::SerdeYaml(_) | KE::Toml(_) | KE::Http(_) | KE::Ocr(_) | KE::Transcriber(_) | KE::Translator(_) | KE::Git(_) | KE::Tls(_) | KE::Archive(_) | KE::Update(_) | KE::Totp(_) | KE::Service(_) | KE::S3(_) | KE::Body(_) => {
                 (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()).into_response()
             }
             KE::DoesNotExist(e) => (StatusCode::NOT_FOUND, e).into_response(),
-            KE::InvalidDirectory(_) | KE::SerdeJson(_) => {
+            KE::InvalidParameter(_) => (StatusCode::BAD_REQUEST, self.to_string()).into_response(),
+            KE::TooManyRequests(retry_after) => (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(axum::http::header::RETRY_AFTER, retry_after.to_string())],
+                self.to_string(),
+            )
+                .into_response(),
+            KE::Conflict(_) => (StatusCode::CONFLICT, self.to_string()).into_response(),
+            KE::InvalidDirectory(_) | KE::SerdeJson(_) | KE::Unprocessable(_) => {
                 (StatusCode::UNPROCESSABLE_ENTITY, self.to_string()).into_response()
             }
             // Occurs on pw verification in handlers
+            KE::Unauthorized(_) => (StatusCode::UNAUTHORIZED, self.to_string()).into_response(),
         }
     }
 }
@@ -0,0 +1,242 @@
+//! Changes of documents and directories made by syncing or reindexing, found by comparing
+//! the stored entries before and after. Sent to webhooks and to clients of `/events`.
+
+use crate::{
+    document::{
+        models::{DirectoryState, DocumentState},
+        modified_time,
+    },
+    error::LedgeknawError,
+    state::DocumentService,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::{collections::HashMap, sync::Arc};
+use tokio::sync::{broadcast, Mutex};
+use tracing::debug;
+
+/// Changes buffered for slow clients of `/events` before they miss some.
+const CHANGE_CAPACITY: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EventKind {
+    Created,
+    Updated,
+    Deleted,
+}
+
+impl EventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Created => "created",
+            Self::Updated => "updated",
+            Self::Deleted => "deleted",
+        }
+    }
+}
+
+/// A changed document, as posted to webhooks.
+#[derive(Debug, Clone, Serialize)]
+pub struct DocumentEvent {
+    pub event: EventKind,
+    pub id: uuid::Uuid,
+    pub path: String,
+    pub title: Option<String>,
+    pub timestamp: DateTime<Utc>,
+    #[serde(skip)]
+    pub private: bool,
+}
+
+impl DocumentEvent {
+    fn new(event: EventKind, document: DocumentState, timestamp: DateTime<Utc>) -> Self {
+        Self {
+            event,
+            id: document.id,
+            path: document.path,
+            title: document.title,
+            timestamp,
+            private: document.private,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryKind {
+    Document,
+    Directory,
+}
+
+impl EntryKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Document => "document",
+            Self::Directory => "directory",
+        }
+    }
+}
+
+/// A changed entry, as sent on `/events`. Only the ID is included,
+/// for clients to drop the cached entry.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Change {
+    #[serde(skip)]
+    pub entry: EntryKind,
+    pub event: EventKind,
+    pub id: uuid::Uuid,
+    /// Only sent to clients with private access
+    #[serde(skip)]
+    pub private: bool,
+}
+
+/// The stored entries before syncing or reindexing.
+#[derive(Debug)]
+pub struct Snapshot {
+    taken_at: DateTime<Utc>,
+    documents: HashMap<uuid::Uuid, DocumentState>,
+    directories: HashMap<uuid::Uuid, DirectoryState>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Notifier {
+    changes: broadcast::Sender<Change>,
+
+    /// When the entries were last compared. Documents whose files
+    /// were modified since are updated, even if their metadata is not.
+    compared_at: Arc<Mutex<Option<DateTime<Utc>>>>,
+}
+
+impl Default for Notifier {
+    fn default() -> Self {
+        Self {
+            changes: broadcast::channel(CHANGE_CAPACITY).0,
+            compared_at: Arc::default(),
+        }
+    }
+}
+
+impl Notifier {
+    pub fn subscribe(&self) -> broadcast::Receiver<Change> {
+        self.changes.subscribe()
+    }
+}
+
+impl DocumentService {
+    /// The stored entries, if anyone is notified of changes.
+    pub async fn snapshot_entries(&self) -> Result<Option<Snapshot>, LedgeknawError> {
+        if !self.webhooks.is_enabled() && self.notifier.changes.receiver_count() == 0 {
+            return Ok(None);
+        }
+
+        let taken_at = Utc::now();
+        let documents = self
+            .db
+            .list_document_states()
+            .await?
+            .into_iter()
+            .map(|document| (document.id, document))
+            .collect();
+        let directories = self
+            .db
+            .list_directory_states()
+            .await?
+            .into_iter()
+            .map(|directory| (directory.id, directory))
+            .collect();
+
+        Ok(Some(Snapshot {
+            taken_at,
+            documents,
+            directories,
+        }))
+    }
+
+    /// Notify the webhooks and clients of `/events` of every entry changed since the snapshot.
+    pub async fn notify_changes(&self, snapshot: Option<Snapshot>) -> Result<(), LedgeknawError> {
+        let Some(Snapshot {
+            taken_at,
+            mut documents,
+            mut directories,
+        }) = snapshot
+        else {
+            return Ok(());
+        };
+
+        // Content changes are only found from the second comparison on,
+        // since no modification times are known before the first
+        let compared_at = self.notifier.compared_at.lock().await.replace(taken_at);
+
+        let now = Utc::now();
+        let mut events = vec![];
+
+        for document in self.db.list_document_states().await? {
+            let event = match documents.remove(&document.id) {
+                None => Some(EventKind::Created),
+                Some(before)
+                    if before.path != document.path || before.digest != document.digest =>
+                {
+                    Some(EventKind::Updated)
+                }
+                Some(_) => compared_at
+                    .filter(|compared_at| {
+                        modified_time(&document.path)
+                            .is_some_and(|modified| modified > *compared_at)
+                    })
+                    .map(|_| EventKind::Updated),
+            };
+
+            if let Some(event) = event {
+                events.push(DocumentEvent::new(event, document, now));
+            }
+        }
+
+        for document in documents.into_values() {
+            events.push(DocumentEvent::new(EventKind::Deleted, document, now));
+        }
+
+        let mut changes = events
+            .iter()
+            .map(|event| Change {
+                entry: EntryKind::Document,
+                event: event.event,
+                id: event.id,
+                private: event.private,
+            })
+            .collect::<Vec<_>>();
+
+        for directory in self.db.list_directory_states().await? {
+            let event = match directories.remove(&directory.id) {
+                None => EventKind::Created,
+                Some(before) if before.version != directory.version => EventKind::Updated,
+                Some(_) => continue,
+            };
+            changes.push(Change {
+                entry: EntryKind::Directory,
+                event,
+                id: directory.id,
+                private: directory.private,
+            });
+        }
+
+        for directory in directories.into_values() {
+            changes.push(Change {
+                entry: EntryKind::Directory,
+                event: EventKind::Deleted,
+                id: directory.id,
+                private: directory.private,
+            });
+        }
+
+        debug!("Found {} changed entries", changes.len());
+
+        // Only fails without clients
+        for change in changes {
+            let _ = self.notifier.changes.send(change);
+        }
+
+        self.send_webhooks(events);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,112 @@
+use crate::error::LedgeknawError;
+use std::{collections::BTreeMap, sync::Arc};
+
+use self::db::FeatureDb;
+
+pub mod db;
+
+/// Querying documents on `/query`
+pub const QUERY: &str = "query";
+
+/// Machine translation of documents
+pub const TRANSLATION: &str = "translation";
+
+/// Derived documents such as summaries and transcripts
+pub const DERIVED: &str = "derived";
+
+/// The full tree export on `/export/tree.json`
+pub const EXPORT: &str = "export";
+
+/// The server rendered pages on `/pages`
+pub const PAGES: &str = "pages";
+
+/// The graph of documents, links and tags on `/graph`
+pub const GRAPH: &str = "graph";
+
+/// Leave directories without documents at any depth out of the sidebar
+pub const HIDE_EMPTY_DIRECTORIES: &str = "hide_empty_directories";
+
+/// Flags enforced by the server and whether they are enabled when neither the config
+/// nor the database sets them. Any other flag is only passed on to the front end.
+const DEFAULTS: [(&str, bool); 7] = [
+    (QUERY, true),
+    (TRANSLATION, true),
+    (DERIVED, true),
+    (EXPORT, true),
+    (PAGES, true),
+    (GRAPH, true),
+    (HIDE_EMPTY_DIRECTORIES, false),
+];
+
+/// Flags for switching subsystems on or off per instance. Flags from the config
+/// are overridden by the ones set on `/admin/flags`, which are stored in the database.
+#[derive(Debug, Clone)]
+pub struct FeatureFlags {
+    db: FeatureDb,
+    config: Arc<BTreeMap<String, bool>>,
+}
+
+impl FeatureFlags {
+    pub fn new(db: FeatureDb, config: BTreeMap<String, bool>) -> Self {
+        Self {
+            db,
+            config: Arc::new(config),
+        }
+    }
+
+    /// All flags with their current state.
+    pub async fn list(&self) -> Result<BTreeMap<String, bool>, LedgeknawError> {
+        let mut flags = DEFAULTS
+            .iter()
+            .map(|(name, enabled)| (name.to_string(), *enabled))
+            .collect::<BTreeMap<_, _>>();
+        flags.extend(
+            self.config
+                .iter()
+                .map(|(name, enabled)| (name.clone(), *enabled)),
+        );
+        flags.extend(self.db.list_flags().await?);
+        Ok(flags)
+    }
+
+    pub async fn enabled(&self, name: &str) -> Result<bool, LedgeknawError> {
+        if let Some(enabled) = self.db.get_flag(name).await? {
+            return Ok(enabled);
+        }
+
+        if let Some(enabled) = self.config.get(name) {
+            return Ok(*enabled);
+        }
+
+        Ok(DEFAULTS
+            .iter()
+            .find(|(flag, _)| *flag == name)
+            .is_some_and(|(_, enabled)| *enabled))
+    }
+
+    /// Responds with 404 if the flag is disabled, as if the route did not exist.
+    pub async fn require(&self, name: &str) -> Result<(), LedgeknawError> {
+        if self.enabled(name).await? {
+            Ok(())
+        } else {
+            Err(LedgeknawError::NotFound(format!("{name} is disabled")))
+        }
+    }
+
+    pub async fn set(&self, name: &str, enabled: bool) -> Result<(), LedgeknawError> {
+        if name.is_empty() || name.len() > 64 {
+            return Err(LedgeknawError::InvalidParameter(
+                "flag names must be between 1 and 64 characters".to_string(),
+            ));
+        }
+        self.db.set_flag(name, enabled).await
+    }
+
+    /// Remove the flag from the database, reverting it to the config or its default.
+    pub async fn reset(&self, name: &str) -> Result<(), LedgeknawError> {
+        if !self.db.delete_flag(name).await? {
+            return Err(LedgeknawError::NotFound(format!("flag {name}")));
+        }
+        Ok(())
+    }
+}
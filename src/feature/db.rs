@@ -0,0 +1,56 @@
+use crate::{db::retry_read, error::LedgeknawError};
+use sqlx::PgPool;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone)]
+pub struct FeatureDb {
+    pool: sqlx::PgPool,
+}
+
+impl FeatureDb {
+    pub async fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn list_flags(&self) -> Result<BTreeMap<String, bool>, LedgeknawError> {
+        Ok(retry_read(|| {
+            sqlx::query!("SELECT name, enabled FROM feature_flags").fetch_all(&self.pool)
+        })
+        .await?
+        .into_iter()
+        .map(|el| (el.name, el.enabled))
+        .collect())
+    }
+
+    pub async fn get_flag(&self, name: &str) -> Result<Option<bool>, LedgeknawError> {
+        Ok(retry_read(|| {
+            sqlx::query!("SELECT enabled FROM feature_flags WHERE name = $1", name)
+                .fetch_optional(&self.pool)
+        })
+        .await?
+        .map(|el| el.enabled))
+    }
+
+    pub async fn set_flag(&self, name: &str, enabled: bool) -> Result<(), LedgeknawError> {
+        sqlx::query!(
+            "INSERT INTO feature_flags(name, enabled) VALUES($1, $2)
+             ON CONFLICT(name) DO UPDATE SET enabled = $2",
+            name,
+            enabled
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Returns whether the flag was stored.
+    pub async fn delete_flag(&self, name: &str) -> Result<bool, LedgeknawError> {
+        Ok(
+            sqlx::query!("DELETE FROM feature_flags WHERE name = $1", name)
+                .execute(&self.pool)
+                .await?
+                .rows_affected()
+                > 0,
+        )
+    }
+}
@@ -0,0 +1,105 @@
+//! Syncing on push webhooks from GitHub and GitLab, for instances
+//! whose documents are kept in git repositories.
+
+use crate::{
+    config::{GitConfig, HookConfig},
+    document::git,
+    error::LedgeknawError,
+    state::DocumentService,
+};
+use axum::http::HeaderMap;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::{collections::HashSet, path::Path};
+use subtle::ConstantTimeEq;
+use tracing::info;
+
+/// Check that the webhook was sent with the configured secret. GitHub signs the
+/// payload with it in `X-Hub-Signature-256`, GitLab sends it as is in `X-Gitlab-Token`.
+pub fn verify(config: &HookConfig, headers: &HeaderMap, body: &[u8]) -> Result<(), LedgeknawError> {
+    if let Some(signature) = headers.get("x-hub-signature-256") {
+        let signature = signature
+            .to_str()
+            .ok()
+            .and_then(|signature| signature.strip_prefix("sha256="))
+            .and_then(|signature| hex::decode(signature).ok())
+            .ok_or_else(|| LedgeknawError::Unauthorized("invalid signature".to_string()))?;
+
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(config.secret.as_bytes())
+            .expect("HMAC accepts keys of any size");
+        mac.update(body);
+
+        return mac
+            .verify_slice(&signature)
+            .map_err(|_| LedgeknawError::Unauthorized("invalid signature".to_string()));
+    }
+
+    match headers.get("x-gitlab-token") {
+        // Compared in constant time so the secret cannot be guessed byte by byte
+        Some(token) if bool::from(token.as_bytes().ct_eq(config.secret.as_bytes())) => Ok(()),
+        Some(_) => Err(LedgeknawError::Unauthorized("invalid token".to_string())),
+        None => Err(LedgeknawError::Unauthorized(
+            "missing signature".to_string(),
+        )),
+    }
+}
+
+/// Whether the webhook announces a push. Others, such as the ping sent
+/// by GitHub when the webhook is created, are acknowledged and ignored.
+pub fn is_push(headers: &HeaderMap) -> bool {
+    let event = headers
+        .get("x-github-event")
+        .or_else(|| headers.get("x-gitlab-event"))
+        .and_then(|event| event.to_str().ok());
+
+    matches!(event, Some("push" | "Push Hook"))
+}
+
+impl DocumentService {
+    /// Pull the repositories of the roots configured to be pulled, if webhooks are
+    /// configured, then sync.
+    pub async fn pull_and_sync(&self) -> Result<(), LedgeknawError> {
+        let Some(config) = self.hooks.as_deref() else {
+            return self.sync().await;
+        };
+
+        let git_config = self.git.as_deref().cloned().unwrap_or_default();
+        let roots = config
+            .pull
+            .iter()
+            .map(|alias| match self.roots.get(alias) {
+                Some(root) => Ok(root.path().to_string()),
+                None => Err(LedgeknawError::NotFound(format!("root directory {alias}"))),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        tokio::task::spawn_blocking(move || {
+            let mut pulled = HashSet::new();
+            for root in roots {
+                pull(&git_config, Path::new(&root), &mut pulled)?;
+            }
+            Ok::<_, LedgeknawError>(())
+        })
+        .await
+        .map_err(|e| LedgeknawError::Git(e.to_string()))??;
+
+        self.sync().await
+    }
+}
+
+/// Pull the repository containing `root`, unless it is in `pulled` already.
+fn pull(
+    config: &GitConfig,
+    root: &Path,
+    pulled: &mut HashSet<String>,
+) -> Result<(), LedgeknawError> {
+    let toplevel = git::toplevel(config, root)?;
+    if !pulled.insert(toplevel.clone()) {
+        return Ok(());
+    }
+
+    let output = git::pull(config, root)?;
+    info!("Pulled {toplevel}: {}", output.trim());
+
+    Ok(())
+}
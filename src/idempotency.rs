@@ -0,0 +1,184 @@
+use crate::{error::LedgeknawError, state::DocumentService};
+use axum::{
+    body::{Body, Bytes},
+    extract::{FromRequest, Request, State},
+    http::{header, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use db::IdempotencyDb;
+use sha2::{Digest, Sha256};
+use tracing::error;
+
+pub mod db;
+
+pub const IDEMPOTENCY_KEY: &str = "idempotency-key";
+
+/// Response recorded for an idempotency key.
+#[derive(Debug)]
+pub struct StoredResponse {
+    pub method: String,
+    pub path: String,
+    /// SHA-256 of the request body, absent for keys claimed before it was recorded
+    pub request_hash: Option<String>,
+    /// Absent while the original request is in progress
+    pub status: Option<i16>,
+    pub content_type: Option<String>,
+    pub body: Option<Vec<u8>>,
+}
+
+/// Middleware replaying the stored response of mutations repeated with the same
+/// `Idempotency-Key` header instead of running them again.
+/// Requests without the header, and reads, pass through untouched. Server errors
+/// are not stored so the request can be retried with the same key, and neither are
+/// requests whose handler never finished, e.g. as the client disconnected.
+/// Reusing a key for a request with another body is rejected with 422.
+pub async fn idempotency(
+    state: State<DocumentService>,
+    request: Request,
+    next: Next,
+) -> Result<Response, LedgeknawError> {
+    if matches!(*request.method(), Method::GET | Method::HEAD) {
+        return Ok(next.run(request).await);
+    }
+
+    let Some(key) = request.headers().get(IDEMPOTENCY_KEY) else {
+        return Ok(next.run(request).await);
+    };
+
+    let key = match key.to_str() {
+        Ok(key) if !key.is_empty() && key.len() <= 255 => key.to_string(),
+        _ => {
+            return Err(LedgeknawError::InvalidParameter(format!(
+                "{IDEMPOTENCY_KEY} must be between 1 and 255 visible ASCII characters"
+            )))
+        }
+    };
+
+    let method = request.method().to_string();
+    let path = request.uri().path().to_string();
+
+    let (parts, body) = request.into_parts();
+    let body = match Bytes::from_request(Request::from_parts(parts.clone(), body), &()).await {
+        Ok(body) => body,
+        Err(rejection) => return Ok(rejection.into_response()),
+    };
+    let request_hash = hex::encode(Sha256::digest(&body));
+
+    if let Some(stored) = state
+        .idempotency
+        .claim(&key, &method, &path, &request_hash)
+        .await?
+    {
+        if stored.method != method || stored.path != path {
+            return Err(LedgeknawError::InvalidParameter(format!(
+                "{key}: key already used for {} {}",
+                stored.method, stored.path
+            )));
+        }
+
+        if stored
+            .request_hash
+            .as_ref()
+            .is_some_and(|stored| *stored != request_hash)
+        {
+            return Err(LedgeknawError::Unprocessable(format!(
+                "{key}: key already used for a request with another body"
+            )));
+        }
+
+        let Some(status) = stored.status else {
+            return Err(LedgeknawError::Conflict(format!(
+                "{key}: request in progress"
+            )));
+        };
+
+        return Ok(stored.into_response(status));
+    }
+
+    let claim = Claim {
+        db: state.idempotency.clone(),
+        key,
+        held: true,
+    };
+
+    let request = Request::from_parts(parts, Body::from(body));
+    let (parts, body) = next.run(request).await.into_parts();
+
+    if parts.status.is_server_error() {
+        claim.release().await?;
+        return Ok(Response::from_parts(parts, body));
+    }
+
+    // The claim is released when dropped on errors
+    let body = axum::body::to_bytes(body, usize::MAX).await?;
+
+    let content_type = parts
+        .headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok());
+
+    state
+        .idempotency
+        .store(
+            &claim.key,
+            parts.status.as_u16() as i16,
+            content_type,
+            &body,
+        )
+        .await?;
+    claim.keep();
+
+    Ok(Response::from_parts(parts, Body::from(body)))
+}
+
+/// A key claimed for a request in progress, released when dropped before the
+/// response is stored so the request can be retried with it.
+struct Claim {
+    db: IdempotencyDb,
+    key: String,
+    held: bool,
+}
+
+impl Claim {
+    async fn release(mut self) -> Result<(), LedgeknawError> {
+        self.held = false;
+        self.db.release(&self.key).await
+    }
+
+    /// Keep the key claimed once the response is stored under it.
+    fn keep(mut self) {
+        self.held = false;
+    }
+}
+
+impl Drop for Claim {
+    fn drop(&mut self) {
+        if !self.held {
+            return;
+        }
+        let db = self.db.clone();
+        let key = std::mem::take(&mut self.key);
+        tokio::spawn(async move {
+            if let Err(e) = db.release(&key).await {
+                error!("Error while releasing idempotency key {key}: {e}");
+            }
+        });
+    }
+}
+
+impl StoredResponse {
+    fn into_response(self, status: i16) -> Response {
+        let mut response = Response::new(Body::from(self.body.unwrap_or_default()));
+        *response.status_mut() = StatusCode::from_u16(status as u16).unwrap_or(StatusCode::OK);
+        if let Some(content_type) = self
+            .content_type
+            .and_then(|value| HeaderValue::from_str(&value).ok())
+        {
+            response
+                .headers_mut()
+                .insert(header::CONTENT_TYPE, content_type);
+        }
+        response
+    }
+}
@@ -0,0 +1,102 @@
+use super::StoredResponse;
+use crate::error::LedgeknawError;
+use sqlx::PgPool;
+
+/// Minutes after which a claim of a request still in progress is discarded
+const CLAIM_TTL_MINUTES: i32 = 10;
+
+#[derive(Debug, Clone)]
+pub struct IdempotencyDb {
+    pool: sqlx::PgPool,
+}
+
+impl IdempotencyDb {
+    pub async fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Claim the key for the request. Returns `None` if the key was claimed,
+    /// otherwise the response stored under it, which has no status if the
+    /// original request is still in progress.
+    /// Keys older than a day, and claims left in progress for longer than
+    /// [CLAIM_TTL_MINUTES] by a process that ended, are discarded beforehand.
+    pub async fn claim(
+        &self,
+        key: &str,
+        method: &str,
+        path: &str,
+        request_hash: &str,
+    ) -> Result<Option<StoredResponse>, LedgeknawError> {
+        sqlx::query!(
+            r#"
+            DELETE FROM idempotency_keys
+            WHERE created_at < NOW() - INTERVAL '1 day'
+            OR (status IS NULL AND created_at < NOW() - MAKE_INTERVAL(mins => $1))
+        "#,
+            CLAIM_TTL_MINUTES
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // The key can be released between the insert and the select, in which case
+        // it is claimed again
+        loop {
+            let claimed = sqlx::query!(
+                r#"
+                INSERT INTO idempotency_keys(key, method, path, request_hash) VALUES($1, $2, $3, $4)
+                ON CONFLICT DO NOTHING
+                RETURNING key
+            "#,
+                key,
+                method,
+                path,
+                request_hash
+            )
+            .fetch_optional(&self.pool)
+            .await?;
+
+            if claimed.is_some() {
+                return Ok(None);
+            }
+
+            let stored = sqlx::query_as!(
+                StoredResponse,
+                "SELECT method, path, request_hash, status, content_type, body FROM idempotency_keys WHERE key = $1",
+                key
+            )
+            .fetch_optional(&self.pool)
+            .await?;
+
+            if stored.is_some() {
+                return Ok(stored);
+            }
+        }
+    }
+
+    pub async fn store(
+        &self,
+        key: &str,
+        status: i16,
+        content_type: Option<&str>,
+        body: &[u8],
+    ) -> Result<(), LedgeknawError> {
+        sqlx::query!(
+            "UPDATE idempotency_keys SET status = $1, content_type = $2, body = $3 WHERE key = $4",
+            status,
+            content_type,
+            body,
+            key
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Release the key so the request can be retried.
+    pub async fn release(&self, key: &str) -> Result<(), LedgeknawError> {
+        sqlx::query!("DELETE FROM idempotency_keys WHERE key = $1", key)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,521 @@
+//! Importing zip archives of markdown, such as Notion exports or plain dumps of a
+//! directory, into a root directory. File names are normalized on the way, Notion's
+//! databases are converted to documents and the links between the files follow them.
+
+use crate::{document::links, error::LedgeknawError, job::SYNC, state::DocumentService};
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Component, Path, PathBuf},
+};
+use tracing::info;
+
+pub mod notion;
+pub mod zip;
+
+/// Largest total size of the unpacked files, so small archives cannot fill the disk.
+const MAX_UNPACKED_BYTES: u64 = 1024 * 1024 * 1024;
+
+#[derive(Debug, Serialize)]
+pub struct ImportReport {
+    /// Directory the archive was unpacked to
+    pub directory: String,
+    /// Written files, relative to the directory
+    pub files: Vec<String>,
+    /// Notion databases converted to documents
+    pub databases: usize,
+}
+
+impl DocumentService {
+    /// Unpack the archive to `path` below the root directory `alias`, overwriting
+    /// existing files, and queue a sync.
+    pub async fn import_archive(
+        &self,
+        alias: &str,
+        path: Option<&str>,
+        archive: axum::body::Bytes,
+    ) -> Result<ImportReport, LedgeknawError> {
+        let Some(root) = self.roots.get(alias) else {
+            return Err(LedgeknawError::NotFound(format!("root directory {alias}")));
+        };
+        if root.s3().is_some() {
+            return Err(LedgeknawError::InvalidParameter(format!(
+                "root directory {alias} is mirrored from S3, upload the files to its bucket instead"
+            )));
+        }
+
+        let mut directory = PathBuf::from(root.path());
+        if let Some(path) = path {
+            let path = Path::new(path);
+            if !path
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)))
+            {
+                return Err(LedgeknawError::InvalidParameter(format!(
+                    "invalid path {}",
+                    path.display()
+                )));
+            }
+            directory.push(path);
+        }
+
+        let report = tokio::task::spawn_blocking(move || unpack(&archive, &directory))
+            .await
+            .map_err(std::io::Error::from)??;
+
+        info!(
+            "Imported {} files to {}",
+            report.files.len(),
+            report.directory
+        );
+
+        self.enqueue_job(SYNC, None).await?;
+
+        Ok(report)
+    }
+}
+
+/// A file of the archive, its path split into components.
+struct ImportedFile {
+    path: Vec<String>,
+    data: Vec<u8>,
+}
+
+impl ImportedFile {
+    fn has_extension(&self, extension: &str) -> bool {
+        self.path.last().is_some_and(|name| {
+            name.rsplit_once('.')
+                .is_some_and(|(_, ext)| ext.eq_ignore_ascii_case(extension))
+        })
+    }
+}
+
+fn unpack(archive: &[u8], directory: &Path) -> Result<ImportReport, LedgeknawError> {
+    let mut files = read_files(archive, MAX_UNPACKED_BYTES)?;
+    unwrap_single_directory(&mut files);
+    drop_partial_databases(&mut files);
+
+    let renames = Renames::new(&files);
+    let mut report = ImportReport {
+        directory: directory.display().to_string(),
+        files: vec![],
+        databases: 0,
+    };
+
+    for file in &files {
+        let target = renames.file(&file.path);
+
+        let data = if file.has_extension("md") {
+            match String::from_utf8(file.data.clone()) {
+                Ok(content) => rewrite_links(&content, &file.path, &target, &renames).into_bytes(),
+                Err(_) => file.data.clone(),
+            }
+        } else if file.has_extension("csv") {
+            report.databases += 1;
+            database_document(file, &target, &files, &renames).into_bytes()
+        } else {
+            file.data.clone()
+        };
+
+        let path = target
+            .iter()
+            .fold(directory.to_path_buf(), |path, part| path.join(part));
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, data)?;
+
+        report.files.push(target.join("/"));
+    }
+
+    Ok(report)
+}
+
+/// The files of the archive, with those of zips in it unpacked next to them as Notion
+/// splits large exports into several. Files added by macOS are left out. Fails once the
+/// files, including those of the nested zips, would take more than `max_size` bytes.
+fn read_files(archive: &[u8], max_size: u64) -> Result<Vec<ImportedFile>, LedgeknawError> {
+    let mut files = vec![];
+    let mut budget = max_size;
+
+    for entry in zip::read(archive, &mut budget)? {
+        let mut path = split_path(&entry.name)?;
+        if path
+            .iter()
+            .any(|part| part == "__MACOSX" || part == ".DS_Store")
+        {
+            continue;
+        }
+
+        let file = ImportedFile {
+            path: path.clone(),
+            data: entry.data,
+        };
+        if !file.has_extension("zip") {
+            files.push(file);
+            continue;
+        }
+
+        path.pop();
+        for entry in zip::read(&file.data, &mut budget)? {
+            let mut nested = path.clone();
+            nested.extend(split_path(&entry.name)?);
+            if nested
+                .iter()
+                .any(|part| part == "__MACOSX" || part == ".DS_Store")
+            {
+                continue;
+            }
+            files.push(ImportedFile {
+                path: nested,
+                data: entry.data,
+            });
+        }
+    }
+
+    Ok(files)
+}
+
+/// Split the name of a zip entry into components. Names escaping the archive or without
+/// any components, such as `./.`, are rejected.
+fn split_path(name: &str) -> Result<Vec<String>, LedgeknawError> {
+    let mut path = vec![];
+    for part in name.split(['/', '\\']) {
+        match part {
+            "" | "." => {}
+            ".." => {
+                return Err(LedgeknawError::InvalidParameter(format!(
+                    "invalid zip: {name} is outside of the archive"
+                )))
+            }
+            part => path.push(part.to_string()),
+        }
+    }
+    if path.is_empty() {
+        return Err(LedgeknawError::InvalidParameter(format!(
+            "invalid zip: {name:?} is not a file name"
+        )));
+    }
+    Ok(path)
+}
+
+/// Archives often contain a single directory with everything else in it, which is left out.
+fn unwrap_single_directory(files: &mut [ImportedFile]) {
+    let Some(first) = files.first().and_then(|file| file.path.first()).cloned() else {
+        return;
+    };
+
+    if files
+        .iter()
+        .all(|file| file.path.len() > 1 && file.path[0] == first)
+    {
+        for file in files {
+            file.path.remove(0);
+        }
+    }
+}
+
+/// Notion exports databases both with the rows of their current view and with all of them.
+/// Only the latter are kept.
+fn drop_partial_databases(files: &mut Vec<ImportedFile>) {
+    let full: HashSet<Vec<String>> = files
+        .iter()
+        .filter(|file| {
+            file.path
+                .last()
+                .is_some_and(|name| notion::is_full_database(name))
+        })
+        .map(|file| {
+            let mut path = file.path.clone();
+            if let Some(name) = path.last_mut() {
+                *name = name.replacen("_all.csv", ".csv", 1);
+            }
+            path
+        })
+        .collect();
+
+    files.retain(|file| !full.contains(&file.path));
+}
+
+/// The normalized paths of the files and directories in the archive, keyed by their paths
+/// in it. Names that would be the same as another's once normalized are kept as they are,
+/// or numbered if that is taken as well.
+struct Renames(HashMap<Vec<String>, Vec<String>>);
+
+impl Renames {
+    fn new(files: &[ImportedFile]) -> Self {
+        let mut renames: HashMap<Vec<String>, Vec<String>> = HashMap::new();
+        let mut taken = HashSet::new();
+
+        for file in files {
+            for len in 1..=file.path.len() {
+                let original = &file.path[..len];
+                if renames.contains_key(original) {
+                    continue;
+                }
+
+                let parent: Vec<String> = match len {
+                    1 => vec![],
+                    _ => renames[&file.path[..len - 1]].clone(),
+                };
+
+                let name = &original[len - 1];
+                // Databases become documents
+                let name = match name.strip_suffix(".csv") {
+                    Some(stem) if len == file.path.len() => format!("{stem}.md"),
+                    _ => name.clone(),
+                };
+
+                // Falling back to the original name and then to numbered ones, as the
+                // original name can be what another file was normalized to
+                let normalized_name = notion::normalize_name(&name);
+                let normalized = [normalized_name.clone(), name]
+                    .into_iter()
+                    .chain((2..).map(|n| numbered(&normalized_name, n)))
+                    .map(|candidate| {
+                        let mut path = parent.clone();
+                        path.push(candidate);
+                        path
+                    })
+                    .find(|path| taken.insert(path.clone()))
+                    .expect("numbered names are unbounded");
+
+                renames.insert(original.to_vec(), normalized);
+            }
+        }
+
+        Self(renames)
+    }
+
+    fn file(&self, path: &[String]) -> Vec<String> {
+        self.0[path].clone()
+    }
+
+    fn get(&self, path: &[String]) -> Option<&Vec<String>> {
+        self.0.get(path)
+    }
+}
+
+/// `name (n).ext`, or `name (n)` without an extension.
+fn numbered(name: &str, n: usize) -> String {
+    match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => format!("{stem} ({n}).{extension}"),
+        _ => format!("{name} ({n})"),
+    }
+}
+
+/// Point the relative links of the document at `path` in the archive to where the linked
+/// files are written, e.g. `Page%20<id>/Subpage%20<id>.md` to `Page/Subpage.md`.
+fn rewrite_links(content: &str, path: &[String], target: &[String], renames: &Renames) -> String {
+    links::rewrite(content, |link| {
+        if link.starts_with(['/', '#']) || link.contains("://") || link.starts_with("mailto:") {
+            return None;
+        }
+
+        let (link, suffix) = match link.find(['#', '?']) {
+            Some(i) => link.split_at(i),
+            None => (link, ""),
+        };
+
+        let mut linked = path[..path.len() - 1].to_vec();
+        for part in links::decode(link).split('/') {
+            match part {
+                "" | "." => {}
+                ".." => {
+                    linked.pop()?;
+                }
+                part => linked.push(part.to_string()),
+            }
+        }
+
+        // Links to the partial CSV of a database are to the one with all rows instead
+        let linked = renames.get(&linked).or_else(|| {
+            let mut full = linked.clone();
+            let name = full.last_mut()?;
+            *name = format!("{}_all.csv", name.strip_suffix(".csv")?);
+            renames.get(&full)
+        })?;
+        let relative = relative_path(&target[..target.len() - 1], linked);
+        Some(format!("{}{suffix}", notion::encode_link(&relative)))
+    })
+}
+
+/// The document listing the rows of the database. Rows with a page in the directory of
+/// the database are linked to it.
+fn database_document(
+    file: &ImportedFile,
+    target: &[String],
+    files: &[ImportedFile],
+    renames: &Renames,
+) -> String {
+    let name = file.path.last().map(String::as_str).unwrap_or_default();
+    let stem = name.strip_suffix(".csv").unwrap_or(name);
+    let title = notion::normalize_name(stem);
+
+    let mut rows_dir = file.path[..file.path.len() - 1].to_vec();
+    rows_dir.push(stem.strip_suffix("_all").unwrap_or(stem).to_string());
+
+    let pages: HashMap<String, &Vec<String>> = files
+        .iter()
+        .filter(|row| row.path.len() == rows_dir.len() + 1 && row.path.starts_with(&rows_dir))
+        .filter(|row| row.has_extension("md"))
+        .filter_map(|row| {
+            let normalized = renames.get(&row.path)?;
+            let title = normalized.last()?.strip_suffix(".md")?;
+            Some((title.to_string(), normalized))
+        })
+        .collect();
+
+    let csv = String::from_utf8_lossy(&file.data);
+    notion::csv_to_markdown(&title, &csv, |cell| {
+        let page = pages.get(&notion::normalize_name(cell.trim()))?;
+        Some(notion::encode_link(&relative_path(
+            &target[..target.len() - 1],
+            page,
+        )))
+    })
+}
+
+/// The `/` separated path to `to` from the directory `from`.
+fn relative_path(from: &[String], to: &[String]) -> String {
+    let common = from.iter().zip(to).take_while(|(a, b)| a == b).count();
+
+    let mut parts = vec![".."; from.len() - common];
+    parts.extend(to[common..].iter().map(String::as_str));
+    parts.join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import::zip::tests::{archive, TestEntry};
+
+    fn files(paths: &[&str]) -> Vec<ImportedFile> {
+        paths
+            .iter()
+            .map(|path| ImportedFile {
+                path: split_path(path).unwrap(),
+                data: vec![],
+            })
+            .collect()
+    }
+
+    fn joined(renames: &Renames, path: &str) -> String {
+        renames.file(&split_path(path).unwrap()).join("/")
+    }
+
+    #[test]
+    fn split_path_rejects_parent_components() {
+        assert!(split_path("../a.md").is_err());
+        assert!(split_path("a/../../b.md").is_err());
+        assert!(split_path("a\\..\\b.md").is_err());
+    }
+
+    #[test]
+    fn split_path_drops_empty_and_current_components() {
+        assert_eq!(split_path("/a//./b\\c.md").unwrap(), ["a", "b", "c.md"]);
+        assert_eq!(split_path("a..b.md").unwrap(), ["a..b.md"]);
+    }
+
+    #[test]
+    fn split_path_rejects_names_without_components() {
+        for name in ["", ".", "./.", "//", "\\.\\"] {
+            assert!(split_path(name).is_err(), "{name:?}");
+        }
+    }
+
+    #[test]
+    fn read_files_rejects_entries_without_components() {
+        let outer = archive(&[
+            TestEntry::stored("a.md", b"# A"),
+            TestEntry::stored("./.", b"x"),
+        ]);
+        assert!(read_files(&outer, 1024).is_err());
+
+        let nested = archive(&[TestEntry::stored(".", b"x")]);
+        let outer = archive(&[TestEntry::stored("part.zip", &nested)]);
+        assert!(read_files(&outer, 1024).is_err());
+    }
+
+    #[test]
+    fn renames_strip_notion_ids() {
+        let files = files(&[
+            "Page 0123456789abcdef0123456789abcdef.md",
+            "Page 0123456789abcdef0123456789abcdef/Sub 0123456789abcdef0123456789abcdee.md",
+        ]);
+        let renames = Renames::new(&files);
+
+        assert_eq!(
+            joined(&renames, "Page 0123456789abcdef0123456789abcdef.md"),
+            "Page.md"
+        );
+        assert_eq!(
+            joined(
+                &renames,
+                "Page 0123456789abcdef0123456789abcdef/Sub 0123456789abcdef0123456789abcdee.md"
+            ),
+            "Page/Sub.md"
+        );
+    }
+
+    #[test]
+    fn renames_never_collide() {
+        let paths = [
+            "Page 0123456789abcdef0123456789abcdef.md",
+            "Page 0123456789abcdef0123456789abcdee.md",
+            "Page.md",
+            "Page 0123456789abcdef0123456789abcded.md",
+        ];
+        let renames = Renames::new(&files(&paths));
+
+        let targets = paths
+            .iter()
+            .map(|path| joined(&renames, path))
+            .collect::<HashSet<_>>();
+        assert_eq!(targets.len(), paths.len());
+        assert_eq!(
+            joined(&renames, "Page 0123456789abcdef0123456789abcdef.md"),
+            "Page.md"
+        );
+        assert_eq!(
+            joined(&renames, "Page 0123456789abcdef0123456789abcdee.md"),
+            "Page 0123456789abcdef0123456789abcdee.md"
+        );
+        assert_eq!(joined(&renames, "Page.md"), "Page (2).md");
+    }
+
+    #[test]
+    fn read_files_unpacks_nested_zips() {
+        let nested = archive(&[TestEntry::stored("b.md", b"# B")]);
+        let outer = archive(&[
+            TestEntry::stored("export/a.md", b"# A"),
+            TestEntry::stored("export/part.zip", &nested),
+            TestEntry::stored("__MACOSX/a.md", b""),
+        ]);
+
+        let files = read_files(&outer, 1024).unwrap();
+        let paths = files
+            .iter()
+            .map(|file| file.path.join("/"))
+            .collect::<Vec<_>>();
+
+        assert_eq!(paths, ["export/a.md", "export/b.md"]);
+    }
+
+    #[test]
+    fn nested_zips_share_the_budget() {
+        let data = [b'x'; 400];
+        let nested = archive(&[TestEntry::deflated("a.md", &data)]);
+        let outer = archive(&[
+            TestEntry::stored("one.zip", &nested),
+            TestEntry::stored("two.zip", &nested),
+            TestEntry::stored("three.zip", &nested),
+        ]);
+
+        // Each nested zip fits on its own, but not all of them together
+        let each = nested.len() as u64 + data.len() as u64;
+        assert!(read_files(&outer, each * 3).is_ok());
+        assert!(read_files(&outer, each * 2).is_err());
+    }
+}
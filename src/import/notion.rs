@@ -0,0 +1,140 @@
+//! Quirks of Notion's markdown exports. Every page and database is suffixed with its ID,
+//! e.g. `Meeting notes 3f1c0a9e5b2d4c7a8e6f1b0d9c2a4e7f.md`, with its subpages in a directory
+//! named the same, and databases are exported as CSV tables next to a directory of their rows.
+
+/// Characters not allowed in file names on some platforms.
+const RESERVED: [char; 9] = ['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+
+/// The name without the Notion ID suffix and with reserved characters replaced,
+/// or the name as is if nothing would be left of it.
+pub fn normalize_name(name: &str) -> String {
+    let (stem, extension) = match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() && !extension.contains(' ') => {
+            (stem, Some(extension))
+        }
+        _ => (name, None),
+    };
+
+    let stem = strip_id(stem)
+        .chars()
+        .map(|c| {
+            if RESERVED.contains(&c) || c.is_control() {
+                '-'
+            } else {
+                c
+            }
+        })
+        .collect::<String>();
+    let stem = stem.trim().trim_end_matches('.');
+
+    if stem.is_empty() {
+        return name.to_string();
+    }
+
+    match extension {
+        Some(extension) => format!("{stem}.{extension}"),
+        None => stem.to_string(),
+    }
+}
+
+/// Remove a trailing ` <32 hex digits>` ID, as well as the `_all` Notion appends
+/// to the CSV of databases with all of their rows.
+fn strip_id(stem: &str) -> &str {
+    let stem = stem.strip_suffix("_all").unwrap_or(stem);
+    match stem.rsplit_once(' ') {
+        Some((name, id)) if id.len() == 32 && id.chars().all(|c| c.is_ascii_hexdigit()) => name,
+        _ => stem,
+    }
+}
+
+/// Whether the CSV contains all rows of its database. Notion exports these next to
+/// the CSV of the database's current view, which is dropped in their favour.
+pub fn is_full_database(name: &str) -> bool {
+    name.strip_suffix(".csv")
+        .is_some_and(|stem| stem.ends_with("_all"))
+}
+
+/// Encode the characters that end a markdown link destination.
+pub fn encode_link(path: &str) -> String {
+    path.replace('%', "%25")
+        .replace(' ', "%20")
+        .replace('(', "%28")
+        .replace(')', "%29")
+}
+
+/// A markdown document titled `title` with the CSV as a table. Cells of the first column
+/// are linked with `link`, which returns the link to the page of the row if there is one.
+pub fn csv_to_markdown(title: &str, csv: &str, link: impl Fn(&str) -> Option<String>) -> String {
+    let rows = parse_csv(csv.trim_start_matches('\u{feff}'));
+    let mut out = format!("# {title}\n");
+
+    let Some((header, rows)) = rows.split_first() else {
+        return out;
+    };
+
+    let columns = header.len().max(1);
+    let line = |cells: Vec<String>| {
+        let mut cells = cells;
+        cells.resize(columns, String::new());
+        format!("| {} |\n", cells.join(" | "))
+    };
+
+    out.push('\n');
+    out.push_str(&line(header.iter().map(|cell| escape_cell(cell)).collect()));
+    out.push_str(&line(vec!["---".to_string(); columns]));
+
+    for row in rows {
+        let cells = row
+            .iter()
+            .enumerate()
+            .map(|(i, cell)| match (i, link(cell)) {
+                (0, Some(link)) => format!("[{}]({link})", escape_cell(cell)),
+                _ => escape_cell(cell),
+            })
+            .collect();
+        out.push_str(&line(cells));
+    }
+
+    out
+}
+
+fn escape_cell(cell: &str) -> String {
+    cell.trim()
+        .replace('|', "\\|")
+        .replace("\r\n", "<br>")
+        .replace('\n', "<br>")
+}
+
+/// Rows of RFC 4180 CSV, with quoted fields spanning lines and `""` escaping quotes.
+fn parse_csv(csv: &str) -> Vec<Vec<String>> {
+    let mut rows = vec![];
+    let mut row = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = csv.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            ('"', true) => quoted = false,
+            ('"', false) if field.is_empty() => quoted = true,
+            (',', false) => row.push(std::mem::take(&mut field)),
+            ('\r', false) if chars.peek() == Some(&'\n') => {}
+            ('\n', false) => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            (c, _) => field.push(c),
+        }
+    }
+
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+
+    rows
+}
@@ -0,0 +1,232 @@
+//! Reading zip archives into memory, taking the sizes of the files from a budget so
+//! archives within archives cannot unpack to more than the limit either.
+
+use crate::error::LedgeknawError;
+use std::io::{Cursor, Read};
+
+/// A file in the archive. Directories are not listed.
+pub struct ZipEntry {
+    /// Path in the archive, `/` separated
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+/// Read the files of the archive, taking their sizes from `budget` and failing once
+/// it runs out. Archives within the archive can be read with what is left of it.
+pub fn read(archive: &[u8], budget: &mut u64) -> Result<Vec<ZipEntry>, LedgeknawError> {
+    let mut archive = ::zip::ZipArchive::new(Cursor::new(archive)).map_err(invalid)?;
+    let mut entries = vec![];
+
+    for i in 0..archive.len() {
+        let file = archive.by_index(i).map_err(invalid)?;
+        if file.is_dir() {
+            continue;
+        }
+
+        let name = file.name().to_string();
+        let size = file.size();
+
+        *budget = budget
+            .checked_sub(size)
+            .ok_or_else(|| invalid("unpacks to more than the size limit"))?;
+
+        // Read one byte past the declared size to catch archives lying about it.
+        // The checksum is verified once the file is read to its end.
+        let mut data = Vec::with_capacity(size as usize);
+        file.take(size + 1)
+            .read_to_end(&mut data)
+            .map_err(|e| invalid(format!("{name}: {e}")))?;
+
+        if data.len() as u64 != size {
+            return Err(invalid(format!("{name}: corrupt data")));
+        }
+
+        entries.push(ZipEntry { name, data });
+    }
+
+    Ok(entries)
+}
+
+fn invalid(message: impl std::fmt::Display) -> LedgeknawError {
+    LedgeknawError::InvalidParameter(format!("invalid zip: {message}"))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use flate2::{write::DeflateEncoder, Compression};
+    use std::io::Write;
+
+    const LOCAL_HEADER: u32 = 0x04034b50;
+    const CENTRAL_HEADER: u32 = 0x02014b50;
+    const END_OF_CENTRAL_DIRECTORY: u32 = 0x06054b50;
+
+    const STORED: u16 = 0;
+    const DEFLATED: u16 = 8;
+
+    pub(crate) struct TestEntry<'a> {
+        pub name: &'a str,
+        pub data: &'a [u8],
+        pub deflate: bool,
+        /// Written instead of the actual size
+        pub size: Option<u32>,
+        /// Written instead of the actual CRC
+        pub crc: Option<u32>,
+    }
+
+    impl<'a> TestEntry<'a> {
+        pub fn stored(name: &'a str, data: &'a [u8]) -> Self {
+            Self {
+                name,
+                data,
+                deflate: false,
+                size: None,
+                crc: None,
+            }
+        }
+
+        pub fn deflated(name: &'a str, data: &'a [u8]) -> Self {
+            Self {
+                deflate: true,
+                ..Self::stored(name, data)
+            }
+        }
+    }
+
+    /// A zip archive of the entries, written by hand so the sizes and checksums can lie.
+    pub(crate) fn archive(entries: &[TestEntry]) -> Vec<u8> {
+        let mut out = vec![];
+        let mut central = vec![];
+
+        for entry in entries {
+            let raw = if entry.deflate {
+                let mut encoder = DeflateEncoder::new(vec![], Compression::default());
+                encoder.write_all(entry.data).unwrap();
+                encoder.finish().unwrap()
+            } else {
+                entry.data.to_vec()
+            };
+            let method = if entry.deflate { DEFLATED } else { STORED };
+            let crc = entry.crc.unwrap_or_else(|| crc32fast::hash(entry.data));
+            let size = entry.size.unwrap_or(entry.data.len() as u32);
+            let offset = out.len() as u32;
+
+            out.extend(LOCAL_HEADER.to_le_bytes());
+            out.extend([20, 0, 0, 0]);
+            out.extend(method.to_le_bytes());
+            out.extend([0; 4]);
+            out.extend(crc.to_le_bytes());
+            out.extend((raw.len() as u32).to_le_bytes());
+            out.extend(size.to_le_bytes());
+            out.extend((entry.name.len() as u16).to_le_bytes());
+            out.extend([0; 2]);
+            out.extend(entry.name.as_bytes());
+            out.extend(&raw);
+
+            central.extend(CENTRAL_HEADER.to_le_bytes());
+            central.extend([20, 0, 20, 0, 0, 0]);
+            central.extend(method.to_le_bytes());
+            central.extend([0; 4]);
+            central.extend(crc.to_le_bytes());
+            central.extend((raw.len() as u32).to_le_bytes());
+            central.extend(size.to_le_bytes());
+            central.extend((entry.name.len() as u16).to_le_bytes());
+            central.extend([0; 12]);
+            central.extend(offset.to_le_bytes());
+            central.extend(entry.name.as_bytes());
+        }
+
+        let central_offset = out.len() as u32;
+        out.extend(&central);
+        out.extend(END_OF_CENTRAL_DIRECTORY.to_le_bytes());
+        out.extend([0; 4]);
+        out.extend((entries.len() as u16).to_le_bytes());
+        out.extend((entries.len() as u16).to_le_bytes());
+        out.extend((central.len() as u32).to_le_bytes());
+        out.extend(central_offset.to_le_bytes());
+        out.extend([0; 2]);
+        out
+    }
+
+    fn read_all(archive: &[u8]) -> Result<Vec<ZipEntry>, LedgeknawError> {
+        let mut budget = u64::MAX;
+        read(archive, &mut budget)
+    }
+
+    #[test]
+    fn reads_stored_and_deflated_entries() {
+        let archive = archive(&[
+            TestEntry::stored("dir/", b""),
+            TestEntry::stored("a.md", b"# A"),
+            TestEntry::deflated("dir/b.md", b"# B, deflated"),
+        ]);
+
+        let entries = read_all(&archive).unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].name, "a.md");
+        assert_eq!(entries[0].data, b"# A");
+        assert_eq!(entries[1].name, "dir/b.md");
+        assert_eq!(entries[1].data, b"# B, deflated");
+    }
+
+    #[test]
+    fn rejects_understated_sizes() {
+        let archive = archive(&[TestEntry {
+            size: Some(4),
+            ..TestEntry::deflated("a.md", b"much more than four bytes")
+        }]);
+
+        assert!(read_all(&archive).is_err());
+    }
+
+    #[test]
+    fn rejects_overstated_sizes() {
+        let archive = archive(&[TestEntry {
+            size: Some(1000),
+            ..TestEntry::deflated("a.md", b"short")
+        }]);
+
+        assert!(read_all(&archive).is_err());
+    }
+
+    #[test]
+    fn rejects_crc_mismatch() {
+        let stored = archive(&[TestEntry {
+            crc: Some(1),
+            ..TestEntry::stored("a.md", b"# A")
+        }]);
+        let deflated = archive(&[TestEntry {
+            crc: Some(1),
+            ..TestEntry::deflated("a.md", b"# A")
+        }]);
+
+        assert!(read_all(&stored).is_err());
+        assert!(read_all(&deflated).is_err());
+    }
+
+    #[test]
+    fn takes_sizes_from_the_budget() {
+        let archive = archive(&[
+            TestEntry::stored("a.md", &[b'a'; 60]),
+            TestEntry::deflated("b.md", &[b'b'; 60]),
+        ]);
+
+        let mut budget = 200;
+        read(&archive, &mut budget).unwrap();
+        assert_eq!(budget, 80);
+
+        let mut budget = 100;
+        assert!(read(&archive, &mut budget).is_err());
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        assert!(read_all(b"").is_err());
+        assert!(read_all(b"not a zip at all").is_err());
+
+        let mut truncated = archive(&[TestEntry::stored("a.md", b"# A")]);
+        truncated.drain(10..20);
+        assert!(read_all(&truncated).is_err());
+    }
+}
@@ -0,0 +1,162 @@
+use crate::{error::LedgeknawError, state::DocumentService};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::time::Duration;
+use tracing::{debug, error, info, warn};
+
+pub mod db;
+
+/// The kind of the job checking the links of all documents.
+pub const LINK_CHECK: &str = "link_check";
+
+/// The kind of the job pulling the repositories of the roots and syncing,
+/// queued by webhooks, imports, restores and `/admin/sync`.
+pub const SYNC: &str = "sync";
+
+/// The kind of the job re-reading the metadata of all documents, queued by `/admin/reindex`.
+pub const REINDEX: &str = "reindex";
+
+/// Background work persisted in the database.
+#[derive(Debug, Serialize)]
+pub struct Job {
+    pub id: uuid::Uuid,
+    /// What the job does, e.g. `ocr` or `transcript`
+    pub kind: String,
+    pub status: String,
+    /// The document the job operates on, if any
+    pub document: Option<uuid::Uuid>,
+    /// Present if the last attempt failed
+    pub error: Option<String>,
+    pub attempts: i32,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobStatus {
+    Pending,
+    Running,
+    Failed,
+    Done,
+    Cancelled,
+}
+
+impl std::str::FromStr for JobStatus {
+    type Err = LedgeknawError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(Self::Pending),
+            "running" => Ok(Self::Running),
+            "failed" => Ok(Self::Failed),
+            "done" => Ok(Self::Done),
+            "cancelled" => Ok(Self::Cancelled),
+            _ => Err(LedgeknawError::InvalidParameter(format!(
+                "unknown job status {s}"
+            ))),
+        }
+    }
+}
+
+impl JobStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Running => "running",
+            Self::Failed => "failed",
+            Self::Done => "done",
+            Self::Cancelled => "cancelled",
+        }
+    }
+}
+
+/// Process pending jobs one at a time, waiting for new ones when the queue is empty.
+/// Returns once the running job is done after a shutdown is requested, leaving
+/// the pending ones for the next start.
+pub async fn run_worker(service: DocumentService) {
+    match service.jobs.requeue_running().await {
+        Ok(0) => {}
+        Ok(count) => info!("Requeued {count} interrupted jobs"),
+        Err(e) => error!("Error while requeueing interrupted jobs: {e}"),
+    }
+
+    while !service.shutdown.is_requested() {
+        let job = match service.jobs.claim_next().await {
+            Ok(Some(job)) => job,
+            Ok(None) => {
+                tokio::select! {
+                    _ = service.job_notify.notified() => {}
+                    _ = service.shutdown.clone().requested() => {}
+                }
+                continue;
+            }
+            Err(e) => {
+                error!("Error while claiming job: {e}");
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                continue;
+            }
+        };
+
+        debug!("Running job {} ({})", job.id, job.kind);
+
+        let result = service.run_job(&job).await;
+
+        let update = match result {
+            Ok(()) => service.jobs.complete(job.id).await,
+            Err(e) => {
+                warn!("Job {} ({}) failed: {e}", job.id, job.kind);
+                service.jobs.fail(job.id, &e.to_string()).await
+            }
+        };
+
+        if let Err(e) = update {
+            error!("Error while updating job {}: {e}", job.id);
+        }
+    }
+}
+
+impl DocumentService {
+    /// Queue a job and wake up the worker. Returns the ID of the job, which is
+    /// the one already queued if the same job is pending.
+    pub async fn enqueue_job(
+        &self,
+        kind: &str,
+        document: Option<uuid::Uuid>,
+    ) -> Result<uuid::Uuid, LedgeknawError> {
+        let (id, inserted) = self.jobs.enqueue(kind, document).await?;
+        if inserted {
+            self.job_notify.notify_one();
+        }
+        Ok(id)
+    }
+
+    /// Queue a job for the whole instance and return it.
+    pub async fn queue_job(&self, kind: &str) -> Result<Job, LedgeknawError> {
+        let id = self.enqueue_job(kind, None).await?;
+        self.get_job(id).await
+    }
+
+    pub async fn get_job(&self, id: uuid::Uuid) -> Result<Job, LedgeknawError> {
+        match self.jobs.get(id).await? {
+            Some(job) => Ok(job),
+            None => Err(LedgeknawError::NotFound(format!("job {id}"))),
+        }
+    }
+
+    /// Requeue a failed or cancelled job.
+    pub async fn retry_job(&self, id: uuid::Uuid) -> Result<(), LedgeknawError> {
+        if !self.jobs.retry(id).await? {
+            return Err(LedgeknawError::NotFound(format!("{id}: no failed job")));
+        }
+        self.job_notify.notify_one();
+        Ok(())
+    }
+
+    /// Cancel a pending job. Running jobs cannot be cancelled.
+    pub async fn cancel_job(&self, id: uuid::Uuid) -> Result<(), LedgeknawError> {
+        if !self.jobs.cancel(id).await? {
+            return Err(LedgeknawError::NotFound(format!("{id}: no pending job")));
+        }
+        Ok(())
+    }
+}
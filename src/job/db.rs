@@ -0,0 +1,187 @@
+use super::{Job, JobStatus};
+use crate::error::LedgeknawError;
+use sqlx::PgPool;
+
+#[derive(Debug, Clone)]
+pub struct JobDb {
+    pool: sqlx::PgPool,
+}
+
+impl JobDb {
+    pub async fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Insert a pending job, unless the same job is already pending. A running job does
+    /// not count, as it may have started before whatever the job is queued for, so at
+    /// most one of the same job waits behind it. Returns the ID of the inserted or
+    /// existing job and whether it was inserted.
+    pub async fn enqueue(
+        &self,
+        kind: &str,
+        document: Option<uuid::Uuid>,
+    ) -> Result<(uuid::Uuid, bool), LedgeknawError> {
+        // The existing jobs are read from before the insert
+        let job = sqlx::query!(
+            r#"
+            WITH existing AS (
+                SELECT id FROM jobs
+                WHERE kind = $1
+                AND document IS NOT DISTINCT FROM $2
+                AND status = 'pending'
+                LIMIT 1
+            ),
+            inserted AS (
+                INSERT INTO jobs(kind, document)
+                SELECT $1, $2
+                WHERE NOT EXISTS (SELECT id FROM existing)
+                RETURNING id
+            )
+            SELECT id AS "id!", TRUE AS "inserted!" FROM inserted
+            UNION ALL
+            SELECT id, FALSE FROM existing
+        "#,
+            kind,
+            document
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok((job.id, job.inserted))
+    }
+
+    /// Mark the oldest pending job as running and return it.
+    pub async fn claim_next(&self) -> Result<Option<Job>, LedgeknawError> {
+        sqlx::query_as!(
+            Job,
+            r#"
+            UPDATE jobs SET status = $1, attempts = attempts + 1, error = NULL
+            WHERE id = (
+                SELECT id FROM jobs WHERE status = $2
+                ORDER BY created_at
+                LIMIT 1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING *
+        "#,
+            JobStatus::Running.as_str(),
+            JobStatus::Pending.as_str()
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(LedgeknawError::from)
+    }
+
+    pub async fn complete(&self, id: uuid::Uuid) -> Result<(), LedgeknawError> {
+        self.set_status(id, JobStatus::Done, None).await
+    }
+
+    pub async fn fail(&self, id: uuid::Uuid, error: &str) -> Result<(), LedgeknawError> {
+        self.set_status(id, JobStatus::Failed, Some(error)).await
+    }
+
+    async fn set_status(
+        &self,
+        id: uuid::Uuid,
+        status: JobStatus,
+        error: Option<&str>,
+    ) -> Result<(), LedgeknawError> {
+        sqlx::query!(
+            "UPDATE jobs SET status = $1, error = $2 WHERE id = $3",
+            status.as_str(),
+            error,
+            id
+        )
+        .execute(&self.pool)
+        .await
+        .map(|_| ())
+        .map_err(LedgeknawError::from)
+    }
+
+    /// Jobs left running are the ones interrupted by a shutdown.
+    pub async fn requeue_running(&self) -> Result<u64, LedgeknawError> {
+        Ok(sqlx::query!(
+            "UPDATE jobs SET status = $1 WHERE status = $2",
+            JobStatus::Pending.as_str(),
+            JobStatus::Running.as_str()
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected())
+    }
+
+    /// Returns whether a failed or cancelled job with the ID was requeued.
+    pub async fn retry(&self, id: uuid::Uuid) -> Result<bool, LedgeknawError> {
+        Ok(sqlx::query!(
+            "UPDATE jobs SET status = $1 WHERE id = $2 AND status IN ($3, $4)",
+            JobStatus::Pending.as_str(),
+            id,
+            JobStatus::Failed.as_str(),
+            JobStatus::Cancelled.as_str()
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected()
+            > 0)
+    }
+
+    /// Returns whether a pending job with the ID was cancelled.
+    pub async fn cancel(&self, id: uuid::Uuid) -> Result<bool, LedgeknawError> {
+        Ok(sqlx::query!(
+            "UPDATE jobs SET status = $1 WHERE id = $2 AND status = $3",
+            JobStatus::Cancelled.as_str(),
+            id,
+            JobStatus::Pending.as_str()
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected()
+            > 0)
+    }
+
+    /// List the pending, running and failed jobs.
+    pub async fn list_queue(&self) -> Result<Vec<Job>, LedgeknawError> {
+        sqlx::query_as!(
+            Job,
+            "SELECT * FROM jobs WHERE status IN ($1, $2, $3) ORDER BY created_at",
+            JobStatus::Pending.as_str(),
+            JobStatus::Running.as_str(),
+            JobStatus::Failed.as_str()
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(LedgeknawError::from)
+    }
+
+    pub async fn get(&self, id: uuid::Uuid) -> Result<Option<Job>, LedgeknawError> {
+        sqlx::query_as!(Job, "SELECT * FROM jobs WHERE id = $1", id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(LedgeknawError::from)
+    }
+
+    /// List the most recent jobs in any status, newest first, optionally only
+    /// those in the status or of the kind.
+    pub async fn list(
+        &self,
+        status: Option<JobStatus>,
+        kind: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<Job>, LedgeknawError> {
+        sqlx::query_as!(
+            Job,
+            r#"
+            SELECT * FROM jobs
+            WHERE ($1::TEXT IS NULL OR status = $1)
+            AND ($2::TEXT IS NULL OR kind = $2)
+            ORDER BY created_at DESC
+            LIMIT $3
+        "#,
+            status.map(|status| status.as_str()),
+            kind,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(LedgeknawError::from)
+    }
+}
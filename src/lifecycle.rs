@@ -0,0 +1,193 @@
+//! Orderly shutdown on SIGINT, SIGTERM or when the Windows service is stopped.
+//! Subsystems are stopped one stage at a time, in the order they depend on each other,
+//! e.g. requests are no longer served once the job queue is drained and the database pool
+//! is closed. A stage that does not finish in time is logged and left behind.
+
+use crate::service;
+use std::{future::Future, pin::Pin, time::Duration};
+use tokio::{sync::watch, time::Instant};
+use tracing::{info, warn};
+
+/// Tells subsystems that a shutdown was requested.
+#[derive(Debug, Clone)]
+pub struct Shutdown(watch::Receiver<bool>);
+
+impl Shutdown {
+    /// Completes once a shutdown is requested.
+    pub async fn requested(mut self) {
+        // Only fails if the lifecycle was dropped, which never shuts down
+        if self.0.wait_for(|requested| *requested).await.is_err() {
+            std::future::pending::<()>().await;
+        }
+    }
+
+    pub fn is_requested(&self) -> bool {
+        *self.0.borrow()
+    }
+}
+
+struct Stage {
+    name: &'static str,
+    timeout: Duration,
+    stop: Pin<Box<dyn Future<Output = ()> + Send>>,
+}
+
+pub struct Lifecycle {
+    requested: watch::Sender<bool>,
+    stages: Vec<Stage>,
+}
+
+impl Default for Lifecycle {
+    fn default() -> Self {
+        Self {
+            requested: watch::channel(false).0,
+            stages: vec![],
+        }
+    }
+}
+
+impl Lifecycle {
+    pub fn shutdown(&self) -> Shutdown {
+        Shutdown(self.requested.subscribe())
+    }
+
+    /// Add a stage run once the stages added before it are done or timed out.
+    pub fn stage(
+        &mut self,
+        name: &'static str,
+        timeout: Duration,
+        stop: impl Future<Output = ()> + Send + 'static,
+    ) {
+        self.stages.push(Stage {
+            name,
+            timeout,
+            stop: Box::pin(stop),
+        });
+    }
+
+    /// Wait for a signal to shut down, then run the stages in order.
+    pub async fn run(self) {
+        terminated().await;
+
+        info!("Shutting down");
+        self.requested.send_replace(true);
+
+        for Stage {
+            name,
+            timeout,
+            stop,
+        } in self.stages
+        {
+            let start = Instant::now();
+            match tokio::time::timeout(timeout, stop).await {
+                Ok(()) => info!("Stopped {name} in {}ms", start.elapsed().as_millis()),
+                Err(_) => warn!("Stopping {name} timed out after {timeout:?}, moving on"),
+            }
+        }
+
+        info!("Shut down");
+    }
+}
+
+/// Completes on the first SIGINT or SIGTERM, or when the service is asked to stop.
+/// Signals received after are handled as usual, so a second one ends the process.
+async fn terminated() {
+    tokio::select! {
+        _ = signal::received() => {}
+        _ = service::stopping() => {}
+    }
+}
+
+#[cfg(unix)]
+mod signal {
+    use std::sync::atomic::{AtomicI32, Ordering};
+    use tokio::sync::oneshot;
+    use tracing::error;
+
+    const SIGNALS: [libc::c_int; 2] = [libc::SIGINT, libc::SIGTERM];
+
+    /// Written to by the signal handler, read by the thread waiting for signals
+    static PIPE: AtomicI32 = AtomicI32::new(-1);
+
+    extern "C" fn handle(_: libc::c_int) {
+        let fd = PIPE.load(Ordering::SeqCst);
+        // SAFETY: write is async-signal-safe, the byte outlives the call
+        unsafe { libc::write(fd, [0u8].as_ptr().cast(), 1) };
+    }
+
+    pub async fn received() {
+        let mut fds = [-1; 2];
+        // SAFETY: The array has room for both descriptors
+        if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+            error!(
+                "Could not handle signals: {}",
+                std::io::Error::last_os_error()
+            );
+            return std::future::pending().await;
+        }
+        let [read, write] = fds;
+        PIPE.store(write, Ordering::SeqCst);
+
+        for signal in SIGNALS {
+            // SAFETY: The handler only does what is safe in signal handlers
+            unsafe {
+                libc::signal(
+                    signal,
+                    handle as extern "C" fn(libc::c_int) as libc::sighandler_t,
+                )
+            };
+        }
+
+        // Blocking reads are left to a thread, which the runtime does not wait for
+        let (tx, rx) = oneshot::channel();
+        std::thread::spawn(move || {
+            let mut byte = 0u8;
+            // SAFETY: The byte outlives the call
+            unsafe { libc::read(read, (&mut byte as *mut u8).cast(), 1) };
+            for signal in SIGNALS {
+                // SAFETY: Restores the default disposition
+                unsafe { libc::signal(signal, libc::SIG_DFL) };
+            }
+            let _ = tx.send(());
+        });
+
+        if rx.await.is_err() {
+            std::future::pending().await
+        }
+    }
+}
+
+#[cfg(windows)]
+mod signal {
+    use std::sync::OnceLock;
+    use tokio::sync::Notify;
+    use tracing::error;
+    use windows_sys::Win32::{Foundation::BOOL, System::Console::SetConsoleCtrlHandler};
+
+    static RECEIVED: OnceLock<Notify> = OnceLock::new();
+
+    fn received_notify() -> &'static Notify {
+        RECEIVED.get_or_init(Notify::new)
+    }
+
+    unsafe extern "system" fn handle(_: u32) -> BOOL {
+        received_notify().notify_one();
+        1
+    }
+
+    pub async fn received() {
+        // SAFETY: The handler is a function valid for the lifetime of the process
+        if unsafe { SetConsoleCtrlHandler(Some(handle), 1) } == 0 {
+            error!(
+                "Could not handle Ctrl+C: {}",
+                std::io::Error::last_os_error()
+            );
+            return std::future::pending().await;
+        }
+
+        received_notify().notified().await;
+
+        // SAFETY: Removes the handler added above
+        unsafe { SetConsoleCtrlHandler(Some(handle), 0) };
+    }
+}
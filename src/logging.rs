@@ -0,0 +1,205 @@
+//! Where and how the logs are written. Events are formatted as text or as a JSON
+//! object per line, filtered by module and written to the console or a file
+//! rotated by the hour or day with tracing-appender.
+
+use crate::{
+    config::{LogConfig, LogFileConfig, LogFormat, LogRotation},
+    error::LedgeknawError,
+};
+use chrono::{SecondsFormat, Utc};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::{io, path::Path};
+use tracing::{field::Field, Event, Level, Subscriber};
+use tracing_appender::{
+    non_blocking::WorkerGuard,
+    rolling::{RollingFileAppender, Rotation},
+};
+use tracing_subscriber::{
+    filter::Targets,
+    fmt::{
+        format::Writer, writer::BoxMakeWriter, FmtContext, FormatEvent, FormatFields,
+        FormattedFields,
+    },
+    layer::SubscriberExt,
+    registry::LookupSpan,
+    util::SubscriberInitExt,
+    Layer,
+};
+
+/// Install the global subscriber. Services have no console, so they log to
+/// `service_log` unless a file is configured. Files are written on a background
+/// thread, which flushes the remaining entries when the returned guard is dropped.
+pub fn init(
+    level: Level,
+    config: LogConfig,
+    service_log: Option<String>,
+) -> Result<Option<WorkerGuard>, LedgeknawError> {
+    let filter = match &config.filter {
+        Some(filter) => filter.parse::<Targets>().map_err(|e| {
+            LedgeknawError::InvalidParameter(format!("invalid log filter {filter}: {e}"))
+        })?,
+        None => Targets::new().with_default(level),
+    };
+
+    let file = config.file.or_else(|| {
+        service_log.map(|path| LogFileConfig {
+            path,
+            rotation: LogRotation::Never,
+            max_files: None,
+        })
+    });
+
+    // Colors are only for text on the console, span fields would otherwise contain them in JSON
+    let ansi = file.is_none() && config.format == LogFormat::Text;
+    let (writer, guard) = match file {
+        Some(file) => {
+            let (writer, guard) = tracing_appender::non_blocking(appender(file)?);
+            (BoxMakeWriter::new(writer), Some(guard))
+        }
+        None => (BoxMakeWriter::new(io::stdout), None),
+    };
+
+    let layer = tracing_subscriber::fmt::layer()
+        .with_writer(writer)
+        .with_ansi(ansi);
+    let layer = match config.format {
+        LogFormat::Text => layer.boxed(),
+        LogFormat::Json => layer.event_format(JsonFormat).boxed(),
+    };
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(layer)
+        .init();
+    Ok(guard)
+}
+
+/// Writes to `<path>.<period>` when rotated, to the path as is otherwise.
+fn appender(config: LogFileConfig) -> Result<RollingFileAppender, LedgeknawError> {
+    let path = Path::new(&config.path);
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let Some(name) = path.file_name() else {
+        return Err(LedgeknawError::InvalidParameter(format!(
+            "invalid log file {}",
+            config.path
+        )));
+    };
+
+    let rotation = match config.rotation {
+        LogRotation::Hourly => Rotation::HOURLY,
+        LogRotation::Daily => Rotation::DAILY,
+        LogRotation::Never => Rotation::NEVER,
+    };
+
+    let mut builder = RollingFileAppender::builder()
+        .rotation(rotation)
+        .filename_prefix(name.to_string_lossy());
+    if let Some(max_files) = config.max_files {
+        builder = builder.max_log_files(max_files);
+    }
+
+    builder.build(directory).map_err(|e| {
+        LedgeknawError::InvalidParameter(format!("invalid log file {}: {e}", config.path))
+    })
+}
+
+/// Formats events as a [JsonLine] each.
+struct JsonFormat;
+
+#[derive(Serialize)]
+struct JsonLine<'a> {
+    timestamp: String,
+    level: &'static str,
+    target: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<Value>,
+    #[serde(skip_serializing_if = "Map::is_empty")]
+    fields: Map<String, Value>,
+    /// From the outermost to the innermost
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    spans: Vec<JsonSpan>,
+}
+
+#[derive(Serialize)]
+struct JsonSpan {
+    name: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fields: Option<String>,
+}
+
+impl<S, N> FormatEvent<S, N> for JsonFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> std::fmt::Result {
+        let mut fields = JsonFields::default();
+        event.record(&mut fields);
+        let message = fields.0.remove("message");
+
+        // Fields of spans are only kept as formatted by the layer
+        let spans = ctx
+            .event_scope()
+            .into_iter()
+            .flat_map(|scope| scope.from_root())
+            .map(|span| JsonSpan {
+                name: span.name(),
+                fields: span
+                    .extensions()
+                    .get::<FormattedFields<N>>()
+                    .filter(|fields| !fields.is_empty())
+                    .map(|fields| fields.to_string()),
+            })
+            .collect();
+
+        let line = JsonLine {
+            timestamp: Utc::now().to_rfc3339_opts(SecondsFormat::Micros, true),
+            level: event.metadata().level().as_str(),
+            target: event.metadata().target(),
+            message,
+            fields: fields.0,
+            spans,
+        };
+        let line = serde_json::to_string(&line).map_err(|_| std::fmt::Error)?;
+        writeln!(writer, "{line}")
+    }
+}
+
+#[derive(Default)]
+struct JsonFields(Map<String, Value>);
+
+impl tracing::field::Visit for JsonFields {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), Value::from(format!("{value:?}")));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+}
@@ -1,61 +1,644 @@
 use clap::Parser;
+use sqlx::PgPool;
 use std::num::NonZeroUsize;
-use tracing::info;
+use std::{collections::HashMap, future::Future, net::SocketAddr, path::Path, time::Duration};
+use tokio::task::JoinHandle;
+use tokio_rustls::TlsAcceptor;
+use tracing::{error, info, warn};
 
 use crate::{
-    config::{Config, StartArgs},
-    document::db::DocumentDb,
-    state::DocumentService,
+    annotation::db::AnnotationDb,
+    archive::{db::ArchiveDb, dump},
+    auth::{db::AuthDb, Auth},
+    bookmark::db::BookmarkDb,
+    config::{
+        normalize_base_path, Command, Config, DatabaseConfig, MigrateCommand, ServiceCommand,
+        StartArgs, TlsConfig,
+    },
+    content::ContentStores,
+    document::{db::DocumentDb, ReadOptions},
+    feature::{db::FeatureDb, FeatureFlags},
+    idempotency::db::IdempotencyDb,
+    job::db::JobDb,
+    lifecycle::Lifecycle,
+    memory::MemoryService,
+    state::{Databases, DocumentService, Integrations, Settings},
+    telemetry::db::TelemetryDb,
+    views::{db::ViewsDb, Views},
 };
 
-pub const FILES_PER_THREAD: usize = 128;
+/// Time given to open connections to finish on shutdown
+const HTTP_SHUTDOWN: Duration = Duration::from_secs(10);
+
+/// Time given to background tasks and the fs watcher to stop on shutdown
+const TASKS_SHUTDOWN: Duration = Duration::from_secs(1);
+
+/// Time given to the running job to finish on shutdown. Jobs still running
+/// after are requeued on the next start.
+const JOBS_SHUTDOWN: Duration = Duration::from_secs(30);
+
+/// Time given to webhook deliveries to finish on shutdown
+const FLUSH_SHUTDOWN: Duration = Duration::from_secs(15);
+
+/// Time given to the database connections to close on shutdown
+const DB_SHUTDOWN: Duration = Duration::from_secs(5);
 
 lazy_static::lazy_static! {
-    pub static ref MAX_THREADS: usize = std::thread::available_parallelism().unwrap_or(NonZeroUsize::new(1).unwrap()).into();
+    /// Maximum amount of documents read from the fs at once when syncing
+    pub static ref MAX_CONCURRENT_READS: usize = std::thread::available_parallelism().unwrap_or(NonZeroUsize::new(1).unwrap()).into();
 }
 
+pub mod access_log;
+pub mod annotation;
+pub mod archive;
+pub mod assets;
+pub mod auth;
+pub mod bookmark;
+pub mod client_ip;
 pub mod config;
+pub mod content;
 pub mod db;
 pub mod document;
 pub mod error;
+pub mod events;
+pub mod feature;
+pub mod hook;
+pub mod idempotency;
+pub mod import;
+pub mod job;
+pub mod lifecycle;
+pub mod logging;
+pub mod memory;
+pub mod pages;
+pub mod rate_limit;
 pub mod router;
+pub mod service;
 pub mod state;
+pub mod systemd;
+pub mod telemetry;
+pub mod tls;
+pub mod update;
+pub mod views;
+pub mod warmup;
+pub mod webhook;
 
 #[tokio::main]
 async fn main() {
+    let args = StartArgs::parse();
+
+    if let Some(working_directory) = &args.windows_service {
+        if let Err(e) = service::start(working_directory) {
+            eprintln!("Could not start the service: {e}");
+            std::process::exit(1);
+        }
+    }
+
     dotenv::dotenv().ok();
     let StartArgs {
         config_path,
         address: host,
         port,
         log_level: level,
-    } = StartArgs::parse();
+        reindex,
+        no_db,
+        base_path: base_path_arg,
+        tls_cert,
+        tls_key,
+        windows_service,
+        command,
+    } = args.clone();
 
-    tracing_subscriber::fmt().with_max_level(level).init();
+    // Invalid configs are reported once logging is set up
+    let log = Path::new(&config_path)
+        .exists()
+        .then(|| Config::read(&config_path).ok())
+        .flatten()
+        .map(|config| config.log)
+        .unwrap_or_default();
+    // Services have no console to log to
+    let service_log = windows_service.map(|_| format!("{}.log", service::SERVICE_NAME));
+    // Flushes the log file when main returns
+    let _log_guard = match logging::init(level, log, service_log) {
+        Ok(guard) => guard,
+        Err(e) => {
+            eprintln!("Could not set up logging: {e}");
+            std::process::exit(1);
+        }
+    };
 
-    let db_url = std::env::var("DATABASE_URL").expect("DATABASE_URL not set");
-    let db_pool = db::create_pool(&db_url).await;
+    let addr = format!("{host}:{port}");
 
-    db::migrate(&db_pool).await;
+    if let Some(Command::Serve { path } | Command::Preview { path }) = &command {
+        let live_reload = matches!(command, Some(Command::Preview { .. }));
+        let config = Path::new(&config_path)
+            .exists()
+            .then(|| read_config(&config_path));
+        let name = Path::new(&path)
+            .canonicalize()
+            .ok()
+            .and_then(|path| Some(path.file_name()?.to_str()?.to_string()))
+            .unwrap_or_else(|| path.clone());
+        let directories = HashMap::from([(name, path.clone())]);
+        let (title, base_path, tls, read_options) = match config {
+            Some(config) => (
+                config.title,
+                config.base_path,
+                config.tls,
+                ReadOptions {
+                    normalize: config.normalize,
+                    reading_time: config.reading_time,
+                    compat: config.compat,
+                    max_depth: config.max_depth,
+                },
+            ),
+            None => (None, None, None, ReadOptions::default()),
+        };
+        let base_path = normalize_base_path(base_path_arg.or(base_path).as_deref());
+        let tls = tls_acceptor(tls_cert, tls_key, tls);
+        serve_without_db(
+            addr,
+            title,
+            base_path,
+            tls,
+            directories,
+            read_options,
+            live_reload,
+        )
+        .await;
+        return;
+    }
 
-    let addr = format!("{host}:{port}");
+    if let Some(Command::Service { action }) = &command {
+        let result = match action {
+            ServiceCommand::Install => service::install(&args),
+            ServiceCommand::Uninstall => service::uninstall(),
+        };
+        if let Err(e) = result {
+            error!("{e}");
+            std::process::exit(1);
+        }
+        return;
+    }
 
-    let Config { title, directories } = Config::read(config_path).expect("invalid config file");
+    if let Some(Command::Migrate { action }) = command {
+        let Config { database, .. } = read_config(&config_path);
+        let db = ArchiveDb::new(connect(&database).await).await;
+        let result = match action {
+            MigrateCommand::Export { path, content } => archive::export(&db, &path, content).await,
+            MigrateCommand::Import { path } => archive::import(&db, &path).await,
+        };
+        if let Err(e) = result {
+            error!("{e}");
+            std::process::exit(1);
+        }
+        return;
+    }
 
-    let document_db = DocumentDb::new(db_pool.clone()).await;
+    if let Some(Command::Dump { .. } | Command::Restore { .. }) = command {
+        let Config { database, .. } = read_config(&config_path);
+        let db = ArchiveDb::new(connect(&database).await).await;
+        let result = match command {
+            Some(Command::Dump { path, format }) => dump::dump_to_file(&db, &path, format).await,
+            Some(Command::Restore {
+                path,
+                format,
+                replace,
+            }) => dump::restore_from_file(&db, &path, format, replace).await,
+            _ => unreachable!(),
+        };
+        if let Err(e) = result {
+            error!("{e}");
+            std::process::exit(1);
+        }
+        return;
+    }
 
-    let documents = DocumentService::new(document_db.clone(), title, directories);
-    documents.sync().await.expect("error in state sync");
+    if no_db {
+        let Config {
+            title,
+            base_path,
+            tls,
+            directories,
+            normalize,
+            reading_time,
+            compat,
+            max_depth,
+            ..
+        } = read_config(&config_path);
+        let directories = directories
+            .into_iter()
+            .filter(|(name, root)| {
+                if root.private() {
+                    warn!("Skipping private directory {name}");
+                }
+                if root.s3().is_some() {
+                    warn!("Skipping S3 directory {name}, which is only pulled with a database");
+                }
+                !root.private() && root.s3().is_none()
+            })
+            .map(|(name, root)| (name, root.path().to_string()))
+            .collect();
+        let read_options = ReadOptions {
+            normalize,
+            reading_time,
+            compat,
+            max_depth,
+        };
+        let base_path = normalize_base_path(base_path_arg.or(base_path).as_deref());
+        let tls = tls_acceptor(tls_cert, tls_key, tls);
+        serve_without_db(
+            addr,
+            title,
+            base_path,
+            tls,
+            directories,
+            read_options,
+            false,
+        )
+        .await;
+        return;
+    }
 
-    info!("Now listening on {addr}");
+    let Config {
+        title,
+        base_path,
+        public_url,
+        trusted_proxies,
+        tls,
+        directories,
+        api_keys,
+        normalize,
+        reading_time,
+        compat,
+        max_depth,
+        ocr,
+        transcriber,
+        translator,
+        git,
+        hooks,
+        link_check,
+        updates,
+        robots,
+        warmup,
+        webhooks,
+        telemetry,
+        views,
+        database,
+        access_log,
+        log: _,
+        limits,
+        cors,
+        flags,
+        collections,
+    } = read_config(&config_path);
 
-    let listener = tokio::net::TcpListener::bind(addr)
+    let base_path = normalize_base_path(base_path_arg.or(base_path).as_deref());
+    let tls = tls_acceptor(tls_cert, tls_key, tls);
+    let cors = match router::cors_layer(&cors) {
+        Ok(cors) => cors,
+        Err(e) => {
+            error!("{e}");
+            std::process::exit(1);
+        }
+    };
+    let content = match ContentStores::new(&directories) {
+        Ok(content) => content,
+        Err(e) => {
+            error!("{e}");
+            std::process::exit(1);
+        }
+    };
+
+    let listener = systemd::bind(&addr)
         .await
+        .expect("error while starting TCP listener")
+        .into_std()
+        .expect("error while starting TCP listener");
+
+    info!(
+        "Now listening on {}",
+        local_addr(listener.local_addr(), addr)
+    );
+
+    // Answer with 503 until everything is set up instead of refusing connections
+    let (started_tx, started_rx) = tokio::sync::oneshot::channel::<()>();
+    let starting = {
+        let listener = tokio::net::TcpListener::from_std(
+            listener
+                .try_clone()
+                .expect("error while starting TCP listener"),
+        )
         .expect("error while starting TCP listener");
+        let router = router::starting_router(&base_path);
+        let tls = tls.clone();
+        tokio::spawn(async move {
+            serve(listener, router, tls, async {
+                let _ = started_rx.await;
+            })
+            .await
+        })
+    };
+
+    systemd::notify("STATUS=Connecting to the database");
+
+    let db_pool = connect(&database).await;
+
+    let metrics = db::QueryMetrics::new(database.slow_query_ms.map(Duration::from_millis));
+    let document_db = DocumentDb::new(db_pool.clone(), metrics).await;
+    let auth_db = AuthDb::new(db_pool.clone()).await;
+    let job_db = JobDb::new(db_pool.clone()).await;
+    let idempotency_db = IdempotencyDb::new(db_pool.clone()).await;
+    let flags = FeatureFlags::new(FeatureDb::new(db_pool.clone()).await, flags);
+
+    let admin_pw_hash = read_secret("ADMIN_PW_HASH");
+    let totp_key = read_secret("TOTP_KEY");
+    let auth = Auth::new(auth_db, admin_pw_hash, api_keys, totp_key);
+
+    let mut lifecycle = Lifecycle::default();
+
+    let documents = DocumentService::new(
+        Databases {
+            db: document_db.clone(),
+            auth,
+            jobs: job_db,
+            idempotency: idempotency_db,
+            flags,
+            telemetry: TelemetryDb::new(db_pool.clone()).await,
+            archive: ArchiveDb::new(db_pool.clone()).await,
+            views: Views::new(ViewsDb::new(db_pool.clone()).await, views.is_some()),
+            bookmarks: BookmarkDb::new(db_pool.clone()).await,
+            annotations: AnnotationDb::new(db_pool.clone()).await,
+        },
+        Settings {
+            title,
+            base_path,
+            public_url,
+            directories,
+            read_options: ReadOptions {
+                normalize,
+                reading_time,
+                compat,
+                max_depth,
+            },
+            warmup,
+            trusted_proxies,
+        },
+        Integrations {
+            ocr,
+            transcriber,
+            translator,
+            git,
+            hooks,
+            link_check,
+            updates,
+            robots,
+            webhooks,
+        },
+        content,
+        lifecycle.shutdown(),
+    );
+    documents
+        .define_collections(collections)
+        .await
+        .expect("invalid collection");
+    documents
+        .apply_revisions()
+        .await
+        .expect("error while checking out revisions");
+    systemd::notify("STATUS=Syncing directories");
+    documents.sync().await.expect("error in state sync");
+
+    if reindex {
+        documents.reindex().await.expect("error in reindex");
+    }
+
+    let worker = tokio::spawn(job::run_worker(documents.clone()));
+
+    let mut tasks = vec![tokio::spawn(update::run(documents.clone()))];
+
+    if let Some(telemetry) = telemetry {
+        info!("Sending usage statistics to {}", telemetry.url);
+        tasks.push(tokio::spawn(telemetry::run(documents.clone(), telemetry)));
+    }
+
+    if let Some(views) = views {
+        tasks.push(tokio::spawn(views::run(documents.clone(), views)));
+    }
+
+    if let Err(e) = documents.queue_asset_jobs().await {
+        error!("Error while queueing asset jobs: {e}");
+    }
+
+    let router = router::router(documents.clone(), &limits, cors, access_log);
+
+    let _ = started_tx.send(());
+    if let Ok(Err(e)) = starting.await {
+        error!("Error while serving during startup: {e}");
+    }
+
+    let listener =
+        tokio::net::TcpListener::from_std(listener).expect("error while starting TCP listener");
 
-    let router = router::router(documents);
+    systemd::notify("READY=1\nSTATUS=Serving");
+    tasks.push(tokio::spawn(systemd::watchdog()));
+    service::ready();
 
-    axum::serve(listener, router)
+    let server = tokio::spawn(serve(
+        listener,
+        router,
+        tls,
+        lifecycle.shutdown().requested(),
+    ));
+
+    lifecycle.stage("the HTTP server", HTTP_SHUTDOWN, stop_server(server));
+    lifecycle.stage("background tasks", TASKS_SHUTDOWN, stop_tasks(tasks));
+    lifecycle.stage("the job worker", JOBS_SHUTDOWN, async move {
+        let _ = worker.await;
+    });
+    lifecycle.stage("webhooks and views", FLUSH_SHUTDOWN, async move {
+        documents.webhooks.flush().await;
+        documents.views.flush().await;
+        let (count, total) = documents.db.metrics.totals();
+        info!(
+            "Ran {count} queries taking {}ms in total",
+            total.as_millis()
+        );
+    });
+    lifecycle.stage("the database pool", DB_SHUTDOWN, async move {
+        db_pool.close().await;
+    });
+
+    lifecycle.run().await;
+    systemd::notify("STOPPING=1");
+    service::stopped();
+}
+
+async fn serve_without_db(
+    addr: String,
+    title: Option<String>,
+    base_path: String,
+    tls: Option<TlsAcceptor>,
+    directories: HashMap<String, String>,
+    read_options: ReadOptions,
+    live_reload: bool,
+) {
+    let mut lifecycle = Lifecycle::default();
+
+    let service = MemoryService::new(title, directories, read_options, lifecycle.shutdown())
+        .expect("error while indexing");
+
+    let watcher = tokio::spawn({
+        let service = service.clone();
+        async move {
+            if let Err(e) = memory::watch(service).await {
+                error!("Error while watching directories: {e}");
+            }
+        }
+    });
+
+    let listener = systemd::bind(&addr)
         .await
-        .expect("error while starting server");
+        .expect("error while starting TCP listener");
+
+    info!(
+        "Now listening on {} without a database",
+        local_addr(listener.local_addr(), addr)
+    );
+
+    systemd::notify("READY=1");
+    let watchdog = tokio::spawn(systemd::watchdog());
+    service::ready();
+
+    let server = tokio::spawn(serve(
+        listener,
+        memory::router::router(service, &base_path, live_reload),
+        tls,
+        lifecycle.shutdown().requested(),
+    ));
+
+    lifecycle.stage("the HTTP server", HTTP_SHUTDOWN, stop_server(server));
+    lifecycle.stage(
+        "the watcher",
+        TASKS_SHUTDOWN,
+        stop_tasks(vec![watcher, watchdog]),
+    );
+
+    lifecycle.run().await;
+    systemd::notify("STOPPING=1");
+    service::stopped();
+}
+
+/// Wait for the connections still open after the server stopped accepting new ones.
+async fn stop_server(server: JoinHandle<std::io::Result<()>>) {
+    match server.await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => error!("Error while serving: {e}"),
+        Err(e) => error!("Error while serving: {e}"),
+    }
+}
+
+/// Stop tasks which only run in the background and have nothing to finish.
+async fn stop_tasks(tasks: Vec<JoinHandle<()>>) {
+    for task in tasks.iter() {
+        task.abort();
+    }
+    for task in tasks {
+        let _ = task.await;
+    }
+}
+
+/// Serve HTTPS if an acceptor is given, plain HTTP otherwise.
+async fn serve(
+    listener: tokio::net::TcpListener,
+    router: axum::Router,
+    tls: Option<TlsAcceptor>,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> std::io::Result<()> {
+    match tls {
+        Some(acceptor) => {
+            tls::serve(listener, acceptor, router, shutdown).await;
+            Ok(())
+        }
+        None => {
+            axum::serve(
+                listener,
+                router.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .with_graceful_shutdown(shutdown)
+            .await
+        }
+    }
+}
+
+/// The TLS acceptor for the certificate given on the command line or,
+/// if none was, in the config. Exits if the certificate cannot be loaded.
+fn tls_acceptor(
+    cert: Option<String>,
+    key: Option<String>,
+    config: Option<TlsConfig>,
+) -> Option<TlsAcceptor> {
+    let (cert, key) = match (cert, key) {
+        (Some(cert), Some(key)) => (cert, key),
+        _ => {
+            let TlsConfig { cert, key } = config?;
+            (cert, key)
+        }
+    };
+
+    match tls::acceptor(&cert, &key) {
+        Ok(acceptor) => {
+            info!("Serving HTTPS with the certificate {cert}");
+            Some(acceptor)
+        }
+        Err(e) => {
+            error!("Could not load the TLS certificate {cert}: {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// The address actually listened on, which differs from the
+/// configured one when the socket was passed by systemd.
+fn local_addr(local_addr: std::io::Result<SocketAddr>, addr: String) -> String {
+    local_addr.map(|addr| addr.to_string()).unwrap_or(addr)
+}
+
+/// Connect to the database and run the migrations, exiting on errors.
+async fn connect(database: &DatabaseConfig) -> PgPool {
+    let Some(db_url) = read_secret("DATABASE_URL").or(database.url.clone()) else {
+        error!("Set DATABASE_URL, DATABASE_URL_FILE or the database url in the config");
+        std::process::exit(1);
+    };
+    let db_pool = match db::create_pool(&db_url, database).await {
+        Ok(pool) => pool,
+        Err(e) => {
+            error!("Could not connect to the database: {e}");
+            std::process::exit(1);
+        }
+    };
+
+    db::migrate(&db_pool).await;
+    db_pool
+}
+
+/// Exits if the secret file cannot be read, as starting without it
+/// would silently disable whatever it configures.
+fn read_secret(name: &str) -> Option<String> {
+    match config::secret(name) {
+        Ok(secret) => secret,
+        Err(e) => {
+            error!("Error while reading secret: {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+fn read_config(path: &str) -> Config {
+    match Config::read(path) {
+        Ok(config) => config,
+        Err(e) => {
+            error!("Invalid config file {path}: {e}");
+            std::process::exit(1);
+        }
+    }
 }
@@ -0,0 +1,420 @@
+//! Serves directories straight from the fs without a database, for quick local previews.
+//! The index is kept in memory and rebuilt whenever anything in the directories changes.
+//! Features requiring the database, such as auth, derived documents and jobs, are not available.
+
+use crate::{
+    document::{
+        find_sidecar_asset, get_valid_name,
+        models::{AuthState, Bootstrap, DirectoryEntry, DocumentInfo, Features},
+        modified_time, DocumentData, DocumentMeta, ReadOptions,
+    },
+    error::LedgeknawError,
+    lifecycle::Shutdown,
+};
+use notify::{RecursiveMode, Watcher};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+use tokio::sync::{broadcast, mpsc, RwLock};
+use tracing::{debug, error, info, warn};
+
+pub mod router;
+
+/// How long to wait for more fs events before rebuilding the index
+const REBUILD_DELAY: Duration = Duration::from_millis(200);
+
+#[derive(Debug)]
+struct IndexEntry {
+    entry: DirectoryEntry,
+    path: PathBuf,
+    children: Vec<uuid::Uuid>,
+    /// Present only in sidecar files
+    asset: Option<String>,
+}
+
+/// In memory counterpart of the directories and documents tables.
+/// Entry IDs are derived from their paths so they stay the same across rebuilds.
+#[derive(Debug, Default)]
+pub struct MemoryIndex {
+    roots: Vec<uuid::Uuid>,
+    entries: HashMap<uuid::Uuid, IndexEntry>,
+}
+
+impl MemoryIndex {
+    /// Index the root directories, mapped from their names to paths.
+    /// Drafts are included since the index only serves local previews.
+    pub fn build(
+        roots: &HashMap<String, String>,
+        options: ReadOptions,
+    ) -> Result<Self, LedgeknawError> {
+        let mut index = Self::default();
+
+        for (alias, path) in roots {
+            let path = Path::new(path).canonicalize()?;
+            let id = path_id(&path);
+
+            index.entries.insert(
+                id,
+                IndexEntry {
+                    entry: DirectoryEntry {
+                        id,
+                        name: get_valid_name(&path)?.to_string(),
+                        parent: None,
+                        r#type: "d".to_string(),
+                        title: Some(alias.clone()),
+                        custom_id: None,
+                        asset: false,
+                        document_count: None,
+                        subdirectory_count: None,
+                        created_at: None,
+                        updated_at: None,
+                        version: None,
+                    },
+                    path: path.clone(),
+                    children: vec![],
+                    asset: None,
+                },
+            );
+            index.roots.push(id);
+            index.add_directory(&path, id, options)?;
+        }
+
+        let mut roots = std::mem::take(&mut index.roots);
+        roots.sort_by(|a, b| index.name(a).cmp(index.name(b)));
+        index.roots = roots;
+
+        Ok(index)
+    }
+
+    fn add_directory(
+        &mut self,
+        path: &Path,
+        id: uuid::Uuid,
+        options: ReadOptions,
+    ) -> Result<(), LedgeknawError> {
+        let mut children = vec![];
+
+        for entry in fs::read_dir(path)?.filter_map(Result::ok) {
+            let path = entry.path();
+
+            if path.is_dir() {
+                let child = path_id(&path);
+                self.entries.insert(
+                    child,
+                    IndexEntry {
+                        entry: DirectoryEntry {
+                            id: child,
+                            name: get_valid_name(&path)?.to_string(),
+                            parent: Some(id),
+                            r#type: "d".to_string(),
+                            title: None,
+                            custom_id: None,
+                            asset: false,
+                            document_count: None,
+                            subdirectory_count: None,
+                            created_at: None,
+                            updated_at: None,
+                            version: None,
+                        },
+                        path: path.clone(),
+                        children: vec![],
+                        asset: None,
+                    },
+                );
+                self.add_directory(&path, child, options)?;
+                children.push(child);
+                continue;
+            }
+
+            if path.extension().and_then(|ext| ext.to_str()) != Some("md") {
+                continue;
+            }
+
+            let (meta, issue) = match DocumentMeta::read_from_file(&path, options) {
+                Ok(meta) => meta,
+                Err(e) => {
+                    warn!("Error while reading {}: {e}", path.display());
+                    continue;
+                }
+            };
+
+            if let Some(issue) = issue {
+                warn!("{}: {issue}", path.display());
+            }
+
+            let asset = find_sidecar_asset(&path)?;
+            let child = path_id(&path);
+
+            self.entries.insert(
+                child,
+                IndexEntry {
+                    entry: DirectoryEntry {
+                        id: child,
+                        name: get_valid_name(&path)?.to_string(),
+                        parent: Some(id),
+                        r#type: "f".to_string(),
+                        title: meta.title,
+                        custom_id: meta.custom_id,
+                        asset: asset.is_some(),
+                        document_count: None,
+                        subdirectory_count: None,
+                        created_at: None,
+                        updated_at: None,
+                        version: None,
+                    },
+                    path,
+                    children: vec![],
+                    asset,
+                },
+            );
+            children.push(child);
+        }
+
+        children.sort_by(|a, b| self.name(a).cmp(self.name(b)));
+
+        let documents = children
+            .iter()
+            .filter(|id| self.entries.get(id).is_some_and(|e| e.entry.r#type == "f"))
+            .count() as i64;
+
+        if let Some(directory) = self.entries.get_mut(&id) {
+            directory.entry.document_count = Some(documents);
+            directory.entry.subdirectory_count = Some(children.len() as i64 - documents);
+            directory.children = children;
+        }
+
+        Ok(())
+    }
+
+    fn name(&self, id: &uuid::Uuid) -> &str {
+        self.entries
+            .get(id)
+            .map(|entry| entry.entry.name.as_str())
+            .unwrap_or_default()
+    }
+
+    pub fn list_roots(&self) -> Vec<DirectoryEntry> {
+        self.roots
+            .iter()
+            .filter_map(|id| Some(self.entries.get(id)?.entry.clone()))
+            .collect()
+    }
+
+    pub fn list_entries(&self, id: uuid::Uuid) -> Vec<DirectoryEntry> {
+        let Some(directory) = self.entries.get(&id) else {
+            return vec![];
+        };
+        directory
+            .children
+            .iter()
+            .filter_map(|id| Some(self.entries.get(id)?.entry.clone()))
+            .collect()
+    }
+
+    /// Find the document by its ID or custom ID.
+    fn get_document(&self, id: &str) -> Option<&IndexEntry> {
+        let document = match uuid::Uuid::from_str(id) {
+            Ok(id) => self.entries.get(&id),
+            Err(_) => self
+                .entries
+                .values()
+                .find(|entry| entry.entry.custom_id.as_deref() == Some(id)),
+        };
+        document.filter(|entry| entry.entry.r#type == "f")
+    }
+
+    fn get_index(&self) -> Option<&IndexEntry> {
+        self.roots.iter().find_map(|root| {
+            self.entries.get(root)?.children.iter().find_map(|id| {
+                let entry = self.entries.get(id)?;
+                (entry.entry.r#type == "f" && entry.entry.name == "index.md").then_some(entry)
+            })
+        })
+    }
+}
+
+fn path_id(path: &Path) -> uuid::Uuid {
+    let hash = Sha256::digest(path.as_os_str().as_encoded_bytes());
+    let mut bytes = [0; 16];
+    bytes.copy_from_slice(&hash[..16]);
+    uuid::Uuid::from_bytes(bytes)
+}
+
+#[derive(Debug, Clone)]
+pub struct MemoryService {
+    pub index: Arc<RwLock<MemoryIndex>>,
+
+    /// The document title for the front end
+    pub title: Arc<Option<String>>,
+
+    /// Maps names to directory paths
+    pub directories: Arc<HashMap<String, String>>,
+
+    pub read_options: ReadOptions,
+
+    /// Notified whenever the index is rebuilt
+    pub changes: broadcast::Sender<()>,
+
+    /// Ends the streams of `/events`
+    pub shutdown: Shutdown,
+}
+
+impl MemoryService {
+    pub fn new(
+        title: Option<String>,
+        directories: HashMap<String, String>,
+        read_options: ReadOptions,
+        shutdown: Shutdown,
+    ) -> Result<Self, LedgeknawError> {
+        let index = MemoryIndex::build(&directories, read_options)?;
+        let (changes, _) = broadcast::channel(16);
+        Ok(Self {
+            index: Arc::new(RwLock::new(index)),
+            title: Arc::new(title),
+            directories: Arc::new(directories),
+            read_options,
+            changes,
+            shutdown,
+        })
+    }
+
+    pub async fn rebuild(&self) -> Result<(), LedgeknawError> {
+        let directories = self.directories.clone();
+        let options = self.read_options;
+        let index = tokio::task::spawn_blocking(move || MemoryIndex::build(&directories, options))
+            .await
+            .map_err(|e| LedgeknawError::IO(std::io::Error::other(e)))??;
+        *self.index.write().await = index;
+        // Only fails if nobody is listening
+        let _ = self.changes.send(());
+        Ok(())
+    }
+
+    /// Only the documents themselves are served without a database,
+    /// so all features are disabled and nobody is authenticated.
+    pub async fn bootstrap(&self, base_path: String) -> Bootstrap {
+        Bootstrap {
+            title: self.title.as_ref().clone(),
+            base_path,
+            roots: self.index.read().await.list_roots(),
+            features: Features::default(),
+            auth: AuthState::default(),
+            flags: BTreeMap::new(),
+        }
+    }
+
+    /// The `id` can either be the main identifier or a custom defined user id.
+    pub async fn read_file(&self, id: String) -> Result<DocumentData, LedgeknawError> {
+        let index = self.index.read().await;
+        let Some(document) = index.get_document(&id) else {
+            return Err(LedgeknawError::NotFound(id));
+        };
+        DocumentData::read_from_disk(document.entry.id, &document.path, self.read_options)
+    }
+
+    pub async fn read_index(&self) -> Result<DocumentData, LedgeknawError> {
+        let index = self.index.read().await;
+        let Some(document) = index.get_index() else {
+            return Err(LedgeknawError::NotFound("index.md".to_string()));
+        };
+        DocumentData::read_from_disk(document.entry.id, &document.path, self.read_options)
+    }
+
+    pub async fn get_file_meta(&self, id: uuid::Uuid) -> Result<DocumentInfo, LedgeknawError> {
+        let index = self.index.read().await;
+        let Some(document) = index.get_document(&id.to_string()) else {
+            return Err(LedgeknawError::NotFound(id.to_string()));
+        };
+        let (meta, _) = DocumentMeta::read_from_file(&document.path, self.read_options)?;
+        Ok(DocumentInfo {
+            meta,
+            created_at: None,
+            updated_at: None,
+            modified: modified_time(&document.path),
+            updated_by: None,
+        })
+    }
+
+    /// Read the asset described by the sidecar document with the given ID.
+    /// Returns the asset contents and its mime type.
+    pub async fn read_asset(&self, id: uuid::Uuid) -> Result<(Vec<u8>, String), LedgeknawError> {
+        let path = {
+            let index = self.index.read().await;
+            let Some(path) = index
+                .get_document(&id.to_string())
+                .and_then(|document| document.asset.clone())
+            else {
+                return Err(LedgeknawError::NotFound(id.to_string()));
+            };
+            path
+        };
+        let content = tokio::fs::read(&path).await?;
+        let mime = mime_guess::from_path(&path).first_or_octet_stream();
+        Ok((content, mime.to_string()))
+    }
+}
+
+/// The directories to watch recursively, without any already covered by another.
+fn watch_paths<'a>(directories: impl Iterator<Item = &'a String>) -> Vec<PathBuf> {
+    let mut paths = directories
+        .map(|path| {
+            Path::new(path)
+                .canonicalize()
+                .unwrap_or_else(|_| path.into())
+        })
+        .collect::<Vec<_>>();
+    // Outer directories sort before the ones nested in them
+    paths.sort();
+
+    let mut watched: Vec<PathBuf> = vec![];
+    for path in paths {
+        if !watched.iter().any(|outer| path.starts_with(outer)) {
+            watched.push(path);
+        }
+    }
+    watched
+}
+
+/// Rebuild the index of the service whenever its directories change.
+/// Runs until the watcher fails.
+pub async fn watch(service: MemoryService) -> Result<(), LedgeknawError> {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+
+    let mut watcher = notify::recommended_watcher(move |event| {
+        let _ = tx.send(event);
+    })?;
+
+    let paths = watch_paths(service.directories.values());
+    for path in paths.iter() {
+        watcher.watch(path, RecursiveMode::Recursive)?;
+    }
+
+    info!("Watching {} directories", paths.len());
+
+    while let Some(event) = rx.recv().await {
+        let event: notify::Event = event?;
+
+        if event.kind.is_access() {
+            continue;
+        }
+
+        debug!("Fs event: {event:?}");
+
+        // Editors tend to emit multiple events per save
+        tokio::time::sleep(REBUILD_DELAY).await;
+        while rx.try_recv().is_ok() {}
+
+        match service.rebuild().await {
+            Ok(()) => info!("Rebuilt index"),
+            Err(e) => error!("Error while rebuilding index: {e}"),
+        }
+    }
+
+    Ok(())
+}
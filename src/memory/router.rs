@@ -0,0 +1,122 @@
+use super::MemoryService;
+use crate::{
+    assets,
+    document::{models::DocumentInfo, DocumentData},
+    error::LedgeknawError,
+    router::{sidebar_response, with_base_path},
+};
+use axum::{
+    http::{header, Method},
+    response::{
+        sse::{Event, KeepAlive},
+        IntoResponse, Response, Sse,
+    },
+    routing::get,
+    Json, Router,
+};
+use futures::{Stream, StreamExt};
+use std::convert::Infallible;
+use tokio::sync::broadcast::error::RecvError;
+use tower_http::{cors::CorsLayer, trace::TraceLayer};
+
+/// The subset of the public routes that works without a database.
+/// With `live_reload`, clients are notified of changes on `/events`.
+pub fn router(state: MemoryService, base_path: &str, live_reload: bool) -> Router {
+    let cors = CorsLayer::new()
+        .allow_origin(tower_http::cors::Any)
+        .allow_methods([Method::GET])
+        .allow_headers([header::IF_NONE_MATCH])
+        .expose_headers([header::ETAG]);
+
+    let router = if live_reload {
+        Router::new().route("/events", get(events))
+    } else {
+        Router::new()
+    };
+
+    let bootstrap = {
+        let base_path = base_path.to_string();
+        move |state: axum::extract::State<MemoryService>| async move {
+            Json(state.bootstrap(base_path).await)
+        }
+    };
+
+    let router = router
+        .merge(assets::router())
+        .route("/meta/:id", get(document_meta))
+        .route("/asset/:id", get(asset))
+        .route("/bootstrap", get(bootstrap))
+        .route("/side", get(sidebar_init))
+        .route("/side/:id", get(sidebar_entries))
+        .route("/document", get(index))
+        .route("/document/:id", get(document))
+        .with_state(state);
+
+    with_base_path(router, base_path)
+        .layer(TraceLayer::new_for_http())
+        .layer(cors)
+}
+
+pub async fn index(
+    state: axum::extract::State<MemoryService>,
+) -> Result<Json<DocumentData>, LedgeknawError> {
+    Ok(Json(state.read_index().await?))
+}
+
+pub async fn document(
+    state: axum::extract::State<MemoryService>,
+    path: axum::extract::Path<String>,
+) -> Result<Json<DocumentData>, LedgeknawError> {
+    Ok(Json(state.read_file(path.0).await?))
+}
+
+pub async fn document_meta(
+    state: axum::extract::State<MemoryService>,
+    id: axum::extract::Path<uuid::Uuid>,
+) -> Result<Json<DocumentInfo>, LedgeknawError> {
+    Ok(Json(state.get_file_meta(*id).await?))
+}
+
+pub async fn asset(
+    state: axum::extract::State<MemoryService>,
+    id: axum::extract::Path<uuid::Uuid>,
+) -> Result<impl IntoResponse, LedgeknawError> {
+    let (content, mime) = state.read_asset(*id).await?;
+    Ok(([(header::CONTENT_TYPE, mime)], content))
+}
+
+/// Sends a `reload` event every time the index is rebuilt, until shutdown.
+pub async fn events(
+    state: axum::extract::State<MemoryService>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let shutdown = state.shutdown.clone().requested();
+    let stream = futures::stream::unfold(state.changes.subscribe(), |mut changes| async move {
+        match changes.recv().await {
+            Ok(()) | Err(RecvError::Lagged(_)) => {
+                Some((Ok(Event::default().event("reload").data("")), changes))
+            }
+            Err(RecvError::Closed) => None,
+        }
+    })
+    .take_until(shutdown);
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+/// Entries are not versioned without a database, so
+/// only conditional requests with the ETag are supported.
+pub async fn sidebar_init(
+    state: axum::extract::State<MemoryService>,
+    headers: axum::http::HeaderMap,
+) -> Result<Response, LedgeknawError> {
+    let entries = state.index.read().await.list_roots();
+    sidebar_response(entries, None, &headers)
+}
+
+pub async fn sidebar_entries(
+    state: axum::extract::State<MemoryService>,
+    path: axum::extract::Path<uuid::Uuid>,
+    headers: axum::http::HeaderMap,
+) -> Result<Response, LedgeknawError> {
+    let entries = state.index.read().await.list_entries(*path);
+    sidebar_response(entries, None, &headers)
+}
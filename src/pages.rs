@@ -0,0 +1,166 @@
+//! Documents and listings rendered to HTML on the server on `/pages`,
+//! for clients that cannot or will not run the front end.
+
+use crate::document::{models::DirectoryEntry, DocumentData};
+
+pub mod markdown;
+
+/// Everything shared by the pages.
+pub struct Layout<'a> {
+    /// Instance title, shown in the header
+    pub title: Option<&'a str>,
+    pub base_path: &'a str,
+    /// Makes canonical links absolute if present
+    pub public_url: Option<&'a str>,
+    /// Root directories and collections, shown as the navigation
+    pub roots: &'a [DirectoryEntry],
+}
+
+impl<'a> Layout<'a> {
+    pub fn document(&self, document: &DocumentData) -> String {
+        let title = document.meta.title.as_deref().unwrap_or("Untitled");
+        let path = format!(
+            "/pages/{}",
+            document
+                .meta
+                .custom_id
+                .clone()
+                .unwrap_or_else(|| document.id.to_string())
+        );
+
+        // Shown when links to the page are shared
+        let mut head = format!(
+            "<meta property=\"og:type\" content=\"article\">\n<meta property=\"og:title\" content=\"{}\">\n",
+            escape(title)
+        );
+        if let Some(site) = self.title {
+            head.push_str(&format!(
+                "<meta property=\"og:site_name\" content=\"{}\">\n",
+                escape(site)
+            ));
+        }
+        match self.public_url {
+            Some(url) => head.push_str(&format!(
+                "<link rel=\"canonical\" href=\"{url}{path}\">\n<meta property=\"og:url\" content=\"{url}{path}\">\n",
+                url = escape(url),
+                path = escape(&path)
+            )),
+            None => head.push_str(&format!(
+                "<link rel=\"canonical\" href=\"{}{}\">\n",
+                escape(self.base_path),
+                escape(&path)
+            )),
+        }
+        let description = document
+            .meta
+            .description
+            .clone()
+            .or_else(|| markdown::summary(&document.content));
+        if let Some(description) = description {
+            head.push_str(&format!(
+                "<meta name=\"description\" content=\"{description}\">\n<meta property=\"og:description\" content=\"{description}\">\n",
+                description = escape(&description)
+            ));
+        }
+        if document.meta.noindex {
+            head.push_str("<meta name=\"robots\" content=\"noindex\">\n");
+        }
+        if let Some(tags) = document.meta.tags.as_ref().filter(|tags| !tags.is_empty()) {
+            head.push_str(&format!(
+                "<meta name=\"keywords\" content=\"{}\">\n",
+                escape(&tags.join(", "))
+            ));
+        }
+
+        self.page(title, &head, &markdown::render(&document.content))
+    }
+
+    pub fn listing(&self, name: &str, entries: &[DirectoryEntry]) -> String {
+        let mut body = format!("<h1>{}</h1>\n", escape(name));
+
+        if entries.is_empty() {
+            body.push_str("<p>Nothing here.</p>\n");
+        } else {
+            body.push_str("<ul>\n");
+            for entry in entries {
+                body.push_str(&format!("<li>{}</li>\n", self.link(entry)));
+            }
+            body.push_str("</ul>\n");
+        }
+
+        self.page(name, "", &body)
+    }
+
+    /// Link to the page of the entry, labelled with its title if it has one.
+    fn link(&self, entry: &DirectoryEntry) -> String {
+        let href = match entry.r#type.as_str() {
+            "f" => format!(
+                "{}/pages/{}",
+                self.base_path,
+                entry
+                    .custom_id
+                    .clone()
+                    .unwrap_or_else(|| entry.id.to_string())
+            ),
+            _ => format!("{}/pages/dir/{}", self.base_path, entry.id),
+        };
+        let label = entry.title.as_deref().unwrap_or(&entry.name);
+        let link = format!("<a href=\"{}\">{}</a>", escape(&href), escape(label));
+
+        // Directories and collections show how many documents they contain directly
+        match entry.document_count.filter(|_| entry.r#type != "f") {
+            Some(count) => format!("{link} ({count})"),
+            None => link,
+        }
+    }
+
+    /// The full page, with `head` added to the document head as is.
+    fn page(&self, title: &str, head: &str, body: &str) -> String {
+        let site = self.title.unwrap_or("Ledgeknaw");
+
+        let mut nav = String::new();
+        for root in self.roots {
+            nav.push_str(&format!("<li>{}</li>\n", self.link(root)));
+        }
+
+        format!(
+            r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{title} - {site}</title>
+{head}</head>
+<body>
+<header><a href="{base}/pages">{site}</a></header>
+<nav>
+<ul>
+{nav}</ul>
+</nav>
+<main>
+{body}</main>
+</body>
+</html>
+"#,
+            title = escape(title),
+            site = escape(site),
+            base = escape(self.base_path),
+        )
+    }
+}
+
+/// Escape text for use in HTML content and quoted attributes.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,369 @@
+//! The subset of markdown rendered on the server: headings, paragraphs, lists,
+//! block quotes, code blocks, tables, rules, emphasis, code spans, links and images.
+//! Everything else is shown as text. All text is escaped, so raw HTML in
+//! documents is never passed through.
+
+use super::escape;
+
+/// Length of [summary] before it is shortened
+const SUMMARY_CHARS: usize = 200;
+
+pub fn render(markdown: &str) -> String {
+    let mut out = String::new();
+    let lines = markdown.lines().collect::<Vec<_>>();
+    let mut paragraph = vec![];
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i].trim_start();
+        i += 1;
+
+        if line.is_empty() {
+            flush_paragraph(&mut paragraph, &mut out);
+            continue;
+        }
+
+        if line.starts_with("```") || line.starts_with("~~~") {
+            flush_paragraph(&mut paragraph, &mut out);
+            let fence = &line[..3];
+            let mut code = vec![];
+            while i < lines.len() && !lines[i].trim_start().starts_with(fence) {
+                code.push(lines[i]);
+                i += 1;
+            }
+            // Skip the closing fence
+            i += 1;
+            out.push_str(&format!(
+                "<pre><code>{}</code></pre>\n",
+                escape(&code.join("\n"))
+            ));
+            continue;
+        }
+
+        if let Some((level, text)) = heading(line) {
+            flush_paragraph(&mut paragraph, &mut out);
+            out.push_str(&format!("<h{level}>{}</h{level}>\n", inline(text)));
+            continue;
+        }
+
+        if is_rule(line) {
+            flush_paragraph(&mut paragraph, &mut out);
+            out.push_str("<hr>\n");
+            continue;
+        }
+
+        if line.starts_with('>') {
+            flush_paragraph(&mut paragraph, &mut out);
+            let mut quote = vec![quoted(line)];
+            while i < lines.len() && lines[i].trim_start().starts_with('>') {
+                quote.push(quoted(lines[i].trim_start()));
+                i += 1;
+            }
+            out.push_str(&format!(
+                "<blockquote>\n{}</blockquote>\n",
+                render(&quote.join("\n"))
+            ));
+            continue;
+        }
+
+        if let Some((ordered, item)) = list_item(line) {
+            flush_paragraph(&mut paragraph, &mut out);
+            let mut items = vec![item.to_string()];
+            while i < lines.len() && !lines[i].trim().is_empty() {
+                let next = lines[i].trim_start();
+                match list_item(next) {
+                    Some((_, item)) => items.push(item.to_string()),
+                    // Continuation of the previous item
+                    None => {
+                        let last = items.last_mut().unwrap();
+                        last.push('\n');
+                        last.push_str(next);
+                    }
+                }
+                i += 1;
+            }
+            let tag = if ordered { "ol" } else { "ul" };
+            out.push_str(&format!("<{tag}>\n"));
+            for item in items {
+                out.push_str(&format!("<li>{}</li>\n", inline(&item)));
+            }
+            out.push_str(&format!("</{tag}>\n"));
+            continue;
+        }
+
+        if line.starts_with('|') && lines.get(i).is_some_and(|next| is_table_separator(next)) {
+            flush_paragraph(&mut paragraph, &mut out);
+            out.push_str("<table>\n<thead><tr>");
+            for cell in cells(line) {
+                out.push_str(&format!("<th>{}</th>", inline(&cell)));
+            }
+            out.push_str("</tr></thead>\n<tbody>\n");
+            // Skip the separator
+            i += 1;
+            while i < lines.len() && lines[i].trim_start().starts_with('|') {
+                out.push_str("<tr>");
+                for cell in cells(lines[i].trim_start()) {
+                    out.push_str(&format!("<td>{}</td>", inline(&cell)));
+                }
+                out.push_str("</tr>\n");
+                i += 1;
+            }
+            out.push_str("</tbody>\n</table>\n");
+            continue;
+        }
+
+        paragraph.push(line);
+    }
+
+    flush_paragraph(&mut paragraph, &mut out);
+    out
+}
+
+/// The text of the first paragraph without markup, shortened to about
+/// [SUMMARY_CHARS] at a word boundary.
+pub fn summary(markdown: &str) -> Option<String> {
+    let mut paragraph = vec![];
+    let mut in_code = false;
+
+    for line in markdown.lines().map(str::trim_start) {
+        if line.starts_with("```") || line.starts_with("~~~") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            continue;
+        }
+
+        let block = line.is_empty()
+            || heading(line).is_some()
+            || is_rule(line)
+            || line.starts_with('>')
+            || line.starts_with('|')
+            || list_item(line).is_some();
+
+        match (block, paragraph.is_empty()) {
+            (true, true) => continue,
+            (true, false) => break,
+            (false, _) => paragraph.push(line),
+        }
+    }
+
+    if paragraph.is_empty() {
+        return None;
+    }
+
+    let text = plain(&inline(&paragraph.join(" ")));
+    let chars = text.chars().collect::<Vec<_>>();
+    if chars.len() <= SUMMARY_CHARS {
+        return Some(text);
+    }
+
+    let cut = chars[..SUMMARY_CHARS]
+        .iter()
+        .rposition(|c| c.is_whitespace())
+        .unwrap_or(SUMMARY_CHARS);
+    let shortened = chars[..cut].iter().collect::<String>();
+    Some(format!("{}…", shortened.trim_end()))
+}
+
+/// Strip the tags from rendered inline markdown and unescape the text.
+fn plain(html: &str) -> String {
+    let mut text = String::new();
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            c if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+fn flush_paragraph(paragraph: &mut Vec<&str>, out: &mut String) {
+    if paragraph.is_empty() {
+        return;
+    }
+    out.push_str(&format!("<p>{}</p>\n", inline(&paragraph.join("\n"))));
+    paragraph.clear();
+}
+
+fn heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    if !(1..=6).contains(&level) {
+        return None;
+    }
+    let text = &line[level..];
+    if !text.is_empty() && !text.starts_with(' ') {
+        return None;
+    }
+    Some((level, text.trim().trim_end_matches('#').trim_end()))
+}
+
+fn is_rule(line: &str) -> bool {
+    let line = line.trim_end().replace(' ', "");
+    line.len() >= 3
+        && ["-", "*", "_"]
+            .iter()
+            .any(|c| line.chars().all(|l| l.to_string() == *c))
+}
+
+fn quoted(line: &str) -> &str {
+    let line = &line[1..];
+    line.strip_prefix(' ').unwrap_or(line)
+}
+
+/// Whether the item is ordered and its text.
+fn list_item(line: &str) -> Option<(bool, &str)> {
+    for marker in ["- ", "* ", "+ "] {
+        if let Some(item) = line.strip_prefix(marker) {
+            return Some((false, item));
+        }
+    }
+
+    let digits = line.chars().take_while(char::is_ascii_digit).count();
+    if digits == 0 {
+        return None;
+    }
+    line[digits..]
+        .strip_prefix(". ")
+        .or_else(|| line[digits..].strip_prefix(") "))
+        .map(|item| (true, item))
+}
+
+fn is_table_separator(line: &str) -> bool {
+    let line = line.trim();
+    line.starts_with('|')
+        && line.contains('-')
+        && line.chars().all(|c| matches!(c, '|' | '-' | ':' | ' '))
+}
+
+/// The cells of a table row, with escaped pipes kept in the cells.
+fn cells(line: &str) -> Vec<String> {
+    let line = line.trim().trim_start_matches('|');
+    let line = line.strip_suffix('|').unwrap_or(line);
+
+    let mut cells = vec![];
+    let mut cell = String::new();
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'|') => {
+                cell.push('|');
+                chars.next();
+            }
+            '|' => cells.push(std::mem::take(&mut cell).trim().to_string()),
+            c => cell.push(c),
+        }
+    }
+    cells.push(cell.trim().to_string());
+    cells
+}
+
+/// Render emphasis, code spans, links and images, escaping everything else.
+fn inline(text: &str) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+
+    while let Some(c) = rest.chars().next() {
+        let after = &rest[c.len_utf8()..];
+
+        match c {
+            '\\' => {
+                if let Some(next) = after.chars().next().filter(char::is_ascii_punctuation) {
+                    out.push_str(&escape(&next.to_string()));
+                    rest = &after[1..];
+                    continue;
+                }
+            }
+            '`' => {
+                if let Some(end) = after.find('`') {
+                    out.push_str(&format!("<code>{}</code>", escape(&after[..end])));
+                    rest = &after[end + 1..];
+                    continue;
+                }
+            }
+            '!' if after.starts_with('[') => {
+                if let Some((alt, url, len)) = link(after) {
+                    out.push_str(&format!(
+                        "<img src=\"{}\" alt=\"{}\">",
+                        escape(&safe_url(url)),
+                        escape(alt)
+                    ));
+                    rest = &after[len..];
+                    continue;
+                }
+            }
+            '[' => {
+                if let Some((label, url, len)) = link(rest) {
+                    out.push_str(&format!(
+                        "<a href=\"{}\">{}</a>",
+                        escape(&safe_url(url)),
+                        inline(label)
+                    ));
+                    rest = &rest[len..];
+                    continue;
+                }
+            }
+            '*' | '_' => {
+                let strong = if c == '*' { "**" } else { "__" };
+                if let Some(inner) = rest.strip_prefix(strong) {
+                    if let Some(end) = inner.find(strong).filter(|end| *end > 0) {
+                        out.push_str(&format!("<strong>{}</strong>", inline(&inner[..end])));
+                        rest = &inner[end + 2..];
+                        continue;
+                    }
+                }
+                let word_start =
+                    !matches!(out.chars().last(), Some(prev) if prev.is_alphanumeric());
+                if c == '*' || word_start {
+                    if let Some(end) = after.find(c).filter(|end| *end > 0) {
+                        out.push_str(&format!("<em>{}</em>", inline(&after[..end])));
+                        rest = &after[end + 1..];
+                        continue;
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        out.push_str(&escape(&c.to_string()));
+        rest = after;
+    }
+
+    out
+}
+
+/// Parse `[label](url)` at the start of the text, returning the label,
+/// the URL and the length of the whole link.
+fn link(text: &str) -> Option<(&str, &str, usize)> {
+    let text_end = text.find("](")?;
+    let label = &text[1..text_end];
+    let url_start = text_end + 2;
+    let url_len = text[url_start..].find(')')?;
+    let url = text[url_start..url_start + url_len]
+        .split_whitespace()
+        .next()
+        .unwrap_or_default();
+    Some((label, url, url_start + url_len + 1))
+}
+
+/// Relative URLs and web and mail links are kept, anything else
+/// such as `javascript:` is replaced.
+fn safe_url(url: &str) -> String {
+    let scheme = url
+        .split_once(':')
+        .map(|(scheme, _)| scheme.to_lowercase())
+        .filter(|scheme| !scheme.contains('/'));
+
+    match scheme.as_deref() {
+        None | Some("http" | "https" | "mailto") => url.to_string(),
+        Some(_) => "#".to_string(),
+    }
+}
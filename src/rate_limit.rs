@@ -0,0 +1,45 @@
+use crate::error::LedgeknawError;
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Limits how many requests a client can make within a fixed window.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    max_requests: u32,
+    window: Duration,
+    /// Start of the current window and the requests made in it, by client address
+    clients: Arc<Mutex<HashMap<IpAddr, (Instant, u32)>>>,
+}
+
+impl RateLimiter {
+    pub fn new(max_requests: u32, window: Duration) -> Self {
+        Self {
+            max_requests,
+            window,
+            clients: Arc::default(),
+        }
+    }
+
+    /// Count a request of the client, failing if it exceeded the limit.
+    pub fn check(&self, client: IpAddr) -> Result<(), LedgeknawError> {
+        let mut clients = self.clients.lock().expect("rate limiter poisoned");
+        let now = Instant::now();
+
+        clients.retain(|_, (start, _)| now - *start < self.window);
+
+        let (start, requests) = clients.entry(client).or_insert((now, 0));
+
+        if *requests >= self.max_requests {
+            let retry_after = (self.window - (now - *start)).as_secs() + 1;
+            return Err(LedgeknawError::TooManyRequests(retry_after));
+        }
+
+        *requests += 1;
+
+        Ok(())
+    }
+}
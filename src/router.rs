@@ -1,80 +1,1362 @@
 use crate::{
-    document::models::DirectoryEntry,
-    document::{DocumentData, DocumentMeta},
+    access_log,
+    annotation::{Annotation, AnnotationPayload},
+    archive::dump::{self, Dump, DumpFormat},
+    assets,
+    auth::{self, Access, Session, TotpEnrollment, TotpStatus},
+    bookmark::{self, Bookmark},
+    client_ip::ClientIp,
+    config::{AccessLogConfig, CorsConfig, LimitsConfig},
+    document::models::{
+        Author, Bootstrap, Collection, DatedEntry, DerivedDocument, DirectoryEntry, DocumentInfo,
+        DocumentIssue, Graph, LinkReport, RollbackReport, RootRevision, SidebarDelta, TableScans,
+        TreeEntry,
+    },
+    document::{self, query::Order, DocumentMeta, TranslatedDocument},
     error::LedgeknawError,
-    state::DocumentService,
+    events::Change,
+    feature, hook, idempotency,
+    import::ImportReport,
+    job::{Job, JobStatus, LINK_CHECK, REINDEX, SYNC},
+    pages::Layout,
+    state::{DocumentService, RollbackTarget},
+    telemetry::SentReport,
+    update::Status,
+    views::PopularEntry,
 };
-use axum::{http::Method, response::IntoResponse, routing::get, Json, Router};
+use axum::{
+    extract::DefaultBodyLimit,
+    http::{header, HeaderName, HeaderValue, Method, StatusCode},
+    middleware,
+    response::{
+        sse::{Event, KeepAlive},
+        AppendHeaders, Html, IntoResponse, Redirect, Response, Sse,
+    },
+    routing::{delete, get, post, put},
+    Json, Router,
+};
+use axum_extra::extract::CookieJar;
 use axum_macros::debug_handler;
+use flate2::{write::GzEncoder, Compression};
+use futures::{Stream, StreamExt};
+use serde::Deserialize;
+use std::{collections::BTreeMap, convert::Infallible, io::Write, str::FromStr, sync::Arc};
+use tokio::sync::broadcast::error::RecvError;
 use tower_http::{
-    cors::CorsLayer,
-    services::{ServeDir, ServeFile},
+    cors::{AllowOrigin, CorsLayer},
+    limit::RequestBodyLimitLayer,
     trace::TraceLayer,
 };
 use tracing::info;
 
-pub fn router(state: DocumentService) -> Router {
-    let router = public_router(state.clone());
+pub fn router(
+    state: DocumentService,
+    limits: &LimitsConfig,
+    cors: CorsLayer,
+    access_log: Option<AccessLogConfig>,
+) -> Router {
+    let base_path = state.base_path.clone();
+    // Imports are merged after the body limits, since archives are larger than other bodies
+    let router = public_router(state.clone(), limits)
+        .merge(admin_router(state.clone()))
+        .layer(DefaultBodyLimit::max(limits.body_bytes))
+        .layer(RequestBodyLimitLayer::new(limits.body_bytes))
+        .merge(import_router(state, limits));
+
+    let router = with_base_path(router, &base_path).layer(TraceLayer::new_for_http());
+    let router = match access_log {
+        Some(config) => router.layer(middleware::from_fn_with_state(
+            Arc::new(config),
+            access_log::access_log,
+        )),
+        None => router,
+    };
+    router.layer(cors)
+}
 
-    let cors = CorsLayer::new()
-        .allow_origin(tower_http::cors::Any)
-        .allow_methods([Method::GET, Method::POST]);
+/// Fails if any of the values is invalid or credentials are allowed for any origin,
+/// which browsers reject.
+pub fn cors_layer(config: &CorsConfig) -> Result<CorsLayer, LedgeknawError> {
+    let invalid = |what: &str, value: &str| {
+        LedgeknawError::InvalidParameter(format!("invalid CORS {what}: {value}"))
+    };
 
-    router.layer(TraceLayer::new_for_http()).layer(cors)
+    let origin = if config.origins.iter().any(|origin| origin == "*") {
+        if config.credentials {
+            return Err(LedgeknawError::InvalidParameter(
+                "CORS credentials require the origins to be listed explicitly".to_string(),
+            ));
+        }
+        AllowOrigin::any()
+    } else {
+        let origins = config
+            .origins
+            .iter()
+            .map(|origin| HeaderValue::from_str(origin).map_err(|_| invalid("origin", origin)))
+            .collect::<Result<Vec<_>, _>>()?;
+        AllowOrigin::list(origins)
+    };
+
+    let methods = config
+        .methods
+        .iter()
+        .map(|method| {
+            Method::from_str(&method.to_uppercase()).map_err(|_| invalid("method", method))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let headers = config
+        .headers
+        .iter()
+        .map(|name| HeaderName::from_str(name).map_err(|_| invalid("header", name)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(CorsLayer::new()
+        .allow_origin(origin)
+        .allow_methods(methods)
+        .allow_headers(headers)
+        .allow_credentials(config.credentials)
+        .expose_headers([header::ETAG]))
 }
 
-fn public_router(state: DocumentService) -> Router {
-    Router::new()
-        .nest_service(
-            "/",
-            ServeDir::new("dist").fallback(ServeFile::new("dist/index.html")),
-        )
+/// Nest the routes under the base path, if any.
+pub(crate) fn with_base_path(router: Router, base_path: &str) -> Router {
+    if base_path.is_empty() {
+        router
+    } else {
+        Router::new().nest_service(base_path, router)
+    }
+}
+
+/// Served on the listener until the database is connected and the directories are
+/// synced, so orchestrators see the service as live but not yet ready.
+pub fn starting_router(base_path: &str) -> Router {
+    let router = Router::new()
+        .route("/health/live", get(live))
+        .fallback(|| async {
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                [(header::RETRY_AFTER, "5")],
+                "Starting",
+            )
+        });
+    with_base_path(router, base_path)
+}
+
+fn public_router(state: DocumentService, limits: &LimitsConfig) -> Router {
+    // Otherwise left to the front end, which may ship its own
+    let router = match state.robots {
+        Some(_) => assets::router().route("/robots.txt", get(robots_txt)),
+        None => assets::router(),
+    };
+
+    router
+        .route("/health/live", get(live))
+        .route("/health/ready", get(ready))
+        .route("/hooks/git", post(git_hook))
         .route("/meta/:id", get(document_meta))
+        .route("/asset/:id", get(asset))
+        .route("/recent", get(recent))
+        .route("/popular", get(popular))
+        .route("/bookmarks", get(bookmarks))
+        .route("/bookmarks/:id", put(add_bookmark).delete(remove_bookmark))
+        .route("/authors", get(authors))
+        .route("/authors/:name", get(author_documents))
+        .route("/bootstrap", get(bootstrap))
         .route("/side", get(sidebar_init))
+        .route("/tree", get(tree))
+        .route("/graph", get(graph))
+        .route("/export/tree.json", get(export_tree))
+        .route("/query", get(query_documents))
+        .route("/collections", get(collections))
+        .route("/collections/:name", get(collection_documents))
         .route("/side/:id", get(sidebar_entries))
+        .route("/events", get(events))
         .route("/document", get(index))
         .route("/document/:id", get(document))
+        .route("/document/:id/translate", get(translate))
+        .route("/document/:id/annotations", get(annotations))
+        .route("/document/:id/derived", get(derived_kinds))
+        .route("/document/:id/derived/:kind", get(derived_document))
+        .route("/pages", get(index_page))
+        .route("/pages/:id", get(document_page))
+        .route("/pages/dir/:id", get(directory_page))
+        .route(
+            "/admin/login",
+            post(login).layer(RequestBodyLimitLayer::new(limits.login_body_bytes)),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            auth::authenticate,
+        ))
+        .with_state(state)
+}
+
+fn admin_router(state: DocumentService) -> Router {
+    Router::new()
+        .route("/admin/logout", post(logout))
+        .route("/admin/sessions", get(sessions))
+        .route("/admin/sessions/:id", delete(revoke_session))
+        .route(
+            "/admin/2fa",
+            get(totp_status).post(enroll_totp).delete(disable_totp),
+        )
+        .route("/admin/2fa/confirm", post(confirm_totp))
+        .route("/admin/issues", get(issues))
+        .route("/admin/table-scans", get(table_scans))
+        .route("/admin/orphans", get(orphans))
+        .route("/admin/deadends", get(dead_ends))
+        .route("/admin/links/check", post(check_links))
+        .route("/admin/reindex", post(reindex))
+        .route("/admin/sync", post(sync))
+        .route("/admin/rollback", post(rollback))
+        .route(
+            "/admin/collections/:name",
+            put(put_collection).delete(delete_collection),
+        )
+        .route("/admin/document/:id/annotations", post(add_annotation))
+        .route(
+            "/admin/annotations/:id",
+            put(update_annotation).delete(delete_annotation),
+        )
+        .route("/admin/revisions", get(revisions))
+        .route(
+            "/admin/revisions/:alias/pin",
+            post(pin_revision).delete(unpin_revision),
+        )
+        .route(
+            "/admin/revisions/:alias/stage",
+            post(stage_revision).delete(discard_staged_revision),
+        )
+        .route("/admin/revisions/:alias/promote", post(promote_revision))
+        .route("/admin/queue", get(queue))
+        .route("/admin/queue/:id/retry", post(retry_job))
+        .route("/admin/queue/:id/cancel", post(cancel_job))
+        .route("/admin/jobs", get(jobs))
+        .route("/admin/jobs/:id", get(job))
+        .route("/admin/metrics", get(metrics))
+        .route("/admin/status", get(status))
+        .route("/admin/telemetry", get(telemetry_reports))
+        .route("/admin/flags", get(flags))
+        .route("/admin/flags/:name", put(set_flag).delete(reset_flag))
+        .route("/admin/dump", get(dump_database))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            idempotency::idempotency,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            auth::require_auth,
+        ))
+        .with_state(state)
+}
+
+fn import_router(state: DocumentService, limits: &LimitsConfig) -> Router {
+    Router::new()
+        .route("/admin/import", post(import))
+        .route("/admin/restore", post(restore_database))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            idempotency::idempotency,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            auth::require_auth,
+        ))
+        // Outside of the idempotency layer, which reads the whole body
+        .layer(DefaultBodyLimit::max(limits.import_body_bytes))
+        .layer(RequestBodyLimitLayer::new(limits.import_body_bytes))
         .with_state(state)
 }
 
 #[debug_handler]
+pub async fn live() -> StatusCode {
+    StatusCode::OK
+}
+
+/// Unavailable while the database cannot be reached.
+pub async fn ready(state: axum::extract::State<DocumentService>) -> StatusCode {
+    match state.db.ping().await {
+        Ok(()) => StatusCode::OK,
+        Err(_) => StatusCode::SERVICE_UNAVAILABLE,
+    }
+}
+
 pub async fn index(
     state: axum::extract::State<DocumentService>,
+    access: axum::Extension<Access>,
 ) -> Result<impl IntoResponse, LedgeknawError> {
     info!("Loading index");
-    let doc_path = state.db.get_index_id_path().await?;
-    let Some((id, path)) = doc_path else {
+    let doc_path = state.db.get_index_id_path(*access).await?;
+    let Some((id, _)) = doc_path else {
         return Err(LedgeknawError::NotFound("index.md".to_string()));
     };
-    let index = DocumentData::read_from_disk(id, path)?;
-    Ok(Json(index).into_response())
+    let index = state.read_file(id.to_string(), *access).await?;
+    let headers = document_headers(&index.meta, *access);
+    Ok((headers, Json(index)).into_response())
+}
+
+pub async fn robots_txt(
+    state: axum::extract::State<DocumentService>,
+) -> Result<impl IntoResponse, LedgeknawError> {
+    let Some(robots) = state.robots_txt().await? else {
+        return Err(LedgeknawError::NotFound("robots.txt".to_string()));
+    };
+    Ok((
+        [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+        robots,
+    ))
 }
 
+/// Push webhooks of GitHub and GitLab, queueing a pull of the configured
+/// repositories and a sync.
+pub async fn git_hook(
+    state: axum::extract::State<DocumentService>,
+    headers: axum::http::HeaderMap,
+    body: axum::body::Bytes,
+) -> Result<StatusCode, LedgeknawError> {
+    let Some(config) = state.hooks.as_deref() else {
+        return Err(LedgeknawError::NotFound(
+            "webhooks are not configured".to_string(),
+        ));
+    };
+
+    hook::verify(config, &headers, &body)?;
+
+    if !hook::is_push(&headers) {
+        return Ok(StatusCode::NO_CONTENT);
+    }
+
+    state.enqueue_job(SYNC, None).await?;
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// Documents not found by their ID are looked up by their aliases,
+/// in which case the request is permanently redirected to the current ID.
 pub async fn document(
     state: axum::extract::State<DocumentService>,
     path: axum::extract::Path<String>,
-) -> Result<Json<DocumentData>, LedgeknawError> {
-    Ok(Json(state.read_file(path.0).await?))
+    access: axum::Extension<Access>,
+) -> Result<Response, LedgeknawError> {
+    match state.read_file(path.0, *access).await {
+        Ok(mut document) => {
+            state.views.record(document.id);
+            document.annotations = state.annotations.list(document.id).await?;
+            let headers = document_headers(&document.meta, *access);
+            Ok((headers, Json(document)).into_response())
+        }
+        Err(LedgeknawError::NotFound(id)) => {
+            let Some(target) = state.resolve_alias(&id, *access).await? else {
+                return Err(LedgeknawError::NotFound(id));
+            };
+            Ok(
+                Redirect::permanent(&format!("{}/document/{target}", state.base_path))
+                    .into_response(),
+            )
+        }
+        Err(e) => Err(e),
+    }
+}
+
+pub async fn annotations(
+    state: axum::extract::State<DocumentService>,
+    id: axum::extract::Path<String>,
+    access: axum::Extension<Access>,
+) -> Result<Json<Vec<Annotation>>, LedgeknawError> {
+    Ok(Json(state.list_annotations(id.0, *access).await?))
+}
+
+pub async fn add_annotation(
+    state: axum::extract::State<DocumentService>,
+    id: axum::extract::Path<String>,
+    access: axum::Extension<Access>,
+    payload: Json<AnnotationPayload>,
+) -> Result<(StatusCode, Json<Annotation>), LedgeknawError> {
+    let annotation = state.add_annotation(id.0, payload.0, *access).await?;
+    Ok((StatusCode::CREATED, Json(annotation)))
+}
+
+pub async fn update_annotation(
+    state: axum::extract::State<DocumentService>,
+    id: axum::extract::Path<uuid::Uuid>,
+    payload: Json<AnnotationPayload>,
+) -> Result<Json<Annotation>, LedgeknawError> {
+    Ok(Json(state.update_annotation(id.0, payload.0).await?))
+}
+
+pub async fn delete_annotation(
+    state: axum::extract::State<DocumentService>,
+    id: axum::extract::Path<uuid::Uuid>,
+) -> Result<StatusCode, LedgeknawError> {
+    state.delete_annotation(id.0).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// The `Cache-Control` and `X-Robots-Tag` headers requested in the frontmatter of the
+/// document. Documents served with private access are only cached by the client.
+fn document_headers(
+    meta: &DocumentMeta,
+    access: Access,
+) -> AppendHeaders<Vec<(HeaderName, String)>> {
+    let mut headers = vec![];
+
+    if let Some(cache) = meta.cache {
+        let value = match cache.max_age() {
+            Some(max_age) if access.private => format!("private, max-age={max_age}"),
+            Some(max_age) => format!("public, max-age={max_age}"),
+            None => "no-store".to_string(),
+        };
+        headers.push((header::CACHE_CONTROL, value));
+    }
+
+    if meta.noindex {
+        headers.push((
+            HeaderName::from_static("x-robots-tag"),
+            "noindex".to_string(),
+        ));
+    }
+
+    AppendHeaders(headers)
 }
 
 pub async fn document_meta(
     state: axum::extract::State<DocumentService>,
     id: axum::extract::Path<uuid::Uuid>,
-) -> Result<Json<DocumentMeta>, LedgeknawError> {
-    Ok(Json(state.get_file_meta(*id).await?))
+    access: axum::Extension<Access>,
+) -> Result<Json<DocumentInfo>, LedgeknawError> {
+    Ok(Json(state.get_file_meta(*id, *access).await?))
 }
 
-pub async fn sidebar_init(
+pub async fn asset(
+    state: axum::extract::State<DocumentService>,
+    id: axum::extract::Path<uuid::Uuid>,
+    access: axum::Extension<Access>,
+) -> Result<impl IntoResponse, LedgeknawError> {
+    let (content, mime) = state.read_asset(*id, *access).await?;
+    Ok(([(header::CONTENT_TYPE, mime)], content))
+}
+
+/// The index document as a page or, if there is none, a listing of the roots.
+pub async fn index_page(
+    state: axum::extract::State<DocumentService>,
+    access: axum::Extension<Access>,
+) -> Result<Response, LedgeknawError> {
+    state.flags.require(feature::PAGES).await?;
+    let roots = state.navigation(*access).await?;
+    let layout = Layout {
+        title: state.title.as_deref(),
+        base_path: &state.base_path,
+        public_url: state.public_url.as_deref(),
+        roots: &roots,
+    };
+
+    let Some((id, _)) = state.db.get_index_id_path(*access).await? else {
+        return Ok(Html(layout.listing("Index", &roots)).into_response());
+    };
+    let index = state.read_page(id.to_string(), *access).await?;
+    let headers = document_headers(&index.meta, *access);
+    Ok((headers, Html(layout.document(&index))).into_response())
+}
+
+/// Like [document], redirecting aliases to the page of the current ID.
+pub async fn document_page(
+    state: axum::extract::State<DocumentService>,
+    path: axum::extract::Path<String>,
+    access: axum::Extension<Access>,
+) -> Result<Response, LedgeknawError> {
+    state.flags.require(feature::PAGES).await?;
+    let document = match state.read_page(path.0, *access).await {
+        Ok(document) => document,
+        Err(LedgeknawError::NotFound(id)) => {
+            let Some(target) = state.resolve_alias(&id, *access).await? else {
+                return Err(LedgeknawError::NotFound(id));
+            };
+            return Ok(
+                Redirect::permanent(&format!("{}/pages/{target}", state.base_path)).into_response(),
+            );
+        }
+        Err(e) => return Err(e),
+    };
+    state.views.record(document.id);
+
+    let roots = state.navigation(*access).await?;
+    let layout = Layout {
+        title: state.title.as_deref(),
+        base_path: &state.base_path,
+        public_url: state.public_url.as_deref(),
+        roots: &roots,
+    };
+    let headers = document_headers(&document.meta, *access);
+    Ok((headers, Html(layout.document(&document))).into_response())
+}
+
+/// The entries of a directory or the documents of a collection.
+pub async fn directory_page(
+    state: axum::extract::State<DocumentService>,
+    id: axum::extract::Path<uuid::Uuid>,
+    access: axum::Extension<Access>,
+) -> Result<Html<String>, LedgeknawError> {
+    state.flags.require(feature::PAGES).await?;
+    let Some(name) = state.db.get_entry_name(*id, *access).await? else {
+        return Err(LedgeknawError::NotFound(id.to_string()));
+    };
+    let entries = match state.db.list_collection_documents(*id, *access).await? {
+        Some(documents) => documents,
+        None => state.list_entries(*id, *access).await?,
+    };
+
+    let roots = state.navigation(*access).await?;
+    let layout = Layout {
+        title: state.title.as_deref(),
+        base_path: &state.base_path,
+        public_url: state.public_url.as_deref(),
+        roots: &roots,
+    };
+    Ok(Html(layout.listing(&name, &entries)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TranslateQuery {
+    lang: String,
+}
+
+pub async fn translate(
+    state: axum::extract::State<DocumentService>,
+    path: axum::extract::Path<String>,
+    query: axum::extract::Query<TranslateQuery>,
+    access: axum::Extension<Access>,
+) -> Result<Json<TranslatedDocument>, LedgeknawError> {
+    state.flags.require(feature::TRANSLATION).await?;
+    Ok(Json(state.translate(path.0, &query.lang, *access).await?))
+}
+
+pub async fn derived_kinds(
+    state: axum::extract::State<DocumentService>,
+    id: axum::extract::Path<uuid::Uuid>,
+    access: axum::Extension<Access>,
+) -> Result<Json<Vec<String>>, LedgeknawError> {
+    state.flags.require(feature::DERIVED).await?;
+    Ok(Json(state.db.list_derived_kinds(*id, *access).await?))
+}
+
+pub async fn derived_document(
+    state: axum::extract::State<DocumentService>,
+    path: axum::extract::Path<(uuid::Uuid, String)>,
+    access: axum::Extension<Access>,
+) -> Result<Json<DerivedDocument>, LedgeknawError> {
+    state.flags.require(feature::DERIVED).await?;
+    let (id, kind) = path.0;
+    Ok(Json(state.get_derived(id, &kind, *access).await?))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GraphQuery {
+    /// Only include the documents below the directory or in the collection
+    directory: Option<uuid::Uuid>,
+}
+
+pub async fn graph(
     state: axum::extract::State<DocumentService>,
+    query: axum::extract::Query<GraphQuery>,
+    access: axum::Extension<Access>,
+) -> Result<Json<Graph>, LedgeknawError> {
+    state.flags.require(feature::GRAPH).await?;
+    Ok(Json(state.graph(query.directory, *access).await?))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TreeQuery {
+    /// Levels of the hierarchy to include, the roots being the first
+    depth: Option<i32>,
+}
+
+pub async fn tree(
+    state: axum::extract::State<DocumentService>,
+    query: axum::extract::Query<TreeQuery>,
+    access: axum::Extension<Access>,
+) -> Result<Json<Vec<TreeEntry>>, LedgeknawError> {
+    if query.depth.is_some_and(|depth| depth < 1) {
+        return Err(LedgeknawError::InvalidParameter(
+            "depth must be at least 1".to_string(),
+        ));
+    }
+    let entries = state.db.list_tree(query.depth, *access).await?;
+    Ok(Json(TreeEntry::build(
+        state.hide_empty(entries, *access).await?,
+    )))
+}
+
+/// The public tree with document metadata. Supports conditional requests with
+/// the ETag and is gzipped if the client accepts it.
+pub async fn export_tree(
+    state: axum::extract::State<DocumentService>,
+    ClientIp(client): ClientIp,
+    headers: axum::http::HeaderMap,
+) -> Result<Response, LedgeknawError> {
+    state.flags.require(feature::EXPORT).await?;
+    state.export_limiter.check(client)?;
+
+    let export = serde_json::to_vec(&state.export_tree().await?)?;
+    let etag = format!("\"{}\"", document::hash_content(&export));
+
+    let cache_headers = [
+        (header::ETAG, etag.clone()),
+        (header::CACHE_CONTROL, "public, max-age=300".to_string()),
+        (header::VARY, header::ACCEPT_ENCODING.to_string()),
+    ];
+
+    let not_modified = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.split(',').any(|tag| tag.trim() == etag));
+
+    if not_modified {
+        return Ok((StatusCode::NOT_MODIFIED, cache_headers).into_response());
+    }
+
+    let gzip = headers
+        .get(header::ACCEPT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.split(',').any(|enc| enc.trim().starts_with("gzip")));
+
+    if !gzip {
+        return Ok((
+            cache_headers,
+            [(header::CONTENT_TYPE, "application/json")],
+            export,
+        )
+            .into_response());
+    }
+
+    let mut encoder = GzEncoder::new(vec![], Compression::default());
+    encoder.write_all(&export)?;
+    let export = encoder.finish()?;
+
+    Ok((
+        cache_headers,
+        [
+            (header::CONTENT_TYPE, "application/json"),
+            (header::CONTENT_ENCODING, "gzip"),
+        ],
+        export,
+    )
+        .into_response())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DocumentQuery {
+    /// See [crate::document::query] for the syntax
+    q: String,
+    /// Restrict matches to the subtree of the directory
+    dir: Option<uuid::Uuid>,
+    #[serde(default)]
+    sort: Order,
+    limit: Option<i64>,
+}
+
+pub async fn query_documents(
+    state: axum::extract::State<DocumentService>,
+    query: axum::extract::Query<DocumentQuery>,
+    access: axum::Extension<Access>,
 ) -> Result<Json<Vec<DirectoryEntry>>, LedgeknawError> {
-    let docs = state.db.list_roots().await?;
+    state.flags.require(feature::QUERY).await?;
+    let expr = document::query::parse(&query.q)?;
+    let limit = query.limit.unwrap_or(50).clamp(1, 500);
+    Ok(Json(
+        state
+            .db
+            .query_documents(&expr, query.dir, query.sort, limit, *access)
+            .await?,
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RecentQuery {
+    limit: Option<i64>,
+}
+
+pub async fn recent(
+    state: axum::extract::State<DocumentService>,
+    query: axum::extract::Query<RecentQuery>,
+    access: axum::Extension<Access>,
+) -> Result<Json<Vec<DatedEntry>>, LedgeknawError> {
+    let limit = query.limit.unwrap_or(20).clamp(1, 100);
+    let docs = state.db.list_recent(limit, *access).await?;
     Ok(Json(docs))
 }
 
+#[derive(Debug, Deserialize)]
+pub struct PopularQuery {
+    /// Views of the last days counted, including today
+    days: Option<i64>,
+    limit: Option<i64>,
+}
+
+/// The most viewed documents of the last 30 days by default.
+pub async fn popular(
+    state: axum::extract::State<DocumentService>,
+    query: axum::extract::Query<PopularQuery>,
+    access: axum::Extension<Access>,
+) -> Result<Json<Vec<PopularEntry>>, LedgeknawError> {
+    if !state.views.is_enabled() {
+        return Err(LedgeknawError::NotFound(
+            "views are not configured".to_string(),
+        ));
+    }
+    let days = query.days.unwrap_or(30).clamp(1, 3650);
+    let limit = query.limit.unwrap_or(20).clamp(1, 100);
+    Ok(Json(
+        state.views.popular(days as u64, limit, *access).await?,
+    ))
+}
+
+/// Bookmarks of the admin for authenticated requests, of the client otherwise.
+pub async fn bookmarks(
+    state: axum::extract::State<DocumentService>,
+    access: axum::Extension<Access>,
+    jar: CookieJar,
+) -> Result<Json<Vec<Bookmark>>, LedgeknawError> {
+    let owner = bookmark::owner(*access, &jar);
+    Ok(Json(state.list_bookmarks(owner, *access).await?))
+}
+
+/// Anonymous clients without bookmarks get a cookie identifying them, which is
+/// renewed on every bookmark.
+pub async fn add_bookmark(
+    state: axum::extract::State<DocumentService>,
+    id: axum::extract::Path<String>,
+    access: axum::Extension<Access>,
+    ClientIp(client): ClientIp,
+    jar: CookieJar,
+) -> Result<(CookieJar, StatusCode), LedgeknawError> {
+    let owner = bookmark::owner(*access, &jar);
+    let owner = state.add_bookmark(owner, id.0, *access, client).await?;
+
+    let jar = if access.authenticated() {
+        jar
+    } else {
+        jar.add(bookmark::bookmark_cookie(owner, &state.base_path))
+    };
+    Ok((jar, StatusCode::NO_CONTENT))
+}
+
+pub async fn remove_bookmark(
+    state: axum::extract::State<DocumentService>,
+    id: axum::extract::Path<String>,
+    access: axum::Extension<Access>,
+    jar: CookieJar,
+) -> Result<StatusCode, LedgeknawError> {
+    if let Some(owner) = bookmark::owner(*access, &jar) {
+        state.bookmarks.delete(owner, &id).await?;
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn authors(
+    state: axum::extract::State<DocumentService>,
+    access: axum::Extension<Access>,
+) -> Result<Json<Vec<Author>>, LedgeknawError> {
+    Ok(Json(state.db.list_authors(*access).await?))
+}
+
+pub async fn author_documents(
+    state: axum::extract::State<DocumentService>,
+    name: axum::extract::Path<String>,
+    access: axum::Extension<Access>,
+) -> Result<Json<Vec<DatedEntry>>, LedgeknawError> {
+    Ok(Json(state.db.list_by_author(&name, *access).await?))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SidebarQuery {
+    /// Respond with a [SidebarDelta] of the entries changed since the version
+    since: Option<i64>,
+}
+
+/// Lists the root directories followed by the collections.
+pub async fn bootstrap(
+    state: axum::extract::State<DocumentService>,
+    access: axum::Extension<Access>,
+) -> Result<Json<Bootstrap>, LedgeknawError> {
+    Ok(Json(state.bootstrap(*access).await?))
+}
+
+pub async fn sidebar_init(
+    state: axum::extract::State<DocumentService>,
+    query: axum::extract::Query<SidebarQuery>,
+    access: axum::Extension<Access>,
+    headers: axum::http::HeaderMap,
+) -> Result<Response, LedgeknawError> {
+    let docs = state.navigation(*access).await?;
+    sidebar_response(docs, query.since, &headers)
+}
+
+/// Sends a `document` or `directory` event for every entry changed by syncing or reindexing,
+/// with the ID and whether it was created, updated or deleted. Clients which fall behind
+/// are sent a `reset` event instead of the changes they missed. Ends on shutdown.
+pub async fn events(
+    state: axum::extract::State<DocumentService>,
+    access: axum::Extension<Access>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let private = access.private;
+    let shutdown = state.shutdown.clone().requested();
+    let stream =
+        futures::stream::unfold(state.notifier.subscribe(), move |mut changes| async move {
+            loop {
+                let event = match changes.recv().await {
+                    Ok(Change { private: true, .. }) if !private => continue,
+                    Ok(change) => Event::default()
+                        .event(change.entry.as_str())
+                        .json_data(change)
+                        .unwrap_or_default(),
+                    Err(RecvError::Lagged(_)) => Event::default().event("reset").data(""),
+                    Err(RecvError::Closed) => return None,
+                };
+                return Some((Ok(event), changes));
+            }
+        })
+        .take_until(shutdown);
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
 pub async fn sidebar_entries(
     state: axum::extract::State<DocumentService>,
     path: axum::extract::Path<uuid::Uuid>,
+    query: axum::extract::Query<SidebarQuery>,
+    access: axum::Extension<Access>,
+    headers: axum::http::HeaderMap,
+) -> Result<Response, LedgeknawError> {
+    if let Some(documents) = state.db.list_collection_documents(*path, *access).await? {
+        return sidebar_response(documents, query.since, &headers);
+    }
+    let files = state.list_entries(*path, *access).await?;
+    sidebar_response(files, query.since, &headers)
+}
+
+/// Responds with 304 if the client's ETag matches the listing, otherwise with the
+/// listing or, if `since` is given and the entries are versioned, only its changes.
+pub(crate) fn sidebar_response(
+    entries: Vec<DirectoryEntry>,
+    since: Option<i64>,
+    headers: &axum::http::HeaderMap,
+) -> Result<Response, LedgeknawError> {
+    let listing = serde_json::to_vec(&entries)?;
+    let etag = format!("\"{}\"", document::hash_content(&listing));
+
+    // Listings depend on the access of the client, so only it may cache them
+    let cache_headers = [
+        (header::ETAG, etag.clone()),
+        (header::CACHE_CONTROL, "private, no-cache".to_string()),
+    ];
+
+    let not_modified = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.split(',').any(|tag| tag.trim() == etag));
+
+    if not_modified {
+        return Ok((StatusCode::NOT_MODIFIED, cache_headers).into_response());
+    }
+
+    let versioned = entries.iter().all(|entry| entry.version.is_some());
+
+    let Some(since) = since.filter(|_| versioned) else {
+        return Ok((
+            cache_headers,
+            [(header::CONTENT_TYPE, "application/json")],
+            listing,
+        )
+            .into_response());
+    };
+
+    let delta = SidebarDelta {
+        version: entries.iter().filter_map(|entry| entry.version).max(),
+        order: entries.iter().map(|entry| entry.id).collect(),
+        entries: entries
+            .into_iter()
+            .filter(|entry| entry.version.is_some_and(|version| version > since))
+            .collect(),
+    };
+
+    Ok((cache_headers, Json(delta)).into_response())
+}
+
+pub async fn collections(
+    state: axum::extract::State<DocumentService>,
+) -> Result<Json<Vec<Collection>>, LedgeknawError> {
+    Ok(Json(state.db.list_collections().await?))
+}
+
+/// The documents of the collection in order, e.g. for feeds.
+pub async fn collection_documents(
+    state: axum::extract::State<DocumentService>,
+    name: axum::extract::Path<String>,
+    access: axum::Extension<Access>,
 ) -> Result<Json<Vec<DirectoryEntry>>, LedgeknawError> {
-    let files = state.db.list_entries(*path).await?;
-    Ok(Json(files))
+    let Some(collection) = state.db.get_collection(&name).await? else {
+        return Err(LedgeknawError::NotFound(name.0));
+    };
+    let documents = state
+        .db
+        .list_collection_documents(collection.id, *access)
+        .await?
+        .unwrap_or_default();
+    Ok(Json(documents))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CollectionPayload {
+    query: String,
+    #[serde(default)]
+    sort: Order,
+    max_documents: Option<i32>,
+}
+
+pub async fn put_collection(
+    state: axum::extract::State<DocumentService>,
+    name: axum::extract::Path<String>,
+    payload: Json<CollectionPayload>,
+) -> Result<Json<Collection>, LedgeknawError> {
+    let CollectionPayload {
+        query,
+        sort,
+        max_documents,
+    } = payload.0;
+    Ok(Json(
+        state
+            .put_collection(&name, &query, sort, max_documents)
+            .await?,
+    ))
+}
+
+/// Collections defined in the config are recreated on startup.
+pub async fn delete_collection(
+    state: axum::extract::State<DocumentService>,
+    name: axum::extract::Path<String>,
+) -> Result<StatusCode, LedgeknawError> {
+    if !state.db.delete_collection(&name).await? {
+        return Err(LedgeknawError::NotFound(name.0));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LoginPayload {
+    password: String,
+    /// TOTP or recovery code, required if 2FA is enabled
+    code: Option<String>,
+}
+
+pub async fn login(
+    state: axum::extract::State<DocumentService>,
+    ClientIp(client): ClientIp,
+    headers: axum::http::HeaderMap,
+    jar: CookieJar,
+    payload: Json<LoginPayload>,
+) -> Result<(CookieJar, StatusCode), LedgeknawError> {
+    let user_agent = headers
+        .get(header::USER_AGENT)
+        .and_then(|value| value.to_str().ok());
+    let session = state
+        .auth
+        .login(
+            &payload.password,
+            payload.code.as_deref(),
+            client,
+            user_agent,
+        )
+        .await?;
+    Ok((
+        jar.add(auth::session_cookie(&session, &state.base_path)),
+        StatusCode::NO_CONTENT,
+    ))
+}
+
+pub async fn logout(
+    state: axum::extract::State<DocumentService>,
+    access: axum::Extension<Access>,
+    jar: CookieJar,
+) -> Result<(CookieJar, StatusCode), LedgeknawError> {
+    if let Some(session) = access.session {
+        state.auth.db.delete_session(session).await?;
+    }
+    Ok((
+        jar.remove(auth::removal_cookie(&state.base_path)),
+        StatusCode::NO_CONTENT,
+    ))
+}
+
+pub async fn sessions(
+    state: axum::extract::State<DocumentService>,
+) -> Result<Json<Vec<Session>>, LedgeknawError> {
+    Ok(Json(state.auth.db.list_sessions().await?))
+}
+
+pub async fn revoke_session(
+    state: axum::extract::State<DocumentService>,
+    id: axum::extract::Path<uuid::Uuid>,
+) -> Result<StatusCode, LedgeknawError> {
+    if !state.auth.db.delete_session(*id).await? {
+        return Err(LedgeknawError::NotFound(id.to_string()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TotpPayload {
+    code: String,
+}
+
+pub async fn totp_status(
+    state: axum::extract::State<DocumentService>,
+) -> Result<Json<TotpStatus>, LedgeknawError> {
+    Ok(Json(state.auth.db.get_totp_status().await?))
+}
+
+pub async fn enroll_totp(
+    state: axum::extract::State<DocumentService>,
+) -> Result<Json<TotpEnrollment>, LedgeknawError> {
+    let issuer = state.title.as_deref().unwrap_or("ledgeknaw");
+    Ok(Json(state.auth.enroll_totp(issuer).await?))
+}
+
+/// Returns the recovery codes.
+pub async fn confirm_totp(
+    state: axum::extract::State<DocumentService>,
+    payload: Json<TotpPayload>,
+) -> Result<Json<Vec<String>>, LedgeknawError> {
+    Ok(Json(state.auth.confirm_totp(&payload.code).await?))
+}
+
+pub async fn disable_totp(
+    state: axum::extract::State<DocumentService>,
+    payload: Json<TotpPayload>,
+) -> Result<StatusCode, LedgeknawError> {
+    state.auth.disable_totp(&payload.code).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn issues(
+    state: axum::extract::State<DocumentService>,
+) -> Result<Json<Vec<DocumentIssue>>, LedgeknawError> {
+    Ok(Json(state.db.list_issues().await?))
+}
+
+pub async fn table_scans(
+    state: axum::extract::State<DocumentService>,
+) -> Result<Json<Vec<TableScans>>, LedgeknawError> {
+    Ok(Json(state.db.list_table_scans().await?))
+}
+
+/// Documents without tags that no other document links to.
+pub async fn orphans(
+    state: axum::extract::State<DocumentService>,
+) -> Result<Json<Vec<LinkReport>>, LedgeknawError> {
+    Ok(Json(state.db.list_orphans().await?))
+}
+
+/// Documents linking to documents or files that do not exist.
+pub async fn dead_ends(
+    state: axum::extract::State<DocumentService>,
+) -> Result<Json<Vec<LinkReport>>, LedgeknawError> {
+    Ok(Json(state.db.list_dead_ends().await?))
+}
+
+/// Queue a check of the links of all documents, reported on `/admin/issues`.
+pub async fn check_links(
+    state: axum::extract::State<DocumentService>,
+) -> Result<(StatusCode, Json<Job>), LedgeknawError> {
+    let job = state.queue_job(LINK_CHECK).await?;
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+pub async fn reindex(
+    state: axum::extract::State<DocumentService>,
+) -> Result<(StatusCode, Json<Job>), LedgeknawError> {
+    let job = state.queue_job(REINDEX).await?;
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+pub async fn sync(
+    state: axum::extract::State<DocumentService>,
+) -> Result<(StatusCode, Json<Job>), LedgeknawError> {
+    let job = state.queue_job(SYNC).await?;
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImportQuery {
+    /// Alias of the root directory to import into
+    root: String,
+    /// Directory below the root to unpack to, created if missing
+    path: Option<String>,
+}
+
+/// Unpack a zip archive of markdown into a root directory and queue a sync.
+pub async fn import(
+    state: axum::extract::State<DocumentService>,
+    query: axum::extract::Query<ImportQuery>,
+    body: axum::body::Bytes,
+) -> Result<(StatusCode, Json<ImportReport>), LedgeknawError> {
+    let report = state
+        .import_archive(&query.root, query.path.as_deref(), body)
+        .await?;
+    Ok((StatusCode::ACCEPTED, Json(report)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DumpQuery {
+    #[serde(default)]
+    format: DumpFormat,
+}
+
+/// Download the directories, documents, links and everything derived from them.
+pub async fn dump_database(
+    state: axum::extract::State<DocumentService>,
+    query: axum::extract::Query<DumpQuery>,
+) -> Result<Response, LedgeknawError> {
+    let mut body = vec![];
+    dump::dump(&state.archive)
+        .await?
+        .write(query.format, &mut body)?;
+
+    let headers = [
+        (
+            header::CONTENT_TYPE,
+            query.format.content_type().to_string(),
+        ),
+        (
+            header::CONTENT_DISPOSITION,
+            format!(
+                "attachment; filename=\"ledgeknaw-dump.{}\"",
+                query.format.extension()
+            ),
+        ),
+    ];
+    Ok((headers, body).into_response())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RestoreQuery {
+    /// Delete the rows already in the database instead of requiring an empty one
+    #[serde(default)]
+    replace: bool,
+}
+
+/// Restore a dump, read as ndjson if sent as `application/x-ndjson` and as JSON otherwise,
+/// and queue a sync. Responds with the number of rows restored per table.
+pub async fn restore_database(
+    state: axum::extract::State<DocumentService>,
+    query: axum::extract::Query<RestoreQuery>,
+    headers: axum::http::HeaderMap,
+    body: axum::body::Bytes,
+) -> Result<(StatusCode, Json<BTreeMap<String, usize>>), LedgeknawError> {
+    let ndjson = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with(DumpFormat::Ndjson.content_type()));
+    let format = if ndjson {
+        DumpFormat::Ndjson
+    } else {
+        DumpFormat::Json
+    };
+
+    let dump = Dump::read(format, body.as_ref())?;
+    let rows = state.restore_dump(dump, query.replace).await?;
+    Ok((StatusCode::ACCEPTED, Json(rows)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RollbackPayload {
+    /// Tag, branch or commit to restore
+    revision: String,
+    /// Alias of the root directory to restore, exclusive with `document`
+    root: Option<String>,
+    document: Option<uuid::Uuid>,
+    /// Only report the changes
+    #[serde(default)]
+    dry_run: bool,
+}
+
+pub async fn rollback(
+    state: axum::extract::State<DocumentService>,
+    access: axum::Extension<Access>,
+    payload: Json<RollbackPayload>,
+) -> Result<Json<RollbackReport>, LedgeknawError> {
+    let Json(RollbackPayload {
+        revision,
+        root,
+        document,
+        dry_run,
+    }) = payload;
+
+    let target = match (root, document) {
+        (Some(root), None) => RollbackTarget::Root(root),
+        (None, Some(document)) => RollbackTarget::Document(document),
+        _ => {
+            return Err(LedgeknawError::InvalidParameter(
+                "expected either root or document".to_string(),
+            ))
+        }
+    };
+
+    Ok(Json(
+        state.rollback(target, &revision, dry_run, *access).await?,
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RevisionPayload {
+    /// Tag, branch or commit
+    revision: String,
+}
+
+pub async fn revisions(
+    state: axum::extract::State<DocumentService>,
+) -> Result<Json<Vec<RootRevision>>, LedgeknawError> {
+    Ok(Json(state.list_revisions().await?))
+}
+
+pub async fn pin_revision(
+    state: axum::extract::State<DocumentService>,
+    alias: axum::extract::Path<String>,
+    payload: Json<RevisionPayload>,
+) -> Result<StatusCode, LedgeknawError> {
+    state.pin_revision(&alias, &payload.revision).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn unpin_revision(
+    state: axum::extract::State<DocumentService>,
+    alias: axum::extract::Path<String>,
+) -> Result<StatusCode, LedgeknawError> {
+    state.unpin_revision(&alias).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn stage_revision(
+    state: axum::extract::State<DocumentService>,
+    alias: axum::extract::Path<String>,
+    payload: Json<RevisionPayload>,
+) -> Result<StatusCode, LedgeknawError> {
+    state.stage_revision(&alias, &payload.revision).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn discard_staged_revision(
+    state: axum::extract::State<DocumentService>,
+    alias: axum::extract::Path<String>,
+) -> Result<StatusCode, LedgeknawError> {
+    state.discard_staged_revision(&alias).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn promote_revision(
+    state: axum::extract::State<DocumentService>,
+    alias: axum::extract::Path<String>,
+) -> Result<StatusCode, LedgeknawError> {
+    state.promote_revision(&alias).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn queue(
+    state: axum::extract::State<DocumentService>,
+) -> Result<Json<Vec<Job>>, LedgeknawError> {
+    Ok(Json(state.jobs.list_queue().await?))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct JobsQuery {
+    status: Option<String>,
+    kind: Option<String>,
+    limit: Option<i64>,
+}
+
+/// The most recent jobs, including finished ones.
+pub async fn jobs(
+    state: axum::extract::State<DocumentService>,
+    query: axum::extract::Query<JobsQuery>,
+) -> Result<Json<Vec<Job>>, LedgeknawError> {
+    let status = query
+        .status
+        .as_deref()
+        .map(JobStatus::from_str)
+        .transpose()?;
+    let limit = query.limit.unwrap_or(100).clamp(1, 1000);
+    Ok(Json(
+        state
+            .jobs
+            .list(status, query.kind.as_deref(), limit)
+            .await?,
+    ))
+}
+
+pub async fn job(
+    state: axum::extract::State<DocumentService>,
+    id: axum::extract::Path<uuid::Uuid>,
+) -> Result<Json<Job>, LedgeknawError> {
+    Ok(Json(state.get_job(*id).await?))
+}
+
+pub async fn status(state: axum::extract::State<DocumentService>) -> Json<Status> {
+    Json(state.status().await)
+}
+
+pub async fn telemetry_reports(
+    state: axum::extract::State<DocumentService>,
+) -> Result<Json<Vec<SentReport>>, LedgeknawError> {
+    Ok(Json(state.telemetry.list_reports().await?))
+}
+
+pub async fn flags(
+    state: axum::extract::State<DocumentService>,
+) -> Result<Json<BTreeMap<String, bool>>, LedgeknawError> {
+    Ok(Json(state.flags.list().await?))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FlagPayload {
+    enabled: bool,
+}
+
+pub async fn set_flag(
+    state: axum::extract::State<DocumentService>,
+    name: axum::extract::Path<String>,
+    payload: Json<FlagPayload>,
+) -> Result<StatusCode, LedgeknawError> {
+    state.flags.set(&name, payload.enabled).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Reverts the flag to its value in the config or its default.
+pub async fn reset_flag(
+    state: axum::extract::State<DocumentService>,
+    name: axum::extract::Path<String>,
+) -> Result<StatusCode, LedgeknawError> {
+    state.flags.reset(&name).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Query durations in the Prometheus text format.
+pub async fn metrics(state: axum::extract::State<DocumentService>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.db.metrics.render(),
+    )
+}
+
+pub async fn retry_job(
+    state: axum::extract::State<DocumentService>,
+    id: axum::extract::Path<uuid::Uuid>,
+) -> Result<StatusCode, LedgeknawError> {
+    state.retry_job(*id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn cancel_job(
+    state: axum::extract::State<DocumentService>,
+    id: axum::extract::Path<uuid::Uuid>,
+) -> Result<StatusCode, LedgeknawError> {
+    state.cancel_job(*id).await?;
+    Ok(StatusCode::NO_CONTENT)
 }
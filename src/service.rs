@@ -0,0 +1,446 @@
+//! Running as a background service where systemd is not available. On Windows the
+//! service is registered with the Service Control Manager and reports its state to it,
+//! on macOS a launchd agent is written. Everything here is a no-op when the process
+//! was not started as a Windows service.
+
+use crate::{config::StartArgs, error::LedgeknawError};
+use std::path::Path;
+
+/// The name of the service, and the launchd label with the prefix on macOS.
+pub const SERVICE_NAME: &str = "ledgeknaw";
+
+/// Register the service, running this executable with the options given on the command line
+/// from the current working directory, so the config, `.env` and `dist` are found.
+pub fn install(args: &StartArgs) -> Result<(), LedgeknawError> {
+    let program = std::env::current_exe()?;
+    let working_directory = std::env::current_dir()?;
+    platform::install(&program, &working_directory, service_args(args)?)
+}
+
+/// Stop and remove the service.
+pub fn uninstall() -> Result<(), LedgeknawError> {
+    platform::uninstall()
+}
+
+/// The options the service is started with, with paths made absolute.
+fn service_args(args: &StartArgs) -> Result<Vec<String>, LedgeknawError> {
+    let absolute = |path: &str| -> Result<String, LedgeknawError> {
+        Ok(Path::new(path).canonicalize()?.display().to_string())
+    };
+
+    let mut service_args = vec![
+        "--config-path".to_string(),
+        absolute(&args.config_path)?,
+        "--address".to_string(),
+        args.address.clone(),
+        "--port".to_string(),
+        args.port.to_string(),
+        "--log-level".to_string(),
+        args.log_level.to_string(),
+    ];
+
+    if let Some(base_path) = &args.base_path {
+        service_args.extend(["--base-path".to_string(), base_path.clone()]);
+    }
+
+    if let (Some(cert), Some(key)) = (&args.tls_cert, &args.tls_key) {
+        service_args.extend([
+            "--tls-cert".to_string(),
+            absolute(cert)?,
+            "--tls-key".to_string(),
+            absolute(key)?,
+        ]);
+    }
+
+    Ok(service_args)
+}
+
+/// Connect to the Service Control Manager in the background and move to
+/// the working directory the service was installed from.
+pub fn start(working_directory: &str) -> Result<(), LedgeknawError> {
+    std::env::set_current_dir(working_directory)?;
+    platform::start()
+}
+
+/// Report that the service is running, once the directories are synced.
+pub fn ready() {
+    platform::ready()
+}
+
+/// Completes when the service is asked to stop. Never completes outside of services.
+pub async fn stopping() {
+    platform::stopping().await
+}
+
+/// Report that the service stopped.
+pub fn stopped() {
+    platform::stopped()
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::SERVICE_NAME;
+    use crate::error::LedgeknawError;
+    use std::{
+        ffi::{c_void, OsStr},
+        io,
+        os::windows::ffi::OsStrExt,
+        path::Path,
+        process::Command,
+        ptr,
+        sync::{
+            atomic::{AtomicIsize, AtomicU32, Ordering},
+            OnceLock,
+        },
+    };
+    use tokio::sync::Notify;
+    use tracing::{error, info};
+    use windows_sys::{
+        core::PWSTR,
+        Win32::{
+            Foundation::{ERROR_CALL_NOT_IMPLEMENTED, NO_ERROR},
+            System::Services::{
+                RegisterServiceCtrlHandlerExW, SetServiceStatus, StartServiceCtrlDispatcherW,
+                SERVICE_ACCEPT_SHUTDOWN, SERVICE_ACCEPT_STOP, SERVICE_CONTROL_INTERROGATE,
+                SERVICE_CONTROL_SHUTDOWN, SERVICE_CONTROL_STOP, SERVICE_RUNNING,
+                SERVICE_START_PENDING, SERVICE_STATUS, SERVICE_STOPPED, SERVICE_STOP_PENDING,
+                SERVICE_TABLE_ENTRYW, SERVICE_WIN32_OWN_PROCESS,
+            },
+        },
+    };
+
+    /// Milliseconds the Service Control Manager waits for the
+    /// directories to be synced before considering the start failed
+    const START_WAIT_HINT_MS: u32 = 10 * 60 * 1000;
+
+    /// Milliseconds the Service Control Manager waits for open connections to close
+    const STOP_WAIT_HINT_MS: u32 = 30 * 1000;
+
+    /// The handle status updates are sent to, 0 until the service is registered
+    static HANDLE: AtomicIsize = AtomicIsize::new(0);
+
+    /// The state last reported, or to report once the service is registered
+    static STATE: AtomicU32 = AtomicU32::new(SERVICE_START_PENDING);
+
+    static STOP: OnceLock<Notify> = OnceLock::new();
+
+    pub fn install(
+        program: &Path,
+        working_directory: &Path,
+        args: Vec<String>,
+    ) -> Result<(), LedgeknawError> {
+        let mut command_line = vec![quote(&program.display().to_string())];
+        command_line.extend(args.iter().map(|arg| quote(arg)));
+        command_line.push("--windows-service".to_string());
+        command_line.push(quote(&working_directory.display().to_string()));
+
+        sc(&[
+            "create",
+            SERVICE_NAME,
+            "binPath=",
+            &command_line.join(" "),
+            "start=",
+            "auto",
+            "DisplayName=",
+            "Ledgeknaw",
+        ])?;
+
+        info!("Installed the {SERVICE_NAME} service, start it with `sc.exe start {SERVICE_NAME}`");
+        Ok(())
+    }
+
+    pub fn uninstall() -> Result<(), LedgeknawError> {
+        // Fails if the service is not running, which is fine
+        let _ = sc(&["stop", SERVICE_NAME]);
+        sc(&["delete", SERVICE_NAME])?;
+        info!("Removed the {SERVICE_NAME} service");
+        Ok(())
+    }
+
+    fn sc(args: &[&str]) -> Result<(), LedgeknawError> {
+        let output = Command::new("sc.exe").args(args).output()?;
+        if !output.status.success() {
+            return Err(LedgeknawError::Service(
+                String::from_utf8_lossy(&output.stdout).trim().to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Quote an argument of the command line the service is started with.
+    fn quote(arg: &str) -> String {
+        format!("\"{}\"", arg.replace('"', "\\\""))
+    }
+
+    pub fn start() -> Result<(), LedgeknawError> {
+        std::thread::spawn(|| {
+            let mut name = wide(SERVICE_NAME);
+            let table = [
+                SERVICE_TABLE_ENTRYW {
+                    lpServiceName: name.as_mut_ptr(),
+                    lpServiceProc: Some(service_main),
+                },
+                SERVICE_TABLE_ENTRYW {
+                    lpServiceName: ptr::null_mut(),
+                    lpServiceProc: None,
+                },
+            ];
+
+            // SAFETY: The table ends with a null entry and outlives the call,
+            // which only returns once the service stopped
+            if unsafe { StartServiceCtrlDispatcherW(table.as_ptr()) } == 0 {
+                error!(
+                    "Could not connect to the Service Control Manager: {}",
+                    io::Error::last_os_error()
+                );
+                std::process::exit(1);
+            }
+        });
+        Ok(())
+    }
+
+    pub fn ready() {
+        set_state(SERVICE_RUNNING);
+    }
+
+    pub async fn stopping() {
+        stop().notified().await
+    }
+
+    pub fn stopped() {
+        set_state(SERVICE_STOPPED);
+    }
+
+    fn stop() -> &'static Notify {
+        STOP.get_or_init(Notify::new)
+    }
+
+    unsafe extern "system" fn service_main(_: u32, _: *mut PWSTR) {
+        let name = wide(SERVICE_NAME);
+        let handle = RegisterServiceCtrlHandlerExW(name.as_ptr(), Some(handler), ptr::null());
+        if handle == 0 {
+            error!(
+                "Could not register the service control handler: {}",
+                io::Error::last_os_error()
+            );
+            return;
+        }
+
+        HANDLE.store(handle, Ordering::SeqCst);
+        report(STATE.load(Ordering::SeqCst));
+    }
+
+    unsafe extern "system" fn handler(control: u32, _: u32, _: *mut c_void, _: *mut c_void) -> u32 {
+        match control {
+            SERVICE_CONTROL_STOP | SERVICE_CONTROL_SHUTDOWN => {
+                info!("Stopping the service");
+                set_state(SERVICE_STOP_PENDING);
+                stop().notify_one();
+                NO_ERROR
+            }
+            SERVICE_CONTROL_INTERROGATE => NO_ERROR,
+            _ => ERROR_CALL_NOT_IMPLEMENTED,
+        }
+    }
+
+    fn set_state(state: u32) {
+        STATE.store(state, Ordering::SeqCst);
+        report(state);
+    }
+
+    fn report(state: u32) {
+        let handle = HANDLE.load(Ordering::SeqCst);
+        if handle == 0 {
+            return;
+        }
+
+        let status = SERVICE_STATUS {
+            dwServiceType: SERVICE_WIN32_OWN_PROCESS,
+            dwCurrentState: state,
+            dwControlsAccepted: if state == SERVICE_RUNNING {
+                SERVICE_ACCEPT_STOP | SERVICE_ACCEPT_SHUTDOWN
+            } else {
+                0
+            },
+            dwWin32ExitCode: NO_ERROR,
+            dwServiceSpecificExitCode: 0,
+            dwCheckPoint: 0,
+            dwWaitHint: match state {
+                SERVICE_START_PENDING => START_WAIT_HINT_MS,
+                SERVICE_STOP_PENDING => STOP_WAIT_HINT_MS,
+                _ => 0,
+            },
+        };
+
+        // SAFETY: The handle was returned by RegisterServiceCtrlHandlerExW
+        if unsafe { SetServiceStatus(handle, &status) } == 0 {
+            error!(
+                "Could not report the service status: {}",
+                io::Error::last_os_error()
+            );
+        }
+    }
+
+    fn wide(s: &str) -> Vec<u16> {
+        OsStr::new(s).encode_wide().chain(Some(0)).collect()
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::SERVICE_NAME;
+    use crate::error::LedgeknawError;
+    use std::{
+        path::{Path, PathBuf},
+        process::Command,
+    };
+    use tracing::info;
+
+    pub fn install(
+        program: &Path,
+        working_directory: &Path,
+        args: Vec<String>,
+    ) -> Result<(), LedgeknawError> {
+        let path = plist_path()?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let mut program_args = vec![program.display().to_string()];
+        program_args.extend(args);
+
+        std::fs::write(&path, plist(&program_args, working_directory))?;
+        launchctl(&["load", "-w", &path.display().to_string()])?;
+
+        info!("Installed and started the launchd agent {}", path.display());
+        Ok(())
+    }
+
+    pub fn uninstall() -> Result<(), LedgeknawError> {
+        let path = plist_path()?;
+        if !path.exists() {
+            return Err(LedgeknawError::Service(format!(
+                "{} does not exist",
+                path.display()
+            )));
+        }
+
+        launchctl(&["unload", "-w", &path.display().to_string()])?;
+        std::fs::remove_file(&path)?;
+
+        info!("Removed the launchd agent {}", path.display());
+        Ok(())
+    }
+
+    fn label() -> String {
+        format!("dev.{SERVICE_NAME}")
+    }
+
+    fn plist_path() -> Result<PathBuf, LedgeknawError> {
+        let home = std::env::var_os("HOME")
+            .ok_or_else(|| LedgeknawError::Service("HOME is not set".to_string()))?;
+        Ok(PathBuf::from(home)
+            .join("Library/LaunchAgents")
+            .join(format!("{}.plist", label())))
+    }
+
+    /// The agent, started on login and restarted whenever it exits,
+    /// logging to `ledgeknaw.log` in the working directory.
+    fn plist(program_args: &[String], working_directory: &Path) -> String {
+        let working_directory = working_directory.display().to_string();
+        let log = format!("{working_directory}/{SERVICE_NAME}.log");
+        let program_args = program_args
+            .iter()
+            .map(|arg| format!("        <string>{}</string>\n", escape(arg)))
+            .collect::<String>();
+
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{label}</string>
+    <key>ProgramArguments</key>
+    <array>
+{program_args}    </array>
+    <key>WorkingDirectory</key>
+    <string>{working_directory}</string>
+    <key>RunAtLoad</key>
+    <true/>
+    <key>KeepAlive</key>
+    <true/>
+    <key>StandardOutPath</key>
+    <string>{log}</string>
+    <key>StandardErrorPath</key>
+    <string>{log}</string>
+</dict>
+</plist>
+"#,
+            label = label(),
+            working_directory = escape(&working_directory),
+            log = escape(&log),
+        )
+    }
+
+    fn escape(s: &str) -> String {
+        s.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+    }
+
+    fn launchctl(args: &[&str]) -> Result<(), LedgeknawError> {
+        let output = Command::new("launchctl").args(args).output()?;
+        if !output.status.success() {
+            return Err(LedgeknawError::Service(
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn start() -> Result<(), LedgeknawError> {
+        Err(LedgeknawError::Service(
+            "only available on Windows".to_string(),
+        ))
+    }
+
+    pub fn ready() {}
+
+    pub async fn stopping() {
+        std::future::pending().await
+    }
+
+    pub fn stopped() {}
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+mod platform {
+    use crate::error::LedgeknawError;
+    use std::path::Path;
+
+    pub fn install(_: &Path, _: &Path, _: Vec<String>) -> Result<(), LedgeknawError> {
+        Err(LedgeknawError::Service(
+            "services are only installed on Windows and macOS, use a systemd unit instead"
+                .to_string(),
+        ))
+    }
+
+    pub fn uninstall() -> Result<(), LedgeknawError> {
+        install(Path::new(""), Path::new(""), vec![])
+    }
+
+    pub fn start() -> Result<(), LedgeknawError> {
+        Err(LedgeknawError::Service(
+            "only available on Windows".to_string(),
+        ))
+    }
+
+    pub fn ready() {}
+
+    pub async fn stopping() {
+        std::future::pending().await
+    }
+
+    pub fn stopped() {}
+}
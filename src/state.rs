@@ -1,43 +1,300 @@
 use crate::{
-    document::{db::DocumentDb, process_root_directory, DocumentData, DocumentMeta},
+    annotation::db::AnnotationDb,
+    archive::db::ArchiveDb,
+    auth::{Access, Auth},
+    bookmark::{self, db::BookmarkDb},
+    config::{
+        CollectionConfig, GitConfig, HookConfig, LinkCheckConfig, OcrConfig, RobotsConfig,
+        RootConfig, TranscriberConfig, TranslatorConfig, UpdateConfig, WebhookConfig,
+        STAGED_SUFFIX,
+    },
+    content::ContentStores,
+    document::{
+        db::DocumentDb,
+        file_id, git, hash_content,
+        links::{self, LinkTarget},
+        models::{
+            AuthState, Bootstrap, Collection, DerivedDocument, DerivedKind, DirectoryEntry,
+            DocumentInfo, Features, Graph, GraphEdge, GraphNode, RollbackReport, RolledBackFile,
+            RootRevision, SyncReport, TreeEntry, TreeExport,
+        },
+        modified_time, ocr, process_root_directory,
+        query::{self, Order},
+        release_taken_custom_id, summary, transcribe, translate, DocumentData, DocumentMeta,
+        ReadOptions, TranslatedDocument,
+    },
     error::LedgeknawError,
+    events::Notifier,
+    feature::{self, FeatureFlags},
+    idempotency::db::IdempotencyDb,
+    job::{db::JobDb, Job, LINK_CHECK, REINDEX, SYNC},
+    lifecycle::Shutdown,
+    rate_limit::RateLimiter,
+    telemetry::db::TelemetryDb,
+    update::{Status, UpdateChecker, VERSION},
+    views::Views,
+    warmup::Warmup,
+    webhook::Webhooks,
 };
+use chrono::{DateTime, Utc};
 use std::str::FromStr;
-use std::{collections::HashMap, path::Path, sync::Arc};
-use tokio::sync::RwLock;
-use tracing::{trace, warn};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    net::IpAddr,
+    path::Path,
+    sync::Arc,
+    time::Duration,
+};
+use tokio::sync::{Notify, RwLock};
+use tracing::{debug, info, trace, warn};
+
+/// Maximum amount of documents listed by queries embedded in documents
+const QUERY_BLOCK_LIMIT: i64 = 100;
+
+/// Maximum amount of documents in collections without a limit
+const COLLECTION_LIMIT: i64 = 1000;
+
+/// Tree exports a client can request per minute
+const EXPORT_REQUESTS: u32 = 30;
 
 #[derive(Debug, Clone)]
 pub struct DocumentService {
     pub db: DocumentDb,
 
+    pub auth: Auth,
+
+    pub jobs: JobDb,
+
+    /// Wakes up the job worker when jobs are queued
+    pub job_notify: Arc<Notify>,
+
+    /// Stored responses of admin mutations
+    pub idempotency: IdempotencyDb,
+
     /// The document title for the front end
     pub title: Arc<Option<String>>,
 
-    /// The list of directories to initially include for the public page.
+    /// Prefix of all routes and links, empty when served from the root
+    pub base_path: Arc<String>,
+
+    /// Public URL of the instance without a trailing slash, if configured
+    pub public_url: Arc<Option<String>>,
+
+    /// The root directories as configured, before any pinned revisions are applied.
     /// Maps names to directory paths.
-    pub directories: Arc<RwLock<HashMap<String, String>>>,
+    pub roots: Arc<HashMap<String, RootConfig>>,
+
+    /// The root directories currently served, swapped out when revisions are pinned.
+    pub directories: Arc<RwLock<HashMap<String, RootConfig>>>,
+
+    /// Content normalization and reading time settings
+    pub read_options: ReadOptions,
+
+    /// Text extraction for assets, disabled if not present
+    pub ocr: Option<Arc<OcrConfig>>,
+
+    /// Transcription of media assets, disabled if not present
+    pub transcriber: Option<Arc<TranscriberConfig>>,
+
+    /// Machine translation of documents, disabled if not present
+    pub translator: Option<Arc<TranslatorConfig>>,
+
+    /// Enrichment of documents with their commit history, disabled if not present
+    pub git: Option<Arc<GitConfig>>,
+
+    /// Syncing on push webhooks, disabled if not present
+    pub hooks: Option<Arc<HookConfig>>,
+
+    /// Checking of external links in the link check job, disabled if not present
+    pub link_check: Option<Arc<LinkCheckConfig>>,
+
+    /// The latest release, if checking for updates is enabled
+    pub updates: UpdateChecker,
+
+    /// Generation of `/robots.txt`, not served if not present
+    pub robots: Option<Arc<RobotsConfig>>,
+
+    /// Reading of the front page after syncing
+    pub warmup: Warmup,
+
+    /// Endpoints notified of changed documents
+    pub webhooks: Webhooks,
+
+    /// Changes of entries, sent on `/events`
+    pub notifier: Notifier,
+
+    /// When the service was started
+    pub started_at: DateTime<Utc>,
+
+    /// Depths of the root directories found by the last sync
+    pub sync_report: Arc<RwLock<Option<SyncReport>>>,
+
+    /// Client for external services
+    pub http: reqwest::Client,
+
+    /// Proxies whose `X-Forwarded-For` header is trusted, see [crate::client_ip]
+    pub trusted_proxies: Arc<Vec<IpAddr>>,
+
+    /// Limits requests for the tree export, which is expensive to generate
+    pub export_limiter: RateLimiter,
+
+    /// Limits how often a client can start bookmarking anonymously
+    pub bookmark_limiter: RateLimiter,
+
+    pub flags: FeatureFlags,
+
+    /// Record of the usage statistics sent
+    pub telemetry: TelemetryDb,
+
+    /// Dumps and restores of the database rows
+    pub archive: ArchiveDb,
+
+    /// View counts of documents, recording nothing if disabled
+    pub views: Views,
+
+    /// Documents pinned by the admin and anonymous clients
+    pub bookmarks: BookmarkDb,
+
+    /// Comments on documents
+    pub annotations: AnnotationDb,
+
+    /// Stops the job worker and ends the streams of `/events`
+    pub shutdown: Shutdown,
+
+    /// Reads the contents of documents and assets from where their roots are stored
+    pub content: ContentStores,
+}
+
+/// What the service keeps in the database.
+pub struct Databases {
+    pub db: DocumentDb,
+    pub auth: Auth,
+    pub jobs: JobDb,
+    pub idempotency: IdempotencyDb,
+    pub flags: FeatureFlags,
+    pub telemetry: TelemetryDb,
+    pub archive: ArchiveDb,
+    pub views: Views,
+    pub bookmarks: BookmarkDb,
+    pub annotations: AnnotationDb,
+}
+
+/// How the documents are read and served.
+pub struct Settings {
+    pub title: Option<String>,
+    pub base_path: String,
+    pub public_url: Option<String>,
+    pub directories: HashMap<String, RootConfig>,
+    pub read_options: ReadOptions,
+    pub warmup: bool,
+    pub trusted_proxies: Vec<IpAddr>,
+}
+
+/// Optional features and the external services they use, disabled if not present.
+pub struct Integrations {
+    pub ocr: Option<OcrConfig>,
+    pub transcriber: Option<TranscriberConfig>,
+    pub translator: Option<TranslatorConfig>,
+    pub git: Option<GitConfig>,
+    pub hooks: Option<HookConfig>,
+    pub link_check: Option<LinkCheckConfig>,
+    pub updates: Option<UpdateConfig>,
+    pub robots: Option<RobotsConfig>,
+    pub webhooks: Vec<WebhookConfig>,
 }
 
 impl DocumentService {
     pub fn new(
-        db: DocumentDb,
-        title: Option<String>,
-        directories: HashMap<String, String>,
+        databases: Databases,
+        settings: Settings,
+        integrations: Integrations,
+        content: ContentStores,
+        shutdown: Shutdown,
     ) -> Self {
+        let Databases {
+            db,
+            auth,
+            jobs,
+            idempotency,
+            flags,
+            telemetry,
+            archive,
+            views,
+            bookmarks,
+            annotations,
+        } = databases;
+        let Settings {
+            title,
+            base_path,
+            public_url,
+            directories,
+            read_options,
+            warmup,
+            trusted_proxies,
+        } = settings;
+        let Integrations {
+            ocr,
+            transcriber,
+            translator,
+            git,
+            hooks,
+            link_check,
+            updates,
+            robots,
+            webhooks,
+        } = integrations;
+
         Self {
             db,
+            auth,
+            jobs,
+            job_notify: Arc::new(Notify::new()),
+            idempotency,
             title: Arc::new(title),
+            base_path: Arc::new(base_path),
+            public_url: Arc::new(public_url.map(|url| url.trim_end_matches('/').to_string())),
+            roots: Arc::new(directories.clone()),
             directories: Arc::new(RwLock::new(directories)),
+            read_options,
+            ocr: ocr.map(Arc::new),
+            transcriber: transcriber.map(Arc::new),
+            translator: translator.map(Arc::new),
+            git: git.map(Arc::new),
+            hooks: hooks.map(Arc::new),
+            link_check: link_check.map(Arc::new),
+            updates: UpdateChecker::new(updates),
+            robots: robots.map(Arc::new),
+            warmup: Warmup::new(warmup),
+            webhooks: Webhooks::new(webhooks),
+            notifier: Notifier::default(),
+            started_at: Utc::now(),
+            sync_report: Arc::default(),
+            http: reqwest::Client::new(),
+            trusted_proxies: Arc::new(trusted_proxies),
+            export_limiter: RateLimiter::new(EXPORT_REQUESTS, Duration::from_secs(60)),
+            bookmark_limiter: RateLimiter::new(
+                bookmark::NEW_OWNERS_PER_HOUR,
+                Duration::from_secs(60 * 60),
+            ),
+            flags,
+            telemetry,
+            archive,
+            views,
+            bookmarks,
+            annotations,
+            shutdown,
+            content,
         }
     }
 
     pub async fn sync(&self) -> Result<(), LedgeknawError> {
+        let snapshot = self.snapshot_entries().await?;
+        self.content.pull().await?;
         let directories = self.directories.read().await;
 
         let paths = directories
             .values()
-            .map(String::to_owned)
+            .map(|root| root.path().to_owned())
             .collect::<Vec<_>>();
 
         let full_paths = paths
@@ -52,50 +309,1303 @@ impl DocumentService {
 
         // Trim any files and directories no longer on fs
         let file_paths = self.db.get_all_file_paths().await?;
+        let mut existing = vec![];
         for path in file_paths {
             if let Err(e) = tokio::fs::metadata(&path).await {
                 warn!("Error while reading file {path}, trimming");
                 trace!("Error: {e}");
                 self.db.remove_file_by_path(&path).await?;
+            } else if let Some(file_id) = file_id(&path) {
+                existing.push((path, file_id));
             }
         }
 
-        for (alias, path) in directories.iter() {
-            process_root_directory(&self.db, path, alias).await?;
+        // Files may have been replaced since, e.g. by editors saving to a new file
+        let (paths, file_ids): (Vec<_>, Vec<_>) = existing.into_iter().unzip();
+        self.db.set_file_ids(&paths, &file_ids).await?;
+
+        // Trim other paths of documents which are no longer hard links to them,
+        // so they are stored as documents of their own
+        for (path, document_file_id) in self.db.list_hard_links().await? {
+            if document_file_id.is_none() || file_id(&path) != document_file_id {
+                debug!("{path} is no longer a hard link to a document");
+                self.db.remove_document_path(&path).await?;
+            }
+        }
+
+        // Aliases of existing roots are updated before new roots are inserted,
+        // which could otherwise take an alias still held by another root
+        let (alias_paths, aliases) = directories
+            .iter()
+            .filter_map(|(alias, root)| {
+                let full_path = Path::new(root.path()).canonicalize().ok()?;
+                Some((full_path.to_str()?.to_owned(), alias.clone()))
+            })
+            .unzip::<_, _, Vec<_>, Vec<_>>();
+        self.db.set_root_aliases(&alias_paths, &aliases).await?;
+
+        let mut roots = BTreeMap::new();
+        for (alias, root) in directories.iter() {
+            let report =
+                process_root_directory(&self.db, root.path(), alias, self.read_options).await?;
+            roots.insert(alias.clone(), report);
+
+            let full_path = Path::new(root.path()).canonicalize()?;
+            if let Some(full_path) = full_path.to_str() {
+                self.db.set_root_private(full_path, root.private()).await?;
+            }
+        }
+
+        drop(directories);
+
+        *self.sync_report.write().await = Some(SyncReport {
+            finished_at: Utc::now(),
+            roots,
+        });
+
+        self.apply_summaries().await?;
+        self.apply_git_history().await?;
+        self.refresh_links().await?;
+        self.refresh_collections().await?;
+        self.db.refresh_empty_directories().await?;
+        self.refresh_sidebar_cache().await?;
+        self.notify_changes(snapshot).await?;
+        self.warm_up().await;
+        Ok(())
+    }
+
+    /// Serve the pinned and staged revisions of the root directories instead of their
+    /// working copies. Staged revisions are served as private roots for previewing.
+    /// The revisions are checked out into work trees before the served directories are
+    /// swapped, so requests never see a partial checkout.
+    pub async fn apply_revisions(&self) -> Result<(), LedgeknawError> {
+        let revisions = self.db.list_root_revisions().await?;
+        let mut directories = (*self.roots).clone();
+
+        for revision in revisions {
+            let Some(config) = self.git.clone() else {
+                warn!(
+                    "Git is disabled, serving the working copy of {}",
+                    revision.alias
+                );
+                continue;
+            };
+
+            let Some(root) = self.roots.get(&revision.alias).cloned() else {
+                warn!(
+                    "Root directory {} does not exist, skipping revisions",
+                    revision.alias
+                );
+                continue;
+            };
+
+            let private = root.private();
+            let crawl = root.crawl();
+            let alias = revision.alias.clone();
+
+            let (pinned, staged) = tokio::task::spawn_blocking(move || {
+                checkout_revisions(&config, root.path(), &revision)
+            })
+            .await
+            .map_err(|e| LedgeknawError::Git(e.to_string()))??;
+
+            if let Some(path) = pinned {
+                directories.insert(
+                    alias.clone(),
+                    RootConfig::Options {
+                        path,
+                        private,
+                        crawl,
+                        s3: None,
+                    },
+                );
+            }
+
+            if let Some(path) = staged {
+                directories.insert(
+                    staged_alias(&alias),
+                    RootConfig::Options {
+                        path,
+                        private: true,
+                        crawl: false,
+                        s3: None,
+                    },
+                );
+            }
+        }
+
+        *self.directories.write().await = directories;
+
+        Ok(())
+    }
+
+    pub async fn list_revisions(&self) -> Result<Vec<RootRevision>, LedgeknawError> {
+        self.db.list_root_revisions().await
+    }
+
+    /// Serve the root directory at the given tag, branch or commit.
+    pub async fn pin_revision(&self, alias: &str, revision: &str) -> Result<(), LedgeknawError> {
+        let commit = self.resolve_commit(alias, revision).await?;
+        let current = self.db.get_root_revision(alias).await?;
+        let staged = current
+            .as_ref()
+            .and_then(|current| current.staged.as_deref());
+        self.set_revision(alias, Some(&commit), staged).await
+    }
+
+    /// Serve the root directory at the given revision privately for preview,
+    /// until it is promoted.
+    pub async fn stage_revision(&self, alias: &str, revision: &str) -> Result<(), LedgeknawError> {
+        let commit = self.resolve_commit(alias, revision).await?;
+        let current = self.db.get_root_revision(alias).await?;
+        let pinned = current
+            .as_ref()
+            .and_then(|current| current.pinned.as_deref());
+        self.set_revision(alias, pinned, Some(&commit)).await
+    }
+
+    /// Pin the staged revision of the root directory.
+    pub async fn promote_revision(&self, alias: &str) -> Result<(), LedgeknawError> {
+        let Some(staged) = self
+            .db
+            .get_root_revision(alias)
+            .await?
+            .and_then(|current| current.staged)
+        else {
+            return Err(LedgeknawError::NotFound(format!(
+                "{alias}: staged revision"
+            )));
+        };
+        self.set_revision(alias, Some(&staged), None).await
+    }
+
+    /// Go back to serving the working copy of the root directory.
+    pub async fn unpin_revision(&self, alias: &str) -> Result<(), LedgeknawError> {
+        let current = self.db.get_root_revision(alias).await?;
+        let staged = current
+            .as_ref()
+            .and_then(|current| current.staged.as_deref());
+        self.set_revision(alias, None, staged).await
+    }
+
+    pub async fn discard_staged_revision(&self, alias: &str) -> Result<(), LedgeknawError> {
+        let current = self.db.get_root_revision(alias).await?;
+        let pinned = current
+            .as_ref()
+            .and_then(|current| current.pinned.as_deref());
+        self.set_revision(alias, pinned, None).await
+    }
+
+    /// Restore the working copy of a root directory or a single document to a git revision
+    /// and update the stored documents accordingly. Dry runs only report the changes.
+    pub async fn rollback(
+        &self,
+        target: RollbackTarget,
+        revision: &str,
+        dry_run: bool,
+        access: Access,
+    ) -> Result<RollbackReport, LedgeknawError> {
+        let Some(config) = self.git.clone() else {
+            return Err(LedgeknawError::NotFound("git is disabled".to_string()));
+        };
+
+        let path = match target {
+            RollbackTarget::Root(alias) => match self.roots.get(&alias) {
+                Some(root) => Path::new(root.path()).canonicalize()?,
+                None => return Err(LedgeknawError::NotFound(alias)),
+            },
+            RollbackTarget::Document(id) => match self.db.get_doc_path(id, access).await? {
+                Some(path) => path.into(),
+                None => return Err(LedgeknawError::NotFound(id.to_string())),
+            },
+        };
+
+        if self.content.is_mirrored(&path) {
+            return Err(LedgeknawError::InvalidParameter(format!(
+                "{} is mirrored from S3 and cannot be rolled back",
+                path.display()
+            )));
+        }
+
+        let revision = revision.to_string();
+
+        let report = tokio::task::spawn_blocking(move || {
+            let dir = if path.is_dir() {
+                path.as_path()
+            } else {
+                path.parent().unwrap_or(&path)
+            };
+
+            let commit = git::resolve_commit(&config, dir, &revision)?;
+
+            let files = git::restore_changes(&config, &path, &commit)?
+                .into_iter()
+                .map(|(path, status)| RolledBackFile {
+                    path,
+                    status: match status {
+                        'A' => "added",
+                        'D' => "deleted",
+                        _ => "modified",
+                    },
+                })
+                .collect();
+
+            let diff = if dry_run {
+                Some(git::restore_diff(&config, &path, &commit)?)
+            } else {
+                git::restore(&config, &path, &commit)?;
+                None
+            };
+
+            Ok::<_, LedgeknawError>(RollbackReport {
+                revision: commit,
+                dry_run,
+                files,
+                diff,
+            })
+        })
+        .await
+        .map_err(|e| LedgeknawError::Git(e.to_string()))??;
+
+        if !dry_run && !report.files.is_empty() {
+            info!(
+                "Restored {} files to {}",
+                report.files.len(),
+                report.revision
+            );
+            self.sync().await?;
+            self.reindex().await?;
+        }
+
+        Ok(report)
+    }
+
+    async fn resolve_commit(&self, alias: &str, revision: &str) -> Result<String, LedgeknawError> {
+        let Some(config) = self.git.clone() else {
+            return Err(LedgeknawError::NotFound("git is disabled".to_string()));
+        };
+
+        let Some(root) = self.roots.get(alias) else {
+            return Err(LedgeknawError::NotFound(alias.to_string()));
+        };
+
+        let root = root.path().to_string();
+        let revision = revision.to_string();
+
+        tokio::task::spawn_blocking(move || git::resolve_commit(&config, root, &revision))
+            .await
+            .map_err(|e| LedgeknawError::Git(e.to_string()))?
+    }
+
+    async fn set_revision(
+        &self,
+        alias: &str,
+        pinned: Option<&str>,
+        staged: Option<&str>,
+    ) -> Result<(), LedgeknawError> {
+        if self.git.is_none() {
+            return Err(LedgeknawError::NotFound("git is disabled".to_string()));
+        }
+
+        if !self.roots.contains_key(alias) {
+            return Err(LedgeknawError::NotFound(alias.to_string()));
+        }
+
+        self.db.set_root_revision(alias, pinned, staged).await?;
+        self.apply_revisions().await?;
+        self.sync().await
+    }
+
+    /// Queue text extraction jobs for the sidecar assets supported by the configured
+    /// OCR command and transcriber. Assets with up to date derived documents are skipped
+    /// and output of identical assets is reused by the asset hash, so unchanged assets
+    /// are only processed once.
+    pub async fn queue_asset_jobs(&self) -> Result<(), LedgeknawError> {
+        if self.ocr.is_none() && self.transcriber.is_none() {
+            return Ok(());
+        }
+
+        let assets = self.db.list_assets().await?;
+        let mut queued = 0;
+
+        for (id, path) in assets {
+            let Some((kind, provenance)) = self.asset_extraction(&path) else {
+                continue;
+            };
+
+            let content = match self.content.read(&path).await {
+                Ok(content) => content,
+                Err(e) => {
+                    warn!("Error while reading asset {path}: {e}");
+                    continue;
+                }
+            };
+
+            let hash = hash_content(&content);
+            let kind = kind.as_str();
+
+            if self.db.derived_up_to_date(id, kind, &hash).await? {
+                continue;
+            }
+
+            match self.db.find_derived_content(kind, &hash).await? {
+                Some(text) => {
+                    self.db
+                        .upsert_derived(id, kind, &text, &provenance, &hash)
+                        .await?
+                }
+                None => {
+                    self.enqueue_job(kind, Some(id)).await?;
+                    queued += 1;
+                }
+            }
+        }
+
+        info!("Queued text extraction for {queued} assets");
+
+        Ok(())
+    }
+
+    /// Run a job claimed by the worker.
+    pub async fn run_job(&self, job: &Job) -> Result<(), LedgeknawError> {
+        match job.kind.as_str() {
+            LINK_CHECK => return self.check_links().await,
+            SYNC => return self.pull_and_sync().await,
+            REINDEX => return self.reindex().await,
+            _ => {}
+        }
+
+        let Some(document) = job.document else {
+            return Err(LedgeknawError::DoesNotExist(format!(
+                "{}: job without document",
+                job.id
+            )));
+        };
+
+        let Some(path) = self.db.get_document_asset(document).await? else {
+            return Err(LedgeknawError::DoesNotExist(format!(
+                "{document}: document without asset"
+            )));
+        };
+
+        let Some((kind, provenance)) = self.asset_extraction(&path) else {
+            return Err(LedgeknawError::DoesNotExist(format!(
+                "{path}: no extraction configured"
+            )));
+        };
+
+        if kind.as_str() != job.kind {
+            return Err(LedgeknawError::DoesNotExist(format!(
+                "{}: unknown job kind",
+                job.kind
+            )));
+        }
+
+        let content = self.content.read(&path).await?;
+        let hash = hash_content(&content);
+        let text = self.extract_text(kind, &path, content).await?;
+
+        self.db
+            .upsert_derived(document, kind.as_str(), &text, &provenance, &hash)
+            .await
+    }
+
+    /// Determine how text is extracted from the asset and by what.
+    fn asset_extraction(&self, path: &str) -> Option<(DerivedKind, String)> {
+        match (self.ocr.as_deref(), self.transcriber.as_deref()) {
+            (Some(config), _) if ocr::is_supported(config, path) => {
+                Some((DerivedKind::Ocr, config.command.join(" ")))
+            }
+            (_, Some(config)) if transcribe::is_supported(config, path) => {
+                Some((DerivedKind::Transcript, config.url.clone()))
+            }
+            _ => None,
+        }
+    }
+
+    async fn extract_text(
+        &self,
+        kind: DerivedKind,
+        path: &str,
+        content: Vec<u8>,
+    ) -> Result<String, LedgeknawError> {
+        match kind {
+            DerivedKind::Ocr => {
+                let Some(config) = self.ocr.clone() else {
+                    return Err(LedgeknawError::Ocr("not configured".to_string()));
+                };
+                let path = path.to_string();
+                tokio::task::spawn_blocking(move || ocr::extract_text(&config, path))
+                    .await
+                    .map_err(|e| LedgeknawError::Ocr(e.to_string()))?
+            }
+            DerivedKind::Transcript => {
+                let Some(config) = self.transcriber.as_deref() else {
+                    return Err(LedgeknawError::Transcriber("not configured".to_string()));
+                };
+                transcribe::transcribe(&self.http, config, path, content).await
+            }
+        }
+    }
+
+    pub async fn get_derived(
+        &self,
+        id: uuid::Uuid,
+        kind: &str,
+        access: Access,
+    ) -> Result<DerivedDocument, LedgeknawError> {
+        self.db
+            .get_derived(id, kind, access)
+            .await?
+            .ok_or_else(|| LedgeknawError::NotFound(format!("{id}/{kind}")))
+    }
+
+    /// Machine translate the document to the given language. Translations are stored
+    /// as derived documents and only regenerated when the document changes.
+    pub async fn translate(
+        &self,
+        id: String,
+        lang: &str,
+        access: Access,
+    ) -> Result<TranslatedDocument, LedgeknawError> {
+        let Some(config) = self.translator.as_deref() else {
+            return Err(LedgeknawError::NotFound(
+                "translation is disabled".to_string(),
+            ));
+        };
+
+        translate::validate_lang(lang)?;
+
+        let DocumentData {
+            id, content, meta, ..
+        } = self.read_file(id, access).await?;
+
+        let kind = translate::derived_kind(lang);
+        let hash = hash_content(content.as_bytes());
+
+        if !self.db.derived_up_to_date(id, &kind, &hash).await? {
+            let translated = match self.db.find_derived_content(&kind, &hash).await? {
+                Some(translated) => translated,
+                None => translate::translate(&self.http, config, &content, lang).await?,
+            };
+            self.db
+                .upsert_derived(id, &kind, &translated, &config.url, &hash)
+                .await?;
+        }
+
+        let derived = self.get_derived(id, &kind, access).await?;
+
+        Ok(TranslatedDocument {
+            id,
+            lang: lang.to_string(),
+            content: derived.content,
+            meta,
+            machine_translated: true,
+            provenance: derived.provenance,
+        })
+    }
+
+    /// Re-read the metadata of every document already stored in the database.
+    /// Sync only processes new files, so this is used to pick up changes in how
+    /// existing documents are processed, e.g. after changing the normalization.
+    pub async fn reindex(&self) -> Result<(), LedgeknawError> {
+        let snapshot = self.snapshot_entries().await?;
+        self.content.pull().await?;
+        let paths = self.db.list_document_paths().await?;
+        let total = paths.len();
+
+        for path in paths {
+            let content = self.content.read_to_string(&path).await;
+            match content.map(|content| DocumentMeta::from_content(content, self.read_options)) {
+                Ok((mut meta, mut issue)) => {
+                    release_taken_custom_id(&self.db, &path, &mut meta, &mut issue).await?;
+                    if let Some(issue) = &issue {
+                        warn!("{path}: {issue}");
+                    }
+                    if let Some(id) = self.db.update_doc_by_path(&path, &meta).await? {
+                        self.db.set_issue(id, issue.as_ref()).await?;
+                    }
+                }
+                Err(e) => warn!("Error while reindexing {path}: {e}"),
+            }
+        }
+
+        info!("Reindexed {total} documents");
+
+        self.apply_summaries().await?;
+        self.apply_git_history().await?;
+        self.refresh_links().await?;
+        self.refresh_collections().await?;
+        self.db.refresh_empty_directories().await?;
+        self.refresh_sidebar_cache().await?;
+        self.notify_changes(snapshot).await?;
+        self.warm_up().await;
+        Ok(())
+    }
+
+    /// Store the commit history of the documents in root directories which are git repositories.
+    async fn apply_git_history(&self) -> Result<(), LedgeknawError> {
+        let Some(config) = self.git.clone() else {
+            return Ok(());
+        };
+
+        let roots = self
+            .directories
+            .read()
+            .await
+            .values()
+            .map(|root| root.path().to_string())
+            .collect::<Vec<_>>();
+
+        for root in roots {
+            let config = config.clone();
+            let history = tokio::task::spawn_blocking(move || git::read_history(&config, root))
+                .await
+                .map_err(|e| LedgeknawError::Git(e.to_string()))??;
+
+            if !history.is_empty() {
+                self.db.set_git_history(&history).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Store the links between documents found in their content,
+    /// along with the links to documents and files that do not exist.
+    /// Returns the links to web pages, which are only checked on request.
+    async fn refresh_links(&self) -> Result<Vec<(uuid::Uuid, String)>, LedgeknawError> {
+        let documents = self.db.list_link_targets().await?;
+
+        let mut by_id = HashMap::new();
+        let mut by_path = HashMap::new();
+        for (id, custom_id, path) in documents.iter() {
+            by_id.insert(id.to_string(), *id);
+            if let Some(custom_id) = custom_id {
+                by_id.insert(custom_id.clone(), *id);
+            }
+            by_path.insert(path.as_str(), *id);
+        }
+
+        let mut links = vec![];
+        let mut unresolved = vec![];
+        let mut external = vec![];
+        for (id, _, path) in documents.iter() {
+            let content = match self.content.read_to_string(path).await {
+                Ok(content) => content,
+                Err(e) => {
+                    warn!("Error while reading links of {path}: {e}");
+                    continue;
+                }
+            };
+
+            for link in links::extract(&content) {
+                if links::is_external(&link) {
+                    external.push((*id, link));
+                    continue;
+                }
+
+                let Some(resolved) = links::resolve(Path::new(path), &link, &self.base_path) else {
+                    continue;
+                };
+
+                let target = match &resolved {
+                    LinkTarget::Id(target) => by_id.get(target),
+                    LinkTarget::Path(target) => target.to_str().and_then(|target| {
+                        // Links may leave out the extension
+                        by_path
+                            .get(target)
+                            .or_else(|| by_path.get(format!("{target}.md").as_str()))
+                    }),
+                };
+
+                match target {
+                    Some(target) if target != id => links.push((*id, *target)),
+                    Some(_) => {}
+                    // Links to other files, such as images, are fine if the file exists
+                    None if matches!(&resolved, LinkTarget::Path(target) if target.exists()) => {}
+                    None => unresolved.push((*id, link)),
+                }
+            }
+        }
+
+        debug!(
+            "Found {} links between documents and {} unresolved links",
+            links.len(),
+            unresolved.len()
+        );
+        self.db.set_links(&links, &unresolved).await?;
+
+        Ok(external)
+    }
+
+    /// Store the links to documents and files that do not exist as issues, along
+    /// with the links to web pages that fail to load if checking them is configured.
+    pub async fn check_links(&self) -> Result<(), LedgeknawError> {
+        let external = self.refresh_links().await?;
+
+        let mut issues = vec![];
+        for document in self.db.list_dead_ends().await? {
+            for link in document.links.into_iter().flatten() {
+                issues.push((document.id, link, "no such document or file".to_string()));
+            }
+        }
+
+        if let Some(config) = self.link_check.as_deref() {
+            let mut documents = BTreeMap::<String, Vec<uuid::Uuid>>::new();
+            for (document, link) in external {
+                documents.entry(link).or_default().push(document);
+            }
+
+            info!("Checking {} external links", documents.len());
+
+            for (i, (link, documents)) in documents.into_iter().enumerate() {
+                if i > 0 {
+                    tokio::time::sleep(Duration::from_millis(config.interval_ms)).await;
+                }
+                if let Some(message) = self.check_external_link(config, &link).await {
+                    debug!("Broken link {link}: {message}");
+                    for document in documents {
+                        issues.push((document, link.clone(), message.clone()));
+                    }
+                }
+            }
+        }
+
+        info!("Found {} broken links", issues.len());
+        self.db.set_link_issues(&issues).await
+    }
+
+    /// Why the web page fails to load, if it does.
+    async fn check_external_link(&self, config: &LinkCheckConfig, link: &str) -> Option<String> {
+        let timeout = Duration::from_secs(config.timeout_secs);
+        let mut response = self.http.head(link).timeout(timeout).send().await;
+
+        // Not every server answers HEAD requests
+        if let Ok(res) = &response {
+            if matches!(
+                res.status(),
+                reqwest::StatusCode::METHOD_NOT_ALLOWED | reqwest::StatusCode::NOT_IMPLEMENTED
+            ) {
+                response = self.http.get(link).timeout(timeout).send().await;
+            }
+        }
+
+        match response {
+            Ok(res) if res.status().is_client_error() || res.status().is_server_error() => {
+                Some(res.status().to_string())
+            }
+            Ok(_) => None,
+            Err(e) => Some(e.without_url().to_string()),
+        }
+    }
+
+    /// The documents and their links and tags, for graph views.
+    pub async fn graph(
+        &self,
+        scope: Option<uuid::Uuid>,
+        access: Access,
+    ) -> Result<Graph, LedgeknawError> {
+        if let Some(scope) = scope {
+            if self.db.get_entry_name(scope, access).await?.is_none() {
+                return Err(LedgeknawError::NotFound(scope.to_string()));
+            }
+        }
+
+        let documents = self.db.list_graph_documents(scope, access).await?;
+        let ids = documents
+            .iter()
+            .map(|document| document.id)
+            .collect::<Vec<_>>();
+
+        let mut nodes = vec![];
+        let mut edges = vec![];
+        let mut tags = BTreeSet::new();
+
+        for document in documents {
+            let id = document.id.to_string();
+            for tag in document.tags.into_iter().flatten() {
+                edges.push(GraphEdge {
+                    source: id.clone(),
+                    target: format!("tag:{tag}"),
+                    kind: "tag",
+                });
+                tags.insert(tag);
+            }
+            nodes.push(GraphNode {
+                id,
+                kind: "document",
+                label: document.title.unwrap_or(document.file_name),
+                custom_id: document.custom_id,
+                directory: Some(document.directory),
+            });
+        }
+
+        nodes.extend(tags.into_iter().map(|tag| GraphNode {
+            id: format!("tag:{tag}"),
+            kind: "tag",
+            label: tag,
+            custom_id: None,
+            directory: None,
+        }));
+
+        edges.extend(self.db.list_links_between(&ids).await?.into_iter().map(
+            |(source, target)| GraphEdge {
+                source: source.to_string(),
+                target: target.to_string(),
+                kind: "link",
+            },
+        ));
+
+        Ok(Graph { nodes, edges })
+    }
+
+    /// Use the commit dates for the created and updated dates missing from the frontmatter,
+    /// or the file times if the document has no commits. Returns the author of the last
+    /// commit. Dates are only filled in if git history is enabled.
+    async fn fill_git_dates(
+        &self,
+        id: uuid::Uuid,
+        path: &str,
+        meta: &mut DocumentMeta,
+    ) -> Result<Option<String>, LedgeknawError> {
+        if self.git.is_none() {
+            return Ok(None);
+        }
+
+        let (created, updated, updated_by) = self.db.get_git_history(id).await?;
+        meta.created = meta.created.or(created);
+        meta.updated = meta.updated.or(updated);
+
+        if meta.created.is_none() || meta.updated.is_none() {
+            if let Ok(metadata) = tokio::fs::metadata(path).await {
+                meta.created = meta
+                    .created
+                    .or_else(|| metadata.created().ok().map(DateTime::from));
+                meta.updated = meta
+                    .updated
+                    .or_else(|| metadata.modified().ok().map(DateTime::from));
+            }
+        }
+
+        Ok(updated_by)
+    }
+
+    /// Create or update the collections from the config.
+    pub async fn define_collections(
+        &self,
+        collections: Vec<CollectionConfig>,
+    ) -> Result<(), LedgeknawError> {
+        for CollectionConfig {
+            name,
+            query: q,
+            sort,
+            max_documents,
+        } in collections
+        {
+            query::parse(&q)?;
+            self.db
+                .upsert_collection(&name, &q, sort, max_documents)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Create or update a collection and list its documents.
+    pub async fn put_collection(
+        &self,
+        name: &str,
+        q: &str,
+        sort: Order,
+        max_documents: Option<i32>,
+    ) -> Result<Collection, LedgeknawError> {
+        let expr = query::parse(q)?;
+        let collection = self
+            .db
+            .upsert_collection(name, q, sort, max_documents)
+            .await?;
+        self.refresh_collection(&collection, &expr).await?;
+        Ok(collection)
+    }
+
+    /// Store the sidebar listings of directories whose listings changed since
+    /// the last refresh, or were never stored, for both access levels.
+    async fn refresh_sidebar_cache(&self) -> Result<(), LedgeknawError> {
+        let uncached = self.db.list_uncached_listings().await?;
+        let total = uncached.len();
+
+        for (directory, private) in uncached {
+            let access = Access {
+                private,
+                session: None,
+            };
+            let entries = self.db.list_entries(directory, access).await?;
+            self.db
+                .cache_entries(directory, private, &serde_json::to_value(entries)?)
+                .await?;
+        }
+
+        debug!("Cached {total} sidebar listings");
+
+        Ok(())
+    }
+
+    pub async fn status(&self) -> Status {
+        Status {
+            version: VERSION,
+            started_at: self.started_at,
+            update: self.updates.status().await,
+            sync: self.sync_report.read().await.clone(),
+            warmup: self.warmup.status().await,
+        }
+    }
+
+    /// The contents of `/robots.txt`, if enabled. Private roots are not listed,
+    /// since crawlers cannot see them and listing them would reveal their documents.
+    pub async fn robots_txt(&self) -> Result<Option<String>, LedgeknawError> {
+        let Some(config) = self.robots.as_deref() else {
+            return Ok(None);
+        };
+
+        let mut disallowed = vec![];
+
+        if config.disallow_all {
+            disallowed.push(format!("{}/", self.base_path));
+        } else {
+            let aliases = self
+                .roots
+                .iter()
+                .filter(|(_, root)| !root.crawl())
+                .map(|(alias, _)| alias.clone())
+                .collect::<Vec<_>>();
+
+            for (id, custom_id, document) in self.db.list_root_contents(&aliases).await? {
+                if !document {
+                    disallowed.push(format!("{}/pages/dir/{id}", self.base_path));
+                    continue;
+                }
+                for id in std::iter::once(id.to_string()).chain(custom_id) {
+                    disallowed.push(format!("{}/{id}", self.base_path));
+                    disallowed.push(format!("{}/document/{id}", self.base_path));
+                    disallowed.push(format!("{}/pages/{id}", self.base_path));
+                }
+            }
+        }
+
+        let mut robots = "User-agent: *\n".to_string();
+        if disallowed.is_empty() {
+            robots.push_str("Disallow:\n");
+        }
+        for path in disallowed {
+            robots.push_str(&format!("Disallow: {path}\n"));
+        }
+        for sitemap in &config.sitemaps {
+            robots.push_str(&format!("\nSitemap: {sitemap}"));
+        }
+        if !config.sitemaps.is_empty() {
+            robots.push('\n');
+        }
+
+        Ok(Some(robots))
+    }
+
+    /// The root directories followed by the collections.
+    pub async fn navigation(&self, access: Access) -> Result<Vec<DirectoryEntry>, LedgeknawError> {
+        let mut roots = self
+            .hide_empty(self.db.list_roots(access).await?, access)
+            .await?;
+        roots.extend(self.db.list_collection_entries(access).await?);
+        Ok(roots)
+    }
+
+    /// Leave out the directories without documents at any depth visible with the access
+    /// if [feature::HIDE_EMPTY_DIRECTORIES] is enabled.
+    pub async fn hide_empty(
+        &self,
+        mut entries: Vec<DirectoryEntry>,
+        access: Access,
+    ) -> Result<Vec<DirectoryEntry>, LedgeknawError> {
+        if !self.flags.enabled(feature::HIDE_EMPTY_DIRECTORIES).await? {
+            return Ok(entries);
+        }
+
+        let directories = entries
+            .iter()
+            .filter(|entry| entry.r#type == "d")
+            .map(|entry| entry.id)
+            .collect::<Vec<_>>();
+        let empty = self.db.list_empty_directories(&directories, access).await?;
+
+        entries.retain(|entry| !empty.contains(&entry.id));
+        Ok(entries)
+    }
+
+    pub async fn bootstrap(&self, access: Access) -> Result<Bootstrap, LedgeknawError> {
+        let roots = self.navigation(access).await?;
+        let flags = self.flags.list().await?;
+        let flag = |name| flags.get(name).copied().unwrap_or_default();
+
+        Ok(Bootstrap {
+            title: self.title.as_ref().clone(),
+            base_path: self.base_path.to_string(),
+            roots,
+            features: Features {
+                query: flag(feature::QUERY),
+                translation: self.translator.is_some() && flag(feature::TRANSLATION),
+                ocr: self.ocr.is_some(),
+                transcription: self.transcriber.is_some(),
+                git_history: self.git.is_some(),
+                admin: true,
+            },
+            auth: AuthState {
+                authenticated: access.authenticated(),
+                private: access.private,
+            },
+            flags,
+        })
+    }
+
+    /// The entries of the directory, from the sidebar cache if present.
+    pub async fn list_entries(
+        &self,
+        directory: uuid::Uuid,
+        access: Access,
+    ) -> Result<Vec<DirectoryEntry>, LedgeknawError> {
+        let entries = match self.db.get_cached_entries(directory, access).await? {
+            Some(entries) => serde_json::from_value(entries)?,
+            None => self.db.list_entries(directory, access).await?,
+        };
+        self.hide_empty(entries, access).await
+    }
+
+    /// Evaluate the queries of all collections. Runs after every sync, so collections
+    /// pick up new and changed documents.
+    async fn refresh_collections(&self) -> Result<(), LedgeknawError> {
+        for collection in self.db.list_collections().await? {
+            match query::parse(&collection.query) {
+                Ok(expr) => self.refresh_collection(&collection, &expr).await?,
+                Err(e) => warn!("Invalid query in collection {}: {e}", collection.name),
+            }
+        }
+        Ok(())
+    }
+
+    /// Store the documents matching the collection query. Private documents are
+    /// included and filtered out when listing the collection.
+    async fn refresh_collection(
+        &self,
+        collection: &Collection,
+        expr: &query::Expr,
+    ) -> Result<(), LedgeknawError> {
+        let sort = Order::from_str(&collection.sort).unwrap_or_default();
+        let limit = collection
+            .max_documents
+            .map_or(COLLECTION_LIMIT, i64::from)
+            .clamp(0, COLLECTION_LIMIT);
+        let access = Access {
+            private: true,
+            session: None,
+        };
+
+        let documents = self
+            .db
+            .query_documents(expr, None, sort, limit, access)
+            .await?
+            .into_iter()
+            .map(|entry| entry.id)
+            .collect::<Vec<_>>();
+
+        self.db
+            .set_collection_documents(collection.id, &documents)
+            .await?;
+
+        trace!(
+            "Collection {} has {} documents",
+            collection.name,
+            documents.len()
+        );
+
+        Ok(())
+    }
+
+    /// Export the public directories and documents, regardless of the access of the request.
+    pub async fn export_tree(&self) -> Result<TreeExport, LedgeknawError> {
+        let access = Access::default();
+        let tree = TreeEntry::build(self.db.list_tree(None, access).await?);
+        let documents = self
+            .db
+            .list_exported_documents(access)
+            .await?
+            .into_iter()
+            .map(|document| (document.id, document))
+            .collect();
+        Ok(TreeExport { tree, documents })
+    }
+
+    /// Replace the `ledge-query` code blocks in the document with the documents
+    /// matching their queries, excluding the document itself. Invalid queries
+    /// are replaced with the error.
+    async fn render_queries(
+        &self,
+        document: &mut DocumentData,
+        access: Access,
+        link_base: &str,
+    ) -> Result<(), LedgeknawError> {
+        let blocks = query::find_blocks(&document.content);
+
+        // Replace from the back so the ranges stay valid
+        for block in blocks.into_iter().rev() {
+            let rendered = match query::parse(&block.query) {
+                Ok(expr) => {
+                    let mut entries = self
+                        .db
+                        .query_documents(&expr, None, Order::default(), QUERY_BLOCK_LIMIT, access)
+                        .await?;
+                    entries.retain(|entry| entry.id != document.id);
+                    query::render(&entries, block.table, link_base)
+                }
+                Err(e) => format!("> {e}\n"),
+            };
+            document.content.replace_range(block.range, &rendered);
+        }
+
+        Ok(())
+    }
+
+    /// Point relative links to other documents at the documents' routes, since the
+    /// documents are served by their ID and not by their path.
+    async fn rewrite_links(
+        &self,
+        document: &mut DocumentData,
+        path: &str,
+        access: Access,
+        link_base: &str,
+    ) -> Result<(), LedgeknawError> {
+        let source = Path::new(path);
+        let resolve = |link: &str| match links::resolve(source, link, &self.base_path) {
+            Some(LinkTarget::Path(target)) => target.to_str().map(str::to_string),
+            _ => None,
+        };
+
+        // Links may leave out the extension
+        let paths = links::extract(&document.content)
+            .iter()
+            .filter_map(|link| resolve(link))
+            .flat_map(|target| [format!("{target}.md"), target])
+            .collect::<Vec<_>>();
+        if paths.is_empty() {
+            return Ok(());
+        }
+
+        let documents = self
+            .db
+            .list_doc_ids_by_paths(&paths, access)
+            .await?
+            .into_iter()
+            .map(|(path, id, custom_id)| (path, custom_id.unwrap_or_else(|| id.to_string())))
+            .collect::<HashMap<_, _>>();
+
+        document.content = links::rewrite(&document.content, |link| {
+            let target = resolve(link)?;
+            let id = documents
+                .get(&target)
+                .or_else(|| documents.get(&format!("{target}.md")))?;
+            let fragment = link.find('#').map_or("", |i| &link[i..]);
+            Some(format!("{link_base}/{id}{fragment}"))
+        });
+
+        Ok(())
+    }
+
+    /// Apply the chapter titles and ordering of mdBook summaries in the root directories.
+    async fn apply_summaries(&self) -> Result<(), LedgeknawError> {
+        if !self.read_options.compat.mdbook {
+            return Ok(());
+        }
+
+        let directories = self.directories.read().await;
+
+        for root in directories.values() {
+            for entry in summary::read_summary(Path::new(root.path()))? {
+                self.db
+                    .set_title_and_weight(&entry.path, &entry.title, entry.weight)
+                    .await?;
+            }
         }
 
         Ok(())
     }
 
     /// The `id` can either be the main identifier or a custom defined user id.
-    pub async fn read_file(&self, id: String) -> Result<DocumentData, LedgeknawError> {
+    pub async fn read_file(
+        &self,
+        id: String,
+        access: Access,
+    ) -> Result<DocumentData, LedgeknawError> {
+        self.read_document(id, access, &self.base_path).await
+    }
+
+    /// Like [Self::read_file], with rendered queries linking to the pages on `/pages`.
+    pub async fn read_page(
+        &self,
+        id: String,
+        access: Access,
+    ) -> Result<DocumentData, LedgeknawError> {
+        let link_base = format!("{}/pages", self.base_path);
+        self.read_document(id, access, &link_base).await
+    }
+
+    /// Read the document, rendering its queries with links prefixed with `link_base`.
+    async fn read_document(
+        &self,
+        id: String,
+        access: Access,
+        link_base: &str,
+    ) -> Result<DocumentData, LedgeknawError> {
         let uuid = uuid::Uuid::from_str(&id);
 
         let Ok(uuid) = uuid else {
-            let Some((id, path)) = self.db.get_doc_id_path_by_custom_id(&id).await? else {
+            let Some((id, path)) = self.db.get_doc_id_path_by_custom_id(&id, access).await? else {
                 return Err(LedgeknawError::NotFound(id));
             };
 
-            let document = DocumentData::read_from_disk(id, path)?;
+            let content = self.content.read_to_string(&path).await?;
+            let mut document = DocumentData::from_content(id, &path, content, self.read_options);
+            self.rewrite_links(&mut document, &path, access, link_base)
+                .await?;
+            self.fill_git_dates(id, &path, &mut document.meta).await?;
+            self.render_queries(&mut document, access, link_base)
+                .await?;
             return Ok(document);
         };
 
-        let doc_path = self.db.get_doc_path(uuid).await?;
+        let doc_path = self.db.get_doc_path(uuid, access).await?;
 
         let Some(path) = doc_path else {
             return Err(LedgeknawError::NotFound(id));
         };
 
-        let document = DocumentData::read_from_disk(uuid, path)?;
+        let content = self.content.read_to_string(&path).await?;
+        let mut document = DocumentData::from_content(uuid, &path, content, self.read_options);
+        self.rewrite_links(&mut document, &path, access, link_base)
+            .await?;
+        self.fill_git_dates(uuid, &path, &mut document.meta).await?;
+        self.render_queries(&mut document, access, link_base)
+            .await?;
         Ok(document)
     }
 
-    pub async fn get_file_meta(&self, id: uuid::Uuid) -> Result<DocumentMeta, LedgeknawError> {
-        let doc_path = self.db.get_doc_path(id).await?;
-        let Some(path) = doc_path else {
+    /// Read the asset described by the sidecar document with the given ID.
+    /// Returns the asset contents and its mime type.
+    pub async fn read_asset(
+        &self,
+        id: uuid::Uuid,
+        access: Access,
+    ) -> Result<(Vec<u8>, String), LedgeknawError> {
+        let Some(path) = self.db.get_asset_path(id, access).await? else {
             return Err(LedgeknawError::NotFound(id.to_string()));
         };
-        let meta = DocumentMeta::read_from_file(path)?;
-        Ok(meta)
+        let content = self.content.read(&path).await?;
+        let mime = mime_guess::from_path(&path).first_or_octet_stream();
+        Ok((content, mime.to_string()))
+    }
+
+    /// Resolve an alias to the identifier the document is currently served under,
+    /// preferring the custom ID over the UUID.
+    pub async fn resolve_alias(
+        &self,
+        alias: &str,
+        access: Access,
+    ) -> Result<Option<String>, LedgeknawError> {
+        Ok(self
+            .db
+            .get_doc_by_alias(alias, access)
+            .await?
+            .map(|(id, custom_id)| custom_id.unwrap_or_else(|| id.to_string())))
+    }
+
+    /// The ID of the document with the ID or custom ID, if it is visible.
+    pub async fn resolve_document_id(
+        &self,
+        id: &str,
+        access: Access,
+    ) -> Result<Option<uuid::Uuid>, LedgeknawError> {
+        Ok(match uuid::Uuid::from_str(id) {
+            Ok(uuid) => self.db.get_doc_path(uuid, access).await?.map(|_| uuid),
+            Err(_) => self
+                .db
+                .get_doc_id_path_by_custom_id(id, access)
+                .await?
+                .map(|(uuid, _)| uuid),
+        })
     }
+
+    pub async fn get_file_meta(
+        &self,
+        id: uuid::Uuid,
+        access: Access,
+    ) -> Result<DocumentInfo, LedgeknawError> {
+        let Some((path, created_at, updated_at)) = self.db.get_doc_timestamps(id, access).await?
+        else {
+            return Err(LedgeknawError::NotFound(id.to_string()));
+        };
+        let modified = modified_time(&path);
+        let content = self.content.read_to_string(&path).await?;
+        let (mut meta, _) = DocumentMeta::from_content(content, self.read_options);
+        let updated_by = self.fill_git_dates(id, &path, &mut meta).await?;
+        Ok(DocumentInfo {
+            meta,
+            created_at: Some(created_at),
+            updated_at: Some(updated_at),
+            modified,
+            updated_by,
+        })
+    }
+}
+
+/// What to restore in a rollback.
+#[derive(Debug)]
+pub enum RollbackTarget {
+    /// The working copy of the root directory with the alias
+    Root(String),
+    Document(uuid::Uuid),
+}
+
+/// The name staged revisions of root directories are served under.
+fn staged_alias(alias: &str) -> String {
+    format!("{alias}{STAGED_SUFFIX}")
+}
+
+/// Check out the pinned and staged revisions of the root directory and remove
+/// work trees of revisions no longer used. Returns the paths to serve them from.
+fn checkout_revisions(
+    config: &GitConfig,
+    root: &str,
+    revision: &RootRevision,
+) -> Result<(Option<String>, Option<String>), LedgeknawError> {
+    let dir = Path::new(&config.worktrees).join(
+        revision
+            .alias
+            .replace(|c: char| !c.is_ascii_alphanumeric(), "_"),
+    );
+
+    let checkout = |commit: &Option<String>| -> Result<Option<String>, LedgeknawError> {
+        let Some(commit) = commit else {
+            return Ok(None);
+        };
+        let path = git::checkout(config, root, &dir, commit)?;
+        Ok(path.to_str().map(String::from))
+    };
+
+    let pinned = checkout(&revision.pinned)?;
+    let staged = checkout(&revision.staged)?;
+
+    let keep = [&revision.pinned, &revision.staged]
+        .into_iter()
+        .filter_map(Option::as_deref)
+        .collect::<Vec<_>>();
+
+    git::remove_checkouts(config, root, &dir, &keep)?;
+
+    Ok((pinned, staged))
 }
@@ -0,0 +1,127 @@
+//! Socket activation and service notifications for running under systemd.
+//! Everything here is a no-op when the process was not started by systemd.
+
+#[cfg(unix)]
+use std::os::unix::{
+    ffi::OsStrExt,
+    io::{FromRawFd, RawFd},
+    net::{SocketAddr, UnixDatagram},
+};
+use std::{env, ffi::OsStr, io, net::TcpListener, time::Duration};
+use tracing::warn;
+
+/// The first descriptor of the sockets passed by systemd
+#[cfg(unix)]
+const LISTEN_FDS_START: RawFd = 3;
+
+/// The listening socket passed by systemd if the process was socket activated.
+#[cfg(unix)]
+pub fn listener() -> Option<TcpListener> {
+    if !for_this_process("LISTEN_PID") {
+        return None;
+    }
+
+    let fds = env::var("LISTEN_FDS").ok()?.parse::<i32>().ok()?;
+
+    if fds < 1 {
+        return None;
+    }
+
+    if fds > 1 {
+        warn!("Received {fds} sockets from systemd, only the first one is used");
+    }
+
+    // SAFETY: systemd passes ownership of the descriptors from LISTEN_FDS_START
+    // onwards to the process LISTEN_PID refers to, which was checked above
+    Some(unsafe { TcpListener::from_raw_fd(LISTEN_FDS_START) })
+}
+
+#[cfg(not(unix))]
+pub fn listener() -> Option<TcpListener> {
+    None
+}
+
+/// Bind to the address, unless systemd passed a listening socket.
+pub async fn bind(addr: &str) -> io::Result<tokio::net::TcpListener> {
+    match listener() {
+        Some(listener) => {
+            listener.set_nonblocking(true)?;
+            tokio::net::TcpListener::from_std(listener)
+        }
+        None => tokio::net::TcpListener::bind(addr).await,
+    }
+}
+
+/// Send a state such as `READY=1` to the service manager.
+pub fn notify(state: &str) {
+    let Some(path) = env::var_os("NOTIFY_SOCKET") else {
+        return;
+    };
+
+    if let Err(e) = send(&path, state) {
+        warn!("Error while notifying systemd: {e}");
+    }
+}
+
+#[cfg(unix)]
+fn send(path: &OsStr, state: &str) -> io::Result<()> {
+    let addr = match path.as_bytes().strip_prefix(b"@") {
+        Some(name) => abstract_addr(name)?,
+        None => SocketAddr::from_pathname(path)?,
+    };
+
+    UnixDatagram::unbound()?.send_to_addr(state.as_bytes(), &addr)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn send(_: &OsStr, _: &str) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "notification sockets are only available on Unix",
+    ))
+}
+
+#[cfg(target_os = "linux")]
+fn abstract_addr(name: &[u8]) -> io::Result<SocketAddr> {
+    use std::os::linux::net::SocketAddrExt;
+    SocketAddr::from_abstract_name(name)
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn abstract_addr(_: &[u8]) -> io::Result<SocketAddr> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "abstract sockets are only available on Linux",
+    ))
+}
+
+/// Ping the watchdog at half the interval systemd expects, if it is enabled.
+pub async fn watchdog() {
+    if !for_this_process("WATCHDOG_PID") && env::var_os("WATCHDOG_PID").is_some() {
+        return;
+    }
+
+    let Some(usec) = env::var("WATCHDOG_USEC")
+        .ok()
+        .and_then(|usec| usec.parse::<u64>().ok())
+    else {
+        return;
+    };
+
+    let mut interval = tokio::time::interval(Duration::from_micros(usec / 2));
+
+    loop {
+        interval.tick().await;
+        notify("WATCHDOG=1");
+    }
+}
+
+/// Whether the variable holds the ID of this process, which systemd
+/// sets so the variables are not picked up by child processes.
+fn for_this_process(var: &str) -> bool {
+    env::var(var)
+        .ok()
+        .and_then(|pid| pid.parse::<u32>().ok())
+        .is_some_and(|pid| pid == std::process::id())
+}
@@ -0,0 +1,111 @@
+use crate::{config::TelemetryConfig, error::LedgeknawError, state::DocumentService};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::time::Duration;
+use tracing::{debug, error, warn};
+
+pub mod db;
+
+/// Usage statistics of an instance. Nothing identifying the instance,
+/// its documents or its users is included.
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub version: &'static str,
+    /// Order of magnitude of the document count, e.g. `100-999`
+    pub documents: String,
+    /// Optional subsystems enabled in the config
+    pub features: Vec<&'static str>,
+}
+
+/// A report as it was sent, listed on `/admin/telemetry`.
+#[derive(Debug, Serialize)]
+pub struct SentReport {
+    pub id: uuid::Uuid,
+    pub url: String,
+    pub payload: serde_json::Value,
+    /// Absent if no response was received
+    pub status: Option<i16>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Send a report every interval. The time of the last report is read from the
+/// database, so restarts do not cause additional reports.
+pub async fn run(service: DocumentService, config: TelemetryConfig) {
+    let interval = Duration::from_secs(config.interval_hours.max(1) * 60 * 60);
+
+    loop {
+        let wait = match service.telemetry.last_sent_at().await {
+            Ok(Some(sent_at)) => (sent_at + interval - Utc::now())
+                .to_std()
+                .unwrap_or_default(),
+            Ok(None) => Duration::ZERO,
+            Err(e) => {
+                error!("Error while reading the last telemetry report: {e}");
+                interval
+            }
+        };
+
+        tokio::time::sleep(wait).await;
+
+        if let Err(e) = send(&service, &config).await {
+            error!("Error while sending telemetry: {e}");
+            tokio::time::sleep(interval).await;
+        }
+    }
+}
+
+async fn send(service: &DocumentService, config: &TelemetryConfig) -> Result<(), LedgeknawError> {
+    let report = report(service).await?;
+    let payload = serde_json::to_value(&report)?;
+
+    let (status, error) = match service.http.post(&config.url).json(&payload).send().await {
+        Ok(response) if response.status().is_success() => {
+            debug!("Sent telemetry report to {}", config.url);
+            (Some(response.status().as_u16() as i16), None)
+        }
+        Ok(response) => {
+            warn!("Telemetry endpoint responded with {}", response.status());
+            (Some(response.status().as_u16() as i16), None)
+        }
+        Err(e) => {
+            warn!("Could not send telemetry report: {e}");
+            (None, Some(e.to_string()))
+        }
+    };
+
+    service
+        .telemetry
+        .insert_report(&config.url, &payload, status, error.as_deref())
+        .await
+}
+
+async fn report(service: &DocumentService) -> Result<Report, LedgeknawError> {
+    let documents = service.db.count_documents().await?;
+
+    let features = [
+        ("ocr", service.ocr.is_some()),
+        ("transcription", service.transcriber.is_some()),
+        ("translation", service.translator.is_some()),
+        ("git_history", service.git.is_some()),
+        ("webhooks", service.webhooks.is_enabled()),
+    ]
+    .into_iter()
+    .filter_map(|(name, enabled)| enabled.then_some(name))
+    .collect();
+
+    Ok(Report {
+        version: env!("CARGO_PKG_VERSION"),
+        documents: bucket(documents),
+        features,
+    })
+}
+
+/// The range of the count's order of magnitude, so only the rough size is shared.
+fn bucket(count: i64) -> String {
+    if count <= 0 {
+        return "0".to_string();
+    }
+    let lower = 10i64.pow(count.ilog10());
+    format!("{lower}-{}", lower * 10 - 1)
+}
@@ -0,0 +1,55 @@
+use super::SentReport;
+use crate::{db::retry_read, error::LedgeknawError};
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+#[derive(Debug, Clone)]
+pub struct TelemetryDb {
+    pool: sqlx::PgPool,
+}
+
+impl TelemetryDb {
+    pub async fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn insert_report(
+        &self,
+        url: &str,
+        payload: &serde_json::Value,
+        status: Option<i16>,
+        error: Option<&str>,
+    ) -> Result<(), LedgeknawError> {
+        sqlx::query!(
+            "INSERT INTO telemetry_reports(url, payload, status, error) VALUES($1, $2, $3, $4)",
+            url,
+            payload,
+            status,
+            error
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Newest first
+    pub async fn list_reports(&self) -> Result<Vec<SentReport>, LedgeknawError> {
+        Ok(retry_read(|| {
+            sqlx::query_as!(
+                SentReport,
+                "SELECT * FROM telemetry_reports ORDER BY created_at DESC"
+            )
+            .fetch_all(&self.pool)
+        })
+        .await?)
+    }
+
+    pub async fn last_sent_at(&self) -> Result<Option<DateTime<Utc>>, LedgeknawError> {
+        Ok(retry_read(|| {
+            sqlx::query!("SELECT MAX(created_at) AS sent_at FROM telemetry_reports")
+                .fetch_one(&self.pool)
+        })
+        .await?
+        .sent_at)
+    }
+}
@@ -0,0 +1,92 @@
+//! Serving HTTPS directly, for deployments without a reverse proxy
+//! terminating TLS in front of the app.
+
+use crate::error::LedgeknawError;
+use axum::Router;
+use hyper_util::{
+    rt::{TokioExecutor, TokioIo},
+    server::conn::auto::Builder,
+    service::TowerToHyperService,
+};
+use std::{fs::File, future::Future, io::BufReader, net::SocketAddr, sync::Arc};
+use tokio::net::TcpListener;
+use tokio_rustls::{rustls::ServerConfig, TlsAcceptor};
+use tower::{Service, ServiceExt};
+use tracing::debug;
+
+/// Create an acceptor from PEM encoded files containing the certificate
+/// chain and the private key.
+pub fn acceptor(cert: &str, key: &str) -> Result<TlsAcceptor, LedgeknawError> {
+    let certs = rustls_pemfile::certs(&mut BufReader::new(File::open(cert)?))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if certs.is_empty() {
+        return Err(LedgeknawError::Tls(format!("no certificates in {cert}")));
+    }
+
+    let Some(key) = rustls_pemfile::private_key(&mut BufReader::new(File::open(key)?))? else {
+        return Err(LedgeknawError::Tls(format!("no private key in {key}")));
+    };
+
+    let mut config = ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|e| LedgeknawError::Tls(e.to_string()))?;
+
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/// Serve the router over TLS until `shutdown` completes. Connections already
+/// accepted at that point are served to completion.
+pub async fn serve(
+    listener: TcpListener,
+    acceptor: TlsAcceptor,
+    router: Router,
+    shutdown: impl Future<Output = ()>,
+) {
+    let mut make_service = router.into_make_service_with_connect_info::<SocketAddr>();
+
+    tokio::pin!(shutdown);
+
+    loop {
+        let (stream, remote_addr) = tokio::select! {
+            conn = listener.accept() => match conn {
+                Ok(conn) => conn,
+                Err(e) => {
+                    debug!("Error while accepting connection: {e}");
+                    continue;
+                }
+            },
+            _ = &mut shutdown => return,
+        };
+
+        let Ok(service) = ServiceExt::<SocketAddr>::ready(&mut make_service).await;
+        let Ok(service) = service.call(remote_addr).await;
+
+        let acceptor = acceptor.clone();
+
+        // The handshake happens in the connection task so a slow
+        // client cannot hold up accepting others
+        tokio::spawn(async move {
+            let stream = match acceptor.accept(stream).await {
+                Ok(stream) => stream,
+                Err(e) => {
+                    debug!("TLS handshake with {remote_addr} failed: {e}");
+                    return;
+                }
+            };
+
+            if let Err(e) = Builder::new(TokioExecutor::new())
+                .serve_connection_with_upgrades(
+                    TokioIo::new(stream),
+                    TowerToHyperService::new(service),
+                )
+                .await
+            {
+                debug!("Error while serving {remote_addr}: {e}");
+            }
+        });
+    }
+}
@@ -0,0 +1,136 @@
+use crate::{
+    config::UpdateConfig, document::models::SyncReport, error::LedgeknawError,
+    state::DocumentService, warmup::WarmupStatus,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{sync::Arc, time::Duration};
+use tokio::sync::RwLock;
+use tracing::{error, info, warn};
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The running instance, served on `/admin/status`.
+#[derive(Debug, Serialize)]
+pub struct Status {
+    pub version: &'static str,
+    pub started_at: DateTime<Utc>,
+    /// Absent if checking for updates is disabled or no check succeeded yet
+    pub update: Option<UpdateStatus>,
+    /// Absent until the first sync finished
+    pub sync: Option<SyncReport>,
+    /// Absent if warming up is disabled or did not finish yet
+    pub warmup: Option<WarmupStatus>,
+}
+
+/// The latest release as of the last check.
+#[derive(Debug, Clone, Serialize)]
+pub struct UpdateStatus {
+    pub latest: String,
+    pub url: String,
+    pub update_available: bool,
+    pub checked_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Release {
+    tag_name: String,
+    html_url: String,
+}
+
+/// Checks for new releases in the background. Requests only ever read the
+/// result of the last check, so GitHub is queried once per interval at most.
+#[derive(Debug, Clone, Default)]
+pub struct UpdateChecker {
+    config: Option<Arc<UpdateConfig>>,
+    status: Arc<RwLock<Option<UpdateStatus>>>,
+}
+
+impl UpdateChecker {
+    pub fn new(config: Option<UpdateConfig>) -> Self {
+        Self {
+            config: config.map(Arc::new),
+            status: Arc::default(),
+        }
+    }
+
+    /// The result of the last successful check, if any.
+    pub async fn status(&self) -> Option<UpdateStatus> {
+        self.status.read().await.clone()
+    }
+}
+
+/// Check for a new release every interval, logging a warning when one is available.
+pub async fn run(service: DocumentService) {
+    let checker = service.updates.clone();
+    let Some(config) = checker.config.clone() else {
+        return;
+    };
+
+    let mut interval =
+        tokio::time::interval(Duration::from_secs(config.interval_hours.max(1) * 60 * 60));
+
+    loop {
+        interval.tick().await;
+
+        match check(&service.http, &config).await {
+            Ok(status) => {
+                if status.update_available {
+                    warn!(
+                        "Ledgeknaw {} is available, running {VERSION}: {}",
+                        status.latest, status.url
+                    );
+                } else {
+                    info!("Ledgeknaw {VERSION} is the latest release");
+                }
+                *checker.status.write().await = Some(status);
+            }
+            Err(e) => error!("Error while checking for updates: {e}"),
+        }
+    }
+}
+
+async fn check(
+    http: &reqwest::Client,
+    config: &UpdateConfig,
+) -> Result<UpdateStatus, LedgeknawError> {
+    let url = format!(
+        "https://api.github.com/repos/{}/releases/latest",
+        config.repository
+    );
+
+    let release = http
+        .get(&url)
+        .header(reqwest::header::USER_AGENT, format!("ledgeknaw/{VERSION}"))
+        .header(reqwest::header::ACCEPT, "application/vnd.github+json")
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| LedgeknawError::Update(e.to_string()))?
+        .json::<Release>()
+        .await
+        .map_err(|e| LedgeknawError::Update(e.to_string()))?;
+
+    let latest = release.tag_name.trim_start_matches('v').to_string();
+
+    Ok(UpdateStatus {
+        update_available: newer(&latest, VERSION),
+        latest,
+        url: release.html_url,
+        checked_at: Utc::now(),
+    })
+}
+
+/// Compares the numeric components of the versions, ignoring pre-release suffixes.
+fn newer(latest: &str, current: &str) -> bool {
+    let parse = |version: &str| {
+        version
+            .split(['-', '+'])
+            .next()
+            .unwrap_or_default()
+            .split('.')
+            .map(|part| part.parse::<u64>().unwrap_or_default())
+            .collect::<Vec<_>>()
+    };
+    parse(latest) > parse(current)
+}
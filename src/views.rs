@@ -0,0 +1,103 @@
+//! Anonymous view counts of documents. Views are counted per document and day in memory
+//! and added to the database every `flush_secs`, so reading a document does not wait
+//! for a write. Nothing about the visitors is recorded.
+
+use crate::{auth::Access, config::ViewsConfig, error::LedgeknawError, state::DocumentService};
+use chrono::{Days, NaiveDate, Utc};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tracing::{debug, error};
+
+pub mod db;
+
+/// A document by its views, listed on `/popular`.
+#[derive(Debug, Serialize)]
+pub struct PopularEntry {
+    pub id: uuid::Uuid,
+    pub name: String,
+    pub title: Option<String>,
+    pub custom_id: Option<String>,
+    pub views: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Views {
+    db: db::ViewsDb,
+    enabled: bool,
+    /// Views not yet in the database
+    pending: Arc<Mutex<HashMap<(uuid::Uuid, NaiveDate), i64>>>,
+}
+
+impl Views {
+    pub fn new(db: db::ViewsDb, enabled: bool) -> Self {
+        Self {
+            db,
+            enabled,
+            pending: Arc::default(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// The most viewed documents of the last `days`, including today.
+    pub async fn popular(
+        &self,
+        days: u64,
+        limit: i64,
+        access: Access,
+    ) -> Result<Vec<PopularEntry>, LedgeknawError> {
+        let since = Utc::now().date_naive() - Days::new(days.saturating_sub(1));
+        self.db.list_popular(since, limit, access).await
+    }
+
+    pub fn record(&self, document: uuid::Uuid) {
+        if !self.enabled {
+            return;
+        }
+        let day = Utc::now().date_naive();
+        *self
+            .pending
+            .lock()
+            .unwrap()
+            .entry((document, day))
+            .or_default() += 1;
+    }
+
+    /// Add the pending views to the database. They are kept for the next flush
+    /// if they cannot be written.
+    pub async fn flush(&self) {
+        let pending = std::mem::take(&mut *self.pending.lock().unwrap());
+        if pending.is_empty() {
+            return;
+        }
+
+        let views = pending.iter().map(|(key, views)| (*key, *views)).collect();
+        match self.db.add(views).await {
+            Ok(()) => debug!("Recorded the views of {} documents", pending.len()),
+            Err(e) => {
+                error!("Error while recording views: {e}");
+                let mut current = self.pending.lock().unwrap();
+                for (key, views) in pending {
+                    *current.entry(key).or_default() += views;
+                }
+            }
+        }
+    }
+}
+
+/// Flush the views every interval.
+pub async fn run(service: DocumentService, config: ViewsConfig) {
+    let mut interval = tokio::time::interval(Duration::from_secs(config.flush_secs.max(1)));
+    interval.tick().await;
+
+    loop {
+        interval.tick().await;
+        service.views.flush().await;
+    }
+}
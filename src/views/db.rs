@@ -0,0 +1,72 @@
+use super::PopularEntry;
+use crate::{auth::Access, db::retry_read, error::LedgeknawError};
+use chrono::NaiveDate;
+use sqlx::PgPool;
+
+#[derive(Debug, Clone)]
+pub struct ViewsDb {
+    pool: sqlx::PgPool,
+}
+
+impl ViewsDb {
+    pub async fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Add to the views of the documents on the days. Views of documents deleted
+    /// in the meantime are dropped.
+    pub async fn add(
+        &self,
+        views: Vec<((uuid::Uuid, NaiveDate), i64)>,
+    ) -> Result<(), LedgeknawError> {
+        let (keys, views): (Vec<_>, Vec<_>) = views.into_iter().unzip();
+        let (documents, days): (Vec<_>, Vec<_>) = keys.into_iter().unzip();
+
+        sqlx::query!(
+            r#"
+            INSERT INTO document_views(document, day, views)
+            SELECT v.document, v.day, v.views
+            FROM UNNEST($1::UUID[], $2::DATE[], $3::BIGINT[]) AS v(document, day, views)
+            WHERE EXISTS (SELECT 1 FROM documents WHERE id = v.document)
+            ON CONFLICT (document, day) DO UPDATE SET views = document_views.views + EXCLUDED.views
+        "#,
+            &documents,
+            &days,
+            &views
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// The documents with the most views since the day, most viewed first.
+    pub async fn list_popular(
+        &self,
+        since: NaiveDate,
+        limit: i64,
+        access: Access,
+    ) -> Result<Vec<PopularEntry>, LedgeknawError> {
+        retry_read(|| {
+            sqlx::query_as!(
+                PopularEntry,
+                r#"
+                SELECT d.id, d.file_name AS name, d.title, d.custom_id, SUM(v.views)::BIGINT AS "views!"
+                FROM document_views v
+                INNER JOIN documents d ON d.id = v.document
+                WHERE v.day >= $1
+                AND (NOT d.draft OR $4) AND (NOT d.private OR $3)
+                GROUP BY d.id
+                ORDER BY 5 DESC, d.file_name
+                LIMIT $2
+            "#,
+                since,
+                limit,
+                access.private,
+                access.authenticated()
+            )
+            .fetch_all(&self.pool)
+        })
+        .await
+        .map_err(LedgeknawError::from)
+    }
+}
@@ -0,0 +1,105 @@
+//! Makes the requests of the first visitors after syncing, so the sidebar and the
+//! front page documents are read from warm caches instead of from disk.
+
+use crate::{auth::Access, error::LedgeknawError, state::DocumentService};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::{sync::Arc, time::Instant};
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+/// How many of the most recent documents are read, besides the index.
+const RECENT_DOCUMENTS: i64 = 20;
+
+/// The last warmup, shown on `/admin/status`.
+#[derive(Debug, Clone, Serialize)]
+pub struct WarmupStatus {
+    pub finished_at: DateTime<Utc>,
+    pub duration_ms: u128,
+    /// Documents read, counted once per access level
+    pub documents: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Warmup {
+    enabled: bool,
+    status: Arc<RwLock<Option<WarmupStatus>>>,
+}
+
+impl Warmup {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            status: Arc::default(),
+        }
+    }
+
+    /// The result of the last successful warmup, if any.
+    pub async fn status(&self) -> Option<WarmupStatus> {
+        self.status.read().await.clone()
+    }
+}
+
+impl DocumentService {
+    /// Warm up the caches if enabled. Errors are only logged,
+    /// since without a warmup only the first requests are slower.
+    pub async fn warm_up(&self) {
+        if !self.warmup.enabled {
+            return;
+        }
+
+        let start = Instant::now();
+
+        match self.read_front_page().await {
+            Ok(documents) => {
+                let duration = start.elapsed();
+                info!(
+                    "Warmed up {documents} documents in {}ms",
+                    duration.as_millis()
+                );
+                *self.warmup.status.write().await = Some(WarmupStatus {
+                    finished_at: Utc::now(),
+                    duration_ms: duration.as_millis(),
+                    documents,
+                });
+            }
+            Err(e) => warn!("Error while warming up: {e}"),
+        }
+    }
+
+    /// Read the sidebar, the index and the most recent documents for both access levels,
+    /// preparing their statements on the way. Returns the number of documents read.
+    async fn read_front_page(&self) -> Result<usize, LedgeknawError> {
+        let mut documents = 0;
+
+        for private in [false, true] {
+            let access = Access {
+                private,
+                session: None,
+            };
+
+            for root in self.navigation(access).await? {
+                if root.r#type == "d" {
+                    self.list_entries(root.id, access).await?;
+                }
+            }
+
+            let mut ids = vec![];
+            if let Some((id, _)) = self.db.get_index_id_path(access).await? {
+                ids.push(id);
+            }
+            for document in self.db.list_recent(RECENT_DOCUMENTS, access).await? {
+                if !ids.contains(&document.id) {
+                    ids.push(document.id);
+                }
+            }
+
+            for id in ids {
+                self.read_file(id.to_string(), access).await?;
+                documents += 1;
+            }
+        }
+
+        Ok(documents)
+    }
+}
@@ -0,0 +1,145 @@
+//! Notifying other services of the documents created, updated and deleted by syncing
+//! or reindexing, see [crate::events].
+
+use crate::{
+    config::WebhookConfig, events::DocumentEvent, state::DocumentService, update::VERSION,
+};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::{sync::Arc, time::Duration};
+use tokio::{sync::Mutex, task::JoinSet};
+use tracing::{debug, info, warn};
+
+/// Attempts made to deliver an event before it is dropped.
+const ATTEMPTS: u32 = 5;
+
+/// Seconds until an endpoint without a response counts as failed.
+const TIMEOUT_SECS: u64 = 10;
+
+#[derive(Debug, Clone, Default)]
+pub struct Webhooks {
+    /// Locked while delivering, so events reach each endpoint in order
+    hooks: Arc<Vec<(WebhookConfig, Mutex<()>)>>,
+
+    /// Deliveries in progress, waited for on shutdown
+    deliveries: Arc<std::sync::Mutex<JoinSet<()>>>,
+}
+
+impl Webhooks {
+    pub fn new(hooks: Vec<WebhookConfig>) -> Self {
+        Self {
+            hooks: Arc::new(
+                hooks
+                    .into_iter()
+                    .map(|hook| (hook, Mutex::new(())))
+                    .collect(),
+            ),
+            deliveries: Arc::default(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.hooks.is_empty()
+    }
+
+    /// Wait for the deliveries in progress, including their retries.
+    pub async fn flush(&self) {
+        let mut deliveries = std::mem::take(&mut *self.deliveries.lock().unwrap());
+        while deliveries.join_next().await.is_some() {}
+    }
+}
+
+impl DocumentService {
+    /// Deliver the events to every webhook in the background.
+    pub(crate) fn send_webhooks(&self, events: Vec<DocumentEvent>) {
+        if events.is_empty() || !self.webhooks.is_enabled() {
+            return;
+        }
+
+        info!(
+            "Notifying {} webhooks of {} changed documents",
+            self.webhooks.hooks.len(),
+            events.len()
+        );
+
+        let mut deliveries = self.webhooks.deliveries.lock().unwrap();
+        while deliveries.try_join_next().is_some() {}
+
+        let events = Arc::new(events);
+        for i in 0..self.webhooks.hooks.len() {
+            let hooks = self.webhooks.hooks.clone();
+            let http = self.http.clone();
+            let events = events.clone();
+            deliveries.spawn(async move {
+                let (hook, lock) = &hooks[i];
+                let _lock = lock.lock().await;
+                for event in events.iter() {
+                    deliver(&http, hook, event).await;
+                }
+            });
+        }
+    }
+}
+
+/// Post the event, retrying with exponential backoff until the endpoint accepts it.
+async fn deliver(http: &reqwest::Client, hook: &WebhookConfig, event: &DocumentEvent) {
+    let body = match serde_json::to_vec(event) {
+        Ok(body) => body,
+        Err(e) => {
+            warn!("Error while serializing event: {e}");
+            return;
+        }
+    };
+
+    let delivery = uuid::Uuid::new_v4().to_string();
+    let signature = hook.secret.as_deref().map(|secret| sign(secret, &body));
+
+    for attempt in 1..=ATTEMPTS {
+        let mut request = http
+            .post(&hook.url)
+            .timeout(Duration::from_secs(TIMEOUT_SECS))
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(reqwest::header::USER_AGENT, format!("ledgeknaw/{VERSION}"))
+            .header("x-ledgeknaw-event", event.event.as_str())
+            .header("x-ledgeknaw-delivery", &delivery)
+            .body(body.clone());
+
+        if let Some(signature) = &signature {
+            request = request.header("x-ledgeknaw-signature-256", signature);
+        }
+
+        let error = match request.send().await {
+            Ok(res) if res.status().is_success() => {
+                debug!(
+                    "Delivered {} event of {} to {}",
+                    event.event.as_str(),
+                    event.id,
+                    hook.url
+                );
+                return;
+            }
+            Ok(res) => res.status().to_string(),
+            Err(e) => e.without_url().to_string(),
+        };
+
+        if attempt == ATTEMPTS {
+            warn!(
+                "Dropping {} event of {} after {ATTEMPTS} attempts to deliver it to {}: {error}",
+                event.event.as_str(),
+                event.id,
+                hook.url
+            );
+        } else {
+            debug!("Error while delivering event to {}: {error}", hook.url);
+            tokio::time::sleep(Duration::from_secs(1 << (attempt - 1))).await;
+        }
+    }
+}
+
+/// The signature of the payload in the format of GitHub's `X-Hub-Signature-256`.
+fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any size");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}